@@ -0,0 +1,108 @@
+// Benchmarks for the mapper hot paths, run against the tmm_rust library so
+// they measure the same code the app calls.
+//
+//     cargo bench --bench hot_paths
+//
+// Fixtures come from tests/support and are seeded, so runs are comparable.
+// Criterion keeps the previous run under target/criterion and prints the
+// change against it; to compare against a fixed point, record one with
+// `-- --save-baseline <name>` and check later runs with `-- --baseline <name>`.
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+use tmm_rust::composite_mapper::{CleanMap, CompositeMapperFile};
+use tmm_rust::{patch, raw_match};
+
+const MAPPER_SIZES: [usize; 3] = [10_000, 50_000, 200_000];
+// (entries, mods, packages per mod). Lookups scan the whole map, so the
+// largest combinations are left out to keep a run in minutes.
+const APPLY_CASES: [(usize, usize, usize); 4] = [(10_000, 10, 10), (10_000, 100, 10), (10_000, 10, 1000), (50_000, 100, 10)];
+// (mods, packages per mod) for the conflict scan, which doesn't touch the map
+const CONFLICT_CASES: [(usize, usize); 4] = [(10, 10), (100, 10), (10, 1000), (100, 1000)];
+
+fn mapper_load_and_save(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("tmm-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut load = c.benchmark_group("mapper_load_parse");
+    load.sample_size(10);
+    for size in MAPPER_SIZES {
+        let path = dir.join(format!("mapper_{}.dat", size));
+        support::synthetic_mapper(size, support::SEED).save(&path).unwrap();
+        load.bench_with_input(BenchmarkId::from_parameter(size), &path, |b, path| {
+            b.iter(|| CompositeMapperFile::new(black_box(path.clone())).unwrap())
+        });
+    }
+    load.finish();
+
+    let mut save = c.benchmark_group("mapper_serialize_encrypt");
+    save.sample_size(10);
+    for size in MAPPER_SIZES {
+        let mapper = support::synthetic_mapper(size, support::SEED);
+        let path = dir.join(format!("save_{}.dat", size));
+        save.bench_with_input(BenchmarkId::from_parameter(size), &mapper, |b, mapper| {
+            b.iter(|| mapper.save(black_box(&path)).unwrap())
+        });
+    }
+    save.finish();
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+// patch::rebuild is what apply_enabled_mods runs once the enabled mods are
+// collected: reset to the clean map, sort, and patch every package
+fn apply_enabled_mods(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_enabled_mods");
+    group.sample_size(10);
+    for (entries, mods, packages) in APPLY_CASES {
+        let mapper = support::synthetic_mapper(entries, support::SEED);
+        let mod_set = support::synthetic_mod_set(&mapper, mods, packages, support::SEED);
+        let backup = CleanMap::from(mapper.clone());
+        let id = format!("{}k/{}x{}", entries / 1000, mods, packages);
+        group.bench_function(BenchmarkId::from_parameter(id), |b| {
+            b.iter_batched(
+                || mapper.clone(),
+                |mut map| {
+                    patch::rebuild(&mut map, &backup, black_box(&mod_set));
+                    map
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+// Run for every enable, against all enabled mods
+fn find_conflicting_indices(c: &mut Criterion) {
+    let mapper = support::synthetic_mapper(10_000, support::SEED);
+    let mut group = c.benchmark_group("find_conflicting_indices");
+    for (mods, packages) in CONFLICT_CASES {
+        let mod_set = support::synthetic_mod_set(&mapper, mods, packages, support::SEED);
+        let new_mod = &mod_set[mods - 1].mod_file.packages;
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", mods, packages)), |b| {
+            b.iter(|| patch::find_conflicting_indices(black_box(&mod_set), new_mod))
+        });
+    }
+    group.finish();
+}
+
+// A one-letter name matches most of the map, the case raw_match bounds
+fn raw_mod_resolution(c: &mut Criterion) {
+    let mut group = c.benchmark_group("raw_mod_resolution");
+    group.sample_size(10);
+    for size in MAPPER_SIZES {
+        let mapper = support::synthetic_mapper(size, support::SEED);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &mapper, |b, mapper| {
+            b.iter(|| raw_match::find(mapper.values(), black_box("S.gpk")).entries.len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, mapper_load_and_save, apply_enabled_mods, find_conflicting_indices, raw_mod_resolution);
+criterion_main!(benches);
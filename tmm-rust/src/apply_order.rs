@@ -0,0 +1,180 @@
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::mod_model::ModEntry;
+
+// Order in which mods are applied to the mapper. Where two mods touch the
+// same objects the one applied later wins, so the base order is the mod
+// list. A mod's apply_after constraints move it behind the named mods; apart
+// from that list order is kept, since of the mods free to go next the
+// earliest listed always goes first. Constraints naming mods outside the
+// given set (disabled or uninstalled) don't affect the order.
+pub struct ApplyOrder {
+    // Indices into the given mods, in apply order
+    pub order: Vec<usize>,
+    // IDs of the mods of each constraint cycle found, in list order. A cycle is
+    // broken by applying its earliest listed mod as if it had no constraints.
+    pub cycles: Vec<Vec<u64>>,
+}
+
+pub fn sort<M: Borrow<ModEntry>>(mods: &[M]) -> ApplyOrder {
+    let position: HashMap<u64, usize> = mods.iter().enumerate().map(|(i, m)| (m.borrow().id, i)).collect();
+    // waiting_on[i]: mods still to be applied before mod i
+    let mut waiting_on: Vec<HashSet<usize>> = mods
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            m.borrow()
+                .apply_after
+                .iter()
+                .filter_map(|id| position.get(id).copied())
+                .filter(|&j| j != i)
+                .collect()
+        })
+        .collect();
+    let mut followers = vec![Vec::new(); mods.len()];
+    for (i, before) in waiting_on.iter().enumerate() {
+        for &j in before {
+            followers[j].push(i);
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..mods.len())
+        .filter(|&i| waiting_on[i].is_empty())
+        .map(Reverse)
+        .collect();
+    let mut placed = vec![false; mods.len()];
+    let mut order = Vec::with_capacity(mods.len());
+    let mut cycles = Vec::new();
+    while order.len() < mods.len() {
+        let next = match ready.pop() {
+            Some(Reverse(i)) => i,
+            None => {
+                // Every mod left waits on another one left, so they hold a cycle
+                let mut cycle = find_cycle(&waiting_on, &placed);
+                cycle.sort_unstable();
+                cycles.push(cycle.iter().map(|&i| mods[i].borrow().id).collect());
+                waiting_on[cycle[0]].clear();
+                cycle[0]
+            }
+        };
+        placed[next] = true;
+        order.push(next);
+        for &f in &followers[next] {
+            if waiting_on[f].remove(&next) && waiting_on[f].is_empty() && !placed[f] {
+                ready.push(Reverse(f));
+            }
+        }
+    }
+    ApplyOrder { order, cycles }
+}
+
+// Follows the earliest listed unmet constraint from the earliest listed
+// unplaced mod until a mod repeats. Only called when every unplaced mod has
+// an unmet constraint, so the walk never ends on a mod without one.
+fn find_cycle(waiting_on: &[HashSet<usize>], placed: &[bool]) -> Vec<usize> {
+    let mut current = placed.iter().position(|&p| !p).expect("an unplaced mod");
+    let mut path = Vec::new();
+    loop {
+        if let Some(start) = path.iter().position(|&i| i == current) {
+            return path[start..].to_vec();
+        }
+        path.push(current);
+        current = *waiting_on[current].iter().min().expect("an unmet constraint");
+    }
+}
+
+// Whether `id` is applied after `other` through its constraints, directly or
+// through a chain of other mods. Adding "other after id" would then close a
+// cycle.
+pub fn depends_on(mods: &[ModEntry], id: u64, other: u64) -> bool {
+    let by_id: HashMap<u64, &ModEntry> = mods.iter().map(|m| (m.id, m)).collect();
+    let mut seen = HashSet::new();
+    let mut stack = vec![id];
+    while let Some(current) = stack.pop() {
+        if !seen.insert(current) {
+            continue;
+        }
+        let Some(m) = by_id.get(&current) else {
+            continue;
+        };
+        for &after in &m.apply_after {
+            if after == other {
+                return true;
+            }
+            stack.push(after);
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mods with IDs 1..=n in list order; `after` pairs (id, applied after id)
+    fn mods(n: u64, after: &[(u64, u64)]) -> Vec<ModEntry> {
+        (1..=n)
+            .map(|id| ModEntry {
+                id,
+                apply_after: after.iter().filter(|(m, _)| *m == id).map(|(_, a)| *a).collect(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn ids(mods: &[ModEntry], order: &[usize]) -> Vec<u64> {
+        order.iter().map(|&i| mods[i].id).collect()
+    }
+
+    #[test]
+    fn unconstrained_mods_keep_list_order() {
+        let list = mods(5, &[]);
+        let sorted = sort(&list);
+        assert_eq!(ids(&list, &sorted.order), [1, 2, 3, 4, 5]);
+        assert!(sorted.cycles.is_empty());
+    }
+
+    #[test]
+    fn constraints_move_mods_behind_the_named_ones() {
+        // 1 after 4, 2 after 1; 3 and 5 keep their places relative to the rest
+        let list = mods(5, &[(1, 4), (2, 1)]);
+        let sorted = sort(&list);
+        assert_eq!(ids(&list, &sorted.order), [3, 4, 1, 2, 5]);
+        assert!(sorted.cycles.is_empty());
+    }
+
+    #[test]
+    fn constraints_on_mods_outside_the_set_are_ignored() {
+        let list = mods(3, &[(1, 99), (2, 2)]);
+        let sorted = sort(&list);
+        assert_eq!(ids(&list, &sorted.order), [1, 2, 3]);
+        assert!(sorted.cycles.is_empty());
+    }
+
+    #[test]
+    fn cycles_are_reported_and_broken_at_the_earliest_listed_mod() {
+        // 2 -> 3 -> 4 -> 2 is a cycle; 1 and 5 are free
+        let list = mods(5, &[(2, 4), (3, 2), (4, 3)]);
+        let sorted = sort(&list);
+        assert_eq!(sorted.cycles, [vec![2, 3, 4]]);
+        assert_eq!(ids(&list, &sorted.order), [1, 5, 2, 3, 4]);
+    }
+
+    #[test]
+    fn sort_accepts_borrowed_mods() {
+        let list = mods(3, &[(1, 3)]);
+        let refs: Vec<&ModEntry> = list.iter().collect();
+        assert_eq!(sort(&refs).order, sort(&list).order);
+    }
+
+    #[test]
+    fn depends_on_follows_chains() {
+        let list = mods(4, &[(1, 2), (2, 3)]);
+        assert!(depends_on(&list, 1, 2));
+        assert!(depends_on(&list, 1, 3));
+        assert!(!depends_on(&list, 3, 1));
+        assert!(!depends_on(&list, 1, 4));
+    }
+}
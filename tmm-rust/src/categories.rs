@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::mod_model::CompositePackage;
+
+// Rules that infer what a mod changes from its object paths. The embedded
+// table ships with TMM; a mod_categories.json in TMM's data folder replaces it
+// when its version is at least as new, so markers can be tuned without a
+// release. Rules are tried in order and the first marker found in a path
+// (case-insensitive) claims that path.
+const EMBEDDED_TABLE: &str = include_str!("../assets/mod_categories.json");
+pub const CATEGORY_TABLE_FILE: &str = "mod_categories.json";
+// For mods none of whose paths match a rule
+pub const OTHER_CATEGORY: &str = "Other";
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct CategoryTable {
+    pub version: u32,
+    pub rules: Vec<CategoryRule>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct CategoryRule {
+    pub category: String,
+    pub markers: Vec<String>,
+}
+
+impl CategoryTable {
+    // Never fails; an unusable file falls back to the embedded table
+    pub fn load(data_dir: Option<&Path>) -> Self {
+        let embedded: Self = serde_json::from_str(EMBEDDED_TABLE).unwrap_or_default();
+        let Some(path) = data_dir.map(|dir| dir.join(CATEGORY_TABLE_FILE)) else {
+            return embedded.normalized();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return embedded.normalized();
+        };
+        match serde_json::from_str::<Self>(&text) {
+            Ok(custom) if custom.version >= embedded.version => custom.normalized(),
+            Ok(_) => embedded.normalized(),
+            Err(e) => {
+                eprintln!("[TMM] Ignoring {}: {}", path.display(), e);
+                embedded.normalized()
+            }
+        }
+    }
+
+    // Markers are matched against lowercased paths
+    fn normalized(mut self) -> Self {
+        for rule in &mut self.rules {
+            for marker in &mut rule.markers {
+                *marker = marker.to_lowercase();
+            }
+            rule.markers.retain(|m| !m.is_empty());
+        }
+        self
+    }
+
+    // The category claiming the most of the packages; ties go to the earlier rule
+    pub fn infer(&self, packages: &[CompositePackage]) -> String {
+        let mut votes = vec![0usize; self.rules.len()];
+        for package in packages {
+            let path = package.object_path.to_lowercase();
+            if let Some(rule) = self.rules.iter().position(|r| r.markers.iter().any(|m| path.contains(m.as_str()))) {
+                votes[rule] += 1;
+            }
+        }
+        let best = votes
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .max_by_key(|&(i, &count)| (count, std::cmp::Reverse(i)));
+        match best {
+            Some((i, _)) => self.rules[i].category.clone(),
+            None => OTHER_CATEGORY.to_string(),
+        }
+    }
+
+    // Every category a mod can end up in, in table order, without duplicates
+    pub fn categories(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for rule in &self.rules {
+            if !names.contains(&rule.category.as_str()) {
+                names.push(&rule.category);
+            }
+        }
+        if !names.contains(&OTHER_CATEGORY) {
+            names.push(OTHER_CATEGORY);
+        }
+        names
+    }
+}
@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+// Command-line flags. Flags only affect the current session and are never
+// written back to settings.bin.
+#[derive(Default, Clone)]
+pub struct CliArgs {
+    pub no_watch: bool,
+    // Also keep the config as settings.toml for this session
+    pub human_config: bool,
+    pub root: Option<PathBuf>,
+}
+
+impl CliArgs {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Self {
+        let mut cli = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--no-watch" => cli.no_watch = true,
+                "--human-config" => cli.human_config = true,
+                "--root" => match args.next() {
+                    Some(path) => cli.root = Some(PathBuf::from(path)),
+                    None => eprintln!("[TMM] --root expects a path"),
+                },
+                other => eprintln!("[TMM] Ignoring unknown argument: {}", other),
+            }
+        }
+        cli
+    }
+}
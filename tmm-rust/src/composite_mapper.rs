@@ -1,14 +1,108 @@
+//! Reading and writing `CompositePackageMapper.dat`, the file that tells the TERA
+//! client which composite package (and which byte range in it) holds each object.
+//!
+//! # Plaintext format
+//!
+//! After decryption the mapper is a single ASCII string made of file blocks:
+//!
+//! ```text
+//! <filename>?<object_path>,<composite_name>,<offset>,<size>,|<object_path>,...,|!
+//! ```
+//!
+//! - `filename` is the container package without its `.gpk` extension.
+//! - Each entry ends with `,|`. `offset` and `size` are decimal byte counts inside
+//!   that container.
+//! - `!` closes the block and the next block's filename follows immediately.
+//!   There is no separator and no trailing newline.
+//! - `composite_name` is unique across the whole map and is the key TMM uses.
+//! - Entries within a block are written in offset order. The client relies on
+//!   that order.
+//!
+//! None of `? ! , |` may appear inside a field (see [`MAPPER_DELIMITERS`]).
+//!
+//! # Encryption
+//!
+//! Encryption never changes the length. It runs three stages in order, and
+//! decryption runs their inverses in reverse order:
+//!
+//! ```text
+//! // 1. XOR with the repeating key "GeneratePackageMapper"
+//! for i in 0..len: data[i] ^= KEY2[i % KEY2.len()]
+//!
+//! // 2. Mirror swap of odd positions towards the end
+//! a = 1; b = len - 1
+//! repeat ceil((len / 2) / 2) times: swap(data[a], data[b]); a += 2; b -= 2
+//!
+//! // 3. Permute every full 16-byte block; a short tail is left as is
+//! for each block: out[i] = block[KEY1[i]]
+//! ```
+//!
+//! The swap stage is its own inverse. Decryption undoes the permutation with
+//! `out[KEY1[i]] = block[i]`.
+//!
+//! `KEY1` and `KEY2` are fixed by the game client. The values are the same ones
+//! the original C++ Tera Mod Manager uses. Changing either makes the client
+//! reject the file.
+
 use anyhow::{Context, Result};
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use crate::utils::incomplete_paths_equal;
 
 const KEY1: [usize; 16] = [12, 6, 9, 4, 3, 14, 1, 10, 13, 2, 7, 15, 0, 8, 5, 11];
 const KEY2: &[u8] = b"GeneratePackageMapper";
+/// Characters that delimit fields and blocks in the plaintext mapper.
+pub const MAPPER_DELIMITERS: [char; 4] = ['?', '!', ',', '|'];
 
-#[derive(Default, Clone)]
+pub fn contains_mapper_delimiter(value: &str) -> bool {
+    value.contains(MAPPER_DELIMITERS)
+}
+
+/// Where [`CompositeMapperFile::save`] writes before renaming over `dest`. A file
+/// found here outside a save was left by a process that died mid-save.
+pub fn temp_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// An entry field holding one of [`MAPPER_DELIMITERS`]. Written out it would
+/// split the entry and leave a file the client can't parse, so saving refuses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelimiterError {
+    pub composite_name: String,
+    pub field: &'static str,
+    pub value: String,
+}
+
+impl std::fmt::Display for DelimiterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "entry {} has a mapper delimiter in its {}: {:?}",
+            self.composite_name, self.field, self.value
+        )
+    }
+}
+
+impl std::error::Error for DelimiterError {}
+
+/// Strips what text editors add to a hand-edited dump: a leading UTF-8 BOM and
+/// CR/LF line breaks. Neither can occur in the format, so every one is removed.
+/// Returns the cleaned text and the number of characters dropped.
+pub fn normalize_plaintext(text: &str) -> (String, usize) {
+    let body = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    let cleaned: String = body.chars().filter(|&c| c != '\r' && c != '\n').collect();
+    let stripped = text.chars().count() - cleaned.chars().count();
+    (cleaned, stripped)
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct CompositeEntry {
     pub filename: String,
     pub object_path: String,
@@ -17,6 +111,16 @@ pub struct CompositeEntry {
     pub size: usize,
 }
 
+/// How much of one container package the map references.
+pub struct FileCoverageStats {
+    pub filename: String,
+    /// 0 when the file isn't on disk.
+    pub file_size_bytes: u64,
+    /// Sum of the sizes of every entry pointing into the file.
+    pub total_covered_bytes: u64,
+    pub coverage_percent: f32,
+}
+
 #[derive(Default, Clone)]
 pub struct CompositeMapperFile {
     pub source_path: PathBuf,
@@ -25,6 +129,237 @@ pub struct CompositeMapperFile {
     pub dirty: bool,
     pub cached_map: String,
     pub plaintext: String,
+    // Set when the file on disk turned out to be unencrypted plaintext
+    pub loaded_from_plaintext: bool,
+    // BOM and line break characters removed from a plaintext file while loading
+    pub normalized_chars: usize,
+    // apply_patch calls per composite name this session
+    pub patch_hit_counts: HashMap<String, u32>,
+    // Filenames of blocks in the loaded file that had no entries. Some client
+    // versions choke on these; the serializer never writes them.
+    pub empty_blocks: Vec<String>,
+    // Everything else the parser skipped or patched up in the loaded file
+    pub parse_errors: Vec<ParseError>,
+    // Changes whenever entries are added or removed or the map is replaced,
+    // see entries_changed
+    pub generation: u64,
+}
+
+/// A problem found while parsing the plaintext map. Offsets are byte positions
+/// in the plaintext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A `filename?...!` block with no entries.
+    EmptyBlock { filename: String },
+    /// A block whose closing `!` is missing; its entries are dropped.
+    UnterminatedBlock { filename: String, offset: usize },
+    /// An entry without exactly four fields, or text after a block's last `,|`.
+    MalformedEntry { filename: String, offset: usize, text: String },
+    /// An offset or size that isn't a number; it is read as 0.
+    InvalidNumber { composite_name: String, field: &'static str, value: String },
+    /// Text after the last block that doesn't start another one.
+    TrailingData { offset: usize },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyBlock { filename } => write!(f, "block {} has no entries", filename),
+            Self::UnterminatedBlock { filename, offset } => {
+                write!(f, "block {} at byte {} is missing its closing '!'", filename, offset)
+            }
+            Self::MalformedEntry { filename, offset, text } => {
+                write!(f, "malformed entry in {} at byte {}: {:?}", filename, offset, text)
+            }
+            Self::InvalidNumber { composite_name, field, value } => {
+                write!(f, "{} of {} is not a number: {:?}", field, composite_name, value)
+            }
+            Self::TrailingData { offset } => write!(f, "unexpected data after the last block at byte {}", offset),
+        }
+    }
+}
+
+impl ParseError {
+    fn offset_mut(&mut self) -> Option<&mut usize> {
+        match self {
+            Self::UnterminatedBlock { offset, .. } | Self::MalformedEntry { offset, .. } | Self::TrailingData { offset } => {
+                Some(offset)
+            }
+            Self::EmptyBlock { .. } | Self::InvalidNumber { .. } => None,
+        }
+    }
+}
+
+// Byte offset in `original` of the byte at `offset` in its normalize_plaintext
+// output, so errors point into the file the user edited
+fn original_offset(original: &str, offset: usize) -> usize {
+    let mut kept = 0;
+    for (i, c) in original.char_indices() {
+        if c == '\r' || c == '\n' || (i == 0 && c == '\u{FEFF}') {
+            continue;
+        }
+        if kept >= offset {
+            return i;
+        }
+        kept += c.len_utf8();
+    }
+    original.len()
+}
+
+enum ParseState<'a> {
+    SeekingFilename,
+    // Inside `filename?`, reading one `...,|` entry per step until `!`.
+    // `first` is where the block's entries begin in the parsed list.
+    InEntries { filename: &'a str, start: usize, first: usize, entries: usize },
+    Done,
+}
+
+/// Parses the plaintext map. Input that doesn't fit the format is skipped and
+/// reported rather than aborting, so one bad entry never hides the rest.
+pub fn parse_entries(data: &str) -> (Vec<CompositeEntry>, Vec<ParseError>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut cursor = 0;
+    let mut state = ParseState::SeekingFilename;
+
+    loop {
+        state = match state {
+            ParseState::SeekingFilename => {
+                if cursor >= data.len() {
+                    ParseState::Done
+                } else if let Some(q) = data[cursor..].find('?') {
+                    let start = cursor;
+                    let filename = &data[start..start + q];
+                    cursor += q + 1;
+                    ParseState::InEntries { filename, start, first: entries.len(), entries: 0 }
+                } else {
+                    errors.push(ParseError::TrailingData { offset: cursor });
+                    ParseState::Done
+                }
+            }
+            ParseState::InEntries { filename, start, first, entries: count } => {
+                let rest = &data[cursor..];
+                // Fields never contain '!', so only the text up to the next
+                // separator needs checking for the end of the block
+                let sep = rest.find(",|");
+                let block_end = match sep {
+                    Some(sep) => rest[..sep].find('!'),
+                    None => rest.find('!'),
+                };
+                match (sep, block_end) {
+                    (Some(sep), None) => {
+                        let text = &rest[..sep];
+                        match parse_entry(filename, text, &mut errors) {
+                            Some(entry) => entries.push(entry),
+                            None => errors.push(ParseError::MalformedEntry {
+                                filename: filename.to_string(),
+                                offset: cursor,
+                                text: text.to_string(),
+                            }),
+                        }
+                        cursor += sep + 2;
+                        ParseState::InEntries { filename, start, first, entries: count + 1 }
+                    }
+                    (_, Some(end)) => {
+                        if end > 0 {
+                            errors.push(ParseError::MalformedEntry {
+                                filename: filename.to_string(),
+                                offset: cursor,
+                                text: rest[..end].to_string(),
+                            });
+                        }
+                        if count == 0 {
+                            errors.push(ParseError::EmptyBlock { filename: filename.to_string() });
+                        }
+                        cursor += end + 1;
+                        ParseState::SeekingFilename
+                    }
+                    (None, None) => {
+                        // Entries of a block that never closes are dropped;
+                        // earlier blocks for the same file are kept
+                        entries.truncate(first);
+                        errors.push(ParseError::UnterminatedBlock { filename: filename.to_string(), offset: start });
+                        ParseState::Done
+                    }
+                }
+            }
+            ParseState::Done => break,
+        };
+    }
+    (entries, errors)
+}
+
+// `object_path,composite_name,offset,size`; None unless there are exactly four fields
+fn parse_entry(filename: &str, text: &str, errors: &mut Vec<ParseError>) -> Option<CompositeEntry> {
+    let fields: Vec<&str> = text.split(',').collect();
+    let [object_path, composite_name, offset, size] = fields[..] else {
+        return None;
+    };
+    let mut number = |field: &'static str, value: &str| {
+        value.parse().unwrap_or_else(|_| {
+            errors.push(ParseError::InvalidNumber {
+                composite_name: composite_name.to_string(),
+                field,
+                value: value.to_string(),
+            });
+            0
+        })
+    };
+    Some(CompositeEntry {
+        filename: filename.to_string(),
+        object_path: object_path.to_string(),
+        composite_name: composite_name.to_string(),
+        offset: number("offset", offset),
+        size: number("size", size),
+    })
+}
+
+/// Parses a hand-edited plaintext map for import. Loading skips what it can't
+/// read; here any problem rejects the whole text, and the first one is
+/// returned with its byte offset where it has one. Offsets count from the text
+/// as given, before [`normalize_plaintext`] removes anything. Empty blocks are
+/// allowed, the serializer drops them; a composite name used twice is not,
+/// since the later entry would silently replace the earlier one.
+pub fn parse_plaintext_strict(text: &str) -> Result<IndexMap<String, CompositeEntry>> {
+    let original = text;
+    let (text, _) = normalize_plaintext(text);
+    let (entries, errors) = parse_entries(&text);
+    if let Some(mut error) = errors.into_iter().find(|e| !matches!(e, ParseError::EmptyBlock { .. })) {
+        if let Some(offset) = error.offset_mut() {
+            *offset = original_offset(original, *offset);
+        }
+        anyhow::bail!("{}", error);
+    }
+    if entries.is_empty() {
+        anyhow::bail!("the text contains no mapper entries");
+    }
+    let mut map = IndexMap::with_capacity(entries.len());
+    for entry in entries {
+        if map.contains_key(&entry.composite_name) {
+            anyhow::bail!("composite name {} is used by more than one entry", entry.composite_name);
+        }
+        map.insert(entry.composite_name.clone(), entry);
+    }
+    CompositeMapperFile::check_delimiters(&map)?;
+    Ok(map)
+}
+
+impl<'a> IntoIterator for &'a CompositeMapperFile {
+    type Item = (&'a String, &'a CompositeEntry);
+    type IntoIter = indexmap::map::Iter<'a, String, CompositeEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.composite_map.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut CompositeMapperFile {
+    type Item = (&'a String, &'a mut CompositeEntry);
+    type IntoIter = indexmap::map::IterMut<'a, String, CompositeEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.composite_map.iter_mut()
+    }
 }
 
 impl CompositeMapperFile {
@@ -44,19 +379,168 @@ impl CompositeMapperFile {
         self.source_size = decrypted.len();
         self.plaintext = decrypted.clone();
         self.composite_map.clear();
+        self.entries_changed();
+        self.loaded_from_plaintext = false;
+        self.normalized_chars = 0;
 
         self.parse_entries_with_offsets(&decrypted);
 
+        // Files left behind by other tools are sometimes already decrypted
+        if self.composite_map.is_empty() && !Self::has_map_structure(&decrypted) {
+            let (raw, stripped) = normalize_plaintext(&String::from_utf8_lossy(&encrypted));
+            if Self::has_map_structure(&raw) {
+                self.parse_entries_with_offsets(&raw);
+                if self.has_blocks() {
+                    eprintln!(
+                        "[TMM] Notice: {} is not encrypted, reading it as plaintext.",
+                        self.source_path.display()
+                    );
+                    self.source_size = raw.len();
+                    self.plaintext = raw;
+                    self.loaded_from_plaintext = true;
+                    self.normalized_chars = stripped;
+                }
+            }
+        }
+
+        // A map whose blocks are all empty still loads, so the caller can
+        // flag it as too small; only text with no blocks at all is rejected
+        if !self.has_blocks() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                Self::describe_unreadable(&self.source_path, &encrypted),
+            ));
+        }
+
         Ok(())
     }
 
-    pub fn save(&self, dest: &Path) -> std::io::Result<()> {
+    fn has_blocks(&self) -> bool {
+        !self.composite_map.is_empty() || !self.empty_blocks.is_empty()
+    }
+
+    fn has_map_structure(text: &str) -> bool {
+        text.contains('?') && text.contains('!') && text.contains(",|")
+    }
+
+    // Explains why a mapper could not be read, including size and leading bytes so
+    // a truncated download can be told apart from a wrong file.
+    fn describe_unreadable(path: &Path, data: &[u8]) -> String {
+        if data.is_empty() {
+            return format!("{} is empty (0 bytes). The file is truncated or was never written.", path.display());
+        }
+
+        let head = &data[..data.len().min(16)];
+        let hex: Vec<String> = head.iter().map(|b| format!("{:02X}", b)).collect();
+        let text: String = head
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        format!(
+            "{} contains no mapper entries ({} bytes, starts with {} \"{}\"). \
+             It may be truncated, an error page saved by a downloader, or not a CompositePackageMapper.dat.",
+            path.display(),
+            data.len(),
+            hex.join(" "),
+            text
+        )
+    }
+
+    /// Number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.composite_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.composite_map.is_empty()
+    }
+
+    /// Whether the map has fewer than `min_entries` entries, too few to be a
+    /// complete client mapper.
+    pub fn is_too_small(&self, min_entries: usize) -> bool {
+        self.len() < min_entries
+    }
+
+    /// Entries in file order.
+    pub fn values(&self) -> impl Iterator<Item = &CompositeEntry> {
+        self.composite_map.values()
+    }
+
+    /// Composite names in file order.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.composite_map.keys()
+    }
+
+    /// Encrypts the map and writes it to `dest`, returning the number of bytes
+    /// written. The file is written under [`temp_path`] first and renamed over
+    /// `dest`, so `dest` is never left half-written. Nothing is written when an
+    /// entry fails [`Self::check_delimiters`].
+    pub fn save(&self, dest: &Path) -> std::io::Result<usize> {
+        Self::check_delimiters(&self.composite_map)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
         // Generate fresh content from the map structure
         let mut plaintext = String::new();
         Self::serialize_composite_map_to_string(&self.composite_map, &mut plaintext, 0);
         
         let encrypted = Self::encrypt_mapper(plaintext.as_bytes());
-        fs::write(dest, encrypted)
+        let temp = temp_path(dest);
+        fs::write(&temp, &encrypted)?;
+        if let Err(e) = fs::rename(&temp, dest) {
+            fs::remove_file(&temp).ok();
+            return Err(e);
+        }
+        Ok(encrypted.len())
+    }
+
+    /// Size `save` would write. The cipher permutes bytes in place and never
+    /// pads, so this is exactly the serialized plaintext length, counted here
+    /// from the entries without building the text.
+    pub fn expected_save_size(&self) -> usize {
+        fn digits(n: usize) -> usize {
+            n.checked_ilog10().unwrap_or(0) as usize + 1
+        }
+        let mut files: HashSet<&str> = HashSet::new();
+        let mut size = 0;
+        for e in self.composite_map.values().filter(|e| !e.filename.is_empty()) {
+            // "<file>?" opens a block and "!" closes it
+            if files.insert(&e.filename) {
+                size += e.filename.len() + 2;
+            }
+            // "<object>,<composite>,<offset>,<size>,|"
+            size += e.object_path.len() + e.composite_name.len() + digits(e.offset) + digits(e.size) + 5;
+        }
+        size
+    }
+
+    /// Every object path in the map, sorted and deduplicated. Lets external modding
+    /// tools get the object list without decrypting the mapper themselves.
+    pub fn export_object_path_list(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .composite_map
+            .values()
+            .map(|e| e.object_path.clone())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Writes `export_object_path_list` to `dest`, one path per line.
+    pub fn export_object_path_list_to_file(&self, dest: &Path) -> std::io::Result<()> {
+        let mut out = self.export_object_path_list().join("\n");
+        out.push('\n');
+        fs::write(dest, out)
+    }
+
+    /// Writes the map to `dest` as unencrypted plaintext, in the layout
+    /// [`Self::save`] encrypts. Serialized from the entries in memory, so
+    /// unsaved changes are included; the map itself is left untouched.
+    pub fn export_plaintext_to_file(&self, dest: &Path) -> std::io::Result<usize> {
+        let mut plaintext = String::new();
+        Self::serialize_composite_map_to_string(&self.composite_map, &mut plaintext, 0);
+        fs::write(dest, &plaintext)?;
+        Ok(plaintext.len())
     }
 
     pub fn get_entry_by_incomplete_object_path(
@@ -79,14 +563,37 @@ impl CompositeMapperFile {
     }
 
 
+    /// Adds an entry back under its composite name. Returns false, changing
+    /// nothing, when that name is already mapped.
+    pub fn insert_entry(&mut self, entry: CompositeEntry) -> bool {
+        if self.composite_map.contains_key(&entry.composite_name) {
+            return false;
+        }
+        self.composite_map.insert(entry.composite_name.clone(), entry);
+        self.entries_changed();
+        self.cached_map.clear();
+        self.dirty = true;
+        true
+    }
+
     pub fn remove_entry(&mut self, entry: &CompositeEntry) -> bool {
         let removed = self.composite_map.shift_remove(&entry.composite_name).is_some();
         if removed {
+            self.entries_changed();
             self.cached_map.clear();
         }
         removed
     }
 
+    /// Records that entries were added, removed or replaced, for code that
+    /// changes `composite_map` directly. Lookups cached against an older
+    /// [`generation`](Self::generation) are stale. Values come from a
+    /// process-wide counter, so a freshly loaded map never repeats one.
+    pub fn entries_changed(&mut self) {
+        static GENERATION: AtomicU64 = AtomicU64::new(1);
+        self.generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+    }
+
     pub fn apply_patch(
         &mut self,
         composite_name: &str,
@@ -94,6 +601,18 @@ impl CompositeMapperFile {
         new_offset: usize,
         new_size: usize,
     ) -> Result<()> {
+        debug_assert!(!new_filename.is_empty(), "filename must not be empty");
+        if new_filename.is_empty() {
+            eprintln!("[TMM] Warning: Patching '{}' with an empty filename.", composite_name);
+        }
+        // Offsets this large can only come from a corrupted mod file
+        anyhow::ensure!(
+            new_offset <= usize::MAX / 2,
+            "Offset {} for '{}' is out of range",
+            new_offset,
+            composite_name
+        );
+
         let entry = self
             .composite_map
             .get_mut(composite_name)
@@ -103,55 +622,90 @@ impl CompositeMapperFile {
         entry.offset = new_offset;
         entry.size = new_size;
 
+        *self.patch_hit_counts.entry(composite_name.to_string()).or_default() += 1;
+
         self.dirty = true;
         Ok(())
     }
 
-    fn parse_entries_with_offsets(&mut self, data: &str) {
-        
-        let mut cursor = 0;
-
-        while let Some(q) = data[cursor..].find('?') {
-            let file_start = cursor;
-            let file_end = cursor + q;
-            let filename = &data[file_start..file_end];
-            cursor = file_end + 1;
-
-            let excl = match data[cursor..].find('!') {
-                Some(p) => cursor + p,
-                None => break,
-            };
-
-            let block = &data[cursor..excl];
-            let mut pos = 0;
-
-            while let Some(sep) = block[pos..].find(",|") {
-                let entry_start = pos;
-                let entry_end = pos + sep;
-                let slice = &block[entry_start..entry_end];
-                pos += sep + 2;
-
-                let mut it = slice.split(',');
-
-                let object_path = it.next().unwrap();
-                let composite_name = it.next().unwrap();
+    /// The `top_n` most patched composite names, most patched first.
+    pub fn get_hot_entries(&self, top_n: usize) -> Vec<(&str, u32)> {
+        let mut hot: Vec<(&str, u32)> = self
+            .patch_hit_counts
+            .iter()
+            .map(|(name, &count)| (name.as_str(), count))
+            .collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        hot.truncate(top_n);
+        hot
+    }
 
-                let offset_str = it.next().unwrap();
-                let size_str = it.next().unwrap();
+    /// Byte coverage of every container in the map, lowest coverage first.
+    /// Overlapping entries are counted twice, so coverage can exceed 100%.
+    pub fn get_file_coverage_stats(&self, mods_dir: &Path) -> Vec<FileCoverageStats> {
+        let mut covered: IndexMap<&str, u64> = IndexMap::new();
+        for entry in self.values() {
+            *covered.entry(entry.filename.as_str()).or_default() += entry.size as u64;
+        }
 
-                let entry = CompositeEntry {
-                    filename: filename.to_string(),
-                    object_path: object_path.to_string(),
-                    composite_name: composite_name.to_string(),
-                    offset: offset_str.parse().unwrap_or(0),
-                    size: size_str.parse().unwrap_or(0),
+        let mut stats: Vec<FileCoverageStats> = covered
+            .into_iter()
+            .map(|(filename, total_covered_bytes)| {
+                let file_size_bytes = fs::metadata(mods_dir.join(format!("{}.{}", filename, crate::utils::GPK_EXTENSION)))
+                    .map(|m| m.len())
+                    .unwrap_or(0);
+                let coverage_percent = if file_size_bytes == 0 {
+                    0.0
+                } else {
+                    (total_covered_bytes as f64 / file_size_bytes as f64 * 100.0) as f32
                 };
+                FileCoverageStats {
+                    filename: filename.to_string(),
+                    file_size_bytes,
+                    total_covered_bytes,
+                    coverage_percent,
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.coverage_percent.total_cmp(&b.coverage_percent));
+        stats
+    }
 
-                self.composite_map.insert(entry.composite_name.clone(), entry);
+    fn parse_entries_with_offsets(&mut self, data: &str) {
+        let (entries, errors) = parse_entries(data);
+        self.empty_blocks.clear();
+        self.parse_errors.clear();
+        for error in errors {
+            match error {
+                ParseError::EmptyBlock { filename } => self.empty_blocks.push(filename),
+                other => self.parse_errors.push(other),
             }
+        }
+        for entry in entries {
+            self.composite_map.insert(entry.composite_name.clone(), entry);
+        }
+    }
 
-            cursor = excl + 1;
+    /// First entry with a delimiter in its filename, object path or composite
+    /// name. Install-time checks normally keep these out, but entries can also
+    /// come from plaintext files and hand edits.
+    pub fn check_delimiters(composite_map: &IndexMap<String, CompositeEntry>) -> Result<(), DelimiterError> {
+        for (key, entry) in composite_map {
+            let fields = [
+                ("filename", &entry.filename),
+                ("object path", &entry.object_path),
+                ("composite name", &entry.composite_name),
+                ("composite name", key),
+            ];
+            if let Some((field, value)) = fields.into_iter().find(|(_, value)| contains_mapper_delimiter(value)) {
+                return Err(DelimiterError {
+                    composite_name: key.clone(),
+                    field,
+                    value: value.clone(),
+                });
+            }
         }
+        Ok(())
     }
 
     pub fn serialize_composite_map_to_string(
@@ -172,12 +726,14 @@ impl CompositeMapperFile {
 
         // Sort by offset, not composite_name. The game engine relies on offset order.
         for entries in by_file.values_mut() {
-            entries.sort_by(|a, b| a.offset.cmp(&b.offset));
+            entries.sort_by_key(|e| e.offset);
         }
 
         for (filename, entries) in by_file {
-            if filename.is_empty() {
-                continue; // Skip entries with empty filenames to prevent invalid map blocks
+            // Skip entries with empty filenames to prevent invalid map blocks, and
+            // never emit a filename immediately followed by '!'
+            if filename.is_empty() || entries.is_empty() {
+                continue;
             }
             
             output.push_str(filename);
@@ -211,7 +767,7 @@ impl CompositeMapperFile {
         if size > 2 {
             let mut a = 1usize;
             let mut b = size - 1;
-            let count = (size / 2 + 1) / 2;
+            let count = (size / 2).div_ceil(2);
             for _ in 0..count {
                 encrypted.swap(a, b);
                 a += 2;
@@ -251,7 +807,7 @@ impl CompositeMapperFile {
             if size > 2 {
                 let mut a = 1usize;
                 let mut b = size - 1;
-                let count = (size / 2 + 1) / 2;
+                let count = (size / 2).div_ceil(2);
                 for _ in 0..count {
                     decrypted.swap(a, b);
                     a += 2;
@@ -267,3 +823,381 @@ impl CompositeMapperFile {
             Ok(String::from_utf8_lossy(&decrypted).into_owned())
         }
 }
+
+/// The clean backup mapper. It wraps a [`CompositeMapperFile`] but only exposes
+/// lookups, iteration and stats, so patching or removing entries from the
+/// backup doesn't compile. Refreshing the backup means loading a new `CleanMap`.
+#[derive(Default, Clone)]
+pub struct CleanMap(CompositeMapperFile);
+
+impl CleanMap {
+    pub fn new(source_path: PathBuf) -> std::io::Result<Self> {
+        CompositeMapperFile::new(source_path).map(Self)
+    }
+
+    /// Number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, composite_name: &str) -> Option<&CompositeEntry> {
+        self.0.composite_map.get(composite_name)
+    }
+
+    /// Entries in file order.
+    pub fn values(&self) -> impl Iterator<Item = &CompositeEntry> {
+        self.0.values()
+    }
+
+    /// All entries keyed by composite name, e.g. to reset an active map to vanilla.
+    pub fn entries(&self) -> &IndexMap<String, CompositeEntry> {
+        &self.0.composite_map
+    }
+
+    pub fn get_entry_by_incomplete_object_path(&self, path: &str, output: &mut CompositeEntry) -> bool {
+        self.0.get_entry_by_incomplete_object_path(path, output)
+    }
+
+    /// See [`CompositeMapperFile::export_object_path_list_to_file`].
+    pub fn export_object_path_list_to_file(&self, dest: &Path) -> std::io::Result<()> {
+        self.0.export_object_path_list_to_file(dest)
+    }
+
+    /// See [`CompositeMapperFile::export_plaintext_to_file`].
+    pub fn export_plaintext_to_file(&self, dest: &Path) -> std::io::Result<usize> {
+        self.0.export_plaintext_to_file(dest)
+    }
+}
+
+/// Takes over an already loaded map as the clean backup.
+impl From<CompositeMapperFile> for CleanMap {
+    fn from(map: CompositeMapperFile) -> Self {
+        Self(map)
+    }
+}
+
+impl<'a> IntoIterator for &'a CleanMap {
+    type Item = (&'a String, &'a CompositeEntry);
+    type IntoIter = indexmap::map::Iter<'a, String, CompositeEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.composite_map.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(n: usize) -> String {
+        let mut text = "S1Data_Test?".to_string();
+        for i in 0..n {
+            text.push_str(&format!("Test.Object{},test_{:04},{},10,|", i, i, i * 10));
+        }
+        text.push('!');
+        text
+    }
+
+    // Writes `plaintext` encrypted, as the client stores it, and loads it
+    fn load(name: &str, plaintext: &str) -> std::io::Result<CompositeMapperFile> {
+        let path = std::env::temp_dir().join(format!("tmm-mapper-{}-{}.dat", name, std::process::id()));
+        fs::write(&path, CompositeMapperFile::encrypt_mapper(plaintext.as_bytes())).unwrap();
+        let map = CompositeMapperFile::new(path.clone());
+        fs::remove_file(path).ok();
+        map
+    }
+
+    // Loads `data` exactly as written, without encrypting it first
+    fn load_raw(name: &str, data: &[u8]) -> std::io::Result<CompositeMapperFile> {
+        let path = std::env::temp_dir().join(format!("tmm-mapper-raw-{}-{}.dat", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        let map = CompositeMapperFile::new(path.clone());
+        fs::remove_file(path).ok();
+        map
+    }
+
+    #[test]
+    fn encrypted_mapper_loads_as_encrypted() {
+        let map = load("encrypted", &entries(3)).unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert!(!map.loaded_from_plaintext);
+    }
+
+    #[test]
+    fn already_decrypted_mapper_is_read_as_plaintext() {
+        let map = load_raw("plaintext", format!("\u{FEFF}{}\r\n", entries(3)).as_bytes()).unwrap();
+
+        assert_eq!(map.len(), 3);
+        assert!(map.loaded_from_plaintext);
+        // The BOM (one char) and CR LF
+        assert_eq!(map.normalized_chars, 3);
+        assert_eq!(map.plaintext, entries(3));
+    }
+
+    #[test]
+    fn empty_file_is_reported_as_empty() {
+        let error = load_raw("zero", &[]).err().unwrap().to_string();
+
+        assert!(error.contains("is empty (0 bytes)"), "{}", error);
+    }
+
+    #[test]
+    fn error_page_is_reported_with_its_size_and_first_bytes() {
+        let page = b"<html><body>404 Not Found</body></html>";
+        let error = load_raw("html", page).err().unwrap().to_string();
+
+        assert!(error.contains(&format!("{} bytes", page.len())), "{}", error);
+        assert!(error.contains("3C 68 74 6D 6C"), "{}", error);
+        assert!(error.contains("\"<html><body>404 \""), "{}", error);
+    }
+
+    #[test]
+    fn unterminated_block_keeps_earlier_blocks_for_the_same_file() {
+        let text = "S1Data_A?A.One,a_0001,0,10,|!S1Data_B?B.One,b_0001,0,10,|!S1Data_A?A.Two,a_0002,10,10,|";
+        let (parsed, errors) = parse_entries(text);
+
+        let names: Vec<&str> = parsed.iter().map(|e| e.composite_name.as_str()).collect();
+        assert_eq!(names, ["a_0001", "b_0001"]);
+        assert!(matches!(&errors[..], [ParseError::UnterminatedBlock { filename, .. }] if filename == "S1Data_A"));
+    }
+
+    #[test]
+    fn normalize_counts_the_bom_and_every_line_break() {
+        assert_eq!(normalize_plaintext("A?B,|!"), ("A?B,|!".to_string(), 0));
+        assert_eq!(normalize_plaintext("\u{FEFF}A?\r\nB,|\n!\r\n"), ("A?B,|!".to_string(), 6));
+        // A lone CR, as old Mac editors write, counts too
+        assert_eq!(normalize_plaintext("A?\rB,|!").1, 1);
+    }
+
+    #[test]
+    fn notepad_mangled_dump_imports_as_the_clean_dump() {
+        let clean = format!("{}S1Data_Other?Other.Object,other_0001,0,123,|!", entries(4));
+        let mangled = format!("\u{FEFF}{}\r\n", clean.replace(",|", ",|\r\n").replace('!', "!\r\n"));
+
+        let imported = CompositeMapperFile {
+            composite_map: parse_plaintext_strict(&mangled).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(serialized(&imported), clean);
+        assert!(normalize_plaintext(&mangled).1 > 0);
+    }
+
+    #[test]
+    fn strict_parse_errors_point_into_the_original_text() {
+        let good = "S1Data_Test?A.B,a_0001,0,10,|";
+        let bad = "oops,|!";
+        for text in [
+            format!("{}{}", good, bad),
+            format!("\u{FEFF}{}\r\n{}", good, bad),
+            format!("{}\n\n\r\n{}\r\n", good, bad),
+        ] {
+            let error = parse_plaintext_strict(&text).err().unwrap().to_string();
+            let offset = text.find("oops").unwrap();
+            assert!(error.contains(&format!("at byte {}:", offset)), "{:?} for {:?}", error, text);
+        }
+    }
+
+    #[test]
+    fn strict_parse_offsets_count_bytes_not_chars() {
+        let text = "\u{FEFF}S1Data_Tëst?\r\nA.B,a_0001,0,10,|\r\n";
+        let error = parse_plaintext_strict(text).err().unwrap().to_string();
+        // The block starts right after the 3-byte BOM
+        assert!(error.contains("at byte 3 "), "{:?}", error);
+    }
+
+    #[test]
+    fn generation_changes_with_the_entries() {
+        let mut map = load("generation", &entries(3)).unwrap();
+        let reloaded = load("generation", &entries(3)).unwrap();
+        assert_ne!(map.generation, reloaded.generation);
+
+        let loaded = map.generation;
+        let entry = map.values().next().unwrap().clone();
+        assert!(!map.insert_entry(entry.clone()));
+        assert_eq!(map.generation, loaded);
+
+        assert!(map.remove_entry(&entry));
+        let removed = map.generation;
+        assert_ne!(removed, loaded);
+        assert!(map.insert_entry(entry));
+        assert_ne!(map.generation, removed);
+
+        // Patching moves entries but doesn't change which paths resolve
+        let patched = map.generation;
+        map.apply_patch("test_0001", "Other", 0, 10).unwrap();
+        assert_eq!(map.generation, patched);
+    }
+
+    #[test]
+    fn map_with_only_empty_blocks_loads_and_is_too_small() {
+        let map = load("empty-blocks", "S1Data_Test?!").unwrap();
+
+        assert!(map.is_empty());
+        assert_eq!(map.empty_blocks, ["S1Data_Test"]);
+        assert!(map.is_too_small(1));
+    }
+
+    fn serialized(map: &CompositeMapperFile) -> String {
+        let mut text = String::new();
+        CompositeMapperFile::serialize_composite_map_to_string(&map.composite_map, &mut text, 0);
+        text
+    }
+
+    #[test]
+    fn removing_the_last_entry_of_a_block_drops_the_block() {
+        let a = "S1Data_A?A.Only,a_0001,0,10,|!";
+        let b = "S1Data_B?B.One,b_0001,0,10,|B.Two,b_0002,10,20,|!";
+        let c = "S1Data_C?C.Only,c_0001,0,30,|!";
+        let mut map = load("prune", &format!("{}{}{}", a, b, c)).unwrap();
+        assert_eq!(serialized(&map), format!("{}{}{}", a, b, c));
+
+        let only = map.values().next().unwrap().clone();
+        assert!(map.remove_entry(&only));
+
+        // The other blocks are written exactly as they were
+        assert_eq!(serialized(&map), format!("{}{}", b, c));
+    }
+
+    #[test]
+    fn loaded_empty_blocks_are_flagged_and_not_written_back() {
+        let map = load("flag-empty", "S1Data_Empty?!S1Data_A?A.Only,a_0001,0,10,|!").unwrap();
+
+        assert_eq!(map.empty_blocks, ["S1Data_Empty"]);
+        assert_eq!(serialized(&map), "S1Data_A?A.Only,a_0001,0,10,|!");
+    }
+
+    fn clean_map(name: &str, plaintext: &str) -> CleanMap {
+        let path = std::env::temp_dir().join(format!("tmm-clean-{}-{}.dat", name, std::process::id()));
+        fs::write(&path, CompositeMapperFile::encrypt_mapper(plaintext.as_bytes())).unwrap();
+        let map = CleanMap::new(path.clone()).unwrap();
+        fs::remove_file(path).ok();
+        map
+    }
+
+    #[test]
+    fn clean_map_reads_like_the_map_it_wraps() {
+        let text = format!("{}S1Data_Other?Other.Object_C,other_0001,0,5,|!", entries(3));
+        let clean = clean_map("reads", &text);
+        let map = load("reads", &text).unwrap();
+
+        assert_eq!(clean.len(), 4);
+        assert!(!clean.is_empty());
+        assert_eq!(clean.get("test_0001").unwrap().object_path, "Test.Object1");
+        assert!(clean.get("missing").is_none());
+        let names: Vec<&String> = clean.values().map(|e| &e.composite_name).collect();
+        assert_eq!(names, map.keys().collect::<Vec<_>>());
+        assert_eq!(clean.entries().len(), 4);
+        assert_eq!((&clean).into_iter().count(), 4);
+
+        // Lookups tolerate the package prefix and class suffix, as on the map
+        let mut found = CompositeEntry::default();
+        assert!(clean.get_entry_by_incomplete_object_path("Object", &mut found));
+        assert_eq!(found.composite_name, "other_0001");
+        assert!(!clean.get_entry_by_incomplete_object_path("Nowhere.Missing", &mut found));
+    }
+
+    #[test]
+    fn clean_map_taken_from_a_map_is_unaffected_by_its_clone() {
+        let mut active = load("clone", &entries(2)).unwrap();
+        let clean = CleanMap::from(active.clone());
+
+        active.apply_patch("test_0000", "ModContainer", 100, 5).unwrap();
+        let only = active.values().last().unwrap().clone();
+        assert!(active.remove_entry(&only));
+
+        assert_eq!(clean.len(), 2);
+        assert_eq!(clean.get("test_0000").unwrap().filename, "S1Data_Test");
+        assert_eq!(clean.get("test_0000").unwrap().offset, 0);
+    }
+
+    #[test]
+    fn clean_map_exports_match_the_wrapped_map() {
+        let text = format!("{}S1Data_Other?Test.Object1,other_0001,0,5,|!", entries(2));
+        let clean = clean_map("exports", &text);
+        let dir = std::env::temp_dir();
+        let (paths, plain) = (
+            dir.join(format!("tmm-clean-paths-{}.txt", std::process::id())),
+            dir.join(format!("tmm-clean-plain-{}.txt", std::process::id())),
+        );
+
+        clean.export_object_path_list_to_file(&paths).unwrap();
+        let written = clean.export_plaintext_to_file(&plain).unwrap();
+        let (paths_text, plain_text) = (fs::read_to_string(&paths).unwrap(), fs::read_to_string(&plain).unwrap());
+        fs::remove_file(paths).ok();
+        fs::remove_file(plain).ok();
+
+        // Sorted, with the path both blocks use listed once
+        assert_eq!(paths_text, "Test.Object0\nTest.Object1\n");
+        assert_eq!(plain_text, text);
+        assert_eq!(written, text.len());
+    }
+
+    #[test]
+    fn poisoned_entries_are_never_saved() {
+        let path = std::env::temp_dir().join(format!("tmm-mapper-poisoned-{}.dat", std::process::id()));
+        let clean = load("poison-source", &entries(2)).unwrap();
+        clean.save(&path).unwrap();
+        let on_disk = fs::read(&path).unwrap();
+
+        for delimiter in MAPPER_DELIMITERS {
+            for field in ["filename", "object path", "composite name"] {
+                let mut map = clean.clone();
+                let mut entry = map.values().next().unwrap().clone();
+                match field {
+                    "filename" => entry.filename.push(delimiter),
+                    "object path" => entry.object_path.insert(0, delimiter),
+                    _ => entry.composite_name = format!("bad{}name", delimiter),
+                }
+                map.composite_map.insert(entry.composite_name.clone(), entry);
+
+                let error = map.save(&path).unwrap_err();
+                assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+                assert!(error.to_string().contains(field), "{} for {:?}", error, delimiter);
+                assert_eq!(fs::read(&path).unwrap(), on_disk);
+                assert!(!temp_path(&path).exists());
+            }
+        }
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn poisoned_imports_are_rejected() {
+        let error = parse_plaintext_strict("S1Data_Test?Bad|Path,a_0001,0,10,|!").err();
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn text_without_blocks_is_rejected() {
+        assert!(load("no-blocks", "").is_err());
+        assert!(load("garbage", "<html>not a mapper</html>").is_err());
+    }
+
+    #[test]
+    fn expected_save_size_matches_the_written_file() {
+        let mut text = entries(12);
+        text.push_str("S1Data_Other?Other.Object,other_0001,0,123456,|!");
+        let map = load("expected-size", &text).unwrap();
+        let path = std::env::temp_dir().join(format!("tmm-mapper-expected-size-saved-{}.dat", std::process::id()));
+        let written = map.save(&path).unwrap();
+        fs::remove_file(path).ok();
+
+        assert_eq!(written, map.expected_save_size());
+        assert_eq!(CompositeMapperFile::default().expected_save_size(), 0);
+    }
+
+    #[test]
+    fn too_small_threshold_is_exclusive() {
+        let map = load("threshold", &entries(5)).unwrap();
+
+        assert_eq!(map.len(), 5);
+        assert!(map.is_too_small(6));
+        assert!(!map.is_too_small(5));
+        assert!(!map.is_too_small(4));
+        assert!(!map.is_too_small(0));
+    }
+}
@@ -0,0 +1,114 @@
+use directories::ProjectDirs;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use crate::utils;
+
+const STARTUP_ERROR_LOG: &str = "startup_error.log";
+
+// Release builds on Windows use the windows subsystem and start without a
+// console, so println!/eprintln! go nowhere. Output is only worth a console
+// when flags were passed (their messages are meant to be read) and stdout
+// isn't already going somewhere, e.g. redirected to a file.
+// `args` are the command-line arguments after the program name.
+pub fn console_needed(args: &[String], stdout_attached: bool) -> bool {
+    !args.is_empty() && !stdout_attached
+}
+
+// Call first thing in main, before anything prints
+pub fn init(args: &[String]) {
+    if console_needed(args, platform::stdout_attached()) {
+        platform::attach_parent_console();
+    }
+}
+
+// For failures before the window exists: the message goes to the console if
+// there is one, to startup_error.log in the data dir, and to a message box.
+pub fn startup_error(message: &str) {
+    if !platform::stdout_attached() {
+        platform::attach_parent_console();
+    }
+    eprintln!("[TMM] {}", message);
+
+    if let Err(e) = append_startup_log(message) {
+        eprintln!("[TMM] Failed to write {}: {}", STARTUP_ERROR_LOG, e);
+    }
+
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title("Tera Mod Manager")
+        .set_description(message)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+// For startup problems TMM can run without: console and startup_error.log,
+// no message box
+pub fn startup_warning(message: &str) {
+    eprintln!("[TMM] Warning: {}", message);
+    if let Err(e) = append_startup_log(&format!("Warning: {}", message)) {
+        eprintln!("[TMM] Failed to write {}: {}", STARTUP_ERROR_LOG, e);
+    }
+}
+
+fn append_startup_log(message: &str) -> std::io::Result<()> {
+    let Some(dirs) = ProjectDirs::from("com", "borkycode", "tera-mod-manager") else {
+        return Ok(());
+    };
+    fs::create_dir_all(dirs.data_dir())?;
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dirs.data_dir().join(STARTUP_ERROR_LOG))?;
+    writeln!(log, "[{}] {}", utils::format_utc(utils::unix_now()), message)
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::Win32::System::Console::{AttachConsole, GetStdHandle, ATTACH_PARENT_PROCESS, STD_OUTPUT_HANDLE};
+
+    pub fn stdout_attached() -> bool {
+        // SAFETY: GetStdHandle only reads the process's standard handle table
+        unsafe { GetStdHandle(STD_OUTPUT_HANDLE) }.is_ok_and(|h| !h.is_invalid())
+    }
+
+    // The console of the shell TMM was started from. Started from Explorer or
+    // a shortcut there is none, and no new console window is opened for it.
+    pub fn attach_parent_console() {
+        // SAFETY: fails harmlessly when there is no parent console or one is already attached
+        unsafe { AttachConsole(ATTACH_PARENT_PROCESS) }.ok();
+    }
+}
+
+// Everywhere else the process keeps the console it was started with
+#[cfg(not(target_os = "windows"))]
+mod platform {
+    pub fn stdout_attached() -> bool {
+        true
+    }
+
+    pub fn attach_parent_console() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_need_a_console_when_output_goes_nowhere() {
+        assert!(console_needed(&args(&["--root", "D:\\TERA\\Client\\S1Game"]), false));
+        assert!(console_needed(&args(&["--help"]), false));
+    }
+
+    #[test]
+    fn no_console_without_flags_or_with_stdout_attached() {
+        assert!(!console_needed(&[], false));
+        assert!(!console_needed(&[], true));
+        // Redirected to a file or run from a console that is already attached
+        assert!(!console_needed(&args(&["--help"]), true));
+    }
+}
@@ -0,0 +1,114 @@
+use discord_rich_presence::{activity::Activity, DiscordIpc, DiscordIpcClient};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Discord accepts about five activity updates per 20 seconds
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(15);
+// How long to wait before trying again when Discord isn't running
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Presence {
+    pub details: String,
+    pub state: String,
+}
+
+// Rich Presence runs on its own thread so a missing or slow Discord never
+// blocks the UI. Dropping this stops the thread and clears the activity.
+pub struct DiscordPresence {
+    client_id: String,
+    tx: Sender<Presence>,
+    last_sent: Option<Presence>,
+}
+
+// Discord shows an activity only under an application registered on its
+// developer portal. TMM has none of its own, so the user supplies the
+// Application ID, a numeric snowflake.
+pub fn is_valid_client_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 20 && id.bytes().all(|b| b.is_ascii_digit())
+}
+
+impl DiscordPresence {
+    pub fn start(client_id: String) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let id = client_id.clone();
+        thread::spawn(move || run(&id, rx));
+        Self { client_id, tx, last_sent: None }
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn update(&mut self, presence: Presence) {
+        if self.last_sent.as_ref() == Some(&presence) {
+            return;
+        }
+        self.tx.send(presence.clone()).ok();
+        self.last_sent = Some(presence);
+    }
+}
+
+fn run(client_id: &str, rx: Receiver<Presence>) {
+    let mut client = DiscordIpcClient::new(client_id);
+    let mut connected = false;
+    let mut last_attempt: Option<Instant> = None;
+    let mut last_update: Option<Instant> = None;
+    let mut pending: Option<Presence> = None;
+
+    loop {
+        match rx.recv_timeout(MIN_UPDATE_INTERVAL) {
+            Ok(presence) => pending = Some(presence),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let Some(presence) = &pending else {
+            continue;
+        };
+        if last_update.is_some_and(|t| t.elapsed() < MIN_UPDATE_INTERVAL) {
+            continue;
+        }
+
+        if !connected {
+            if last_attempt.is_some_and(|t| t.elapsed() < RECONNECT_INTERVAL) {
+                continue;
+            }
+            last_attempt = Some(Instant::now());
+            connected = client.connect().is_ok();
+            if !connected {
+                continue;
+            }
+        }
+
+        let activity = Activity::new()
+            .details(presence.details.as_str())
+            .state(presence.state.as_str());
+        if client.set_activity(activity).is_ok() {
+            last_update = Some(Instant::now());
+            pending = None;
+        } else {
+            // Discord went away; reconnect on a later pass
+            connected = false;
+        }
+    }
+
+    if connected {
+        client.clear_activity().ok();
+        client.close().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_ids_are_numeric_snowflakes() {
+        assert!(is_valid_client_id("1234567890123456789"));
+        assert!(!is_valid_client_id(""));
+        assert!(!is_valid_client_id("12345 67890"));
+        assert!(!is_valid_client_id("my-app"));
+        assert!(!is_valid_client_id("123456789012345678901"));
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// Known CompositePackageMapper.dat hashes (SHA-256 of the encrypted file, the
+// same hash the checksum sidecar records). The embedded copy ships with TMM
+// and holds no entries yet. Lists the community collects are imported from
+// Settings into mapper_fingerprints.json in TMM's data folder and merged with
+// the embedded copy on load, so they need no release.
+const EMBEDDED_DB: &str = include_str!("../assets/mapper_fingerprints.json");
+pub const FINGERPRINT_DB_FILE: &str = "mapper_fingerprints.json";
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FingerprintDb {
+    pub version: u32,
+    pub clean: Vec<CleanFingerprint>,
+    pub modded: Vec<ModdedFingerprint>,
+}
+
+// An untouched mapper as shipped with a client build
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CleanFingerprint {
+    pub sha256: String,
+    pub build: String,
+}
+
+// A mapper as left behind by another mod tool
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ModdedFingerprint {
+    pub sha256: String,
+    pub tool: String,
+}
+
+pub enum Fingerprint {
+    Clean { build: String },
+    Modded { tool: String },
+    Unknown,
+}
+
+impl FingerprintDb {
+    // Never fails: a missing or unreadable list in the data folder is skipped
+    // and callers fall back to their own heuristics for unknown hashes.
+    pub fn load(data_dir: Option<&Path>) -> Self {
+        let mut db: Self = serde_json::from_str(EMBEDDED_DB).unwrap_or_default();
+        let Some(path) = data_dir.map(|dir| dir.join(FINGERPRINT_DB_FILE)) else {
+            return db;
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return db;
+        };
+        match Self::parse(&text) {
+            Ok(local) => db.merge(local),
+            Err(e) => eprintln!("[TMM] Ignoring {}: {:#}", path.display(), e),
+        }
+        db
+    }
+
+    // Parses a fingerprint list, rejecting entries that couldn't match
+    pub fn parse(text: &str) -> Result<Self> {
+        let db: Self = serde_json::from_str(text)?;
+        let hashes = db.clean.iter().map(|f| &f.sha256).chain(db.modded.iter().map(|f| &f.sha256));
+        for hash in hashes {
+            anyhow::ensure!(
+                hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit()),
+                "'{}' is not a SHA-256 hash",
+                hash
+            );
+        }
+        anyhow::ensure!(db.clean.iter().all(|f| !f.build.is_empty()), "a clean entry has no build");
+        anyhow::ensure!(db.modded.iter().all(|f| !f.tool.is_empty()), "a modded entry has no tool");
+        Ok(db)
+    }
+
+    // Adds the hashes `other` knows and this list doesn't; known hashes keep
+    // their entry
+    pub fn merge(&mut self, other: Self) {
+        self.version = self.version.max(other.version);
+        for f in other.clean {
+            if matches!(self.classify(&f.sha256), Fingerprint::Unknown) {
+                self.clean.push(f);
+            }
+        }
+        for f in other.modded {
+            if matches!(self.classify(&f.sha256), Fingerprint::Unknown) {
+                self.modded.push(f);
+            }
+        }
+    }
+
+    // Merges the list in `text` into the one in `data_dir` and writes it back.
+    // Returns how many hashes were new.
+    pub fn import(data_dir: &Path, text: &str) -> Result<usize> {
+        let imported = Self::parse(text)?;
+        let path = data_dir.join(FINGERPRINT_DB_FILE);
+        let mut local = match fs::read_to_string(&path) {
+            Ok(existing) => Self::parse(&existing).with_context(|| format!("{} is invalid", path.display()))?,
+            Err(_) => Self::default(),
+        };
+        let before = local.len();
+        local.merge(imported);
+        fs::create_dir_all(data_dir)?;
+        fs::write(&path, serde_json::to_string_pretty(&local)?)?;
+        Ok(local.len() - before)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clean.len() + self.modded.len()
+    }
+
+    pub fn classify(&self, sha256: &str) -> Fingerprint {
+        if let Some(f) = self.clean.iter().find(|f| f.sha256.eq_ignore_ascii_case(sha256)) {
+            return Fingerprint::Clean { build: f.build.clone() };
+        }
+        if let Some(f) = self.modded.iter().find(|f| f.sha256.eq_ignore_ascii_case(sha256)) {
+            return Fingerprint::Modded { tool: f.tool.clone() };
+        }
+        Fingerprint::Unknown
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clean.is_empty() && self.modded.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const HASH_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn list(version: u32, clean: &[(&str, &str)], modded: &[(&str, &str)]) -> String {
+        serde_json::json!({
+            "version": version,
+            "clean": clean.iter().map(|(h, b)| serde_json::json!({"sha256": h, "build": b})).collect::<Vec<_>>(),
+            "modded": modded.iter().map(|(h, t)| serde_json::json!({"sha256": h, "tool": t})).collect::<Vec<_>>(),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn embedded_list_parses() {
+        assert!(FingerprintDb::parse(EMBEDDED_DB).is_ok());
+    }
+
+    #[test]
+    fn parse_rejects_entries_that_cannot_match() {
+        assert!(FingerprintDb::parse(&list(1, &[("abc", "100.02")], &[])).is_err());
+        assert!(FingerprintDb::parse(&list(1, &[(HASH_A, "")], &[])).is_err());
+        assert!(FingerprintDb::parse(&list(1, &[], &[(HASH_B, "")])).is_err());
+        assert!(FingerprintDb::parse("not json").is_err());
+    }
+
+    #[test]
+    fn classify_ignores_case() {
+        let db = FingerprintDb::parse(&list(1, &[(HASH_A, "100.02")], &[(HASH_B, "Other tool")])).unwrap();
+        assert!(matches!(db.classify(&HASH_A.to_uppercase()), Fingerprint::Clean { build } if build == "100.02"));
+        assert!(matches!(db.classify(HASH_B), Fingerprint::Modded { tool } if tool == "Other tool"));
+        assert!(matches!(db.classify(&"c".repeat(64)), Fingerprint::Unknown));
+    }
+
+    #[test]
+    fn imports_accumulate_and_load_merges_them() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(FingerprintDb::import(dir.path(), &list(2, &[(HASH_A, "100.02")], &[])).unwrap(), 1);
+        // Known hashes keep their first entry
+        assert_eq!(
+            FingerprintDb::import(dir.path(), &list(1, &[(HASH_A, "other")], &[(HASH_B, "Other tool")])).unwrap(),
+            1
+        );
+
+        let db = FingerprintDb::load(Some(dir.path()));
+        assert_eq!(db.len(), FingerprintDb::load(None).len() + 2);
+        assert!(matches!(db.classify(HASH_A), Fingerprint::Clean { build } if build == "100.02"));
+    }
+
+    #[test]
+    fn invalid_files_are_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(FINGERPRINT_DB_FILE), "{ broken").unwrap();
+
+        assert!(FingerprintDb::import(dir.path(), &list(1, &[(HASH_A, "100.02")], &[])).is_err());
+        assert_eq!(fs::read_to_string(dir.path().join(FINGERPRINT_DB_FILE)).unwrap(), "{ broken");
+        // Loading falls back to the embedded list
+        assert_eq!(FingerprintDb::load(Some(dir.path())).len(), FingerprintDb::load(None).len());
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use crate::utils;
+
+// Hashes files on a worker thread so checking a 500 MB package doesn't stall
+// the UI. Poll once per frame until the results arrive.
+pub struct HashJob {
+    rx: Receiver<Vec<Result<String, String>>>,
+    count: usize,
+}
+
+impl HashJob {
+    pub fn start(paths: Vec<PathBuf>) -> Self {
+        let count = paths.len();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let hashes = paths
+                .iter()
+                .map(|path| utils::sha256_file(path).map_err(|e| format!("{}: {}", path.display(), e)))
+                .collect();
+            tx.send(hashes).ok();
+        });
+        Self { rx, count }
+    }
+
+    // One hash per path in the order given, None while still running
+    pub fn poll(&self) -> Option<Vec<Result<String, String>>> {
+        match self.rx.try_recv() {
+            Ok(hashes) => Some(hashes),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(vec![Err("hashing stopped unexpectedly".to_string()); self.count]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    fn wait(job: &HashJob) -> Vec<Result<String, String>> {
+        let started = Instant::now();
+        loop {
+            if let Some(hashes) = job.poll() {
+                return hashes;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "hashing never finished");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn hashes_each_path_in_order() {
+        let dir = std::env::temp_dir().join(format!("tmm-hash-job-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.gpk"), dir.join("b.gpk"));
+        // Larger than one read chunk, so the streamed hash covers several reads
+        let big = vec![7u8; (1 << 20) + 3];
+        fs::write(&a, &big).unwrap();
+        fs::write(&b, b"small").unwrap();
+
+        let hashes = wait(&HashJob::start(vec![a, dir.join("missing.gpk"), b]));
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(hashes[0], Ok(utils::sha256_hex(&big)));
+        assert!(hashes[1].as_ref().is_err_and(|e| e.contains("missing.gpk")));
+        assert_eq!(hashes[2], Ok(utils::sha256_hex(b"small")));
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::mod_state::ChangeOrigin;
+
+// A hook that hasn't exited by then is killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Written to the hook's stdin as one JSON object. `event` names the variant in
+// snake_case; the field names are part of the hook interface and must not change.
+#[derive(Serialize)]
+pub struct HookPayload {
+    #[serde(flatten)]
+    pub event: HookEvent,
+    pub timestamp: u64,
+    pub tmm_version: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    ModInstalled { mod_name: String, file: String, enabled: bool },
+    ModEnabled { mod_name: String, file: String, origin: ChangeOrigin },
+    ModDisabled { mod_name: String, file: String, origin: ChangeOrigin },
+    ApplyCompleted { results: Vec<ModApplyResult> },
+    RestoreCompleted { success: bool },
+}
+
+impl HookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ModInstalled { .. } => "mod_installed",
+            Self::ModEnabled { .. } => "mod_enabled",
+            Self::ModDisabled { .. } => "mod_disabled",
+            Self::ApplyCompleted { .. } => "apply_completed",
+            Self::RestoreCompleted { .. } => "restore_completed",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ModApplyResult {
+    pub mod_name: String,
+    pub file: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+// Runs the user's event hook command for each event, one at a time, on a
+// worker thread so a slow or hanging script never stalls the UI. `{event}` in
+// the command is replaced with the event name. Dropping this ends the thread
+// once the queue is drained.
+pub struct EventHook {
+    tx: Sender<(String, HookPayload)>,
+}
+
+impl EventHook {
+    pub fn start() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || run(rx));
+        Self { tx }
+    }
+
+    pub fn send(&self, command: &str, payload: HookPayload) {
+        let command = command.replace("{event}", payload.event.name());
+        self.tx.send((command, payload)).ok();
+    }
+}
+
+fn run(rx: Receiver<(String, HookPayload)>) {
+    for (command, payload) in rx {
+        let event = payload.event.name();
+        if let Err(e) = invoke(&command, &payload, HOOK_TIMEOUT) {
+            eprintln!("[TMM] Event hook for {} failed: {}", event, e);
+        }
+    }
+}
+
+fn invoke(command: &str, payload: &HookPayload, timeout: Duration) -> anyhow::Result<()> {
+    let json = serde_json::to_vec(payload)?;
+    let mut child = shell(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Written on its own thread: a hook that never reads would block the
+    // write once the pipe is full, before the timeout below could start. The
+    // thread isn't joined, since processes the hook started may hold the pipe
+    // open after it is killed. A hook that closes stdin early is fine.
+    if let Some(mut stdin) = child.stdin.take() {
+        thread::spawn(move || {
+            stdin.write_all(&json).ok();
+        });
+    }
+
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            if !status.success() {
+                anyhow::bail!("exited with {}", status);
+            }
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            child.kill().ok();
+            child.wait().ok();
+            anyhow::bail!("timed out after {:.1}s", timeout.as_secs_f32());
+        }
+        thread::sleep(HOOK_POLL_INTERVAL);
+    }
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so hooks don't flash a console
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command).creation_flags(CREATE_NO_WINDOW);
+        cmd
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn payload(event: HookEvent) -> Value {
+        serde_json::to_value(HookPayload { event, timestamp: 1_700_000_000, tmm_version: "1.0.0" }).unwrap()
+    }
+
+    // Hooks read these names; changing any of them breaks user scripts
+    #[test]
+    fn payload_field_names_are_stable() {
+        assert_eq!(
+            payload(HookEvent::ModInstalled { mod_name: "Armor".into(), file: "Armor.gpk".into(), enabled: true }),
+            json!({
+                "event": "mod_installed",
+                "mod_name": "Armor",
+                "file": "Armor.gpk",
+                "enabled": true,
+                "timestamp": 1_700_000_000,
+                "tmm_version": "1.0.0",
+            })
+        );
+        assert_eq!(
+            payload(HookEvent::ModEnabled { mod_name: "Armor".into(), file: "Armor.gpk".into(), origin: ChangeOrigin::ConflictWindow }),
+            json!({
+                "event": "mod_enabled",
+                "mod_name": "Armor",
+                "file": "Armor.gpk",
+                "origin": "conflict_window",
+                "timestamp": 1_700_000_000,
+                "tmm_version": "1.0.0",
+            })
+        );
+        assert_eq!(payload(HookEvent::ModDisabled { mod_name: "A".into(), file: "A.gpk".into(), origin: ChangeOrigin::DisableAll })["origin"], "disable_all");
+        assert_eq!(
+            payload(HookEvent::ApplyCompleted {
+                results: vec![ModApplyResult { mod_name: "A".into(), file: "A.gpk".into(), success: false, error: Some("boom".into()) }],
+            })["results"],
+            json!([{ "mod_name": "A", "file": "A.gpk", "success": false, "error": "boom" }])
+        );
+        assert_eq!(
+            payload(HookEvent::RestoreCompleted { success: true }),
+            json!({ "event": "restore_completed", "success": true, "timestamp": 1_700_000_000, "tmm_version": "1.0.0" })
+        );
+    }
+
+    #[test]
+    fn event_names_match_the_serialized_tag() {
+        let events = [
+            HookEvent::ModInstalled { mod_name: String::new(), file: String::new(), enabled: false },
+            HookEvent::ModEnabled { mod_name: String::new(), file: String::new(), origin: ChangeOrigin::Checkbox },
+            HookEvent::ModDisabled { mod_name: String::new(), file: String::new(), origin: ChangeOrigin::Checkbox },
+            HookEvent::ApplyCompleted { results: Vec::new() },
+            HookEvent::RestoreCompleted { success: false },
+        ];
+        for event in events {
+            let name = event.name();
+            assert_eq!(payload(event)["event"], name);
+        }
+    }
+
+    // A payload larger than a pipe buffer, sent to a hook that never reads it
+    #[cfg(unix)]
+    #[test]
+    fn hook_that_ignores_stdin_still_times_out() {
+        let results = (0..5_000)
+            .map(|i| ModApplyResult { mod_name: format!("Mod {}", i), file: format!("Mod_{}.gpk", i), success: true, error: None })
+            .collect();
+        let payload = HookPayload { event: HookEvent::ApplyCompleted { results }, timestamp: 0, tmm_version: "test" };
+        assert!(serde_json::to_vec(&payload).unwrap().len() > 1 << 17);
+
+        let started = Instant::now();
+        let result = invoke("sleep 5", &payload, Duration::from_millis(300));
+        assert!(result.is_err_and(|e| e.to_string().contains("timed out")));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hook_exit_status_is_reported() {
+        let payload = HookPayload { event: HookEvent::RestoreCompleted { success: true }, timestamp: 0, tmm_version: "test" };
+        assert!(invoke("cat > /dev/null", &payload, Duration::from_secs(5)).is_ok());
+        assert!(invoke("exit 3", &payload, Duration::from_secs(5)).is_err());
+    }
+}
@@ -0,0 +1,10 @@
+// The parts of TMM that don't depend on the UI: the mapper format, mod files
+// and how mods are applied. The app, the benchmarks and the integration tests
+// all build on this.
+pub mod apply_order;
+pub mod composite_mapper;
+pub mod mod_model;
+pub mod patch;
+pub mod raw_match;
+pub mod utils;
+pub mod versioned_io;
@@ -6,88 +6,637 @@ use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use sysinfo::{System, ProcessesToUpdate, RefreshKind, ProcessRefreshKind};
-use eframe::egui::{CentralPanel, Layout};
-use bincode::{encode_to_vec, decode_from_slice};
-use bincode::config;
+use eframe::egui::{CentralPanel, Layout, SidePanel, TopBottomPanel};
 use eframe::icon_data::from_png_bytes;
 use egui::{Context, IconData};
 use egui::output::OpenUrl;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
-mod composite_mapper;
-mod mod_model;
+mod categories;
+mod cli;
+mod console;
+mod discord;
+mod fingerprints;
+mod hash_job;
+mod hooks;
+mod mod_state;
+mod perf;
+mod removal_audit;
+mod retry;
+mod root_context;
+mod settings;
+mod storage_migration;
 mod ui;
-mod utils;
 
-use composite_mapper::{CompositeEntry, CompositeMapperFile};
-use mod_model::{GameConfigFile, ModEntry, ModFile, CompositePackage};
-use ui::{buttons_ui, mod_list_ui, root_dir_ui};
+use tmm_rust::{apply_order, composite_mapper, mod_model, patch, raw_match, utils, versioned_io};
+
+use categories::CategoryTable;
+use cli::CliArgs;
+use composite_mapper::{CleanMap, CompositeEntry, CompositeMapperFile};
+use discord::{DiscordPresence, Presence};
+use fingerprints::{Fingerprint, FingerprintDb};
+use hash_job::HashJob;
+use hooks::{EventHook, HookEvent, HookPayload, ModApplyResult};
+use mod_model::{GameConfigFile, ModEntry, ModFile, CompositePackage, TmodMetadata};
+use mod_state::{ChangeOrigin, ChangeOutcome};
+use retry::{SaveRetryQueue, SaveTarget};
+use versioned_io::WrittenBy;
+use perf::{PerfStats, Timing};
+use root_context::{PackageResolution, RootContext, StorageScan};
+use settings::{ApplyMode, HumanConfig, InstallStats, OnTeraClose, PortableSettings, SettingsReader, SettingsWriter};
+use ui::{
+    about_window_ui, batch_edit_window_ui, buttons_ui, conflicts_window_ui, consistency_check_window_ui, details_panel_ui, duplicate_mods_window_ui,
+    force_disable_window_ui, log_panel_ui, mapper_too_small_ui, missing_mods_window_ui, foreign_backups_window_ui,
+    mod_details_window_ui, ModDetailsView, ModSort, mod_list_ui, perf_overlay_ui, persistent_mode_window_ui, preview_apply_window_ui,
+    quick_panel_ui, quit_unsaved_window_ui, remove_mods_window_ui, removed_entries_ui, root_dir_ui, save_retry_ui, settings_window_ui, shortcuts_window_ui, solo_banner_ui,
+    stats_window_ui, storage_window_ui, tera_status_ui, update_mods_window_ui, overwrite_window_ui,
+    interrupted_save_window_ui, conflict_prompt_window_ui, raw_pick_window_ui,
+};
+
+// Two installed mods that override at least one common object path. Mods are
+// held by ID so a pair never names the wrong mod after the list changes.
+struct ConflictPair {
+    a: u64,
+    b: u64,
+    shared: usize,
+}
+
+// A package of an enabled mod whose object path isn't in the backup mapper
+struct UnresolvablePackage {
+    mod_name: String,
+    object_path: String,
+}
+
+// The GPK wrapped in an added .tmod, unpacked to a temporary file so it is
+// hashed, compared and copied like a plain .gpk. The file goes away with it.
+struct UnpackedTmod {
+    tmod: PathBuf,
+    gpk: PathBuf,
+    meta: TmodMetadata,
+}
+
+impl UnpackedTmod {
+    fn new(tmod: &Path) -> Result<Self> {
+        static UNPACKED: AtomicUsize = AtomicUsize::new(0);
+        let data = fs::read(tmod)?;
+        let (gpk, meta) = mod_model::read_tmod(&data)?;
+        // Each in its own folder, as two .tmods may wrap GPKs of the same name
+        let dir = std::env::temp_dir()
+            .join("tmm-tmod")
+            .join(format!("{}-{}", std::process::id(), UNPACKED.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(&dir)?;
+        let stem = tmod.file_stem().unwrap_or_default().to_string_lossy();
+        let gpk_path = dir.join(format!("{}.{}", stem, utils::GPK_EXTENSION));
+        fs::write(&gpk_path, gpk)?;
+        Ok(Self { tmod: tmod.to_path_buf(), gpk: gpk_path, meta })
+    }
+}
+
+impl Drop for UnpackedTmod {
+    fn drop(&mut self) {
+        fs::remove_file(&self.gpk).ok();
+        if let Some(dir) = self.gpk.parent() {
+            fs::remove_dir(dir).ok();
+        }
+    }
+}
+
+// The file to copy into mods_dir: the unpacked GPK for a .tmod, else `source`
+fn install_content<'a>(source: &'a Path, tmod: Option<&'a UnpackedTmod>) -> &'a Path {
+    tmod.map_or(source, |t| t.gpk.as_path())
+}
+
+// What install_mod_as adds: the file the user picked, the GPK unpacked from it
+// when it is a .tmod, and its hash when a check already read the file
+struct InstallSource<'a> {
+    path: &'a Path,
+    tmod: Option<&'a UnpackedTmod>,
+    hash: Option<String>,
+    // Object paths the user picked for a raw GPK, used like a .tmod's
+    targets: Vec<String>,
+    // The listed mod this is a new version of, whose ID and settings it takes
+    replaces: Option<&'a ModEntry>,
+}
+
+impl<'a> InstallSource<'a> {
+    fn new(path: &'a Path, tmod: Option<&'a UnpackedTmod>) -> Self {
+        Self { path, tmod, hash: None, targets: Vec::new(), replaces: None }
+    }
+
+    fn with_hash(self, hash: Option<String>) -> Self {
+        Self { hash, ..self }
+    }
+
+    fn replacing(self, old: &'a ModEntry) -> Self {
+        Self { replaces: Some(old), ..self }
+    }
+
+    fn content(&self) -> &'a Path {
+        install_content(self.path, self.tmod)
+    }
+}
+
+// An added file named like a listed mod, being hashed to tell a second add of
+// the same file from a new version of it
+struct DuplicateCheck {
+    id: u64,
+    // The file the user added, a .gpk or a .tmod
+    source: PathBuf,
+    tmod: Option<UnpackedTmod>,
+    // Hash recorded for the listed file; empty when the job hashes it too
+    known_hash: String,
+    job: HashJob,
+}
+
+// An added file whose name is taken in mods_dir by an unlisted file of the
+// same size, being hashed to tell the same file from a different one
+struct OverwriteCheck {
+    source: PathBuf,
+    tmod: Option<UnpackedTmod>,
+    job: HashJob,
+}
+
+// Outcome of install_mods, one count per file
+#[derive(Default)]
+struct InstallCounts {
+    installed: usize,
+    failed: usize,
+    // Named like a listed mod or an existing file; compared in the background
+    checking: usize,
+    // Would overwrite a different unlisted file; the user chooses
+    waiting: usize,
+    // Raw GPKs waiting for their targets to be picked
+    picking: usize,
+}
+
+impl InstallCounts {
+    // The files still waiting, for the end of a status line
+    fn waiting_note(&self) -> String {
+        let mut note = String::new();
+        if self.checking > 0 {
+            note.push_str(&format!(" Comparing {} with files already there.", self.checking));
+        }
+        if self.waiting > 0 {
+            note.push_str(&format!(" {} would overwrite an existing file.", self.waiting));
+        }
+        if self.picking > 0 {
+            note.push_str(&format!(" {} need their targets picked.", self.picking));
+        }
+        note
+    }
+}
+
+// A raw GPK whose name matches too many mapper entries to trust the closest
+// ones, waiting for the user to narrow the name or tick its targets
+pub struct PendingRawPick {
+    // The file the user added, a .gpk or a .tmod
+    pub source: PathBuf,
+    pub file_name: String,
+    pub enable: bool,
+    hash: Option<String>,
+    // Name matched against the mapper's package names
+    pub query: String,
+    // Set when the query was edited; it is run again once typing settles
+    pub edited_at: Option<std::time::Instant>,
+    // (object path, package file name) of each match, in map order
+    pub matches: Vec<(String, String)>,
+    pub picked: Vec<bool>,
+    pub truncated: bool,
+    // The user already chose to replace the file of the same name in mods_dir
+    overwrite: bool,
+}
+
+// A new version of a listed mod, waiting for the user to confirm the update
+struct PendingUpdate {
+    id: u64,
+    file: String,
+    source: PathBuf,
+    tmod: Option<UnpackedTmod>,
+    // Hash of the new version, taken by the duplicate check
+    hash: String,
+}
+
+// An added file whose name is taken in mods_dir by a different, unlisted
+// file, waiting for the user to overwrite it, rename the new one or cancel
+struct PendingOverwrite {
+    source: PathBuf,
+    tmod: Option<UnpackedTmod>,
+    // Hash of the added file when the sizes matched and both were hashed
+    hash: Option<String>,
+    file: String,
+    // Free name offered for the rename
+    renamed: String,
+    // The existing file is a game package, which is never overwritten
+    game_file: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OverwriteChoice {
+    Overwrite,
+    Rename,
+    Cancel,
+}
+
+// A mod the user turned on while it conflicts with enabled mods, waiting for
+// them to pick the winner
+struct PendingConflict {
+    id: u64,
+    origin: ChangeOrigin,
+    // Each enabled mod it conflicts with and the object paths they share;
+    // no paths means they conflict through their exclusive group
+    conflicts: Vec<(u64, Vec<String>)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConflictChoice {
+    // Turn the conflicting mods off and this one on
+    Enable,
+    // Leave the conflicting mods on and this one off
+    KeepEnabled,
+    Cancel,
+}
+
+// A mapper backup left in CookedPC by another tool that looks vanilla
+struct ForeignBackup {
+    path: PathBuf,
+    entries: usize,
+}
+
+// Pending values of the "Edit selected" dialog. A field whose values differ
+// across the selection starts out mixed and is left alone unless edited.
+#[derive(Default)]
+struct BatchEdit {
+    author: String,
+    author_mixed: bool,
+    exclusive_group: String,
+    group_mixed: bool,
+    // None while the selection is partly locked
+    locked: Option<bool>,
+    // Comma-separated
+    add_tags: String,
+    remove_tags: String,
+}
 
 const CONFIG_FILE: &str = "settings.bin";
+// Written next to settings.bin in human-readable mode and preferred on load
+const CONFIG_TOML_FILE: &str = "settings.toml";
 const GAME_CONFIG_FILE: &str = "ModList.mods";
 const COMPOSITE_MAPPER_FILE: &str = "CompositePackageMapper.dat";
 const BACKUP_COMPOSITE_MAPPER_FILE: &str = "CompositePackageMapper.clean";
+// Backup names used by other mod tools and older TMM versions
+const FOREIGN_BACKUP_FILES: [&str; 3] = [
+    "CompositePackageMapper.dat.bak",
+    "CompositePackageMapper.dat.orig",
+    "CompositePackageMapper_backup.dat",
+];
 const COOKED_PC_DIR: &str = "CookedPC";
 const MODS_STORAGE_DIR: &str = "CookedPC";
+// Appended to the mapper file name for the optional checksum sidecar
+const CHECKSUM_SIDECAR_SUFFIX: &str = ".tmm.sha256";
+// Game executable, relative to the client folder above S1Game
+const TERA_EXE_PATH: [&str; 2] = ["Binaries", "TERA.exe"];
+const SNAPSHOT_DIR: &str = "TMM_Snapshots";
+const MAX_VERSIONED_BACKUPS: usize = 5;
+const DEFAULT_MIN_BACKUP_ENTRIES: usize = 1000;
+const BACKUP_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+const DEFAULT_DETAILS_PANEL_WIDTH: f32 = 250.0;
+const DEFAULT_LOG_PANEL_HEIGHT: f32 = 120.0;
+// Bytes of the selected .gpk shown in the details panel's raw preview
+const RAW_PREVIEW_BYTES: u64 = 128;
+const DEFAULT_TOOLTIP_DELAY_MS: u64 = 500;
+const DEFAULT_TERA_STATE_DEBOUNCE_MS: u64 = 3000;
+// Longer container names bloat the mapper (they repeat in every entry) and some
+// client builds reportedly fail to load them
+const DEFAULT_MAX_CONTAINER_LEN: usize = 64;
+// Leaves room for a readable prefix next to the uniqueness suffix
+const MIN_CONTAINER_LEN: usize = 16;
+const MOD_LIST_SAVE_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(1500);
+// Pause in typing before the raw target picker searches the mapper again
+const RAW_PICK_QUERY_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(300);
 
 struct TmmApp {
     root_dir: PathBuf,
-    client_dir: PathBuf,
-    mods_dir: PathBuf,
-    composite_mapper_path: PathBuf,
-    backup_composite_mapper_path: PathBuf,
-    game_config_path: PathBuf,
-    wait_for_tera: bool,
+    // Paths and caches of the current root_dir
+    root: RootContext,
+    apply_mode: ApplyMode,
+    // The one-time Persistent mode warning was accepted
+    persistent_risk_accepted: bool,
+    // Persistent was chosen and the warning is showing
+    confirm_persistent_mode: bool,
+    // Persistent was just left; offers a consistency check
+    offer_consistency_check: bool,
     game_config: GameConfigFile,
     composite_map: CompositeMapperFile,
-    backup_map: CompositeMapperFile,
+    // Read-only; a refreshed backup is loaded as a new CleanMap
+    backup_map: CleanMap,
     mod_list: Vec<ModEntry>,
     selected_mods: Vec<usize>,
+    // Row a Shift-click or Shift+arrow selects from, and the row the arrow
+    // keys move from; both indices into mod_list
+    selection_anchor: Option<usize>,
+    selection_focus: Option<usize>,
     tera_running: bool,
     sys: System,
     last_tera_check: std::time::Instant,
+    // When tera_running last became true
+    tera_launch_time: Option<std::time::Instant>,
+    // Last check that found tera.exe; TERA only counts as closed once it has
+    // been gone for tera_state_debounce_ms, so a quick crash-relaunch doesn't
+    // trigger a restore and re-apply
+    tera_last_seen: Option<std::time::Instant>,
+    tera_state_debounce_ms: u64,
+    // TERA is gone but the debounce hasn't run out yet
+    tera_close_pending: bool,
+    // The header's running indicator is redrawn once a second
+    last_status_update: std::time::Instant,
     error_msg: Option<String>,
     status_msg: String,
     warning_msg: String,
     initialized: bool,
+    install_stats: InstallStats,
+    show_stats: bool,
+    edit_mod: Option<usize>,
+    edit_container_override: String,
+    edit_file_name: String,
+    safe_apply: bool,
+    show_settings: bool,
+    show_shortcuts: bool,
+    show_about: bool,
+    perf: PerfStats,
+    mod_list_dirty: bool,
+    mod_list_last_edit: std::time::Instant,
+    log: Vec<String>,
+    monitor_tera: bool,
+    cli: CliArgs,
+    // Folders for settings.bin/settings.toml and for fingerprints, categories
+    // and caches. None in an app built by Default, which keeps nothing there.
+    config_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    // IDs of listed mods whose files are gone
+    missing_mods: Vec<u64>,
+    // Groups of mod IDs that share a file name or file hash
+    duplicate_mods: Vec<Vec<u64>>,
+    duplicate_checks: Vec<DuplicateCheck>,
+    overwrite_checks: Vec<OverwriteCheck>,
+    // Files installed or listed without a known hash, being hashed for the
+    // mods with these IDs, one per path in the job
+    file_hash_jobs: Vec<(Vec<u64>, HashJob)>,
+    pending_updates: Vec<PendingUpdate>,
+    pending_overwrites: Vec<PendingOverwrite>,
+    pending_raw_picks: Vec<PendingRawPick>,
+    pending_conflicts: Vec<PendingConflict>,
+    remember_conflict_choice: bool,
+    // Temporary mapper that parses while the mapper itself doesn't
+    interrupted_save: Option<PathBuf>,
+    min_backup_entries: usize,
+    show_conflicts: bool,
+    conflicts_enabled_only: bool,
+    conflict_pairs: Option<Vec<ConflictPair>>,
+    // IDs of the pair shown in detail and their shared object paths
+    conflict_detail: Option<(u64, u64, Vec<String>)>,
+    on_tera_close: OnTeraClose,
+    show_storage: bool,
+    storage_cleanup_days: u32,
+    confirm_storage_cleanup: bool,
+    confirm_force_disable: bool,
+    // ID of the only mod applied while in solo mode; never saved
+    solo_mod: Option<u64>,
+    // Set when the loaded mapper has fewer entries than min_backup_entries;
+    // applying and enabling mods are blocked until a valid mapper is loaded
+    mapper_too_small: bool,
+    fingerprints: FingerprintDb,
+    categories: CategoryTable,
+    // Mod table shows only this category (None = all)
+    category_filter: Option<String>,
+    show_category_column: bool,
+    show_details_panel: bool,
+    show_log_panel: bool,
+    // Small always-on-top window with the essentials, for streaming
+    show_quick_panel: bool,
+    // Remove was pressed and the confirmation is showing
+    confirm_remove: bool,
+    // Removing a mod also deletes its .gpk from mods_dir
+    delete_files_on_remove: bool,
+    // Accept .gpk files without a package signature at either end
+    allow_headerless_gpk: bool,
+    // Outer position of the quick panel, restored when it reopens
+    quick_panel_pos: Option<(f32, f32)>,
+    details_panel_width: f32,
+    log_panel_height: f32,
+    // While --root is in effect the persisted root stays whatever it was on disk
+    root_override_active: bool,
+    saved_root_dir: PathBuf,
+    tooltip_enabled: bool,
+    tooltip_delay_ms: u64,
+    max_container_len: usize,
+    // Mod list row under the pointer and when the hover started
+    hovered_row: Option<(usize, std::time::Instant)>,
+    // Wildcard patterns for files in mods_dir that are never treated as mods
+    exclusion_patterns: Vec<String>,
+    // Open "Preview Apply" window with its pre-flight results
+    preview_apply: Option<Vec<UnresolvablePackage>>,
+    write_checksum_sidecar: bool,
+    mod_sort: ModSort,
+    // Mod list and mapper saves that failed and are retried automatically
+    save_retry: SaveRetryQueue,
+    // A close was requested while a save was still pending
+    confirm_quit_unsaved: bool,
+    quit_confirmed: bool,
+    show_patches_column: bool,
+    // Candidates offered when TMM's own clean backup is missing or broken
+    foreign_backups: Vec<ForeignBackup>,
+    // root_dir came from an imported settings file and hasn't been found yet
+    root_dir_unvalidated: bool,
+    // (root_dir, folder) for roots whose mapper isn't in COOKED_PC_DIR
+    mapper_dirs: Vec<(String, String)>,
+    // Settings and ModList.mods from a newer TMM are never saved over
+    settings_written_by: WrittenBy,
+    mod_list_written_by: WrittenBy,
+    double_click_toggle: bool,
+    human_readable_config: bool,
+    // Command run with a JSON payload on stdin for each mod event (empty = none)
+    event_hook: String,
+    event_hook_worker: Option<EventHook>,
+    // Last enable/disable per mod ID, for the row toggle cooldown
+    last_row_toggle: HashMap<u64, std::time::Instant>,
+    show_hex_offsets: bool,
+    // Shows expert-only editing such as package offsets
+    advanced_mode: bool,
+    // (offset, size) text being edited per package in Mod Details
+    edit_package_offsets: Vec<(String, String)>,
+    discord_presence: bool,
+    // Application ID from the Discord developer portal; TMM doesn't ship one
+    discord_client_id: String,
+    // Running only while discord_presence is on and a client ID is set
+    discord: Option<DiscordPresence>,
+    batch_edit: Option<BatchEdit>,
 }
 
 impl Default for TmmApp {
     fn default() -> Self {
-        let mut app = Self {
+        Self {
             root_dir: PathBuf::new(),
-            client_dir: PathBuf::new(),
-            mods_dir: PathBuf::new(),
-            composite_mapper_path: PathBuf::new(),
-            backup_composite_mapper_path: PathBuf::new(),
-            game_config_path: PathBuf::new(),
-            wait_for_tera: false,
-            game_config: GameConfigFile { mods: Vec::new() },
+            root: RootContext::default(),
+            apply_mode: ApplyMode::default(),
+            persistent_risk_accepted: false,
+            confirm_persistent_mode: false,
+            offer_consistency_check: false,
+            game_config: GameConfigFile::default(),
             composite_map: CompositeMapperFile::default(),
-            backup_map: CompositeMapperFile::default(),
+            backup_map: CleanMap::default(),
             mod_list: Vec::new(),
             selected_mods: Vec::new(),
+            selection_anchor: None,
+            selection_focus: None,
             tera_running: false,
             sys: System::new_with_specifics(
                 RefreshKind::new()
                     .with_processes(ProcessRefreshKind::everything()),
             ),
             last_tera_check: std::time::Instant::now(),
+            tera_launch_time: None,
+            tera_last_seen: None,
+            tera_state_debounce_ms: DEFAULT_TERA_STATE_DEBOUNCE_MS,
+            tera_close_pending: false,
+            last_status_update: std::time::Instant::now(),
             error_msg: None,
             status_msg: String::new(),
             warning_msg: String::new(),
             initialized: false,
-        };
+            install_stats: InstallStats::default(),
+            show_stats: false,
+            edit_mod: None,
+            edit_container_override: String::new(),
+            edit_file_name: String::new(),
+            safe_apply: false,
+            show_settings: false,
+            show_shortcuts: false,
+            show_about: false,
+            perf: PerfStats::default(),
+            mod_list_dirty: false,
+            mod_list_last_edit: std::time::Instant::now(),
+            log: Vec::new(),
+            monitor_tera: true,
+            cli: CliArgs::default(),
+            config_dir: None,
+            data_dir: None,
+            missing_mods: Vec::new(),
+            duplicate_mods: Vec::new(),
+            duplicate_checks: Vec::new(),
+            overwrite_checks: Vec::new(),
+            file_hash_jobs: Vec::new(),
+            pending_updates: Vec::new(),
+            pending_overwrites: Vec::new(),
+            pending_raw_picks: Vec::new(),
+            pending_conflicts: Vec::new(),
+            remember_conflict_choice: false,
+            interrupted_save: None,
+            min_backup_entries: DEFAULT_MIN_BACKUP_ENTRIES,
+            show_conflicts: false,
+            conflicts_enabled_only: false,
+            conflict_pairs: None,
+            conflict_detail: None,
+            on_tera_close: OnTeraClose::default(),
+            show_storage: false,
+            storage_cleanup_days: 30,
+            confirm_storage_cleanup: false,
+            confirm_force_disable: false,
+            solo_mod: None,
+            mapper_too_small: false,
+            fingerprints: FingerprintDb::default(),
+            categories: CategoryTable::default(),
+            category_filter: None,
+            show_category_column: true,
+            show_details_panel: true,
+            show_log_panel: true,
+            show_quick_panel: false,
+            confirm_remove: false,
+            delete_files_on_remove: false,
+            allow_headerless_gpk: false,
+            quick_panel_pos: None,
+            details_panel_width: DEFAULT_DETAILS_PANEL_WIDTH,
+            log_panel_height: DEFAULT_LOG_PANEL_HEIGHT,
+            tooltip_enabled: true,
+            tooltip_delay_ms: DEFAULT_TOOLTIP_DELAY_MS,
+            max_container_len: DEFAULT_MAX_CONTAINER_LEN,
+            hovered_row: None,
+            exclusion_patterns: settings::default_exclusion_patterns(),
+            preview_apply: None,
+            write_checksum_sidecar: false,
+            mod_sort: ModSort::ListOrder,
+            save_retry: SaveRetryQueue::default(),
+            confirm_quit_unsaved: false,
+            quit_confirmed: false,
+            show_patches_column: true,
+            foreign_backups: Vec::new(),
+            root_dir_unvalidated: false,
+            mapper_dirs: Vec::new(),
+            settings_written_by: WrittenBy::Compatible,
+            mod_list_written_by: WrittenBy::Compatible,
+            double_click_toggle: true,
+            human_readable_config: false,
+            event_hook: String::new(),
+            event_hook_worker: None,
+            last_row_toggle: HashMap::new(),
+            show_hex_offsets: false,
+            advanced_mode: false,
+            edit_package_offsets: Vec::new(),
+            discord_presence: false,
+            discord_client_id: String::new(),
+            discord: None,
+            batch_edit: None,
+            root_override_active: false,
+            saved_root_dir: PathBuf::new(),
+        }
+    }
+}
 
+impl TmmApp {
+    fn new(cli: CliArgs) -> Self {
+        let dirs = ProjectDirs::from("com", "borkycode", "tera-mod-manager");
+        let mut app = Self {
+            cli,
+            config_dir: dirs.as_ref().map(|d| d.config_dir().to_path_buf()),
+            data_dir: dirs.as_ref().map(|d| d.data_dir().to_path_buf()),
+            ..Default::default()
+        };
         // Load basic config (settings.bin) to restore previous path
         app.load_app_config().ok();
-
+        if let Some(root) = app.cli.root.clone() {
+            app.saved_root_dir = std::mem::replace(&mut app.root_dir, root);
+            app.root_override_active = true;
+        }
+        // Writes settings.toml from the settings.bin loaded above
+        if app.cli.human_config && !app.config_toml_path().is_some_and(|p| p.exists()) {
+            app.save_app_config().ok();
+        }
         app
     }
-}
 
-impl TmmApp {
+    fn human_config_enabled(&self) -> bool {
+        self.human_readable_config || self.cli.human_config
+    }
+
+    fn config_toml_path(&self) -> Option<PathBuf> {
+        self.config_dir.as_ref().map(|dir| dir.join(CONFIG_TOML_FILE))
+    }
+
+    fn is_watching_tera(&self) -> bool {
+        self.monitor_tera && !self.cli.no_watch
+    }
+
+    // Changes are held back for the TERA launch only while someone is watching for it
+    fn defer_until_tera(&self) -> bool {
+        self.apply_mode == ApplyMode::WaitForTera && self.is_watching_tera()
+    }
+
     fn initialize(&mut self) {
+        // Reloading reads the mod list back from disk
+        self.flush_mod_list();
+
         // Setup Paths
         // If root_dir is empty, this will fail, and we handle it in update().
         if let Err(e) = self.setup_paths() {
@@ -96,22 +645,58 @@ impl TmmApp {
         }
 
         // Load Backup Map
-        match CompositeMapperFile::new(self.backup_composite_mapper_path.clone()) {
+        match CleanMap::new(self.root.backup_composite_mapper_path.clone()) {
             Ok(backup) => {
                 self.backup_map = backup;
                 println!("[TMM] Backup Mapper Loaded.");
             }
             Err(e) => {
                 self.error_msg = Some(format!("Failed to load backup mapper: {}", e));
+                self.offer_foreign_backups();
                 return;
             }
         }
 
+        if !self.recover_interrupted_save() {
+            return;
+        }
+
         // Load Active Composite Map
-        match CompositeMapperFile::new(self.composite_mapper_path.clone()) {
+        match CompositeMapperFile::new(self.root.composite_mapper_path.clone()) {
             Ok(map) => {
                 self.composite_map = map;
                 println!("[TMM] Active Mapper Loaded.");
+                if self.composite_map.loaded_from_plaintext {
+                    self.warning_msg = "CompositePackageMapper.dat was not encrypted; it was read as plaintext and will be re-encrypted on save.".to_string();
+                }
+                if self.composite_map.normalized_chars > 0 {
+                    self.log_msg(format!(
+                        "Stripped {} BOM/line break characters from the plaintext mapper.",
+                        self.composite_map.normalized_chars
+                    ));
+                    self.warning_msg.push_str(" Editor artifacts (BOM, line breaks) were removed.");
+                }
+                let parse_errors: Vec<String> =
+                    self.composite_map.parse_errors.iter().map(|e| e.to_string()).collect();
+                for error in parse_errors {
+                    self.log_msg(format!("Mapper: {}", error));
+                }
+                self.mapper_too_small = self.composite_map.is_too_small(self.min_backup_entries);
+                if self.mapper_too_small {
+                    self.log_msg(format!(
+                        "Mapper has only {} entries (expected at least {}); applying and enabling mods is disabled.",
+                        self.composite_map.len(),
+                        self.min_backup_entries
+                    ));
+                }
+                if !self.composite_map.empty_blocks.is_empty() {
+                    let blocks = self.composite_map.empty_blocks.join(", ");
+                    self.log_msg(format!(
+                        "Mapper contains {} filename blocks without entries ({}); they are dropped on the next save.",
+                        self.composite_map.empty_blocks.len(),
+                        blocks
+                    ));
+                }
             }
             Err(e) => {
                 self.error_msg = Some(format!("Failed to load mapper: {}", e));
@@ -121,22 +706,47 @@ impl TmmApp {
 
         // Load Mod List
         if let Err(e) = self.load_game_config() {
-            self.error_msg = Some(format!("Failed to load mod list: {}", e));
+            self.error_msg = Some(format!("Failed to load mod list: {:#}", e));
             return;
         }
-        self.mod_list = self.game_config.mods.clone();
+        let assigned = mod_model::assign_missing_ids(&mut self.game_config.mods);
+        if assigned > 0 {
+            self.log_msg(format!("Assigned IDs to {} mods.", assigned));
+            self.mod_list_dirty = true;
+        }
+        let mods = self.game_config.mods.clone();
+        self.preserving_selection(|app| app.mod_list = mods);
+
+        self.detect_renamed_mod_files();
+
+        // Listed mods whose files are gone from disk
+        self.missing_mods = self
+            .mod_list
+            .iter()
+            .filter(|m| !m.archived && !self.root.mods_dir.join(&m.file).exists())
+            .map(|m| m.id)
+            .collect();
 
         // Scan Mod Files (Logic from previous 'new')
         println!("[TMM] Scanning Mod Files...");
+        let scan_started = self.perf.start();
         let _mod_list_length = self.mod_list.len();
-        for (_index, mod_entry) in self.mod_list.iter_mut().enumerate() {
+        let mut unhashed = Vec::new();
+        let mut recategorized = 0;
+        let mut truncated_raw = Vec::new();
+        for mod_entry in self.mod_list.iter_mut() {
             let filename = &mod_entry.file;
-            let gpk_path = self.mods_dir.join(filename);
+            let gpk_path = self.root.mods_dir.join(filename);
             
             if !gpk_path.exists() {
                 continue;
             }
 
+            // Entries from before hashes were stored; hashed off the UI thread
+            if mod_entry.file_hash.is_empty() {
+                unhashed.push((mod_entry.id, gpk_path.clone()));
+            }
+
             let mut file = match File::open(&gpk_path) {
                 Ok(f) => f,
                 Err(_) => continue,
@@ -145,37 +755,36 @@ impl TmmApp {
             let is_raw = if mod_model::read_mod_file(&mut file, &mut mod_entry.mod_file).is_err() {
                 true
             } else {
-                mod_entry.mod_file.packages.len() == 1 && mod_entry.mod_file.packages[0].size == 0
+                // A lone author tag leaves no packages to point anywhere
+                mod_entry.mod_file.packages.is_empty()
+                    || (mod_entry.mod_file.packages.len() == 1 && mod_entry.mod_file.packages[0].size == 0)
             };
 
-            let mod_container_name = filename.trim_end_matches(".gpk").to_string();
-
-            if is_raw {
-                let mod_name_stem = filename.trim_end_matches(".gpk").to_lowercase();
-                let mut matched_packages = Vec::new();
-                let mut found_match = false;
-
-                for entry in self.composite_map.composite_map.values() {
-                    let entry_name_stem = entry.filename.trim_end_matches(".gpk").to_lowercase();
-                    if mod_name_stem.contains(&entry_name_stem) || entry_name_stem.contains(&mod_name_stem) {
-                        matched_packages.push(composite_mapper::CompositeEntry {
-                            filename: filename.clone(),
-                            object_path: entry.object_path.clone(),
-                            composite_name: entry.composite_name.clone(),
-                            offset: 0,
-                            size: 0,
-                        });
-                        found_match = true;
-                    }
-                }
+            let mod_container_name = utils::strip_gpk_ext(filename).to_string();
 
-                if found_match {
-                    mod_entry.mod_file.packages = matched_packages
-                        .into_iter()
+            if is_raw && !mod_entry.target_paths.is_empty() {
+                // Declared by the .tmod it was installed from
+                mod_entry.mod_file.packages = mod_entry
+                    .target_paths
+                    .iter()
+                    .map(|object_path| mod_model::CompositePackage {
+                        object_path: object_path.clone(),
+                        ..Default::default()
+                    })
+                    .collect();
+                mod_entry.mod_file.container = mod_container_name;
+            } else if is_raw {
+                let matches = raw_match::find(self.composite_map.values(), filename);
+                // The closest of too many matches are a guess; the mod is left
+                // without targets rather than pointed at the wrong objects
+                if matches.truncated {
+                    truncated_raw.push(filename.clone());
+                } else if !matches.entries.is_empty() {
+                    mod_entry.mod_file.packages = matches
+                        .entries
+                        .iter()
                         .map(|e| mod_model::CompositePackage {
-                            object_path: e.object_path,
-                            offset: e.offset,
-                            size: e.size,
+                            object_path: e.object_path.clone(),
                             ..Default::default()
                         })
                         .collect();
@@ -190,10 +799,37 @@ impl TmmApp {
                     mod_entry.mod_file.container = mod_container_name;
                 }
             }
+            mod_model::fill_missing_author(&mut mod_entry.mod_file, filename);
+
+            let category = self.categories.infer(&mod_entry.mod_file.packages);
+            if mod_entry.category != category {
+                mod_entry.category = category;
+                recategorized += 1;
+            }
+        }
+        if recategorized > 0 {
+            self.update_mods_list(self.mod_list.clone());
+        }
+        if !unhashed.is_empty() {
+            let (ids, paths) = unhashed.into_iter().unzip();
+            self.file_hash_jobs.push((ids, HashJob::start(paths)));
+        }
+        for filename in truncated_raw {
+            self.log_msg(format!(
+                "Raw mod '{}' matches too many game packages to guess its targets and was left without any. Rename it to match the game file more exactly, or remove it and install it again to pick them.",
+                filename
+            ));
         }
 
+        self.detect_new_mod_files();
+        self.check_duplicate_mods();
+        self.flag_long_containers();
+        self.perf.record(Timing::Scan, scan_started);
+
         // 6. Apply Mods
-        if !self.wait_for_tera {
+        if self.mapper_too_small {
+            self.status_msg = "Mods loaded for inspection only.".to_string();
+        } else if !self.defer_until_tera() {
             println!("[TMM] Applying Enabled Mods...");
             if let Err(e) = self.apply_enabled_mods() {
                 self.error_msg = Some(format!("Startup apply failed: {:?}", e));
@@ -207,340 +843,3252 @@ impl TmmApp {
     }
 
     fn load_app_config(&mut self) -> Result<()> {
-        if let Some(proj_dirs) = ProjectDirs::from("com", "borkycode", "tera-mod-manager") {
-            let config_path = proj_dirs.config_dir().join(CONFIG_FILE);
+        if let Some(toml_path) = self.config_toml_path().filter(|p| p.exists()) {
+            return self.load_app_config_toml(&toml_path);
+        }
+        if let Some(config_dir) = &self.config_dir {
+            let config_path = config_dir.join(CONFIG_FILE);
             if config_path.exists() {
                 let mut file = File::open(config_path)?;
                 let mut buf = Vec::new();
                 file.read_to_end(&mut buf)?;
-                let cfg = config::standard();
-                let ((root_dir, wait_for_tera), _bytes_read): ((PathBuf, bool), usize) = decode_from_slice(&buf, cfg)?;
-                self.root_dir = root_dir;
-                self.wait_for_tera = wait_for_tera;
+                let mut reader = SettingsReader::new(&buf);
+                self.root_dir = reader.read()?;
+                // Written as a bool before ApplyMode existed; the mode itself follows later
+                let wait_for_tera: bool = reader.read()?;
+                self.apply_mode = if wait_for_tera { ApplyMode::WaitForTera } else { ApplyMode::Immediate };
+                self.install_stats = reader.read_or_default();
+                self.safe_apply = reader.read_or_default();
+                self.monitor_tera = reader.read().unwrap_or(true);
+                self.min_backup_entries = reader.read().unwrap_or(DEFAULT_MIN_BACKUP_ENTRIES);
+                self.on_tera_close = reader.read_or_default();
+                self.show_details_panel = reader.read().unwrap_or(true);
+                self.show_log_panel = reader.read().unwrap_or(true);
+                self.details_panel_width = reader.read().unwrap_or(DEFAULT_DETAILS_PANEL_WIDTH);
+                self.log_panel_height = reader.read().unwrap_or(DEFAULT_LOG_PANEL_HEIGHT);
+                self.tooltip_enabled = reader.read().unwrap_or(true);
+                self.tooltip_delay_ms = reader.read().unwrap_or(DEFAULT_TOOLTIP_DELAY_MS);
+                self.exclusion_patterns = reader.read().unwrap_or_else(|_| settings::default_exclusion_patterns());
+                self.write_checksum_sidecar = reader.read_or_default();
+                self.double_click_toggle = reader.read().unwrap_or(true);
+                self.show_hex_offsets = reader.read_or_default();
+                self.advanced_mode = reader.read_or_default();
+                self.discord_presence = reader.read_or_default();
+                self.human_readable_config = reader.read_or_default();
+                self.event_hook = reader.read_or_default();
+                self.max_container_len = reader.read().unwrap_or(DEFAULT_MAX_CONTAINER_LEN);
+                self.tera_state_debounce_ms = reader.read().unwrap_or(DEFAULT_TERA_STATE_DEBOUNCE_MS);
+                self.show_patches_column = reader.read().unwrap_or(true);
+                self.show_category_column = reader.read().unwrap_or(true);
+                self.show_quick_panel = reader.read_or_default();
+                self.quick_panel_pos = reader.read_or_default();
+                self.mapper_dirs = reader.read_or_default();
+                let written_by: Option<String> = reader.read().ok();
+                self.check_settings_version(written_by.as_deref());
+                if let Ok(mode) = reader.read() {
+                    self.apply_mode = mode;
+                }
+                self.persistent_risk_accepted = reader.read_or_default();
+                self.delete_files_on_remove = reader.read_or_default();
+                self.allow_headerless_gpk = reader.read_or_default();
+                self.discord_client_id = reader.read_or_default();
             }
         }
         Ok(())
     }
 
+    fn load_app_config_toml(&mut self, path: &Path) -> Result<()> {
+        let config: HumanConfig = toml::from_str(&fs::read_to_string(path)?)?;
+        self.check_settings_version(config.tmm_version.as_deref());
+        self.persistent_risk_accepted = config.persistent_risk_accepted.unwrap_or_default();
+        if let Some(root_dir) = &config.settings.root_dir {
+            self.root_dir = root_dir.clone();
+        }
+        self.apply_portable_settings(config.settings);
+        if let Some(stats) = config.install_stats {
+            self.install_stats = stats;
+        }
+        if let Some(mapper_dirs) = config.mapper_dirs {
+            self.mapper_dirs = mapper_dirs;
+        }
+        if config.quick_panel_pos.is_some() {
+            self.quick_panel_pos = config.quick_panel_pos;
+        }
+        Ok(())
+    }
+
+    fn check_settings_version(&mut self, written_by: Option<&str>) {
+        self.settings_written_by = WrittenBy::check(written_by);
+        if let Some(warning) = self.settings_written_by.warning("Settings") {
+            self.log_msg(warning.clone());
+            self.warning_msg = warning;
+        }
+    }
+
     fn save_app_config(&self) -> Result<()> {
-        if let Some(proj_dirs) = ProjectDirs::from("com", "borkycode", "tera-mod-manager") {
-            let config_path = proj_dirs.config_dir().join(CONFIG_FILE);
-            if let Some(parent) = config_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            let cfg = config::standard();
-            let data = encode_to_vec(
-                &(self.root_dir.clone(), self.wait_for_tera),
-                cfg,
-            )?;
+        if let Some(warning) = self.settings_written_by.warning("Settings") {
+            anyhow::bail!(warning);
+        }
+        if let Some(config_dir) = &self.config_dir {
+            let config_path = config_dir.join(CONFIG_FILE);
+            fs::create_dir_all(config_dir)?;
+            let mut writer = SettingsWriter::default();
+            let root_dir = if self.root_override_active { &self.saved_root_dir } else { &self.root_dir };
+            writer.write(root_dir)?;
+            writer.write(&(self.apply_mode == ApplyMode::WaitForTera))?;
+            writer.write(&self.install_stats)?;
+            writer.write(&self.safe_apply)?;
+            writer.write(&self.monitor_tera)?;
+            writer.write(&self.min_backup_entries)?;
+            writer.write(&self.on_tera_close)?;
+            writer.write(&self.show_details_panel)?;
+            writer.write(&self.show_log_panel)?;
+            writer.write(&self.details_panel_width)?;
+            writer.write(&self.log_panel_height)?;
+            writer.write(&self.tooltip_enabled)?;
+            writer.write(&self.tooltip_delay_ms)?;
+            writer.write(&self.exclusion_patterns)?;
+            writer.write(&self.write_checksum_sidecar)?;
+            writer.write(&self.double_click_toggle)?;
+            writer.write(&self.show_hex_offsets)?;
+            writer.write(&self.advanced_mode)?;
+            writer.write(&self.discord_presence)?;
+            writer.write(&self.human_readable_config)?;
+            writer.write(&self.event_hook)?;
+            writer.write(&self.max_container_len)?;
+            writer.write(&self.tera_state_debounce_ms)?;
+            writer.write(&self.show_patches_column)?;
+            writer.write(&self.show_category_column)?;
+            writer.write(&self.show_quick_panel)?;
+            writer.write(&self.quick_panel_pos)?;
+            writer.write(&self.mapper_dirs)?;
+            writer.write(&versioned_io::APP_VERSION.to_string())?;
+            writer.write(&self.apply_mode)?;
+            writer.write(&self.persistent_risk_accepted)?;
+            writer.write(&self.delete_files_on_remove)?;
+            writer.write(&self.allow_headerless_gpk)?;
+            writer.write(&self.discord_client_id)?;
             let mut file = File::create(config_path)?;
-            file.write_all(&data)?;
+            file.write_all(&writer.into_bytes())?;
+        }
+
+        if let Some(toml_path) = self.config_toml_path() {
+            if self.human_config_enabled() {
+                fs::write(toml_path, toml::to_string_pretty(&self.human_config())?)?;
+            } else if toml_path.exists() {
+                // A leftover settings.toml would win over settings.bin on the next load
+                fs::remove_file(toml_path)?;
+            }
         }
         Ok(())
     }
 
+    // settings.toml holds what settings.bin does, this machine's state included
+    fn human_config(&self) -> HumanConfig {
+        HumanConfig {
+            settings: self.portable_settings(),
+            install_stats: Some(self.install_stats.clone()),
+            tmm_version: Some(versioned_io::APP_VERSION.to_string()),
+            persistent_risk_accepted: Some(self.persistent_risk_accepted),
+            mapper_dirs: Some(self.mapper_dirs.clone()),
+            quick_panel_pos: self.quick_panel_pos,
+        }
+    }
+
+    pub fn export_settings_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.portable_settings())?)
+    }
+
+    fn portable_settings(&self) -> PortableSettings {
+        let root_dir = if self.root_override_active { &self.saved_root_dir } else { &self.root_dir };
+        PortableSettings {
+            root_dir: Some(root_dir.clone()),
+            wait_for_tera: Some(self.apply_mode == ApplyMode::WaitForTera),
+            apply_mode: Some(self.apply_mode),
+            safe_apply: Some(self.safe_apply),
+            monitor_tera: Some(self.monitor_tera),
+            min_backup_entries: Some(self.min_backup_entries),
+            on_tera_close: Some(self.on_tera_close),
+            show_details_panel: Some(self.show_details_panel),
+            show_log_panel: Some(self.show_log_panel),
+            details_panel_width: Some(self.details_panel_width),
+            log_panel_height: Some(self.log_panel_height),
+            tooltip_enabled: Some(self.tooltip_enabled),
+            tooltip_delay_ms: Some(self.tooltip_delay_ms),
+            exclusion_patterns: Some(self.exclusion_patterns.clone()),
+            write_checksum_sidecar: Some(self.write_checksum_sidecar),
+            double_click_toggle: Some(self.double_click_toggle),
+            show_hex_offsets: Some(self.show_hex_offsets),
+            advanced_mode: Some(self.advanced_mode),
+            discord_presence: Some(self.discord_presence),
+            human_readable_config: Some(self.human_readable_config),
+            event_hook: Some(self.event_hook.clone()),
+            max_container_len: Some(self.max_container_len),
+            tera_state_debounce_ms: Some(self.tera_state_debounce_ms),
+            show_patches_column: Some(self.show_patches_column),
+            show_category_column: Some(self.show_category_column),
+            show_quick_panel: Some(self.show_quick_panel),
+            delete_files_on_remove: Some(self.delete_files_on_remove),
+            allow_headerless_gpk: Some(self.allow_headerless_gpk),
+            discord_client_id: Some(self.discord_client_id.clone()),
+        }
+    }
+
+    // The imported root_dir is kept even if it doesn't exist here; it stays
+    // flagged as unvalidated until setup_paths finds it.
+    pub fn import_settings_json(&mut self, json: &str) -> Result<()> {
+        let portable: PortableSettings = serde_json::from_str(json)?;
+
+        if let Some(root_dir) = portable.root_dir.clone() {
+            if root_dir != self.root_dir {
+                self.flush_mod_list();
+                self.root_dir = root_dir;
+                self.root_override_active = false;
+                self.root_dir_unvalidated = true;
+                self.initialized = false;
+            }
+        }
+        self.apply_portable_settings(portable);
+
+        self.save_app_config()
+    }
+
+    // Everything but root_dir, which each caller handles itself
+    fn apply_portable_settings(&mut self, portable: PortableSettings) {
+        if let Some(v) = portable.wait_for_tera {
+            self.apply_mode = if v { ApplyMode::WaitForTera } else { ApplyMode::Immediate };
+        }
+        match portable.apply_mode {
+            // Imported settings don't carry the acknowledgement; ask for it here
+            Some(ApplyMode::Persistent) if !self.persistent_risk_accepted => self.confirm_persistent_mode = true,
+            Some(v) => self.apply_mode = v,
+            None => {}
+        }
+        if let Some(v) = portable.safe_apply { self.safe_apply = v; }
+        if let Some(v) = portable.monitor_tera { self.monitor_tera = v; }
+        if let Some(v) = portable.min_backup_entries { self.min_backup_entries = v; }
+        if let Some(v) = portable.on_tera_close { self.on_tera_close = v; }
+        if let Some(v) = portable.show_details_panel { self.show_details_panel = v; }
+        if let Some(v) = portable.show_log_panel { self.show_log_panel = v; }
+        if let Some(v) = portable.details_panel_width { self.details_panel_width = v; }
+        if let Some(v) = portable.log_panel_height { self.log_panel_height = v; }
+        if let Some(v) = portable.tooltip_enabled { self.tooltip_enabled = v; }
+        if let Some(v) = portable.tooltip_delay_ms { self.tooltip_delay_ms = v.min(2000); }
+        if let Some(v) = portable.exclusion_patterns { self.exclusion_patterns = v; }
+        if let Some(v) = portable.write_checksum_sidecar { self.write_checksum_sidecar = v; }
+        if let Some(v) = portable.double_click_toggle { self.double_click_toggle = v; }
+        if let Some(v) = portable.show_hex_offsets { self.show_hex_offsets = v; }
+        if let Some(v) = portable.advanced_mode { self.advanced_mode = v; }
+        if let Some(v) = portable.discord_presence { self.discord_presence = v; }
+        if let Some(v) = portable.human_readable_config { self.human_readable_config = v; }
+        if let Some(v) = portable.event_hook { self.event_hook = v; }
+        if let Some(v) = portable.max_container_len { self.max_container_len = v.max(MIN_CONTAINER_LEN); }
+        if let Some(v) = portable.tera_state_debounce_ms { self.tera_state_debounce_ms = v; }
+        if let Some(v) = portable.show_patches_column { self.show_patches_column = v; }
+        if let Some(v) = portable.show_category_column { self.show_category_column = v; }
+        if let Some(v) = portable.show_quick_panel { self.show_quick_panel = v; }
+        if let Some(v) = portable.delete_files_on_remove { self.delete_files_on_remove = v; }
+        if let Some(v) = portable.allow_headerless_gpk { self.allow_headerless_gpk = v; }
+        if let Some(v) = portable.discord_client_id { self.discord_client_id = v; }
+    }
+
     fn setup_paths(&mut self) -> Result<()> {
         self.warning_msg.clear();
         self.error_msg = None;
+        self.mapper_too_small = false;
         if self.root_dir.as_os_str().is_empty() || !self.root_dir.exists() {
+            // Don't keep working on the previous root's files
+            self.root = RootContext::default();
             return Ok(());
         }
+        self.root_dir_unvalidated = false;
 
-        // Construct paths
-        self.composite_mapper_path = self.root_dir.join(COOKED_PC_DIR).join(COMPOSITE_MAPPER_FILE);
-        self.backup_composite_mapper_path = self.root_dir.join(MODS_STORAGE_DIR).join(BACKUP_COMPOSITE_MAPPER_FILE);
+        let data_dir = self.data_dir.clone();
+        let cooked_pc_name = self.resolve_cooked_pc_dir();
+        if self.root.root_dir != self.root_dir || self.root.cooked_pc_name != cooked_pc_name {
+            self.root.removal_audit.close();
+            self.root = RootContext::new(&self.root_dir, &cooked_pc_name, data_dir.as_deref());
+            if let Err(e) = self.root.prepare_cache_dir() {
+                eprintln!("[TMM] Failed to create cache folder: {:?}", e);
+            }
+            let recovered = self.root.removal_audit.recovered_count();
+            if recovered > 0 {
+                self.log_msg(format!(
+                    "The last session didn't shut down cleanly; {} removed mapper entries were recovered from its journal.",
+                    recovered
+                ));
+            }
+            if let Some(warning) = self.root.removal_audit.warning.clone() {
+                self.log_msg(warning);
+            }
+        }
         
+        // Program Files installs need elevation to write to CookedPC
+        if !utils::is_path_writable(&self.root.cooked_pc_dir) {
+            anyhow::bail!(
+                "TMM cannot write to the selected directory. Try running as Administrator or moving TERA to a user directory."
+            );
+        }
+        let root = &self.root;
+        for file in [&root.composite_mapper_path, &root.backup_composite_mapper_path, &root.game_config_path] {
+            if file.exists() && !utils::is_path_writable(file) {
+                anyhow::bail!(
+                    "TMM cannot write to {}. Try running as Administrator or clearing the file's read-only flag.",
+                    file.display()
+                );
+            }
+        }
+
         // Ensure the mods directory exists
-        if let Err(e) = fs::create_dir_all(&self.mods_dir) {
+        if let Err(e) = fs::create_dir_all(&self.root.mods_dir) {
              eprintln!("Failed to create mods dir: {:?}", e);
         }
+        if let Err(e) = self.migrate_storage_layout() {
+            anyhow::bail!(
+                "Moving TMM files to {} failed: {}. It will resume on the next start.",
+                self.root.mods_dir.display(),
+                e
+            );
+        }
 
         // Check if the critical game file exists
-        if !self.composite_mapper_path.exists() {
+        if !self.root.composite_mapper_path.exists() {
             self.warning_msg = "CompositePackageMapper.dat not found in the selected directory.".to_string();
         }
 
         // Perform backup
+        self.fingerprints = FingerprintDb::load(data_dir.as_deref());
+        self.categories = CategoryTable::load(data_dir.as_deref());
+        if self.fingerprints.is_empty() {
+            println!("[TMM] No mapper fingerprints known; backups are checked heuristically. A list can be imported in Settings.");
+        }
         if !self.backup_composite_mapper() {
-            self.error_msg = Some("Backup Failed".to_string());
+            self.error_msg = Some(
+                "No valid backup of CompositePackageMapper.dat exists yet. Verify the game files and restart TMM."
+                    .to_string(),
+            );
+            self.offer_foreign_backups();
         }
 
-        self.client_dir = self.root_dir.parent().unwrap_or(&PathBuf::new()).to_path_buf();
-        self.mods_dir = self.root_dir.join(MODS_STORAGE_DIR);
-        self.game_config_path = self.mods_dir.join(GAME_CONFIG_FILE);
         self.save_app_config()?;
         Ok(())
     }
 
-    fn backup_composite_mapper(&self) -> bool {
-        if self.backup_composite_mapper_path.exists() {
-            return true;
-        }
+    // Folder under root_dir that holds the mapper. Some regional and private
+    // server clients don't use CookedPC; for those one level of subfolders is
+    // searched and the result remembered for this root.
+    fn resolve_cooked_pc_dir(&mut self) -> String {
+        let root_key = self.root_dir.to_string_lossy().into_owned();
+        let has_mapper = |name: &str| self.root_dir.join(name).join(COMPOSITE_MAPPER_FILE).is_file();
 
-        if !self.composite_mapper_path.exists() {
-            return false;
+        let saved = self.mapper_dirs.iter().find(|(root, _)| *root == root_key).map(|(_, dir)| dir.clone());
+        if let Some(dir) = saved.filter(|dir| has_mapper(dir)) {
+            return dir;
         }
-        
-        fs::copy(&self.composite_mapper_path, &self.backup_composite_mapper_path).is_ok()
-    }
-
-    fn restore_composite_mapper(&mut self) -> bool {
-        if !self.backup_composite_mapper_path.exists() {
-            self.error_msg = Some("Restore Failed - Missing Backup File, Please Turn Off All Mods And Restart TMM".to_string());
-            return false;
+        if has_mapper(COOKED_PC_DIR) {
+            self.remember_mapper_dir(&root_key, None);
+            return COOKED_PC_DIR.to_string();
         }
-        fs::copy(&self.backup_composite_mapper_path, &self.composite_mapper_path).is_ok()
-    }
 
-    fn update_mods_list(&mut self, mod_data: Vec<ModEntry>) {
-        self.game_config.mods = mod_data;
-        self.save_game_config().ok();
+        let mut candidates: Vec<String> = fs::read_dir(&self.root_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| has_mapper(name))
+            .collect();
+        candidates.sort();
+        let Some(found) = candidates.into_iter().next() else {
+            return COOKED_PC_DIR.to_string();
+        };
+        self.log_msg(format!(
+            "{} isn't in {}; using the one in {} for this root.",
+            COMPOSITE_MAPPER_FILE, COOKED_PC_DIR, found
+        ));
+        self.remember_mapper_dir(&root_key, Some(&found));
+        found
     }
 
-    // Helper to find indices of currently enabled mods that share object paths with the provided packages
-    fn find_conflicting_indices(&self, packages: &[CompositePackage]) -> Vec<usize> {
-        let mut conflicts = Vec::new();
-
-        for (i, existing_mod) in self.mod_list.iter().enumerate() {
-            if !existing_mod.enabled {
-                continue; // Only check against active mods
-            }
-
-            // Check intersection of packages
-            for new_pkg in packages {
-                for existing_pkg in &existing_mod.mod_file.packages {
-                    if new_pkg.object_path == existing_pkg.object_path {
-                        conflicts.push(i);
-                        break; 
-                    }
-                }
+    fn remember_mapper_dir(&mut self, root_key: &str, dir: Option<&str>) {
+        let before = self.mapper_dirs.len();
+        self.mapper_dirs.retain(|(root, _)| root != root_key);
+        let changed = dir.is_some() || self.mapper_dirs.len() != before;
+        if let Some(dir) = dir {
+            self.mapper_dirs.push((root_key.to_string(), dir.to_string()));
+        }
+        if changed {
+            if let Err(e) = self.save_app_config() {
+                self.log_msg(format!("Failed to save settings: {}", e));
             }
         }
-        conflicts
     }
 
-
-    fn install_mod(&mut self, path: &Path, save: bool) -> bool {
-        let target_path = self.mods_dir.join(path.file_name().unwrap_or_default());
-        if fs::copy(path, &target_path).is_err() {
-            self.error_msg = Some(format!("Failed to copy mod file: {:?}", path));
-            return false;
+    // Brings TMM's files into the storage folder when an older version kept
+    // them elsewhere under the root: resumes a move the journal shows
+    // unfinished, or starts one when the storage folder has no mod list and
+    // exactly one older folder does. Then stamps the folder with the layout.
+    fn migrate_storage_layout(&mut self) -> Result<()> {
+        let mods_dir = self.root.mods_dir.clone();
+        let stamp = storage_migration::read_stamp(&mods_dir);
+        if stamp > storage_migration::LAYOUT_VERSION {
+            self.log_msg(format!(
+                "{} was laid out by a newer version of TMM; its files are left where they are.",
+                mods_dir.display()
+            ));
+            return Ok(());
         }
 
-        let mut file = match File::open(&target_path) {
-            Ok(f) => f,
-            Err(_) => return false,
-        };
-
-        let mut mod_file = ModFile::default();
-    
-        let is_raw = if mod_model::read_mod_file(&mut file, &mut mod_file).is_err() {
-            true // Failed to read, definitely raw
-        } else {
-            // Check if the read resulted in the "dummy" single package (size 0)
-            // If mod_file.packages has 1 item with size 0, it's likely a raw fallback from read_mod_file
-            mod_file.packages.len() == 1 && mod_file.packages[0].size == 0
-        };
-
-        let file_name = target_path.file_name().unwrap().to_string_lossy().to_string();
-
-        // Logic for Raw GPKs (Fallback)
-        if is_raw {
-            println!("Detected Raw/Unpacked GPK. Attempting to resolve via filename matching...");
-
-            // Try to find the mod name in the existing composite map.
-            // This assumes the user named the mod file exactly as the file it replaces.
-            let mod_name_stem = file_name.trim_end_matches(".gpk").to_lowercase();
-            let mut matched_packages = Vec::new();
-            let mut found_match = false;
-
-            // Scan the composite map
-            for entry in self.composite_map.composite_map.values() {
-                let entry_name_stem = entry.filename.trim_end_matches(".gpk").to_lowercase();
-                
-                // Check for partial match (e.g. "S1_Elin" matches "S1_Elin_Mod")
-                if mod_name_stem.contains(&entry_name_stem) || entry_name_stem.contains(&mod_name_stem) {
-                    matched_packages.push(CompositePackage {
-                        object_path: entry.object_path.clone(),
-                        offset: 0, 
-                        size: 0,
-                        file_version: 0,
-                        licensee_version: 0,
-                    });
-                    found_match = true;
-                }
-            }
-
-            if found_match {
-                mod_file.packages = matched_packages;
-                // Since we don't have the real name, use the filename as the display name
-                mod_file.mod_name = file_name.clone(); 
-                // Use filename as container if empty
-                if mod_file.container.is_empty() {
-                    mod_file.container = file_name.trim_end_matches(".gpk").to_string();
+        let mut journal = match storage_migration::Journal::read(&mods_dir)? {
+            Some(journal) if !journal.done => Some(journal),
+            _ if !self.root.game_config_path.exists() => {
+                let old = storage_migration::find_old_storage(&self.root.root_dir, &mods_dir);
+                match old.as_slice() {
+                    [] => None,
+                    [from] => Some(storage_migration::Journal::start(from, &mods_dir)?),
+                    _ => {
+                        let names: Vec<String> = old.iter().map(|d| d.display().to_string()).collect();
+                        self.log_msg(format!(
+                            "Mod lists from an older TMM were found in {}; move the one to keep into {} by hand.",
+                            names.join(", "),
+                            mods_dir.display()
+                        ));
+                        None
+                    }
                 }
-                println!("Fallback successful. Associated with {} game objects.", mod_file.packages.len());
-            } else {
-                self.error_msg = Some(format!(
-                    "Could not auto-detect target for raw mod '{}'.\nPlease rename it to match the game file (e.g. S1_Elin_PC.gpk).", 
-                    file_name
-                ));
-                return false;
             }
-        } else {
-            // Ensure container is populated even for TMM-packed mods if somehow empty
-            if mod_file.container.is_empty() {
-                mod_file.container = file_name.trim_end_matches(".gpk").to_string();
+            _ => None,
+        };
+        if let Some(journal) = &mut journal {
+            let old_mapper = journal.from.join(COMPOSITE_MAPPER_FILE);
+            let renamed = journal.run(&[&self.root.composite_mapper_path, &old_mapper])?;
+            self.log_msg(format!(
+                "Moved TMM's files from {} to {}.",
+                journal.from.display(),
+                journal.to.display()
+            ));
+            for (old, new) in renamed {
+                self.log_msg(format!("{} was renamed to {}; a different file by that name was already there.", old, new));
             }
         }
 
-        let conflicts = self.find_conflicting_indices(&mod_file.packages);
-        for &idx in &conflicts {
-            if self.mod_list[idx].enabled {
-                println!("[TMM] Conflict detected: Disabling '{}' in favor of '{}'", self.mod_list[idx].file, file_name);
-        
-                let existing_file = self.mod_list[idx].mod_file.clone();
+        if stamp < storage_migration::LAYOUT_VERSION {
+            storage_migration::write_stamp(&mods_dir)?;
+        }
+        Ok(())
+    }
 
-                self.mod_list[idx].enabled = false;
-                // Restore the map for the conflicting mod
-                if let Err(e) = self.turn_off_mod(&existing_file, true) {
-                     eprintln!("Failed to disable conflicting mod: {:?}", e);
+    fn backup_composite_mapper(&mut self) -> bool {
+        // A mapper the fingerprint database knows settles the question without
+        // the heuristics below; unknown hashes fall through to them
+        let active_hash = utils::sha256_file(&self.root.composite_mapper_path).ok();
+        let known = active_hash.as_deref().map_or(Fingerprint::Unknown, |h| self.fingerprints.classify(h));
+        match known {
+            Fingerprint::Clean { build } => {
+                let backup_hash = utils::sha256_file(&self.root.backup_composite_mapper_path).ok();
+                if backup_hash == active_hash {
+                    return true;
                 }
+                return match fs::copy(&self.root.composite_mapper_path, &self.root.backup_composite_mapper_path) {
+                    Ok(_) => {
+                        self.log_msg(format!("Backup refreshed from the known clean mapper of build {}.", build));
+                        true
+                    }
+                    Err(e) => {
+                        self.log_msg(format!("Failed to refresh backup: {}", e));
+                        self.root.backup_composite_mapper_path.exists()
+                    }
+                };
             }
-        }
-
-        let mod_entry = ModEntry {
-            file: file_name.clone(),
-            enabled: true,
-            mod_file,
-        };
-
-        self.mod_list.push(mod_entry.clone());
-        self.game_config.mods.push(mod_entry.clone());
-        
-        if !self.wait_for_tera {
-            // Pass the filename
-            if let Err(e) = self.turn_on_mod(&mod_entry.mod_file) {
-                self.error_msg = Some(format!("Failed to apply new mod: {:?}", e));
+            Fingerprint::Modded { tool } => {
+                self.warning_msg = format!(
+                    "CompositePackageMapper.dat matches a mapper modified by {}. Restore the game files before using TMM.",
+                    tool
+                );
+                self.log_msg(self.warning_msg.clone());
+                // Never take a backup of it
+                return self.root.backup_composite_mapper_path.exists();
             }
-            self.composite_map.dirty = true;
-            self.commit_changes();
-        }
-        
-        if save {
-            self.save_game_config().ok();
+            Fingerprint::Unknown => {}
         }
-        self.status_msg = format!("Installed {:?}", mod_entry.mod_file.mod_name);
-        true
-    }
 
-    pub fn enable_mod_safely(&mut self, index: usize) -> Result<()> {
-        if index >= self.mod_list.len() {
-            return Ok(());
+        if self.root.backup_composite_mapper_path.exists() {
+            return true;
         }
 
-        let target_mod = self.mod_list[index].clone();
-        
-        // Find conflicts with OTHER enabled mods
-        let conflicts = self.find_conflicting_indices(&target_mod.mod_file.packages);
+        if !self.root.composite_mapper_path.exists() {
+            return false;
+        }
 
-        // Disable conflicting mods first
-        for &conflict_idx in &conflicts {
-            if self.mod_list[conflict_idx].enabled {
-                println!("[TMM] Disabling conflicting mod: {}", self.mod_list[conflict_idx].file);
-                self.mod_list[conflict_idx].enabled = false;
-                let m_file = self.mod_list[conflict_idx].mod_file.clone();
-                if let Err(e) = self.turn_off_mod(&m_file, true) {
-                    eprintln!("Error disabling conflicting mod: {:?}", e);
+        // The launcher may be mid-write, so give a broken copy one more chance
+        for attempt in 0..2 {
+            if attempt > 0 {
+                std::thread::sleep(BACKUP_RETRY_DELAY);
+            }
+            if fs::copy(&self.root.composite_mapper_path, &self.root.backup_composite_mapper_path).is_err() {
+                continue;
+            }
+            match self.validate_backup() {
+                Ok(()) => return true,
+                Err(e) => {
+                    self.log_msg(format!("Backup rejected: {}", e));
+                    fs::remove_file(&self.root.backup_composite_mapper_path).ok();
                 }
             }
         }
+        false
+    }
 
-        // Enable the target mod
-        self.mod_list[index].enabled = true;
-        if let Err(e) = self.turn_on_mod(&target_mod.mod_file) {
-            return Err(e);
+    // A backup is only trusted if it decrypts and parses to a plausible number of entries
+    fn validate_backup(&mut self) -> Result<()> {
+        let backup = CompositeMapperFile::new(self.root.backup_composite_mapper_path.clone())?;
+        if backup.is_too_small(self.min_backup_entries) {
+            anyhow::bail!(
+                "only {} entries, expected at least {}",
+                backup.len(),
+                self.min_backup_entries
+            );
         }
-        
-        self.composite_map.dirty = true;
-        self.update_mods_list(self.mod_list.clone());
+
+        let hash = utils::sha256_file(&self.root.backup_composite_mapper_path)?;
+        self.log_msg(format!(
+            "Backup verified: {} entries, sha256 {}",
+            backup.len(),
+            hash
+        ));
         Ok(())
     }
 
-    pub fn turn_on_mod(&mut self, mod_file: &ModFile) -> Result<()> {
-        
-        for pkg in &mod_file.packages {
-            let mut entry = CompositeEntry::default();
-
-            // Try to find the object
-            if !self
-                .composite_map
-                .get_entry_by_incomplete_object_path(&pkg.object_path, &mut entry)
-            {
-                // LOG the error but DON'T bail. Continue to the next package.
-                eprintln!("[TMM] Warning: Object '{}' not found in CompositeMap. Skipping.", pkg.object_path);
-                continue;
-            }
-
-            // Apply patch if found
-            if let Err(e) = self.composite_map.apply_patch(
-                &entry.composite_name,
-                &mod_file.container,
-                pkg.offset,
-                pkg.size,
-            ) {
-                eprintln!("[TMM] Warning: Failed to patch '{}': {:?}", pkg.object_path, e);
-            }
+    fn offer_foreign_backups(&mut self) {
+        self.foreign_backups = self.find_foreign_backups();
+        if !self.foreign_backups.is_empty() {
+            self.log_msg(format!(
+                "Found {} mapper backups left by other tools.",
+                self.foreign_backups.len()
+            ));
         }
-
-        Ok(())
     }
 
+    // Backups from other tools that decrypt to a plausible vanilla map: enough
+    // entries and no entry pointing at the container of an installed mod.
+    fn find_foreign_backups(&self) -> Vec<ForeignBackup> {
+        let cooked_pc = &self.root.cooked_pc_dir;
+        let mod_containers: HashSet<String> = File::open(&self.root.game_config_path)
+            .ok()
+            .and_then(|mut f| mod_model::read_game_config(&mut f).ok())
+            .map(|cfg| cfg.mods.iter().map(|m| m.effective_container().to_lowercase()).collect())
+            .unwrap_or_default();
 
-    pub fn turn_off_mod(&mut self, mod_file: &ModFile, silent: bool) -> Result<()> {
-        for pkg in &mod_file.packages {
-            let mut original = CompositeEntry::default();
-
-            // Try to find the original entry in the backup (clean) map
-            if self.backup_map.get_entry_by_incomplete_object_path(&pkg.object_path, &mut original) {
-                self.composite_map.apply_patch(
-                    &original.composite_name,
-                    &original.filename,
-                    original.offset,
-                    original.size,
-                )?;
+        FOREIGN_BACKUP_FILES
+            .iter()
+            .map(|name| cooked_pc.join(name))
+            .filter(|path| path.is_file())
+            .filter_map(|path| {
+                let map = CompositeMapperFile::new(path.clone()).ok()?;
+                let entries = map.len();
+                let modded = map
+                    .values()
+                    .any(|e| mod_containers.contains(&utils::strip_gpk_ext(&e.filename).to_lowercase()));
+                (entries >= self.min_backup_entries && !modded).then_some(ForeignBackup { path, entries })
+            })
+            .collect()
+    }
+
+    // Copies a foreign backup into place as TMM's clean backup. The original is
+    // left where it is.
+    fn adopt_foreign_backup(&mut self, index: usize) -> Result<()> {
+        let source = self.foreign_backups[index].path.clone();
+        fs::copy(&source, &self.root.backup_composite_mapper_path)?;
+        if let Err(e) = self.validate_backup() {
+            fs::remove_file(&self.root.backup_composite_mapper_path).ok();
+            return Err(e);
+        }
+        self.log_msg(format!("Adopted {} as the clean backup.", source.display()));
+        self.foreign_backups.clear();
+        self.initialized = false;
+        Ok(())
+    }
+
+    // A mapper that loads without parse errors and has at least the minimum
+    // number of entries. A save cut short leaves a shorter or unparsable file.
+    fn mapper_is_intact(&self, path: &Path) -> bool {
+        CompositeMapperFile::new(path.to_path_buf())
+            .is_ok_and(|map| map.parse_errors.is_empty() && map.len() >= self.min_backup_entries)
+    }
+
+    // Deals with a temporary mapper left by a TMM process that died while
+    // saving. Returns false when the user has to choose between the temporary
+    // file and the clean backup before the mapper can be loaded.
+    fn recover_interrupted_save(&mut self) -> bool {
+        let mapper_path = self.root.composite_mapper_path.clone();
+        let temp = composite_mapper::temp_path(&mapper_path);
+        if !temp.exists() {
+            return true;
+        }
+        let temp_intact = self.mapper_is_intact(&temp);
+        let mapper_intact = self.mapper_is_intact(&mapper_path);
+        self.log_msg(format!(
+            "Found {} left by an interrupted save (temporary file {}, mapper {}).",
+            temp.display(),
+            if temp_intact { "intact" } else { "damaged" },
+            if mapper_intact { "intact" } else { "damaged" }
+        ));
+
+        match (temp_intact, mapper_intact) {
+            (_, true) => {
+                match fs::remove_file(&temp) {
+                    Ok(()) => self.log_msg("The mapper is intact; removed the stale temporary file.".to_string()),
+                    Err(e) => self.log_msg(format!("Failed to remove {}: {}", temp.display(), e)),
+                }
+                true
+            }
+            (true, false) => {
+                self.log_msg("Asking whether to use the temporary file or restore the clean backup.".to_string());
+                self.interrupted_save = Some(temp);
+                false
+            }
+            (false, false) => {
+                let restored = self.restore_composite_mapper();
+                fs::remove_file(&temp).ok();
+                let msg = if restored {
+                    "TMM was interrupted while saving CompositePackageMapper.dat and neither the mapper nor the half-written copy could be read. The clean backup was restored; apply your mods again."
+                } else {
+                    "TMM was interrupted while saving CompositePackageMapper.dat and neither the mapper nor the half-written copy could be read. Restoring the clean backup failed too; repair the game files."
+                };
+                self.log_msg(msg.to_string());
+                self.error_msg = Some(msg.to_string());
+                restored
+            }
+        }
+    }
+
+    // Answers the question from recover_interrupted_save: the temporary file
+    // replaces the damaged mapper, or the clean backup does. Loading then
+    // starts over.
+    fn resolve_interrupted_save(&mut self, use_temp: bool) {
+        let Some(temp) = self.interrupted_save.take() else {
+            return;
+        };
+        if use_temp {
+            match fs::rename(&temp, &self.root.composite_mapper_path) {
+                Ok(()) => self.log_msg("Replaced the damaged mapper with the completed temporary file.".to_string()),
+                Err(e) => {
+                    self.log_msg(format!("Failed to promote {}: {}", temp.display(), e));
+                    self.error_msg = Some(format!("Failed to use the saved mapper: {}", e));
+                    return;
+                }
+            }
+        } else {
+            if !self.restore_composite_mapper() {
+                self.log_msg("Restoring the clean backup over the damaged mapper failed.".to_string());
+                return;
+            }
+            fs::remove_file(&temp).ok();
+            self.log_msg("Restored the clean backup over the damaged mapper; discarded the temporary file.".to_string());
+        }
+        self.initialized = false;
+    }
+
+    fn restore_composite_mapper(&mut self) -> bool {
+        if !self.root.backup_composite_mapper_path.exists() {
+            self.error_msg = Some("Restore Failed - Missing Backup File, Please Turn Off All Mods And Restart TMM".to_string());
+            return false;
+        }
+        let restored = fs::copy(&self.root.backup_composite_mapper_path, &self.root.composite_mapper_path).is_ok();
+        if restored {
+            self.remove_checksum_sidecar();
+        }
+        self.emit_event(HookEvent::RestoreCompleted { success: restored });
+        restored
+    }
+
+    // Saves the active map and refreshes the checksum sidecar when enabled
+    fn save_mapper(&mut self) -> std::io::Result<usize> {
+        let written = self.composite_map.save(&self.root.composite_mapper_path)?;
+        if self.write_checksum_sidecar {
+            if let Err(e) = self.write_checksum_sidecar_file() {
+                self.log_msg(format!("Failed to write checksum sidecar: {}", e));
+            }
+        }
+        Ok(written)
+    }
+
+    fn checksum_sidecar_path(&self) -> PathBuf {
+        let mut name = self.root.composite_mapper_path.clone().into_os_string();
+        name.push(CHECKSUM_SIDECAR_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    // Lets external tools check the mapper without decrypting it. Written to a
+    // temporary file first so readers never see a half-written sidecar.
+    fn write_checksum_sidecar_file(&self) -> Result<()> {
+        let hash = utils::sha256_file(&self.root.composite_mapper_path)?;
+        let clean = utils::sha256_file(&self.root.backup_composite_mapper_path).is_ok_and(|backup| backup == hash);
+        let contents = format!(
+            "sha256={}\ntmm_version={}\nstate={}\n",
+            hash,
+            env!("CARGO_PKG_VERSION"),
+            if clean { "clean" } else { "modded" }
+        );
+
+        let path = self.checksum_sidecar_path();
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn remove_checksum_sidecar(&mut self) {
+        let path = self.checksum_sidecar_path();
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                self.log_msg(format!("Failed to remove checksum sidecar: {}", e));
+            }
+        }
+    }
+
+    // Edits only mark the mod list dirty; it is written once edits settle for
+    // MOD_LIST_SAVE_DEBOUNCE, or immediately on commit, root change and exit.
+    fn update_mods_list(&mut self, mod_data: Vec<ModEntry>) {
+        self.game_config.mods = mod_data;
+        // Rebuilt from the new list when the Conflicts window next draws
+        self.conflict_pairs = None;
+        self.root.storage_scan = None;
+        self.mod_list_dirty = true;
+        self.mod_list_last_edit = std::time::Instant::now();
+    }
+
+    fn flush_mod_list(&mut self) {
+        // Edits to a read-only mod list are kept for this session only
+        if self.mod_list_written_by.is_read_only() {
+            self.mod_list_dirty = false;
+        }
+        if !self.mod_list_dirty {
+            self.save_retry.succeeded(SaveTarget::ModList);
+            return;
+        }
+        if let Err(e) = self.save_game_config() {
+            self.error_msg = Some(format!("Failed to save mod list: {}", e));
+            self.save_retry.failed(SaveTarget::ModList, e.to_string(), std::time::Instant::now());
+        } else {
+            self.mod_list_dirty = false;
+            self.save_retry.succeeded(SaveTarget::ModList);
+        }
+    }
+
+    // Closing with a save still pending would lose it, so ask first
+    fn handle_close_request(&mut self, ctx: &Context) {
+        let close_requested = ctx.input(|i| i.viewport().close_requested());
+        if !close_requested || self.quit_confirmed {
+            return;
+        }
+        // The pending save may go through on this last attempt
+        self.save_retry.retry_now(std::time::Instant::now());
+        self.retry_failed_saves();
+        if self.save_retry.is_pending() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.confirm_quit_unsaved = true;
+        }
+    }
+
+    // Runs the saves whose retry is due; each records its own outcome
+    fn retry_failed_saves(&mut self) {
+        for target in self.save_retry.due(std::time::Instant::now()) {
+            self.log_msg(format!("Retrying {} save...", target.label()));
+            match target {
+                SaveTarget::ModList => self.flush_mod_list(),
+                SaveTarget::Mapper => self.commit_changes(),
+            }
+            if !self.save_retry.contains(target) {
+                self.log_msg(format!("Saved {} on retry.", target.label()));
+            }
+        }
+    }
+
+    // Helper to find indices of currently enabled mods that share object paths with the provided packages
+    fn find_conflicting_indices(&self, packages: &[CompositePackage]) -> Vec<usize> {
+        patch::find_conflicting_indices(&self.mod_list, packages)
+    }
+
+    // Plain click selects only the row, Ctrl+click toggles it and Shift+click
+    // selects the rows from the anchor to it, added to the selection with Ctrl
+    // also held. Ranges follow `rows`, the table rows in display order.
+    fn click_row(&mut self, index: usize, modifiers: egui::Modifiers, rows: &[usize]) {
+        if modifiers.shift {
+            self.select_range(index, modifiers.command, rows);
+        } else if modifiers.command {
+            if self.selected_mods.contains(&index) {
+                self.selected_mods.retain(|&x| x != index);
+            } else {
+                self.selected_mods.push(index);
+            }
+            self.selection_anchor = Some(index);
+        } else {
+            self.selected_mods = vec![index];
+            self.selection_anchor = Some(index);
+        }
+        self.selection_focus = Some(index);
+    }
+
+    // Up/Down select the next row in display order; with Shift the selection
+    // stretches from the anchor instead
+    fn step_selection(&mut self, down: bool, extend: bool, rows: &[usize]) {
+        let Some(last) = rows.len().checked_sub(1) else {
+            return;
+        };
+        let next = match self.selection_focus.and_then(|f| rows.iter().position(|&r| r == f)) {
+            Some(p) if down => (p + 1).min(last),
+            Some(p) => p.saturating_sub(1),
+            None if down => 0,
+            None => last,
+        };
+        let index = rows[next];
+        if extend {
+            self.select_range(index, false, rows);
+        } else {
+            self.selected_mods = vec![index];
+            self.selection_anchor = Some(index);
+        }
+        self.selection_focus = Some(index);
+    }
+
+    // A hidden or missing anchor makes the range just the row itself
+    fn select_range(&mut self, index: usize, add: bool, rows: &[usize]) {
+        let Some(to) = rows.iter().position(|&r| r == index) else {
+            return;
+        };
+        let from = self
+            .selection_anchor
+            .and_then(|a| rows.iter().position(|&r| r == a))
+            .unwrap_or_else(|| {
+                self.selection_anchor = Some(index);
+                to
+            });
+        if !add {
+            self.selected_mods.clear();
+        }
+        for &i in &rows[from.min(to)..=from.max(to)] {
+            if !self.selected_mods.contains(&i) {
+                self.selected_mods.push(i);
+            }
+        }
+    }
+
+    // Runs a change that may drop or reorder entries of mod_list, then points
+    // the selection, its anchor and focus and the mod being edited back at the
+    // same mods by ID. Mods that are gone drop out.
+    fn preserving_selection<R>(&mut self, change: impl FnOnce(&mut Self) -> R) -> R {
+        let id_at = |app: &Self, i: usize| app.mod_list.get(i).map(|m| m.id);
+        let selected: Vec<u64> = self.selected_mods.iter().filter_map(|&i| id_at(self, i)).collect();
+        let anchor = self.selection_anchor.and_then(|i| id_at(self, i));
+        let focus = self.selection_focus.and_then(|i| id_at(self, i));
+        let editing = self.edit_mod.and_then(|i| id_at(self, i));
+
+        let result = change(self);
+
+        let position = |id: u64| self.mod_list.iter().position(|m| m.id == id);
+        let mut selected_mods: Vec<usize> = selected.into_iter().filter_map(position).collect();
+        selected_mods.dedup();
+        let (anchor, focus, editing) = (anchor.and_then(position), focus.and_then(position), editing.and_then(position));
+        self.selected_mods = selected_mods;
+        self.selection_anchor = anchor;
+        self.selection_focus = focus;
+        self.edit_mod = editing;
+        result
+    }
+
+    // Adds `indices` to the selection, keeping `clicked` selected
+    fn add_to_selection(&mut self, clicked: usize, indices: Vec<usize>) {
+        let before = self.selected_mods.len();
+        for i in std::iter::once(clicked).chain(indices) {
+            if !self.selected_mods.contains(&i) {
+                self.selected_mods.push(i);
+            }
+        }
+        self.status_msg = format!("Selected {} more mods.", self.selected_mods.len() - before);
+    }
+
+    // Indexes every object path to the mods that patch it, then counts shared
+    // paths per pair. Only the counts are kept; path lists are built on demand.
+    fn build_conflict_pairs(&self, enabled_only: bool) -> Vec<ConflictPair> {
+        let mut by_path: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, m) in self.mod_list.iter().enumerate() {
+            if enabled_only && !m.enabled {
+                continue;
+            }
+            for pkg in &m.mod_file.packages {
+                let owners = by_path.entry(pkg.object_path.as_str()).or_default();
+                if owners.last() != Some(&i) {
+                    owners.push(i);
+                }
+            }
+        }
+
+        let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+        for owners in by_path.values() {
+            for (n, &a) in owners.iter().enumerate() {
+                for &b in &owners[n + 1..] {
+                    *counts.entry((a, b)).or_default() += 1;
+                }
+            }
+        }
+
+        let mut pairs: Vec<ConflictPair> = counts
+            .into_iter()
+            .map(|((a, b), shared)| ConflictPair { a: self.mod_list[a].id, b: self.mod_list[b].id, shared })
+            .collect();
+        pairs.sort_by(|x, y| y.shared.cmp(&x.shared).then((x.a, x.b).cmp(&(y.a, y.b))));
+        pairs
+    }
+
+    // The enabled mod whose container the active mapper entry now points at.
+    // None when the entry still matches the clean backup.
+    pub fn find_mod_that_patched(&self, composite_name: &str) -> Option<&ModEntry> {
+        let entry = self.composite_map.composite_map.get(composite_name)?;
+        let vanilla = self.backup_map.get(composite_name)?;
+        if entry.filename == vanilla.filename {
+            return None;
+        }
+        self.mod_list
+            .iter()
+            .find(|m| m.enabled && m.effective_container().eq_ignore_ascii_case(&entry.filename))
+    }
+
+    pub fn patched_by_label(&self, composite_name: &str) -> String {
+        if let Some(m) = self.find_mod_that_patched(composite_name) {
+            return format!("Patched by: {}", m.mod_file.mod_name);
+        }
+        let current = self.composite_map.composite_map.get(composite_name);
+        let vanilla = self.backup_map.get(composite_name);
+        match (current, vanilla) {
+            (Some(c), Some(v)) if c.filename != v.filename => format!("Points at {} (no enabled mod)", c.filename),
+            _ => "Vanilla (not modded)".to_string(),
+        }
+    }
+
+    // Pre-flight check before applying: object paths that no longer exist in the
+    // game's mapper (usually after a patch renamed them) would be skipped silently.
+    pub fn check_all_object_paths_resolvable(&self) -> Vec<UnresolvablePackage> {
+        let mut unresolvable = Vec::new();
+        for m in self.mod_list.iter().filter(|m| m.enabled) {
+            for pkg in &m.mod_file.packages {
+                let mut entry = CompositeEntry::default();
+                if !self.backup_map.get_entry_by_incomplete_object_path(&pkg.object_path, &mut entry) {
+                    unresolvable.push(UnresolvablePackage {
+                        mod_name: m.mod_file.mod_name.clone(),
+                        object_path: pkg.object_path.clone(),
+                    });
+                }
+            }
+        }
+        unresolvable
+    }
+
+    fn shared_object_paths(&self, a: usize, b: usize) -> Vec<String> {
+        let theirs: HashSet<&str> = self.mod_list[b]
+            .mod_file
+            .packages
+            .iter()
+            .map(|p| p.object_path.as_str())
+            .collect();
+        let mut shared: Vec<String> = self.mod_list[a]
+            .mod_file
+            .packages
+            .iter()
+            .filter(|p| theirs.contains(p.object_path.as_str()))
+            .map(|p| p.object_path.clone())
+            .collect();
+        shared.sort();
+        shared.dedup();
+        shared
+    }
+
+    fn export_conflicts_csv(&self, dest: &Path) -> Result<()> {
+        let mut csv = String::from("mod_a,mod_b,shared_objects\n");
+        for pair in self.conflict_pairs.iter().flatten() {
+            let (Some(a), Some(b)) = (self.mod_index(pair.a), self.mod_index(pair.b)) else {
+                continue;
+            };
+            csv.push_str(&format!(
+                "\"{}\",\"{}\",{}\n",
+                self.mod_list[a].file.replace('"', "\"\""),
+                self.mod_list[b].file.replace('"', "\"\""),
+                pair.shared
+            ));
+        }
+        fs::write(dest, csv)?;
+        Ok(())
+    }
+
+    fn export_changes_report_dialog(&mut self) {
+        let Some(dest) = rfd::FileDialog::new()
+            .add_filter("Markdown", &["md"])
+            .set_file_name("tmm-changes.md")
+            .save_file()
+        else {
+            return;
+        };
+        match self.export_changes_report(&dest) {
+            Ok(changed) => {
+                self.status_msg = format!("Exported {} changed entries to {}", changed, dest.display())
+            }
+            Err(e) => self.error_msg = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    // Markdown list of every mapper entry that differs from the clean backup,
+    // grouped by the mod whose container it points at. Read from the mapper on
+    // disk, not composite_map, so it shows what the game will actually load.
+    // Returns the number of changed entries.
+    fn export_changes_report(&self, dest: &Path) -> Result<usize> {
+        let path = &self.root.composite_mapper_path;
+        let hash = utils::sha256_file(path)?;
+        let on_disk = CompositeMapperFile::new(path.clone())?;
+
+        // (current entry, vanilla entry)
+        type Change<'a> = (&'a CompositeEntry, Option<&'a CompositeEntry>);
+        // Owner index -> changes, in mod list order; None collects the rest
+        let mut groups: Vec<(Option<usize>, Vec<Change>)> = Vec::new();
+        let mut changed = 0;
+        for entry in on_disk.values() {
+            let vanilla = self.backup_map.get(&entry.composite_name);
+            let unchanged = vanilla.is_some_and(|v| {
+                v.filename == entry.filename && v.offset == entry.offset && v.size == entry.size
+            });
+            if unchanged {
+                continue;
+            }
+            changed += 1;
+            let owns = |m: &ModEntry| m.effective_container().eq_ignore_ascii_case(&entry.filename);
+            let owner = self
+                .mod_list
+                .iter()
+                .position(|m| m.enabled && owns(m))
+                .or_else(|| self.mod_list.iter().position(owns));
+            match groups.iter_mut().find(|(o, _)| *o == owner) {
+                Some((_, changes)) => changes.push((entry, vanilla)),
+                None => groups.push((owner, vec![(entry, vanilla)])),
+            }
+        }
+        groups.sort_by_key(|(owner, _)| owner.unwrap_or(usize::MAX));
+
+        let mut report = String::from("# TMM changes report\n\n");
+        report.push_str(&format!("- Generated: {}\n", utils::format_utc(utils::unix_now())));
+        report.push_str(&format!("- Mapper: {}\n", path.display()));
+        report.push_str(&format!("- Mapper SHA-256: {}\n", hash));
+        report.push_str(&format!("- Changed entries: {}\n", changed));
+        for (owner, changes) in &groups {
+            match owner {
+                Some(i) => {
+                    let m = &self.mod_list[*i];
+                    report.push_str(&format!("\n## {} ({})\n\n", m.mod_file.mod_name, m.file));
+                }
+                None => report.push_str("\n## Unattributed changes\n\n"),
+            }
+            report.push_str("| Object path | Vanilla file | Current container |\n|---|---|---|\n");
+            for (entry, vanilla) in changes {
+                report.push_str(&format!(
+                    "| {} | {} | {} |\n",
+                    entry.object_path,
+                    vanilla.map_or("(not in clean mapper)", |v| v.filename.as_str()),
+                    entry.filename
+                ));
+            }
+        }
+        fs::write(dest, report)?;
+        Ok(changed)
+    }
+
+    fn mod_file_size(&self, entry: &ModEntry) -> u64 {
+        fs::metadata(self.root.mods_dir.join(&entry.file))
+            .map(|m| m.len())
+            .unwrap_or(0)
+    }
+
+    fn scan_storage(&mut self) {
+        if self.root.storage_scan.is_none() {
+            let scan = StorageScan {
+                sizes: self.mod_list.iter().map(|m| (m.id, self.mod_file_size(m))).collect(),
+                restorable: self
+                    .mod_list
+                    .iter()
+                    .filter(|m| !m.source_path.is_empty() && Path::new(&m.source_path).is_file())
+                    .map(|m| m.id)
+                    .collect(),
+            };
+            self.root.storage_scan = Some(scan);
+        }
+    }
+
+    // (total bytes of installed mod files, bytes belonging to disabled mods)
+    fn storage_overview(&mut self) -> (u64, u64) {
+        self.scan_storage();
+        let sizes = &self.root.storage_scan.as_ref().expect("scanned above").sizes;
+        self.mod_list.iter().fold((0, 0), |(total, disabled), m| {
+            let size = sizes.get(&m.id).copied().unwrap_or(0);
+            (total + size, if m.enabled { disabled } else { disabled + size })
+        })
+    }
+
+    // Disabled mods whose files are still present, that haven't been applied
+    // (or installed, if never applied) within `days`, and whose source is
+    // still there to reinstall them from
+    fn stale_disabled_mods(&mut self, days: u32) -> Vec<usize> {
+        let cutoff = utils::unix_now().saturating_sub(days as u64 * 86_400);
+        self.scan_storage();
+        let restorable = &self.root.storage_scan.as_ref().expect("scanned above").restorable;
+        self.mod_list
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| mod_model::is_stale(m, cutoff) && restorable.contains(&m.id))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn cleanup_stale_mods(&mut self, days: u32) {
+        let stale = self.stale_disabled_mods(days);
+        let mut freed = 0;
+        let mut removed = 0;
+        for &idx in &stale {
+            let path = self.root.mods_dir.join(&self.mod_list[idx].file);
+            let size = self.mod_file_size(&self.mod_list[idx]);
+            match fs::remove_file(&path) {
+                Ok(()) => {
+                    self.mod_list[idx].archived = true;
+                    freed += size;
+                    removed += 1;
+                    self.log_msg(format!("Removed file of {}", self.mod_list[idx].file));
+                }
+                Err(e) => self.log_msg(format!("Failed to remove {}: {}", path.display(), e)),
+            }
+        }
+        self.update_mods_list(self.mod_list.clone());
+        self.status_msg = format!(
+            "Removed {}/{} files, freed {}.",
+            removed,
+            stale.len(),
+            utils::format_bytes(freed)
+        );
+    }
+
+    fn reinstall_archived_mod(&mut self, idx: usize) -> Result<()> {
+        let entry = &self.mod_list[idx];
+        let source = PathBuf::from(&entry.source_path);
+        anyhow::ensure!(source.exists(), "Source file {:?} no longer exists", source);
+        let target = self.root.mods_dir.join(&entry.file);
+        if utils::is_tmod(&source) {
+            let data = fs::read(&source)?;
+            fs::write(&target, mod_model::read_tmod(&data)?.0)?;
+        } else {
+            fs::copy(&source, &target)?;
+        }
+        self.mod_list[idx].archived = false;
+        self.update_mods_list(self.mod_list.clone());
+        Ok(())
+    }
+
+    fn install_mod(&mut self, path: &Path, save: bool, enable: bool) -> bool {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        self.install_mod_as(InstallSource::new(path, None), &file_name, save, enable, false)
+    }
+
+    // Installs `source` into mods_dir as `file_name`. A file already there
+    // under that name is only replaced when `overwrite` is set; callers compare
+    // the two beforehand, off the UI thread. For a .tmod the metadata is used
+    // instead of file name guesses. Without a known hash the file is hashed in
+    // the background.
+    fn install_mod_as(&mut self, source: InstallSource, file_name: &str, save: bool, enable: bool, overwrite: bool) -> bool {
+        let path = source.content();
+        let tmod = source.tmod;
+        let metadata = tmod.map(|t| &t.meta);
+        if !utils::is_gpk(path) {
+            self.error_msg = Some(format!("Not a .gpk file: {:?}", path));
+            return false;
+        }
+        // Checked before anything is copied into the game folder
+        if !self.allow_headerless_gpk {
+            match File::open(path).map_err(anyhow::Error::from).and_then(|mut f| mod_model::has_package_signature(&mut f)) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.error_msg = Some(format!(
+                        "{} is not an Unreal package: it has no package signature (0x{:08X}) at the start or end. Enable \"Accept .gpk files without a package signature\" in Settings if it really is one.",
+                        path.display(),
+                        mod_model::PACKAGE_MAGIC
+                    ));
+                    return false;
+                }
+                Err(e) => {
+                    self.error_msg = Some(format!("Failed to read {}: {}", path.display(), e));
+                    return false;
+                }
+            }
+        }
+
+        let target_path = self.root.mods_dir.join(file_name);
+        if self.mod_list.iter().any(|m| m.file.eq_ignore_ascii_case(file_name)) {
+            self.error_msg = Some(format!("{} is already installed", file_name));
+            return false;
+        }
+        // Files picked up from mods_dir itself are registered in place. Copying
+        // a file onto itself would truncate it on Windows, so the paths are
+        // compared as resolved by the file system, not as spelled.
+        let copied = !utils::same_file(path, &target_path);
+        // A copy is staged next to the target and only renamed over it once
+        // the mod's targets are known, so a failed install never costs the
+        // file that was already there
+        let staged = if copied { composite_mapper::temp_path(&target_path) } else { target_path.clone() };
+        if copied {
+            if !overwrite && target_path.exists() {
+                self.error_msg = Some(format!(
+                    "{} already exists in {}",
+                    file_name,
+                    self.root.mods_dir.display()
+                ));
+                return false;
+            }
+            if fs::copy(path, &staged).is_err() {
+                fs::remove_file(&staged).ok();
+                self.error_msg = Some(format!("Failed to copy mod file: {:?}", path));
+                return false;
+            }
+        }
+        let discard_copy = || {
+            if copied {
+                fs::remove_file(&staged).ok();
+            }
+        };
+
+        let mut mod_file = ModFile::default();
+        let read = match File::open(&staged) {
+            Ok(mut file) => mod_model::read_mod_file(&mut file, &mut mod_file),
+            Err(e) => {
+                discard_copy();
+                self.error_msg = Some(format!("Failed to open {}: {}", staged.display(), e));
+                return false;
+            }
+        };
+    
+        let is_raw = if read.is_err() {
+            true // Failed to read, definitely raw
+        } else {
+            // Check if the read resulted in the "dummy" single package (size 0)
+            // If mod_file.packages has 1 item with size 0, it's likely a raw fallback from read_mod_file.
+            // A lone author tag leaves no packages at all.
+            mod_file.packages.is_empty() || (mod_file.packages.len() == 1 && mod_file.packages[0].size == 0)
+        };
+
+        let file_name = file_name.to_string();
+        // Raw mods are matched by the name they were added with, not the one
+        // they were renamed to on install
+        let source_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        let declared_paths = match metadata {
+            Some(meta) if source.targets.is_empty() => meta.object_paths.as_slice(),
+            _ => source.targets.as_slice(),
+        };
+        let unresolved: Vec<&String> = declared_paths
+            .iter()
+            .filter(|object_path| {
+                !self
+                    .composite_map
+                    .get_entry_by_incomplete_object_path(object_path, &mut CompositeEntry::default())
+            })
+            .collect();
+        if !unresolved.is_empty() {
+            let msg = format!(
+                "{} declares {} of {} object paths the mapper doesn't have; they will be skipped: {}",
+                source_name,
+                unresolved.len(),
+                declared_paths.len(),
+                unresolved.iter().take(5).map(|p| p.as_str()).collect::<Vec<_>>().join(", ")
+            );
+            self.log_msg(msg.clone());
+            self.warning_msg = msg;
+        }
+
+        if is_raw && !declared_paths.is_empty() {
+            mod_file.packages = declared_paths
+                .iter()
+                .map(|object_path| CompositePackage { object_path: object_path.clone(), ..Default::default() })
+                .collect();
+            mod_file.mod_name = file_name.clone();
+            mod_file.container = utils::strip_gpk_ext(&file_name).to_string();
+            println!("[TMM] Raw GPK targets {} game objects declared by its .tmod metadata or picked.", mod_file.packages.len());
+        } else if is_raw {
+            // Logic for Raw GPKs (Fallback)
+            println!("Detected Raw/Unpacked GPK. Attempting to resolve via filename matching...");
+
+            // Try to find the mod name in the existing composite map.
+            // This assumes the user named the mod file exactly as the file it replaces
+            // (partial matches count, e.g. "S1_Elin" matches "S1_Elin_Mod").
+            let matches = raw_match::find(self.composite_map.values(), &source_name);
+            // The closest few of too many matches are a guess, never applied.
+            // The file waits for the user to pick its targets instead. An
+            // update keeps the listed mod's settings, which a pick made later
+            // can't carry, so it is only refused.
+            if matches.truncated {
+                discard_copy();
+                if source.replaces.is_some() {
+                    self.error_msg = Some(format!(
+                        "'{}' matches too many game packages to pick its targets from the name. Rename it to match the game file more exactly (e.g. S1_Elin_PC.gpk).",
+                        source_name
+                    ));
+                    return false;
+                }
+                let pick = PendingRawPick {
+                    source: source.path.to_path_buf(),
+                    file_name: file_name.clone(),
+                    enable,
+                    hash: source.hash.clone(),
+                    query: utils::strip_gpk_ext(&source_name).to_string(),
+                    edited_at: None,
+                    picked: vec![false; matches.entries.len()],
+                    matches: matches.entries.iter().map(|e| (e.object_path.clone(), e.filename.clone())).collect(),
+                    truncated: true,
+                    overwrite,
+                };
+                self.pending_raw_picks.push(pick);
+                self.error_msg = Some(format!(
+                    "'{}' matches too many game packages to pick its targets from the name. Pick them in the window that opened, or rename it to match the game file more exactly (e.g. S1_Elin_PC.gpk).",
+                    source_name
+                ));
+                return false;
+            }
+            let matched_packages: Vec<CompositePackage> = matches
+                .entries
+                .iter()
+                .map(|entry| CompositePackage {
+                    object_path: entry.object_path.clone(),
+                    offset: 0,
+                    size: 0,
+                    file_version: 0,
+                    licensee_version: 0,
+                })
+                .collect();
+
+            if !matched_packages.is_empty() {
+                mod_file.packages = matched_packages;
+                // Since we don't have the real name, use the filename as the display name
+                mod_file.mod_name = file_name.clone(); 
+                // Use filename as container if empty
+                if mod_file.container.is_empty() {
+                    mod_file.container = utils::strip_gpk_ext(&file_name).to_string();
+                }
+                println!("Fallback successful. Associated with {} game objects.", mod_file.packages.len());
+            } else {
+                // Left behind, the next scan would register it as a new mod
+                discard_copy();
+                self.error_msg = Some(format!(
+                    "Could not auto-detect target for raw mod '{}'.\nPlease rename it to match the game file (e.g. S1_Elin_PC.gpk).", 
+                    source_name
+                ));
+                return false;
+            }
+        } else {
+            // Ensure container is populated even for TMM-packed mods if somehow empty
+            if mod_file.container.is_empty() {
+                mod_file.container = utils::strip_gpk_ext(&file_name).to_string();
+            }
+        }
+        // The client loads the container by file name, so a file installed
+        // under another name takes that name as its container
+        if !file_name.eq_ignore_ascii_case(&source_name) {
+            mod_file.container = utils::strip_gpk_ext(&file_name).to_string();
+        }
+        // For the same reason an over-long container is shortened together
+        // with the file name
+        let (mut file_name, mut target_path) = (file_name, target_path);
+        let container = mod_file.container.clone();
+        if container.chars().count() > self.max_container_len {
+            mod_file.container = self.shorten_container(&container);
+            file_name = format!("{}.{}", mod_file.container, utils::GPK_EXTENSION);
+            target_path = self.root.mods_dir.join(&file_name);
+            self.log_msg(format!(
+                "Container {} is longer than {} characters; installed as {} instead.",
+                container, self.max_container_len, file_name
+            ));
+        }
+        if staged != target_path {
+            if let Err(e) = fs::rename(&staged, &target_path) {
+                discard_copy();
+                self.error_msg = Some(format!("Failed to move the mod into {}: {}", self.root.mods_dir.display(), e));
+                return false;
+            }
+        }
+        if let Some(meta) = metadata.filter(|m| !m.name.is_empty()) {
+            mod_file.mod_name = meta.name.clone();
+        }
+        mod_model::fill_missing_author(&mut mod_file, &file_name);
+
+        let now = utils::unix_now();
+        let mut mod_entry = ModEntry {
+            id: mod_model::next_mod_id(&self.mod_list),
+            file: file_name.clone(),
+            file_hash: source.hash.clone().unwrap_or_default(),
+            mod_file,
+            installed_at: now,
+            // Archived mods are brought back from the .tmod, not the temporary copy
+            source_path: tmod.map_or(path, |t| t.tmod.as_path()).to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+        if let Some(meta) = metadata {
+            mod_entry.author = meta.author.clone();
+            mod_entry.description = meta.description.clone();
+            mod_entry.version = meta.version.clone();
+            mod_entry.target_paths = meta.object_paths.clone();
+        }
+        // Kept so the next scan doesn't guess them from the name again
+        if !source.targets.is_empty() {
+            mod_entry.target_paths = source.targets.clone();
+        }
+        mod_entry.category = self.categories.infer(&mod_entry.mod_file.packages);
+        if let Some(old) = source.replaces {
+            mod_entry.id = old.id;
+            mod_entry.author = old.author.clone();
+            mod_entry.tags = old.tags.clone();
+            mod_entry.exclusive_group = old.exclusive_group.clone();
+            mod_entry.locked = old.locked;
+            mod_entry.category_override = old.category_override.clone();
+            mod_entry.apply_after = old.apply_after.clone();
+            mod_entry.manual_apply_only = old.manual_apply_only;
+            mod_entry.conflict_wins = old.conflict_wins.clone();
+            if !old.container_override.is_empty() {
+                mod_entry.container_override = old.container_override.clone();
+            }
+        }
+
+        if mod_entry.file_hash.is_empty() {
+            self.file_hash_jobs.push((vec![mod_entry.id], HashJob::start(vec![target_path.clone()])));
+        }
+        self.mod_list.push(mod_entry.clone());
+        self.update_mods_list(self.mod_list.clone());
+        // An update is reported by the caller
+        if source.replaces.is_none() {
+            self.install_stats.total_mods_installed += 1;
+            self.status_msg = format!("Installed {:?}", mod_entry.mod_file.mod_name);
+            self.emit_event(HookEvent::ModInstalled {
+                mod_name: mod_entry.mod_file.mod_name.clone(),
+                file: mod_entry.file.clone(),
+                enabled: enable,
+            });
+        }
+
+        // A conflict with enabled mods leaves it installed but off until the
+        // user picks the winner
+        if enable {
+            match self.set_mod_state(mod_entry.id, true, ChangeOrigin::Install) {
+                Ok(outcome) => {
+                    if outcome.awaiting_choice {
+                        self.status_msg.push_str(", conflicts with enabled mods");
+                    }
+                    if !outcome.blocked_by.is_empty() {
+                        self.status_msg.push_str(", left off: conflicts with locked mods");
+                    }
+                    self.commit_mod_states();
+                }
+                Err(e) => self.error_msg = Some(format!("Failed to apply new mod: {:?}", e)),
+            }
+        }
+        // Batches save both once, after their last file
+        if save {
+            self.flush_mod_list();
+            self.save_app_config().ok();
+        }
+        true
+    }
+
+    fn remove_missing_mods(&mut self) {
+        let mods_dir = self.root.mods_dir.clone();
+        let before = self.mod_list.len();
+        self.preserving_selection(|app| app.mod_list.retain(|m| m.archived || mods_dir.join(&m.file).exists()));
+        self.update_mods_list(self.mod_list.clone());
+        self.flush_mod_list();
+        self.status_msg = format!("Removed {} missing mods from the list.", before - self.mod_list.len());
+        self.missing_mods.clear();
+    }
+
+    // Asks for the location of each missing file in turn and copies it back
+    // into mods_dir under its listed name.
+    fn locate_missing_mods(&mut self) {
+        let missing = std::mem::take(&mut self.missing_mods);
+        let files: Vec<String> = self
+            .mod_list
+            .iter()
+            .filter(|m| missing.contains(&m.id))
+            .map(|m| m.file.clone())
+            .collect();
+        let mut located = 0;
+        for file in &files {
+            let Some(source) = rfd::FileDialog::new()
+                .set_title(format!("Locate {}", file))
+                .pick_file()
+            else {
+                continue;
+            };
+            match fs::copy(&source, self.root.mods_dir.join(file)) {
+                Ok(_) => located += 1,
+                Err(e) => self.log_msg(format!("Failed to restore {}: {}", file, e)),
+            }
+        }
+
+        if located > 0 {
+            // Rescan so the restored files get their packages read
+            self.initialized = false;
+        }
+        self.status_msg = format!("Located {}/{} missing mods.", located, missing.len());
+    }
+
+    // Entries pointing at the same .gpk (name compared case-insensitively) or at
+    // files with the same contents. Each group lists IDs in mod_list order.
+    fn find_duplicate_mods(&self) -> Vec<Vec<u64>> {
+        let listed: Vec<&ModEntry> = self.mod_list.iter().filter(|m| !m.archived).collect();
+        let mut groups: Vec<Vec<u64>> = Vec::new();
+        let mut grouped: HashSet<u64> = HashSet::new();
+        for (i, a) in listed.iter().enumerate() {
+            if grouped.contains(&a.id) {
+                continue;
+            }
+            let mut group = vec![a.id];
+            for b in &listed[i + 1..] {
+                let same_file = a.file.eq_ignore_ascii_case(&b.file);
+                let same_hash = !a.file_hash.is_empty() && a.file_hash == b.file_hash;
+                if (same_file || same_hash) && !grouped.contains(&b.id) {
+                    group.push(b.id);
+                }
+            }
+            if group.len() > 1 {
+                grouped.extend(group.iter().copied());
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    fn check_duplicate_mods(&mut self) {
+        self.duplicate_mods = self.find_duplicate_mods();
+        let groups = self.duplicate_mods.clone();
+        for group in &groups {
+            let files: Vec<&str> = self
+                .mod_list
+                .iter()
+                .filter(|m| group.contains(&m.id))
+                .map(|m| m.file.as_str())
+                .collect();
+            let msg = format!("Duplicate mod entries: {}", files.join(", "));
+            self.log_msg(msg);
+        }
+    }
+
+    // Collapses each duplicate group into its first entry, or with `merge` unset
+    // just drops the others. Merging keeps the union of the user's metadata and
+    // leaves the entry enabled if any copy was. Extra files with the same
+    // contents as the kept one are deleted so they aren't picked up again as new.
+    fn resolve_duplicate_mods(&mut self, merge: bool) {
+        let groups = std::mem::take(&mut self.duplicate_mods);
+        let mut removed = 0;
+        for group in &groups {
+            let Some(keep_idx) = self.mod_list.iter().position(|m| m.id == group[0]) else {
+                continue;
+            };
+            let keep_id = self.mod_list[keep_idx].id;
+            let enabled: Vec<u64> = self
+                .mod_list
+                .iter()
+                .filter(|m| group.contains(&m.id) && m.enabled)
+                .map(|m| m.id)
+                .collect();
+            // Merging leaves the entry enabled if any copy was
+            let enable_kept = if merge { !enabled.is_empty() } else { enabled.contains(&keep_id) };
+
+            // Switch every enabled copy off first, their package lists may differ
+            for &id in &enabled {
+                if let Err(e) = self.set_mod_state(id, false, ChangeOrigin::Duplicates) {
+                    let name = self.mod_names(&[id]).concat();
+                    self.log_msg(format!("Failed to revert duplicate {}: {:?}", name, e));
+                }
+            }
+
+            let extras: Vec<ModEntry> = self
+                .mod_list
+                .iter()
+                .filter(|m| group[1..].contains(&m.id))
+                .cloned()
+                .collect();
+            let Some(keep_idx) = self.mod_list.iter().position(|m| m.id == keep_id) else {
+                continue;
+            };
+            let keep = &mut self.mod_list[keep_idx];
+            if merge {
+                for extra in &extras {
+                    if keep.author.is_empty() {
+                        keep.author = extra.author.clone();
+                    }
+                    if keep.exclusive_group.is_empty() {
+                        keep.exclusive_group = extra.exclusive_group.clone();
+                    }
+                    if keep.container_override.is_empty() {
+                        keep.container_override = extra.container_override.clone();
+                    }
+                    if keep.source_path.is_empty() {
+                        keep.source_path = extra.source_path.clone();
+                    }
+                    for tag in &extra.tags {
+                        if !keep.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                            keep.tags.push(tag.clone());
+                        }
+                    }
+                    keep.locked |= extra.locked;
+                    if extra.installed_at != 0 && (keep.installed_at == 0 || extra.installed_at < keep.installed_at) {
+                        keep.installed_at = extra.installed_at;
+                    }
+                    keep.last_applied_at = keep.last_applied_at.max(extra.last_applied_at);
+                }
+            }
+            let keep = keep.clone();
+
+            for extra in &extras {
+                let same_contents = !extra.file_hash.is_empty() && extra.file_hash == keep.file_hash;
+                if same_contents && !extra.file.eq_ignore_ascii_case(&keep.file) {
+                    if let Err(e) = fs::remove_file(self.root.mods_dir.join(&extra.file)) {
+                        self.log_msg(format!("Failed to delete duplicate file {}: {}", extra.file, e));
+                    }
+                }
+            }
+            self.preserving_selection(|app| app.mod_list.retain(|m| !group[1..].contains(&m.id)));
+            removed += extras.len();
+
+            if enable_kept {
+                if let Err(e) = self.set_mod_state(keep_id, true, ChangeOrigin::Duplicates) {
+                    self.error_msg = Some(format!("Failed to re-apply {}: {:?}", keep.file, e));
+                }
+            }
+        }
+
+        self.update_mods_list(self.mod_list.clone());
+        self.commit_mod_states();
+        let action = if merge { "Merged" } else { "Removed" };
+        self.status_msg = format!("{} {} duplicate mod entries.", action, removed);
+        self.log_msg(self.status_msg.clone());
+    }
+
+    // .gpk files in mods_dir that aren't in the list yet, plus the files skipped by
+    // the exclusion rules with the reason. CookedPC is full of vanilla packages, so
+    // only files newer than the clean backup whose names aren't vanilla composite
+    // files are considered.
+    fn scan_unlisted_mod_files(&self) -> (Vec<PathBuf>, Vec<(PathBuf, String)>) {
+        let mut found = Vec::new();
+        let mut ignored = Vec::new();
+        let Ok(since) = fs::metadata(&self.root.backup_composite_mapper_path).and_then(|m| m.modified()) else {
+            return (found, ignored);
+        };
+        let Ok(dir) = fs::read_dir(&self.root.mods_dir) else {
+            return (found, ignored);
+        };
+
+        let known: HashSet<String> = self.mod_list.iter().map(|m| m.file.to_lowercase()).collect();
+        let vanilla: HashSet<String> = self
+            .backup_map
+            .values()
+            .map(|e| utils::strip_gpk_ext(&e.filename).to_lowercase())
+            .collect();
+
+        let candidates: Vec<PathBuf> = dir
+            .filter_map(|e| e.ok())
+            .filter(|e| e.metadata().and_then(|m| m.modified()).is_ok_and(|t| t > since))
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+                !known.contains(&name) && !vanilla.contains(utils::strip_gpk_ext(&name))
+            })
+            .collect();
+
+        // Vanilla packages by size, built only when there is something to compare
+        let mut vanilla_by_size: Option<HashMap<u64, Vec<PathBuf>>> = None;
+        for path in candidates {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if let Some(pattern) = self.excluded_by(&name) {
+                ignored.push((path, format!("matches {}", pattern)));
+                continue;
+            }
+            if !utils::is_gpk(&path) {
+                continue;
+            }
+
+            let by_size = vanilla_by_size.get_or_insert_with(|| self.vanilla_packages_by_size());
+            match Self::find_identical_file(&path, by_size) {
+                Some(original) => {
+                    let original = original.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    ignored.push((path, format!("copy of vanilla {}", original)));
+                }
+                None => found.push(path),
+            }
+        }
+        (found, ignored)
+    }
+
+    // The first exclusion pattern `name` matches
+    fn excluded_by(&self, name: &str) -> Option<&str> {
+        self.exclusion_patterns
+            .iter()
+            .map(|p| p.trim())
+            .find(|p| !p.is_empty() && utils::glob_match(p, name))
+    }
+
+    // The clean mapper's packages found in mods_dir, by file size
+    fn vanilla_packages_by_size(&self) -> HashMap<u64, Vec<PathBuf>> {
+        // Keyed case-insensitively, but looked up as the mapper spells them
+        let stems: HashMap<String, &str> = self
+            .backup_map
+            .values()
+            .map(|e| utils::strip_gpk_ext(&e.filename))
+            .map(|stem| (stem.to_lowercase(), stem))
+            .collect();
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for stem in stems.into_values() {
+            let vanilla_path = self.root.mods_dir.join(format!("{}.{}", stem, utils::GPK_EXTENSION));
+            if let Ok(meta) = fs::metadata(&vanilla_path) {
+                by_size.entry(meta.len()).or_default().push(vanilla_path);
+            }
+        }
+        by_size
+    }
+
+    // Only files of the same size are hashed
+    fn find_identical_file<'a>(path: &Path, by_size: &'a HashMap<u64, Vec<PathBuf>>) -> Option<&'a PathBuf> {
+        let same_size = by_size.get(&fs::metadata(path).ok()?.len())?;
+        let hash = utils::sha256_file(path).ok()?;
+        same_size
+            .iter()
+            .find(|other| utils::sha256_file(other).is_ok_and(|other| other == hash))
+    }
+
+    // A listed mod whose file is gone but whose hash matches an unlisted file was
+    // renamed on disk. The entry follows the new name and keeps its ID.
+    fn detect_renamed_mod_files(&mut self) {
+        let mods_dir = self.root.mods_dir.clone();
+        let orphaned: Vec<usize> = (0..self.mod_list.len())
+            .filter(|&i| {
+                let m = &self.mod_list[i];
+                !m.archived && !m.file_hash.is_empty() && !mods_dir.join(&m.file).exists()
+            })
+            .collect();
+        if orphaned.is_empty() {
+            return;
+        }
+
+        let mut renamed = 0;
+        let (unlisted, _) = self.scan_unlisted_mod_files();
+        for path in unlisted {
+            let Ok(hash) = utils::sha256_file(&path) else {
+                continue;
+            };
+            let Some(&idx) = orphaned
+                .iter()
+                .find(|&&i| self.mod_list[i].file_hash == hash && !mods_dir.join(&self.mod_list[i].file).exists())
+            else {
+                continue;
+            };
+            let new_name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            self.log_msg(format!("{} was renamed to {}", self.mod_list[idx].file, new_name));
+            self.mod_list[idx].file = new_name;
+            renamed += 1;
+        }
+        if renamed > 0 {
+            self.update_mods_list(self.mod_list.clone());
+        }
+    }
+
+    // Registers .gpk files copied into mods_dir by hand since the last session.
+    fn detect_new_mod_files(&mut self) {
+        let (new_files, ignored) = self.scan_unlisted_mod_files();
+        for (path, reason) in &ignored {
+            self.log_msg(format!("Ignored {}: {}", path.display(), reason));
+        }
+        if new_files.is_empty() {
+            return;
+        }
+
+        let mut added = 0;
+        for path in &new_files {
+            self.error_msg = None;
+            let picks_before = self.pending_raw_picks.len();
+            if self.install_mod(path, false, false) {
+                added += 1;
+            } else if self.pending_raw_picks.len() > picks_before {
+                self.error_msg = None;
+                self.log_msg(format!("{} matches too many game packages; waiting for its targets to be picked.", path.display()));
+            } else {
+                let reason = self.error_msg.take().unwrap_or_default();
+                self.log_msg(format!("Skipped new file {}: {}", path.display(), reason));
+            }
+        }
+        if added > 0 {
+            self.flush_mod_list();
+            self.save_app_config().ok();
+            let msg = format!("Detected {} new mod files. Added to list (disabled).", added);
+            self.log_msg(msg.clone());
+            self.warning_msg = msg;
+        }
+    }
+
+    // Installs in the given order, so when two of the files touch the same
+    // objects the later one wins and the earlier one is disabled. The mod list
+    // is written once at the end. Files named like a listed mod are compared in
+    // the background instead, files that would overwrite a different unlisted
+    // file wait for the user to choose, and raw GPKs matching too much of the
+    // mapper wait for their targets to be picked. A .tmod goes through both checks as the GPK it
+    // wraps.
+    fn install_mods(&mut self, paths: &[PathBuf]) -> InstallCounts {
+        let mut counts = InstallCounts::default();
+        for path in paths {
+            self.error_msg = None;
+            let picks_before = self.pending_raw_picks.len();
+            let mut tmod = None;
+            if utils::is_tmod(path) {
+                match UnpackedTmod::new(path) {
+                    Ok(unpacked) => tmod = Some(unpacked),
+                    Err(e) => {
+                        counts.failed += 1;
+                        self.log_msg(format!("Failed to install {}: failed to read it: {}", path.display(), e));
+                        continue;
+                    }
+                }
+            }
+            let installed_now = if self.start_duplicate_check(path, &mut tmod) || self.start_overwrite_check(path, &mut tmod) {
+                counts.checking += 1;
+                continue;
+            } else if self.queue_overwrite_choice(path, &mut tmod, None) {
+                counts.waiting += 1;
+                continue;
+            } else if let Some(tmod) = &tmod {
+                self.install_tmod(tmod, true)
+            } else {
+                self.install_mod(path, false, true)
+            };
+            if installed_now {
+                counts.installed += 1;
+                self.log_msg(format!("Installed {}", path.display()));
+            } else if self.pending_raw_picks.len() > picks_before {
+                counts.picking += 1;
+                self.error_msg = None;
+                self.log_msg(format!("{} matches too many game packages; waiting for its targets to be picked.", path.display()));
+            } else {
+                counts.failed += 1;
+                let reason = self.error_msg.take().unwrap_or_else(|| "unknown error".to_string());
+                self.log_msg(format!("Failed to install {}: {}", path.display(), reason));
+            }
+        }
+
+        if counts.installed > 0 {
+            self.flush_mod_list();
+            self.save_app_config().ok();
+        }
+        self.check_duplicate_mods();
+        self.status_msg = format!("Installed {}/{} mods.{}", counts.installed, paths.len(), counts.waiting_note());
+        if counts.failed > 0 {
+            self.status_msg.push_str(&format!(" {} failed.", counts.failed));
+            self.error_msg = Some(format!("{} mods failed to install. See the log for details.", counts.failed));
+        }
+        counts
+    }
+
+    // Installs the GPK wrapped in a .tmod as "<stem>.gpk", described by the
+    // .tmod's metadata
+    fn install_tmod(&mut self, tmod: &UnpackedTmod, enable: bool) -> bool {
+        let file_name = tmod.gpk.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        self.install_mod_as(InstallSource::new(&tmod.tmod, Some(tmod)), &file_name, false, enable, false)
+    }
+
+    // Packs an installed mod as a .tmod: its file in mods_dir followed by its
+    // name, author, description, version and object paths.
+    fn export_tmod(&self, index: usize, dest: &Path) -> Result<()> {
+        let m = &self.mod_list[index];
+        let gpk = fs::read(self.root.mods_dir.join(&m.file))?;
+        let meta = TmodMetadata {
+            name: m.mod_file.mod_name.clone(),
+            author: m.display_author().to_string(),
+            description: m.description.clone(),
+            version: m.version.clone(),
+            object_paths: m.mod_file.packages.iter().map(|p| p.object_path.clone()).collect(),
+        };
+        let mut out = Vec::new();
+        mod_model::write_tmod(&gpk, &meta, &mut out)?;
+        fs::write(dest, out)?;
+        Ok(())
+    }
+
+    fn export_tmod_dialog(&mut self, index: usize) {
+        let stem = utils::strip_gpk_ext(&self.mod_list[index].file).to_string();
+        let Some(dest) = rfd::FileDialog::new()
+            .add_filter("TMM package", &[utils::TMOD_EXTENSION])
+            .set_file_name(format!("{}.{}", stem, utils::TMOD_EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+        match self.export_tmod(index, &dest) {
+            Ok(()) => self.status_msg = format!("Exported {} to {}", self.mod_list[index].file, dest.display()),
+            Err(e) => self.error_msg = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    // Writes the active map (`backup` unset) or the clean backup as plaintext
+    // for reading. Only the chosen file is written: not the mapper, not the
+    // dirty flag, and nothing in CookedPC, where the client might pick it up.
+    fn export_mapper_text_dialog(&mut self, backup: bool) {
+        let which = if backup { "backup" } else { "active" };
+        let Some(dest) = rfd::FileDialog::new()
+            .add_filter("Text", &["txt"])
+            .set_file_name(format!("CompositePackageMapper_{}.txt", which))
+            .save_file()
+        else {
+            return;
+        };
+        let in_game_dir = dest
+            .parent()
+            .is_some_and(|dir| utils::same_file(dir, &self.root.cooked_pc_dir) || utils::same_file(dir, &self.root.mods_dir));
+        if in_game_dir {
+            self.error_msg = Some("Choose a folder outside CookedPC and the mods folder for the export.".to_string());
+            return;
+        }
+        let written = if backup {
+            self.backup_map.export_plaintext_to_file(&dest)
+        } else {
+            self.composite_map.export_plaintext_to_file(&dest)
+        };
+        match written {
+            Ok(bytes) => {
+                self.status_msg = format!("Exported the {} mapper ({}) to {}", which, utils::format_bytes(bytes as u64), dest.display());
+            }
+            Err(e) => self.error_msg = Some(format!("Export failed: {}", e)),
+        }
+    }
+
+    // Replaces the active mapper with a hand-edited plaintext dump. Nothing is
+    // written unless the whole text parses, and save() goes through a temporary
+    // file; if writing fails the map in memory is put back, so a failed import
+    // changes nothing. Returns the number of entries imported.
+    fn import_mapper_text(&mut self, source: &Path) -> Result<usize> {
+        let text = fs::read_to_string(source)?;
+        let map = composite_mapper::parse_plaintext_strict(&text)?;
+        if map.len() < self.min_backup_entries {
+            anyhow::bail!(
+                "it has only {} entries (expected at least {}, see Settings)",
+                map.len(),
+                self.min_backup_entries
+            );
+        }
+        let count = map.len();
+        let previous = std::mem::replace(&mut self.composite_map.composite_map, map);
+        self.composite_map.entries_changed();
+        let was_dirty = std::mem::replace(&mut self.composite_map.dirty, true);
+        // The whole map is replaced, so the old one is snapshotted whether or
+        // not Safe Apply is on
+        match self.save_mapper_safely() {
+            Ok(written) => {
+                self.composite_map.dirty = false;
+                self.composite_map.source_size = written;
+                self.mapper_too_small = false;
+                self.log_msg(format!("Imported {} mapper entries from {}.", count, source.display()));
+                Ok(count)
+            }
+            Err(e) => {
+                self.composite_map.composite_map = previous;
+                self.composite_map.entries_changed();
+                self.composite_map.dirty = was_dirty;
+                Err(e)
+            }
+        }
+    }
+
+    fn import_mapper_text_dialog(&mut self) {
+        let Some(source) = rfd::FileDialog::new().add_filter("Text", &["txt"]).pick_file() else {
+            return;
+        };
+        match self.import_mapper_text(&source) {
+            Ok(count) => {
+                self.status_msg = format!("Imported {} mapper entries. The next apply rebuilds the mapper from the backup.", count);
+            }
+            Err(e) => self.error_msg = Some(format!("Import failed, the mapper was not changed: {}", e)),
+        }
+    }
+
+    // Adds a community list of mapper fingerprints to the one in the data
+    // folder. It takes effect at once, for the next backup check.
+    fn import_fingerprints_dialog(&mut self) {
+        let Some(data_dir) = self.data_dir.clone() else {
+            self.error_msg = Some("TMM has no data folder to keep the fingerprints in.".to_string());
+            return;
+        };
+        let Some(source) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() else {
+            return;
+        };
+        let imported = fs::read_to_string(&source)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| FingerprintDb::import(&data_dir, &text));
+        match imported {
+            Ok(added) => {
+                self.fingerprints = FingerprintDb::load(Some(&data_dir));
+                let msg = format!(
+                    "Imported {} new mapper fingerprints from {}; {} are known.",
+                    added,
+                    source.display(),
+                    self.fingerprints.len()
+                );
+                self.log_msg(msg.clone());
+                self.status_msg = msg;
+            }
+            Err(e) => self.error_msg = Some(format!("Import failed: {:#}", e)),
+        }
+    }
+
+    // Installs every .gpk and .tmod under `folder`, in path order so that among
+    // conflicting files the last one wins, like a multi-file Add.
+    fn install_folder(&mut self, folder: &Path) {
+        let (paths, skipped) = self.scan_mod_folder(folder);
+        for (path, reason) in &skipped {
+            self.log_msg(format!("Skipped {}: {}", path.display(), reason));
+        }
+        let counts = if paths.is_empty() { InstallCounts::default() } else { self.install_mods(&paths) };
+        self.status_msg = format!(
+            "Add Folder: {} installed, {} skipped, {} failed.{}",
+            counts.installed,
+            skipped.len(),
+            counts.failed,
+            counts.waiting_note()
+        );
+        self.log_msg(self.status_msg.clone());
+    }
+
+    // .gpk files under `folder` and the ones passed over with the reason.
+    // Anything inside the game folder, reached directly or through a link, is
+    // left alone so files already in CookedPC aren't installed again, and so
+    // are copies of vanilla packages, as when scanning mods_dir.
+    fn scan_mod_folder(&self, folder: &Path) -> (Vec<PathBuf>, Vec<(PathBuf, String)>) {
+        let mut found = Vec::new();
+        let mut skipped = Vec::new();
+        let mut vanilla_by_size: Option<HashMap<u64, Vec<PathBuf>>> = None;
+        let game_dir = fs::canonicalize(&self.root_dir).ok();
+        let in_game_dir = |path: &Path| {
+            let real = fs::canonicalize(path).ok();
+            matches!((&real, &game_dir), (Some(real), Some(game)) if real.starts_with(game))
+        };
+
+        let mut visited = HashSet::new();
+        let mut pending = vec![folder.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            if in_game_dir(&dir) {
+                skipped.push((dir, "inside the game folder".to_string()));
+                continue;
+            }
+            // A link cycle would otherwise walk forever
+            if !visited.insert(fs::canonicalize(&dir).unwrap_or_else(|_| dir.clone())) {
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                skipped.push((dir, "can't be read".to_string()));
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_link = entry.file_type().is_ok_and(|t| t.is_symlink());
+                if is_link && in_game_dir(&path) {
+                    skipped.push((path, "links into the game folder".to_string()));
+                } else if path.is_dir() {
+                    pending.push(path);
+                } else if utils::is_installable(&path) {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    if let Some(pattern) = self.excluded_by(&name) {
+                        skipped.push((path, format!("matches {}", pattern)));
+                        continue;
+                    }
+                    let original = if utils::is_gpk(&path) {
+                        let by_size = vanilla_by_size.get_or_insert_with(|| self.vanilla_packages_by_size());
+                        Self::find_identical_file(&path, by_size)
+                    } else {
+                        None
+                    };
+                    match original {
+                        Some(original) => {
+                            let original = original.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                            skipped.push((path, format!("copy of vanilla {}", original)));
+                        }
+                        None => found.push(path),
+                    }
+                }
+            }
+        }
+        found.sort();
+        (found, skipped)
+    }
+
+    // Starts hashing `path` when a listed mod already uses its file name,
+    // taking an unpacked .tmod along. Returns false when the file can be
+    // installed right away.
+    fn start_duplicate_check(&mut self, source: &Path, tmod: &mut Option<UnpackedTmod>) -> bool {
+        let path = install_content(source, tmod.as_ref());
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            return false;
+        };
+        let Some(existing) = self.mod_list.iter().find(|m| m.file.eq_ignore_ascii_case(&name)) else {
+            return false;
+        };
+        let listed_path = self.root.mods_dir.join(&existing.file);
+        if utils::same_file(path, &listed_path) {
+            return false;
+        }
+        let mut paths = vec![path.to_path_buf()];
+        if existing.file_hash.is_empty() {
+            paths.push(listed_path);
+        }
+        let check = DuplicateCheck {
+            id: existing.id,
+            source: source.to_path_buf(),
+            tmod: tmod.take(),
+            known_hash: existing.file_hash.clone(),
+            job: HashJob::start(paths),
+        };
+        self.duplicate_checks.push(check);
+        self.log_msg(format!("{} is already listed; comparing contents.", name));
+        true
+    }
+
+    // The unlisted file in mods_dir that an added file would replace, if any
+    fn overwrite_target(&self, source: &Path, tmod: Option<&UnpackedTmod>) -> Option<PathBuf> {
+        let path = install_content(source, tmod);
+        if !utils::is_gpk(path) {
+            return None;
+        }
+        let target_path = self.root.mods_dir.join(path.file_name()?);
+        (target_path.exists() && !utils::same_file(path, &target_path)).then_some(target_path)
+    }
+
+    // Starts hashing an added file and the unlisted file of the same size it
+    // would replace, taking an unpacked .tmod along. Files of different sizes
+    // differ without being read. Returns whether a check was started.
+    fn start_overwrite_check(&mut self, source: &Path, tmod: &mut Option<UnpackedTmod>) -> bool {
+        let Some(target_path) = self.overwrite_target(source, tmod.as_ref()) else {
+            return false;
+        };
+        let path = install_content(source, tmod.as_ref()).to_path_buf();
+        let same_size = match (fs::metadata(&path), fs::metadata(&target_path)) {
+            (Ok(a), Ok(b)) => a.len() == b.len(),
+            _ => false,
+        };
+        if !same_size {
+            return false;
+        }
+        self.log_msg(format!("{} already exists in {}; comparing contents.", target_path.display(), self.root.mods_dir.display()));
+        let job = HashJob::start(vec![path, target_path]);
+        self.overwrite_checks.push(OverwriteCheck { source: source.to_path_buf(), tmod: tmod.take(), job });
+        true
+    }
+
+    // Queues an added file whose name is already taken in mods_dir by a
+    // different file that isn't in the mod list, taking an unpacked .tmod
+    // along. Returns whether it was queued.
+    fn queue_overwrite_choice(&mut self, source: &Path, tmod: &mut Option<UnpackedTmod>, hash: Option<String>) -> bool {
+        let Some(target_path) = self.overwrite_target(source, tmod.as_ref()) else {
+            return false;
+        };
+        let file = target_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let stem = utils::strip_gpk_ext(&file).to_string();
+        let game_file = self
+            .backup_map
+            .values()
+            .any(|e| utils::strip_gpk_ext(&e.filename).eq_ignore_ascii_case(&stem));
+        let renamed = self.free_mod_file_name(&file);
+        self.log_msg(format!("{} already exists in {} with different contents.", file, self.root.mods_dir.display()));
+        self.pending_overwrites.push(PendingOverwrite {
+            source: source.to_path_buf(),
+            tmod: tmod.take(),
+            hash,
+            file,
+            renamed,
+            game_file,
+        });
+        true
+    }
+
+    // Same contents means the file is already in mods_dir and only needs
+    // listing; anything else is left for the user to overwrite or rename.
+    fn poll_overwrite_checks(&mut self) {
+        let mut i = 0;
+        while i < self.overwrite_checks.len() {
+            let Some(hashes) = self.overwrite_checks[i].job.poll() else {
+                i += 1;
+                continue;
+            };
+            let mut check = self.overwrite_checks.remove(i);
+            match (&hashes[0], &hashes[1]) {
+                (Ok(new_hash), Ok(existing_hash)) if new_hash == existing_hash => {
+                    let source = InstallSource::new(&check.source, check.tmod.as_ref()).with_hash(Some(new_hash.clone()));
+                    let file_name = source.content().file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    self.error_msg = None;
+                    if self.install_mod_as(source, &file_name, true, true, true) {
+                        self.log_msg(format!("Installed {}; the same file was already in the mods folder.", check.source.display()));
+                    } else {
+                        let reason = self.error_msg.take().unwrap_or_else(|| "unknown error".to_string());
+                        self.log_msg(format!("Failed to install {}: {}", check.source.display(), reason));
+                        self.error_msg = Some(format!("Failed to install {}: {}", file_name, reason));
+                    }
+                }
+                (Ok(new_hash), Ok(_)) => {
+                    self.queue_overwrite_choice(&check.source, &mut check.tmod, Some(new_hash.clone()));
+                }
+                (Err(e), _) | (_, Err(e)) => {
+                    self.log_msg(format!("Failed to compare {}: {}", check.source.display(), e));
+                    self.error_msg = Some(format!("Failed to read {}", e));
+                }
+            }
+        }
+    }
+
+    // Records the hashes of mod files that had none. Duplicates by hash
+    // are looked for again once they are in.
+    fn poll_file_hashes(&mut self) {
+        let mut hashed = 0;
+        let mut i = 0;
+        while i < self.file_hash_jobs.len() {
+            let Some(hashes) = self.file_hash_jobs[i].1.poll() else {
+                i += 1;
+                continue;
+            };
+            let (ids, _) = self.file_hash_jobs.remove(i);
+            for (id, hash) in ids.into_iter().zip(hashes) {
+                match hash {
+                    Ok(hash) => {
+                        if let Some(m) = self.mod_list.iter_mut().find(|m| m.id == id) {
+                            m.file_hash = hash;
+                            hashed += 1;
+                        }
+                    }
+                    Err(e) => self.log_msg(format!("Failed to hash {}", e)),
+                }
+            }
+        }
+        if hashed > 0 {
+            self.update_mods_list(self.mod_list.clone());
+            self.check_duplicate_mods();
+        }
+    }
+
+    // First of "<stem>_2.gpk", "<stem>_3.gpk", ... that is neither on disk, listed
+    // nor the name of a game package. An underscore rather than " (2)" keeps
+    // the derived container a plain package name.
+    fn free_mod_file_name(&self, file: &str) -> String {
+        let stem = utils::strip_gpk_ext(file);
+        (2..)
+            .map(|n| format!("{}_{}", stem, n))
+            .find(|candidate| {
+                let name = format!("{}.{}", candidate, utils::GPK_EXTENSION);
+                !self.root.mods_dir.join(&name).exists()
+                    && !self.mod_list.iter().any(|m| m.file.eq_ignore_ascii_case(&name))
+                    && !self
+                        .backup_map
+                        .values()
+                        .any(|e| utils::strip_gpk_ext(&e.filename).eq_ignore_ascii_case(candidate))
+            })
+            .map(|stem| format!("{}.{}", stem, utils::GPK_EXTENSION))
+            .expect("some numbered name is free")
+    }
+
+    fn resolve_pending_overwrite(&mut self, index: usize, choice: OverwriteChoice) {
+        if index >= self.pending_overwrites.len() {
+            return;
+        }
+        let pending = self.pending_overwrites.remove(index);
+        let file_name = match choice {
+            OverwriteChoice::Cancel => {
+                self.log_msg(format!("Kept the existing {}; {} was not installed.", pending.file, pending.source.display()));
+                return;
+            }
+            // The dialog doesn't offer it; game packages are never replaced
+            OverwriteChoice::Overwrite if pending.game_file => return,
+            OverwriteChoice::Overwrite => pending.file.clone(),
+            // Taken since the dialog opened
+            OverwriteChoice::Rename if self.root.mods_dir.join(&pending.renamed).exists() => {
+                self.free_mod_file_name(&pending.file)
+            }
+            OverwriteChoice::Rename => pending.renamed.clone(),
+        };
+        let overwrite = choice == OverwriteChoice::Overwrite;
+        self.error_msg = None;
+        let source = InstallSource::new(&pending.source, pending.tmod.as_ref()).with_hash(pending.hash.clone());
+        if self.install_mod_as(source, &file_name, true, true, overwrite) {
+            self.log_msg(format!("Installed {} as {}", pending.source.display(), file_name));
+        } else {
+            let reason = self.error_msg.take().unwrap_or_else(|| "unknown error".to_string());
+            self.log_msg(format!("Failed to install {}: {}", pending.source.display(), reason));
+            self.error_msg = Some(format!("Failed to install {}: {}", file_name, reason));
+        }
+    }
+
+    // Searches the mapper again for a raw pick whose query was edited, once
+    // typing has paused. Ticks on paths still listed are kept.
+    fn requery_raw_picks(&mut self) {
+        for pick in &mut self.pending_raw_picks {
+            match pick.edited_at {
+                Some(edited) if edited.elapsed() >= RAW_PICK_QUERY_DEBOUNCE => {}
+                _ => continue,
+            }
+            pick.edited_at = None;
+            let picked: HashSet<String> = pick
+                .matches
+                .iter()
+                .zip(&pick.picked)
+                .filter(|(_, &p)| p)
+                .map(|((object_path, _), _)| object_path.clone())
+                .collect();
+            let found = raw_match::find(self.composite_map.values(), pick.query.trim());
+            pick.matches = found.entries.iter().map(|e| (e.object_path.clone(), e.filename.clone())).collect();
+            pick.picked = pick.matches.iter().map(|(object_path, _)| picked.contains(object_path)).collect();
+            pick.truncated = found.truncated;
+        }
+    }
+
+    // Installs a raw GPK with the targets ticked in the picker, or drops it
+    fn resolve_raw_pick(&mut self, index: usize, install: bool) {
+        if index >= self.pending_raw_picks.len() {
+            return;
+        }
+        let pick = self.pending_raw_picks.remove(index);
+        if !install {
+            self.log_msg(format!("{} was not installed; no targets were picked.", pick.source.display()));
+            return;
+        }
+        let targets: Vec<String> = pick
+            .matches
+            .into_iter()
+            .zip(pick.picked)
+            .filter(|(_, p)| *p)
+            .map(|((object_path, _), _)| object_path)
+            .collect();
+        let tmod = if utils::is_tmod(&pick.source) {
+            match UnpackedTmod::new(&pick.source) {
+                Ok(unpacked) => Some(unpacked),
+                Err(e) => {
+                    self.error_msg = Some(format!("Failed to read {}: {}", pick.source.display(), e));
+                    return;
+                }
+            }
+        } else {
+            None
+        };
+        self.error_msg = None;
+        let source = InstallSource { targets, ..InstallSource::new(&pick.source, tmod.as_ref()).with_hash(pick.hash) };
+        if self.install_mod_as(source, &pick.file_name, true, pick.enable, pick.overwrite) {
+            self.log_msg(format!("Installed {} with its picked targets", pick.source.display()));
+        } else {
+            let reason = self.error_msg.take().unwrap_or_else(|| "unknown error".to_string());
+            self.log_msg(format!("Failed to install {}: {}", pick.source.display(), reason));
+            self.error_msg = Some(format!("Failed to install {}: {}", pick.file_name, reason));
+        }
+    }
+
+    // Same contents means the file is already installed; anything else is
+    // offered as an update of the listed mod.
+    fn poll_duplicate_checks(&mut self) {
+        let mut i = 0;
+        while i < self.duplicate_checks.len() {
+            let Some(hashes) = self.duplicate_checks[i].job.poll() else {
+                i += 1;
+                continue;
+            };
+            let check = self.duplicate_checks.remove(i);
+            let Some(file) = self.mod_list.iter().find(|m| m.id == check.id).map(|m| m.file.clone()) else {
+                // Removed while hashing; nothing left to clash with
+                self.install_mods(&[check.source]);
+                continue;
+            };
+            let new_hash = match &hashes[0] {
+                Ok(hash) => hash.clone(),
+                Err(e) => {
+                    self.error_msg = Some(format!("Failed to read {}", e));
+                    continue;
+                }
+            };
+            let listed_hash = if check.known_hash.is_empty() {
+                hashes.get(1).and_then(|h| h.clone().ok()).unwrap_or_default()
+            } else {
+                check.known_hash
+            };
+
+            if new_hash == listed_hash {
+                let msg = format!("{} is already installed.", file);
+                self.log_msg(msg.clone());
+                self.status_msg = msg;
+            } else {
+                self.log_msg(format!("{} differs from the installed {}.", check.source.display(), file));
+                self.pending_updates.push(PendingUpdate {
+                    id: check.id,
+                    file,
+                    source: check.source,
+                    tmod: check.tmod,
+                    hash: new_hash,
+                });
+            }
+        }
+    }
+
+    // Replaces a listed mod's file with a new version. The entry keeps its
+    // place, ID, enabled state and the metadata set in TMM.
+    fn update_mod_in_place(&mut self, id: u64, source: InstallSource) -> Result<()> {
+        let added = source.path.to_path_buf();
+        let idx = self
+            .mod_list
+            .iter()
+            .position(|m| m.id == id)
+            .ok_or_else(|| anyhow::anyhow!("The mod is no longer listed"))?;
+        anyhow::ensure!(!self.mod_list[idx].locked, "{} is locked", self.mod_list[idx].file);
+
+        // Switched off under its own ID and on again once the new file is in,
+        // so the change goes through the conflict policy with the ID that stays
+        let was_enabled = self.mod_list[idx].enabled;
+        if was_enabled {
+            self.set_mod_state(id, false, ChangeOrigin::Update)?;
+        }
+        let old = self.mod_list.remove(idx);
+        self.selected_mods.clear();
+        self.error_msg = None;
+        // The old file is replaced under its listed name
+        if !self.install_mod_as(source.replacing(&old), &old.file, false, false, true) {
+            let reason = self.error_msg.take().unwrap_or_else(|| "unknown error".to_string());
+            self.mod_list.insert(idx, old.clone());
+            self.update_mods_list(self.mod_list.clone());
+            if was_enabled {
+                self.set_mod_state(id, true, ChangeOrigin::Update)?;
+                self.commit_mod_states();
+            }
+            anyhow::bail!(reason);
+        }
+
+        // Back at its place in the list
+        let new_idx = self.mod_list.iter().position(|m| m.id == id).expect("install_mod_as lists the entry");
+        let updated = self.mod_list.remove(new_idx);
+        self.mod_list.insert(idx, updated);
+        self.update_mods_list(self.mod_list.clone());
+        self.status_msg = format!("Updated {}.", old.file);
+        if was_enabled {
+            match self.set_mod_state(id, true, ChangeOrigin::Update) {
+                Ok(outcome) => {
+                    if outcome.awaiting_choice || outcome.kept_off_by.is_some() {
+                        self.status_msg.push_str(" It now conflicts with enabled mods and was left off.");
+                    }
+                    if !outcome.blocked_by.is_empty() {
+                        self.status_msg.push_str(" It now conflicts with locked mods and was left off.");
+                    }
+                    self.commit_mod_states();
+                }
+                Err(e) => self.error_msg = Some(format!("Failed to re-enable {}: {:?}", old.file, e)),
+            }
+        }
+        self.flush_mod_list();
+        self.save_app_config().ok();
+        self.log_msg(format!("Updated {} from {}", old.file, added.display()));
+        Ok(())
+    }
+
+    fn resolve_pending_update(&mut self, index: usize, update: bool) {
+        if index >= self.pending_updates.len() {
+            return;
+        }
+        let pending = self.pending_updates.remove(index);
+        if !update {
+            self.log_msg(format!("Kept the installed {}.", pending.file));
+            return;
+        }
+        let source = InstallSource::new(&pending.source, pending.tmod.as_ref()).with_hash(Some(pending.hash.clone()));
+        if let Err(e) = self.update_mod_in_place(pending.id, source) {
+            self.error_msg = Some(format!("Failed to update {}: {}", pending.file, e));
+        }
+    }
+
+    // Installs .gpk files dropped onto the window the same way as the Add
+    // button. Files that aren't readable packages are listed in error_msg.
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        if dropped.is_empty() {
+            return;
+        }
+        if !self.initialized {
+            self.error_msg = Some("Select the S1Game directory before installing mods.".to_string());
+            return;
+        }
+
+        let mut paths = Vec::new();
+        let mut rejected = Vec::new();
+        for file in dropped {
+            let Some(path) = file.path else {
+                rejected.push(format!("{}: no file path", file.name));
+                continue;
+            };
+            if !utils::is_installable(&path) {
+                rejected.push(format!(
+                    "{}: not a .{} or .{} file",
+                    path.display(),
+                    utils::GPK_EXTENSION,
+                    utils::TMOD_EXTENSION
+                ));
+            } else if let Err(e) = File::open(&path) {
+                rejected.push(format!("{}: {}", path.display(), e));
             } else {
-                let mut active_entry = CompositeEntry::default();
-                if self.composite_map.get_entry_by_incomplete_object_path(&pkg.object_path, &mut active_entry) {
-                    println!("[TMM] Removing new object entry: {}", pkg.object_path);
-                    self.composite_map.remove_entry(&active_entry);
-                    self.composite_map.dirty = true;
-                } else if !silent {
-                    // If we can't find it in the active map either, it's likely a data mismatch.
-                    eprintln!("[TMM] Warning: Object '{}' not found in active map or backup.", pkg.object_path);
+                paths.push(path);
+            }
+        }
+
+        let counts = if paths.is_empty() { InstallCounts::default() } else { self.install_mods(&paths) };
+        self.status_msg = format!(
+            "Installed {} mods, {} failed.{}",
+            counts.installed,
+            counts.failed + rejected.len(),
+            counts.waiting_note()
+        );
+        if !rejected.is_empty() {
+            for reason in &rejected {
+                self.log_msg(format!("Not installed: {}", reason));
+            }
+            let mut msg = format!("{} dropped files were not installed:\n{}", rejected.len(), rejected.join("\n"));
+            if let Some(install_error) = self.error_msg.take() {
+                msg = format!("{}\n{}", install_error, msg);
+            }
+            self.error_msg = Some(msg);
+        }
+    }
+
+    pub fn log_msg(&mut self, msg: String) {
+        println!("[TMM] {}", msg);
+        self.log.push(msg);
+    }
+
+    // Queues the event for the user's hook command, if one is set
+    fn emit_event(&mut self, event: HookEvent) {
+        let command = self.event_hook.trim();
+        if command.is_empty() {
+            self.event_hook_worker = None;
+            return;
+        }
+        let payload = HookPayload {
+            event,
+            timestamp: utils::unix_now(),
+            tmm_version: env!("CARGO_PKG_VERSION"),
+        };
+        self.event_hook_worker.get_or_insert_with(EventHook::start).send(command, payload);
+    }
+
+    fn mod_event(&self, index: usize, enabled: bool, origin: ChangeOrigin) -> HookEvent {
+        let m = &self.mod_list[index];
+        let (mod_name, file) = (m.mod_file.mod_name.clone(), m.file.clone());
+        if enabled {
+            HookEvent::ModEnabled { mod_name, file, origin }
+        } else {
+            HookEvent::ModDisabled { mod_name, file, origin }
+        }
+    }
+
+    // The one place a mod is turned on or off. Enabling a mod that conflicts
+    // with enabled mods (shared objects or the same exclusive group) asks the
+    // user which side wins, unless a remembered choice settles it; the change
+    // then waits in pending_conflicts. While changes wait for TERA the mapper
+    // is left alone, since the launch rebuilds it from the backup; otherwise
+    // the patches are applied to the in-memory map. The mod list is marked
+    // for saving and each change, conflicts included, is logged and sent to
+    // the event hook. Writing the mapper is left to the caller, once per
+    // batch, via commit_mod_states.
+    pub fn set_mod_state(&mut self, id: u64, enabled: bool, origin: ChangeOrigin) -> Result<ChangeOutcome> {
+        // Keep in the Conflicts window is already the user's pick of a winner
+        self.change_mod_state(id, enabled, origin, origin == ChangeOrigin::ConflictWindow)
+    }
+
+    fn change_mod_state(&mut self, id: u64, enabled: bool, origin: ChangeOrigin, override_conflicts: bool) -> Result<ChangeOutcome> {
+        let Some(index) = self.mod_list.iter().position(|m| m.id == id) else {
+            anyhow::bail!("No mod with ID {} is installed", id);
+        };
+        let target = &self.mod_list[index];
+        if target.enabled == enabled || (target.locked && origin.respects_lock()) {
+            return Ok(ChangeOutcome::default());
+        }
+        let deferred = self.defer_until_tera() && !origin.leaves_mapper();
+        let mut outcome = ChangeOutcome { changed: true, deferred, ..Default::default() };
+
+        let mut steps = Vec::new();
+        if enabled {
+            self.ensure_mapper_usable()?;
+            let conflicts = self.enabled_conflicts(index);
+            let locked: Vec<u64> = conflicts
+                .iter()
+                .filter(|&&i| self.mod_list[i].locked)
+                .map(|&i| self.mod_list[i].id)
+                .collect();
+            if !locked.is_empty() {
+                let names = self.mod_names(&locked).join(", ");
+                let name = self.mod_names(&[id]).concat();
+                self.log_msg(format!("Left {} off: it conflicts with locked {}.", name, names));
+                return Ok(ChangeOutcome { blocked_by: locked, ..Default::default() });
+            }
+            if !override_conflicts && !conflicts.is_empty() {
+                if let Some(&winner) = conflicts.iter().find(|&&i| self.mod_list[i].conflict_wins.contains(&id)) {
+                    let names = self.mod_names(&[id, self.mod_list[winner].id]);
+                    self.log_msg(format!("Left {} off: {} was chosen over it before.", names[0], names[1]));
+                    return Ok(ChangeOutcome { kept_off_by: Some(self.mod_list[winner].id), ..Default::default() });
+                }
+                let wins = &self.mod_list[index].conflict_wins;
+                if conflicts.iter().any(|&i| !wins.contains(&self.mod_list[i].id)) {
+                    self.queue_conflict_choice(index, &conflicts, origin);
+                    return Ok(ChangeOutcome { awaiting_choice: true, ..Default::default() });
+                }
+            }
+            for conflict_idx in conflicts {
+                steps.push((conflict_idx, false, ChangeOrigin::Conflict));
+                outcome.disabled_conflicts.push(self.mod_list[conflict_idx].id);
+            }
+        }
+        steps.push((index, enabled, origin));
+
+        // Either every switch happens or, after a failure, the ones already
+        // made are undone, so a mod never ends up on next to one it conflicts with
+        let result = self.switch_mods(&steps, deferred);
+        self.update_mods_list(self.mod_list.clone());
+        result.map(|_| outcome)
+    }
+
+    // Switches each mod in turn. When one fails the earlier ones are switched
+    // back in reverse order and the first error is returned.
+    fn switch_mods(&mut self, steps: &[(usize, bool, ChangeOrigin)], deferred: bool) -> Result<()> {
+        for (done, &(index, enabled, origin)) in steps.iter().enumerate() {
+            let Err(e) = self.switch_mod(index, enabled, origin, deferred) else {
+                continue;
+            };
+            for &(undo, was_enabled, undo_origin) in steps[..done].iter().rev() {
+                if let Err(undo_error) = self.switch_mod(undo, !was_enabled, undo_origin, deferred) {
+                    let name = self.mod_names(&[self.mod_list[undo].id]).concat();
+                    self.log_msg(format!("Could not switch {} back: {:?}", name, undo_error));
+                }
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Indices of the enabled mods that can't stay on alongside the given one
+    fn enabled_conflicts(&self, index: usize) -> Vec<usize> {
+        let target = &self.mod_list[index];
+        let mut conflicts = self.find_conflicting_indices(&target.mod_file.packages);
+        if !target.exclusive_group.is_empty() {
+            conflicts.extend((0..self.mod_list.len()).filter(|&i| {
+                self.mod_list[i].enabled && self.mod_list[i].exclusive_group == target.exclusive_group
+            }));
+        }
+        conflicts.sort_unstable();
+        conflicts.dedup();
+        conflicts.retain(|&i| i != index);
+        conflicts
+    }
+
+    fn queue_conflict_choice(&mut self, index: usize, conflicts: &[usize], origin: ChangeOrigin) {
+        let id = self.mod_list[index].id;
+        if self.pending_conflicts.iter().any(|p| p.id == id) {
+            return;
+        }
+        let conflicts = conflicts
+            .iter()
+            .map(|&i| (self.mod_list[i].id, self.shared_object_paths(index, i)))
+            .collect();
+        self.pending_conflicts.push(PendingConflict { id, origin, conflicts });
+    }
+
+    // With `remember` set the winner is recorded against each mod of the
+    // prompt, so enabling either side again doesn't ask.
+    fn resolve_pending_conflict(&mut self, index: usize, choice: ConflictChoice, remember: bool) {
+        if index >= self.pending_conflicts.len() {
+            return;
+        }
+        let pending = self.pending_conflicts.remove(index);
+        let name = self.mod_names(&[pending.id]).concat();
+        if remember && choice != ConflictChoice::Cancel {
+            let others: Vec<u64> = pending.conflicts.iter().map(|(other, _)| *other).collect();
+            for m in self.mod_list.iter_mut() {
+                if m.id == pending.id {
+                    m.conflict_wins.retain(|w| !others.contains(w));
+                    if choice == ConflictChoice::Enable {
+                        m.conflict_wins.extend(&others);
+                    }
+                } else if others.contains(&m.id) {
+                    m.conflict_wins.retain(|&w| w != pending.id);
+                    if choice == ConflictChoice::KeepEnabled {
+                        m.conflict_wins.push(pending.id);
+                    }
+                }
+            }
+            self.update_mods_list(self.mod_list.clone());
+            self.flush_mod_list();
+        }
+        match choice {
+            ConflictChoice::Enable => match self.change_mod_state(pending.id, true, pending.origin, true) {
+                Ok(outcome) if !outcome.blocked_by.is_empty() => {
+                    let locked = self.mod_names(&outcome.blocked_by).join(", ");
+                    self.status_msg = format!("Left {} off: unlock {} first.", name, locked);
+                }
+                Ok(outcome) => {
+                    let deferred = self.commit_mod_states();
+                    let turned_off = self.mod_names(&outcome.disabled_conflicts).join(", ");
+                    self.status_msg = format!("Enabled {}, turned off {}", name, turned_off);
+                    if deferred {
+                        self.status_msg.push_str(" (pending TERA launch)");
+                    }
                 }
+                Err(e) => self.error_msg = Some(format!("Turn on failed: {:?}", e)),
+            },
+            ConflictChoice::KeepEnabled => {
+                self.log_msg(format!("Left {} off in favour of the mods it conflicts with.", name));
+                self.status_msg = format!("Left {} off.", name);
+            }
+            ConflictChoice::Cancel => {
+                self.status_msg = format!("Cancelled enabling {}.", name);
+            }
+        }
+    }
+
+    // Undoes a remembered conflict choice; the pair is asked about again
+    pub fn forget_conflict_win(&mut self, index: usize, other: u64) {
+        self.mod_list[index].conflict_wins.retain(|&id| id != other);
+        self.update_mods_list(self.mod_list.clone());
+        self.flush_mod_list();
+    }
+
+    fn switch_mod(&mut self, index: usize, enabled: bool, origin: ChangeOrigin, deferred: bool) -> Result<()> {
+        let entry = self.mod_list[index].clone();
+        if !deferred && !origin.leaves_mapper() {
+            if enabled {
+                self.turn_on_mod(&entry)?;
+            } else if let Err(e) = self.turn_off_mod(&entry.mod_file, origin == ChangeOrigin::Conflict) {
+                // Some packages may already be reverted; the mod stays on, so
+                // point all of them at it again
+                self.turn_on_mod(&entry).ok();
+                return Err(e);
+            }
+            self.composite_map.dirty = true;
+        }
+        let m = &mut self.mod_list[index];
+        m.enabled = enabled;
+        if enabled {
+            m.last_applied_at = utils::unix_now();
+        }
+        let verb = if enabled { "Enabled" } else { "Disabled" };
+        let pending = if deferred { ", pending TERA launch" } else { "" };
+        self.log_msg(format!("{} {} ({}){}", verb, entry.mod_file.mod_name, origin.label(), pending));
+        self.emit_event(self.mod_event(index, enabled, origin));
+        Ok(())
+    }
+
+    // Writes the mapper after a batch of set_mod_state calls, unless the
+    // changes wait for TERA. Returns whether they wait.
+    fn commit_mod_states(&mut self) -> bool {
+        if self.defer_until_tera() {
+            return true;
+        }
+        self.commit_changes();
+        false
+    }
+
+    pub fn turn_on_mod(&mut self, mod_entry: &ModEntry) -> Result<()> {
+        patch::turn_on_mod(&mut self.composite_map, mod_entry)
+    }
+
+    pub fn turn_off_mod(&mut self, mod_file: &ModFile, silent: bool) -> Result<()> {
+        let removed = patch::turn_off_mod(&mut self.composite_map, &self.backup_map, mod_file, silent)?;
+        for entry in removed {
+            self.root.removal_audit.record(entry, &mod_file.mod_name);
+        }
+        Ok(())
+    }
+
+    // Container names longer than the client accepts are refused
+    fn check_container_len(&self, container: &str) -> Result<()> {
+        let len = container.chars().count();
+        if len > self.max_container_len {
+            anyhow::bail!(
+                "Container name is {} characters long; the limit is {} (see Settings)",
+                len,
+                self.max_container_len
+            );
+        }
+        Ok(())
+    }
+
+    // A name within max_container_len that no other mod, game package or file
+    // in mods_dir uses
+    fn shorten_container(&self, container: &str) -> String {
+        utils::shorten_container(container, self.max_container_len, |candidate| {
+            let file = format!("{}.{}", candidate, utils::GPK_EXTENSION);
+            self.root.mods_dir.join(&file).exists()
+                || self.mod_list.iter().any(|m| {
+                    m.file.eq_ignore_ascii_case(&file) || m.effective_container().eq_ignore_ascii_case(candidate)
+                })
+                || self
+                    .backup_map
+                    .values()
+                    .any(|e| utils::strip_gpk_ext(&e.filename).eq_ignore_ascii_case(candidate))
+        })
+    }
+
+    // Over-long container names already registered in the active mapper, for
+    // example by mods installed before the limit existed
+    fn flag_long_containers(&mut self) {
+        let mut long: Vec<String> = self
+            .composite_map
+            .values()
+            .map(|e| e.filename.clone())
+            .filter(|f| f.chars().count() > self.max_container_len)
+            .collect();
+        long.sort();
+        long.dedup();
+        for container in long {
+            self.log_msg(format!(
+                "Container {} is longer than {} characters; set a shorter container override for its mod.",
+                container, self.max_container_len
+            ));
+        }
+    }
+
+    // Puts an entry from the removal audit back into the mapper
+    pub fn reinsert_removed_entry(&mut self, index: usize) {
+        let Some(removed) = self.root.removal_audit.entries.get(index) else {
+            return;
+        };
+        if self.composite_map.composite_map.contains_key(&removed.entry.composite_name) {
+            self.error_msg = Some(format!(
+                "{} is already in the mapper; remove it first to re-insert the old entry.",
+                removed.entry.composite_name
+            ));
+            return;
+        }
+        let Some(removed) = self.root.removal_audit.take(index) else {
+            return;
+        };
+        let object_path = removed.entry.object_path.clone();
+        self.composite_map.insert_entry(removed.entry);
+        self.log_msg(format!("Re-inserted mapper entry {}.", object_path));
+        self.status_msg = format!("Re-inserted {}. Apply Now to save it.", object_path);
+    }
+
+    // Where the mod is applied among the enabled mods: (1-based position,
+    // number of enabled mods). None when it isn't enabled.
+    pub fn apply_position(&self, id: u64) -> Option<(usize, usize)> {
+        let index = self.mod_list.iter().position(|m| m.id == id)?;
+        let order = self.apply_order();
+        let position = order[index]?;
+        Some((position, order.iter().flatten().count()))
+    }
+
+    pub fn mod_index(&self, id: u64) -> Option<usize> {
+        self.mod_list.iter().position(|m| m.id == id)
+    }
+
+    // Display names of the listed mods with these IDs, in the given order.
+    // Unlisted IDs are flagged rather than dropped.
+    pub fn mod_names(&self, ids: &[u64]) -> Vec<String> {
+        ids.iter()
+            .map(|&id| match self.mod_list.iter().find(|m| m.id == id) {
+                Some(m) => m.mod_file.mod_name.clone(),
+                None => format!("mod #{} (not installed)", id),
+            })
+            .collect()
+    }
+
+    pub fn add_apply_after(&mut self, index: usize, other: u64) -> Result<()> {
+        let m = &self.mod_list[index];
+        if m.id == other || m.apply_after.contains(&other) {
+            return Ok(());
+        }
+        if apply_order::depends_on(&self.mod_list, other, m.id) {
+            anyhow::bail!(
+                "{} is already applied after {}; this would form a cycle",
+                self.mod_names(&[other])[0],
+                m.mod_file.mod_name
+            );
+        }
+        self.mod_list[index].apply_after.push(other);
+        self.apply_order_changed(index);
+        Ok(())
+    }
+
+    pub fn remove_apply_after(&mut self, index: usize, other: u64) {
+        let before = self.mod_list[index].apply_after.len();
+        self.mod_list[index].apply_after.retain(|&id| id != other);
+        if self.mod_list[index].apply_after.len() != before {
+            self.apply_order_changed(index);
+        }
+    }
+
+    fn apply_order_changed(&mut self, index: usize) {
+        self.update_mods_list(self.mod_list.clone());
+        self.flush_mod_list();
+        if self.mod_list[index].enabled && !self.defer_until_tera() {
+            if let Err(e) = self.apply_enabled_mods() {
+                self.error_msg = Some(format!("Apply failed: {:?}", e));
+            }
+            self.commit_changes();
+        }
+    }
+
+    pub fn set_manual_apply_only(&mut self, index: usize, manual_only: bool) {
+        let m = &mut self.mod_list[index];
+        if m.manual_apply_only == manual_only {
+            return;
+        }
+        m.manual_apply_only = manual_only;
+        let msg = if manual_only {
+            format!("{} is left out of the automatic apply when TERA starts.", m.mod_file.mod_name)
+        } else {
+            format!("{} is included in the automatic apply again.", m.mod_file.mod_name)
+        };
+        self.update_mods_list(self.mod_list.clone());
+        self.log_msg(msg.clone());
+        self.status_msg = msg;
+    }
+
+    // An empty category goes back to the inferred one
+    pub fn set_category_override(&mut self, index: usize, category: &str) {
+        let m = &mut self.mod_list[index];
+        if m.category_override == category {
+            return;
+        }
+        m.category_override = category.to_string();
+        self.update_mods_list(self.mod_list.clone());
+    }
+
+    // Changes the container a mod is registered under. For an enabled mod the old
+    // patches are reverted and re-applied with the new container; if that fails the
+    // previous override is put back.
+    pub fn set_container_override(&mut self, index: usize, container_override: &str) -> Result<()> {
+        let container_override = container_override.trim();
+        if composite_mapper::contains_mapper_delimiter(container_override) {
+            anyhow::bail!(
+                "Container name must not contain any of {:?}",
+                composite_mapper::MAPPER_DELIMITERS
+            );
+        }
+
+        self.check_container_len(container_override)?;
+
+        let old_entry = self.mod_list[index].clone();
+        if old_entry.container_override == container_override {
+            return Ok(());
+        }
+        self.mod_list[index].container_override = container_override.to_string();
+
+        if old_entry.enabled && !self.defer_until_tera() {
+            let new_entry = self.mod_list[index].clone();
+            let reapplied = self
+                .turn_off_mod(&old_entry.mod_file, true)
+                .and_then(|_| self.turn_on_mod(&new_entry));
+            if let Err(e) = reapplied {
+                self.mod_list[index].container_override = old_entry.container_override.clone();
+                self.turn_on_mod(&old_entry).ok();
+                return Err(e);
+            }
+            self.composite_map.dirty = true;
+            self.commit_changes();
+        }
+
+        self.update_mods_list(self.mod_list.clone());
+        Ok(())
+    }
+
+    // Re-reads the preview only when the selection or its file name changed
+    fn refresh_raw_preview(&mut self, index: usize) {
+        let m = &self.mod_list[index];
+        let key = (m.id, m.file.clone());
+        if self.root.raw_preview.as_ref().is_some_and(|(k, _)| *k == key) {
+            return;
+        }
+        let mut head = Vec::new();
+        let dump = File::open(self.root.mods_dir.join(&m.file))
+            .and_then(|f| f.take(RAW_PREVIEW_BYTES).read_to_end(&mut head))
+            .ok()
+            .map(|_| utils::hex_dump(&head));
+        self.root.raw_preview = Some((key, dump));
+    }
+
+    fn refresh_package_resolution(&mut self, index: usize) {
+        let m = &self.mod_list[index];
+        let mut paths = std::collections::hash_map::DefaultHasher::new();
+        for p in &m.mod_file.packages {
+            p.object_path.hash(&mut paths);
+        }
+        let key = (m.id, self.composite_map.generation, paths.finish());
+        if self.root.package_resolution.as_ref().is_some_and(|r| r.key == key) {
+            return;
+        }
+        let composite_names = m
+            .mod_file
+            .packages
+            .iter()
+            .map(|p| {
+                let mut entry = CompositeEntry::default();
+                self.composite_map
+                    .get_entry_by_incomplete_object_path(&p.object_path, &mut entry)
+                    .then_some(entry.composite_name)
+            })
+            .collect();
+        self.root.package_resolution = Some(PackageResolution { key, composite_names });
+    }
+
+    fn reset_package_offset_edits(&mut self, index: usize) {
+        let hex = self.show_hex_offsets;
+        self.edit_package_offsets = self.mod_list[index]
+            .mod_file
+            .packages
+            .iter()
+            .map(|p| (utils::format_offset(p.offset, hex), utils::format_offset(p.size, hex)))
+            .collect();
+    }
+
+    // Advanced mode: replaces the package offsets and sizes read from the .gpk.
+    // Packages aren't stored in ModList.mods, so this lasts until the next reload.
+    pub fn set_package_offsets(&mut self, index: usize, edits: &[(String, String)]) -> Result<()> {
+        let old_entry = self.mod_list[index].clone();
+        if edits.len() != old_entry.mod_file.packages.len() {
+            anyhow::bail!("Package list changed while editing");
+        }
+
+        let mut packages = old_entry.mod_file.packages.clone();
+        for (pkg, (offset, size)) in packages.iter_mut().zip(edits) {
+            pkg.offset = utils::parse_offset(offset)
+                .ok_or_else(|| anyhow::anyhow!("Invalid offset '{}' for {}", offset, pkg.object_path))?;
+            pkg.size = utils::parse_offset(size)
+                .ok_or_else(|| anyhow::anyhow!("Invalid size '{}' for {}", size, pkg.object_path))?;
+        }
+        let file_len = fs::metadata(self.root.mods_dir.join(&old_entry.file))?.len() as usize;
+        mod_model::check_package_ranges(&packages, file_len)?;
+        self.mod_list[index].mod_file.packages = packages;
+
+        if old_entry.enabled && !self.defer_until_tera() {
+            let new_entry = self.mod_list[index].clone();
+            let reapplied = self
+                .turn_off_mod(&old_entry.mod_file, true)
+                .and_then(|_| self.turn_on_mod(&new_entry));
+            if let Err(e) = reapplied {
+                self.mod_list[index] = old_entry.clone();
+                self.turn_on_mod(&old_entry).ok();
+                return Err(e);
+            }
+            self.composite_map.dirty = true;
+            self.commit_changes();
+        }
+        Ok(())
+    }
+
+    // Renames the mod's .gpk inside mods_dir. Without a container override the
+    // container follows the file name, so an enabled mod is re-applied under the
+    // new name; any failure puts the old file name and mapper entries back.
+    pub fn rename_mod_file(&mut self, index: usize, new_name: &str) -> Result<()> {
+        let mut new_name = new_name.trim().to_string();
+        if utils::strip_gpk_ext(&new_name).is_empty() {
+            anyhow::bail!("File name must not be empty");
+        }
+        if !utils::is_gpk(Path::new(&new_name)) {
+            new_name = format!("{}.{}", new_name, utils::GPK_EXTENSION);
+        }
+        if new_name.contains(['/', '\\']) {
+            anyhow::bail!("File name must not contain path separators");
+        }
+        if composite_mapper::contains_mapper_delimiter(&new_name) {
+            anyhow::bail!(
+                "File name must not contain any of {:?}",
+                composite_mapper::MAPPER_DELIMITERS
+            );
+        }
+
+        let old_entry = self.mod_list[index].clone();
+        if old_entry.file == new_name {
+            return Ok(());
+        }
+        let new_stem = utils::strip_gpk_ext(&new_name).to_string();
+        if old_entry.container_override.is_empty() {
+            self.check_container_len(&new_stem)?;
+        }
+        // A case-only rename refers to the same file on Windows
+        if !old_entry.file.eq_ignore_ascii_case(&new_name) {
+            if self.root.mods_dir.join(&new_name).exists() {
+                anyhow::bail!("A file named {} already exists in {}", new_name, self.root.mods_dir.display());
+            }
+            if self.mod_list.iter().any(|m| m.file.eq_ignore_ascii_case(&new_name)) {
+                anyhow::bail!("Another mod is already listed as {}", new_name);
+            }
+        }
+        if self
+            .backup_map
+            .values()
+            .any(|e| utils::strip_gpk_ext(&e.filename).eq_ignore_ascii_case(&new_stem))
+        {
+            anyhow::bail!("{} is the name of a game package", new_name);
+        }
+        if self.check_tera() {
+            anyhow::bail!("TERA is running and may have the file locked. Close the game before renaming.");
+        }
+
+        let old_path = self.root.mods_dir.join(&old_entry.file);
+        let new_path = self.root.mods_dir.join(&new_name);
+        if let Err(e) = fs::rename(&old_path, &new_path) {
+            if e.kind() == std::io::ErrorKind::PermissionDenied {
+                anyhow::bail!("{} is locked by another program: {}", old_entry.file, e);
+            }
+            anyhow::bail!("Failed to rename {}: {}", old_entry.file, e);
+        }
+
+        self.mod_list[index].file = new_name;
+        if old_entry.container_override.is_empty() {
+            self.mod_list[index].mod_file.container = new_stem;
+        }
+
+        if old_entry.enabled && !self.defer_until_tera() {
+            let new_entry = self.mod_list[index].clone();
+            let reapplied = self
+                .turn_off_mod(&old_entry.mod_file, true)
+                .and_then(|_| self.turn_on_mod(&new_entry));
+            if let Err(e) = reapplied {
+                fs::rename(&new_path, &old_path).ok();
+                self.mod_list[index] = old_entry.clone();
+                self.turn_on_mod(&old_entry).ok();
+                return Err(e);
             }
+            self.composite_map.dirty = true;
         }
 
+        self.update_mods_list(self.mod_list.clone());
+        self.commit_changes();
         Ok(())
     }
 
+    // Copies the active mapper into the snapshot folder, keeping the newest
+    // MAX_VERSIONED_BACKUPS copies. Returns the path of the new snapshot.
+    fn create_versioned_backup(&self) -> Result<PathBuf> {
+        let snapshot_dir = self.root.mods_dir.join(SNAPSHOT_DIR);
+        fs::create_dir_all(&snapshot_dir)?;
+
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_millis();
+        let snapshot_path = snapshot_dir.join(format!("{}.{}", COMPOSITE_MAPPER_FILE, stamp));
+        fs::copy(&self.root.composite_mapper_path, &snapshot_path)?;
+
+        // Timestamps sort lexically, oldest first
+        let mut snapshots: Vec<PathBuf> = fs::read_dir(&snapshot_dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .collect();
+        snapshots.sort();
+        let excess = snapshots.len().saturating_sub(MAX_VERSIONED_BACKUPS);
+        for old in &snapshots[..excess] {
+            fs::remove_file(old).ok();
+        }
+
+        Ok(snapshot_path)
+    }
+
+    // Safe apply: snapshot the mapper on disk before writing the map in memory
+    // over it. A failed write puts the snapshot back; the map in memory is
+    // kept as it is, so a retry writes the same edits.
+    fn save_mapper_safely(&mut self) -> Result<usize> {
+        let snapshot_path = self.create_versioned_backup()?;
+        match self.save_mapper() {
+            Ok(written) => Ok(written),
+            Err(e) => {
+                if let Err(restore) = fs::copy(&snapshot_path, &self.root.composite_mapper_path) {
+                    self.log_msg(format!("Failed to put back snapshot {}: {}", snapshot_path.display(), restore));
+                }
+                Err(e.into())
+            }
+        }
+    }
 
     fn commit_changes(&mut self) {
+        let started = self.perf.start();
+        self.flush_mod_list();
+
         if self.composite_map.dirty {
-            if let Err(e) = self
-                .composite_map
-                .save(&self.composite_mapper_path)
-            {
-                self.error_msg = Some(format!("Failed to save: {}", e));
+            let saved = if self.safe_apply {
+                self.save_mapper_safely()
             } else {
-                self.composite_map.dirty = false;
+                self.save_mapper().map_err(anyhow::Error::from)
+            };
+            match saved {
+                Ok(written) => {
+                    debug_assert_eq!(written, self.composite_map.expected_save_size());
+                    self.composite_map.dirty = false;
+                    self.composite_map.source_size = written;
+                    self.log_msg(format!("Saved {} bytes to mapper.", written));
+                }
+                Err(e) => {
+                    let action = if self.safe_apply { "Safe apply failed" } else { "Failed to save" };
+                    self.error_msg = Some(format!("{}: {}", action, e));
+                    self.save_retry.failed(SaveTarget::Mapper, e.to_string(), std::time::Instant::now());
+                }
             }
         }
+        if !self.composite_map.dirty {
+            self.save_retry.succeeded(SaveTarget::Mapper);
+        }
+        self.perf.record(Timing::Commit, started);
+    }
+
+    fn launch_game(&self) -> Result<()> {
+        let exe = TERA_EXE_PATH.iter().fold(self.root.client_dir.clone(), |path, part| path.join(part));
+        if !exe.is_file() {
+            anyhow::bail!("{} not found", exe.display());
+        }
+        std::process::Command::new(&exe)
+            .current_dir(exe.parent().unwrap_or(&self.root.client_dir))
+            .spawn()?;
+        Ok(())
+    }
+
+    pub fn apply_and_launch(&mut self) {
+        self.error_msg = None;
+        if let Err(e) = self.apply_enabled_mods() {
+            self.error_msg = Some(format!("Apply failed: {:?}", e));
+            return;
+        }
+        self.commit_changes();
+        if self.error_msg.is_some() {
+            return;
+        }
+        match self.launch_game() {
+            Ok(()) => self.status_msg = "Mods applied. Starting TERA...".to_string(),
+            Err(e) => self.error_msg = Some(format!("Failed to start TERA: {}", e)),
+        }
+    }
+
+    fn restore_button(&mut self) {
+        self.restore_composite_mapper();
+        self.disable_all_mods();
+    }
+
+    // One-line summary of where the mapper stands, for the quick panel
+    fn apply_status(&self) -> &'static str {
+        if self.mapper_too_small {
+            "Mapper unusable"
+        } else if self.save_retry.is_pending() {
+            "Save pending"
+        } else if self.composite_map.dirty {
+            "Changes not applied"
+        } else if self.defer_until_tera() && !self.tera_running {
+            "Waiting for TERA"
+        } else {
+            "Applied"
+        }
     }
 
     fn save_button(&mut self){
-        if let Err(e) = self.composite_map.save(&self.composite_mapper_path) {
+        if let Err(e) = self.ensure_mapper_usable() {
+            self.error_msg = Some(format!("Save Failed {:?}", e));
+            return;
+        }
+        if let Err(e) = self.save_mapper() {
                     self.error_msg = Some(format!("Save Failed {:?}", e));
                 } else {
                     self.status_msg = "Manual Save Successful".to_string();
@@ -548,132 +4096,791 @@ impl TmmApp {
     }
 
     fn load_game_config(&mut self) -> Result<()> {
-        if self.game_config_path.exists() {
-            let mut file = File::open(&self.game_config_path)?;
+        if self.root.game_config_path.exists() {
+            let mut file = File::open(&self.root.game_config_path)?;
             self.game_config = mod_model::read_game_config(&mut file)?;
+            self.mod_list_written_by = WrittenBy::check(self.game_config.written_by.as_deref());
+            if let Some(warning) = self.mod_list_written_by.warning(GAME_CONFIG_FILE) {
+                self.log_msg(warning.clone());
+                self.warning_msg = warning;
+            }
         } else {
+            self.mod_list_written_by = WrittenBy::Compatible;
             self.save_game_config()?;
         }
         Ok(())
     }
 
     fn save_game_config(&self) -> Result<()> {
-        let mut file = File::create(&self.game_config_path)?;
+        if let Some(warning) = self.mod_list_written_by.warning(GAME_CONFIG_FILE) {
+            anyhow::bail!(warning);
+        }
+        let mut file = File::create(&self.root.game_config_path)?;
         mod_model::write_game_config(&self.game_config, &mut file)?;
         Ok(())
     }
 
     fn check_tera(&mut self) -> bool {
+        let started = self.perf.start();
         self.sys.refresh_processes(ProcessesToUpdate::All);
 
-        self.sys.processes().values().any(|p| {
+        let running = self.sys.processes().values().any(|p| {
             p.name().eq_ignore_ascii_case("tera.exe")
-        })
+        });
+        self.perf.record(Timing::TeraCheck, started);
+        running
+    }
+
+    // 1-based position of each mod in the apply sequence, None when disabled.
+    // Mods are applied in list order adjusted by their apply-after
+    // constraints, so later mods win shared objects.
+    fn apply_order(&self) -> Vec<Option<usize>> {
+        let enabled: Vec<usize> = (0..self.mod_list.len()).filter(|&i| self.mod_list[i].enabled).collect();
+        let entries: Vec<&ModEntry> = enabled.iter().map(|&i| &self.mod_list[i]).collect();
+        let mut order = vec![None; self.mod_list.len()];
+        for (position, &k) in apply_order::sort(&entries).order.iter().enumerate() {
+            order[enabled[k]] = Some(position + 1);
+        }
+        order
+    }
+
+    // A mapper with (almost) no entries isn't something mods can be applied to;
+    // writing it back would only make things worse
+    fn ensure_mapper_usable(&self) -> Result<()> {
+        if self.mapper_too_small {
+            anyhow::bail!(
+                "the mapper has only {} entries (expected at least {})",
+                self.composite_map.len(),
+                self.min_backup_entries
+            );
+        }
+        Ok(())
     }
 
     pub fn apply_enabled_mods(&mut self) -> Result<()> {
-        // 1. Reset the composite map to the clean backup state
-        self.composite_map.composite_map = self.backup_map.composite_map.clone();
+        self.apply_mods(false).map(|_| ())
+    }
+
+    // The apply started by detecting TERA's process, which leaves out mods
+    // marked manual apply only. Returns how many were left out.
+    fn auto_apply_enabled_mods(&mut self) -> Result<usize> {
+        let skipped = self.apply_mods(true)?;
+        if skipped > 0 {
+            self.log_msg(format!("Automatic apply skipped {} mods marked manual apply only.", skipped));
+        }
+        Ok(skipped)
+    }
+
+    // Rebuilds the mapper from the clean backup and the enabled mods (or the
+    // solo mod). Returns how many enabled mods were left out as manual apply
+    // only, which only happens with `skip_manual_only`.
+    fn apply_mods(&mut self, skip_manual_only: bool) -> Result<usize> {
+        self.ensure_mapper_usable()?;
+        let started = self.perf.start();
+        self.flush_mod_list();
+
+        // 1. Collect enabled mods into a new Vector that owns the data (cloning).
+        // This breaks the link to 'self', allowing us to call mutable methods on 'self' afterwards.
+        let now = utils::unix_now();
+        let solo = self.solo_mod;
+        let mut skipped = 0;
+        let mods_to_apply: Vec<ModEntry> = self
+            .mod_list
+            .iter_mut()
+            .filter(|entry| match solo {
+                Some(id) => entry.id == id,
+                None if skip_manual_only && entry.enabled && entry.manual_apply_only => {
+                    skipped += 1;
+                    false
+                }
+                None => entry.enabled,
+            })
+            .map(|entry| {
+                entry.last_applied_at = now;
+                entry.clone()
+            })
+            .collect();
+        self.perf.count_clones(mods_to_apply.len());
+
+        // 2. Reset the composite map to the clean backup state and apply the
+        // mods in list order adjusted by the "apply after" constraints
+        let rebuild = patch::rebuild(&mut self.composite_map, &self.backup_map, &mods_to_apply);
+        for cycle in &rebuild.cycles {
+            let names = self.mod_names(cycle).join(", ");
+            self.log_msg(format!("Apply-after constraints form a cycle between {}; the first listed is applied first.", names));
+            self.warning_msg = format!("Apply-after cycle between {}. See the log.", names);
+        }
+        let mut results = Vec::new();
+        for (i, result) in rebuild.results {
+            let mod_entry = &mods_to_apply[i];
+            if let Err(e) = &result {
+                eprintln!("Failed to apply mod {}: {:?}", mod_entry.file, e);
+                self.error_msg = Some(format!("Failed to apply mod {}: {:?}", mod_entry.file, e));
+            }
+            results.push(ModApplyResult {
+                mod_name: mod_entry.mod_file.mod_name.clone(),
+                file: mod_entry.file.clone(),
+                success: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        self.emit_event(HookEvent::ApplyCompleted { results });
+
+        self.install_stats.total_applies += 1;
+        self.perf.record(Timing::Apply, started);
+        Ok(skipped)
+    }
+
+    // Solo mode rebuilds the mapper from the backup with just one mod, to find
+    // which mod crashes the client without touching anyone's enabled flags. In
+    // "Wait for TERA" mode the solo set is what gets applied on launch.
+    fn enter_solo(&mut self, index: usize) {
+        let Some(m) = self.mod_list.get(index) else {
+            return;
+        };
+        self.solo_mod = Some(m.id);
+        let name = m.mod_file.mod_name.clone();
+        if !self.defer_until_tera() {
+            if let Err(e) = self.apply_enabled_mods() {
+                self.error_msg = Some(format!("Solo apply failed: {:?}", e));
+            }
+            self.commit_changes();
+        }
+        self.log_msg(format!("Solo mode: only {} is applied.", name));
+    }
+
+    fn exit_solo(&mut self) {
+        if self.solo_mod.take().is_none() {
+            return;
+        }
+        if !self.defer_until_tera() {
+            if let Err(e) = self.apply_enabled_mods() {
+                self.error_msg = Some(format!("Apply failed: {:?}", e));
+            }
+            self.commit_changes();
+        }
+        self.status_msg = "Left solo mode. Enabled mods re-applied.".to_string();
+    }
+
+    pub fn solo_mod_name(&self) -> Option<&str> {
+        let id = self.solo_mod?;
+        self.mod_list.iter().find(|m| m.id == id).map(|m| m.mod_file.mod_name.as_str())
+    }
+
+    fn disable_all_mods(&mut self) {
+        let enabled: Vec<u64> = self.mod_list.iter().filter(|m| m.enabled).map(|m| m.id).collect();
+
+        // Nothing to do
+        if enabled.is_empty() {
+            self.status_msg = "No mods were enabled.".to_string();
+            return;
+        }
+
+        for id in enabled {
+            if let Err(e) = self.set_mod_state(id, false, ChangeOrigin::DisableAll) {
+                self.error_msg = Some(format!("Failed to disable {}: {:?}", self.mod_names(&[id]).concat(), e));
+                return;
+            }
+        }
+
+        // The restore below puts back the clean mapper whether or not the
+        // disables were held for TERA
+        self.composite_map.dirty = true;
+        self.commit_changes();
+        self.restore_composite_mapper();
+        // UI feedback
+        self.selected_mods.clear();
+        self.status_msg = "Backup Restored. All mods have been disabled.".to_string();
+    }
+
+    // Emergency variant of disable_all_mods for when the backup file is gone or
+    // corrupt. The active mapper is rebuilt from the backup loaded in memory and
+    // written directly, skipping restore_composite_mapper and safe-apply
+    // snapshots so no other file is touched.
+    pub fn force_disable_all_without_restore(&mut self) -> Result<()> {
+        if self.backup_map.is_empty() {
+            anyhow::bail!("No clean mapper is loaded; force disabling would leave the mapper empty");
+        }
+        let enabled: Vec<u64> = self.mod_list.iter().filter(|m| m.enabled).map(|m| m.id).collect();
+        for id in enabled {
+            self.set_mod_state(id, false, ChangeOrigin::ForceDisable)?;
+        }
+        self.composite_map.composite_map = self.backup_map.entries().clone();
+        self.composite_map.entries_changed();
+        self.composite_map.dirty = true;
+
+        let written = self.save_mapper()?;
+        self.composite_map.dirty = false;
+        self.composite_map.source_size = written;
+
+        self.game_config.mods = self.mod_list.clone();
+        self.save_game_config()?;
+        self.mod_list_dirty = false;
+        self.selected_mods.clear();
+        self.log_msg("Force disabled all mods without restoring the backup file.".to_string());
+        Ok(())
+    }
+
+    // Persistent needs the risk warning accepted once; leaving it offers a check
+    // of the mapper it left behind.
+    fn set_apply_mode(&mut self, mode: ApplyMode) {
+        if mode == self.apply_mode {
+            return;
+        }
+        if mode == ApplyMode::Persistent && !self.persistent_risk_accepted {
+            self.confirm_persistent_mode = true;
+            return;
+        }
+        let was_persistent = self.apply_mode == ApplyMode::Persistent;
+        self.apply_mode = mode;
+        if let Err(e) = self.save_app_config() {
+            self.error_msg = Some(format!("Failed to save settings: {}", e));
+        }
+        self.status_msg = format!("Apply mode: {}.", mode.label());
+        self.log_msg(format!("Apply mode set to {}.", mode.label()));
+
+        if mode == ApplyMode::Persistent && !self.tera_running && !self.mapper_too_small {
+            if let Err(e) = self.apply_enabled_mods() {
+                self.error_msg = Some(format!("Apply failed: {:?}", e));
+            }
+            self.commit_changes();
+        }
+        if was_persistent {
+            self.offer_consistency_check = true;
+        }
+    }
+
+    fn accept_persistent_mode(&mut self) {
+        self.confirm_persistent_mode = false;
+        self.persistent_risk_accepted = true;
+        self.log_msg("Persistent mode risk acknowledged.".to_string());
+        self.set_apply_mode(ApplyMode::Persistent);
+    }
+
+    // Verifies the clean backup and lists enabled mod objects missing from the
+    // mapper, the same checks Preview Apply and startup run
+    fn run_consistency_check(&mut self) {
+        self.offer_consistency_check = false;
+        match self.validate_backup() {
+            Ok(()) => self.status_msg = "Consistency check: backup OK.".to_string(),
+            Err(e) => {
+                self.error_msg = Some(format!("Consistency check: backup problem: {}", e));
+                self.log_msg(format!("Consistency check: backup problem: {}", e));
+            }
+        }
+        self.preview_apply = Some(self.check_all_object_paths_resolvable());
+    }
+
+    fn on_tera_closed(&mut self) {
+        match self.on_tera_close {
+            OnTeraClose::RestoreVanilla => self.restore_vanilla_on_close(),
+            OnTeraClose::KeepMods => {
+                self.status_msg = "TERA closed. Mods kept applied.".to_string();
+            }
+            OnTeraClose::ApplyChanges => {
+                self.status_msg = "TERA closed. Applying current mod selection.".to_string();
+                match self.auto_apply_enabled_mods() {
+                    Ok(skipped) if skipped > 0 => {
+                        self.status_msg.push_str(&format!(" Skipped {} marked manual apply only.", skipped));
+                    }
+                    Ok(_) => {}
+                    Err(e) => self.error_msg = Some(format!("Apply failed: {:?}", e)),
+                }
+            }
+        }
+    }
+
+    fn restore_vanilla_on_close(&mut self) {
+        self.status_msg = "TERA closed. Restoring original files.".to_string();
+        let mut success = false;
+        if self.root.backup_composite_mapper_path.exists() {
+            match CompositeMapperFile::new(self.root.backup_composite_mapper_path.clone()) {
+                Ok(backup) => {
+                    self.composite_map = backup;
+                    if let Err(e) = self.composite_map.save(&self.root.composite_mapper_path) {
+                        self.error_msg = Some(format!(
+                            "Failed to restore CompositePackageMapper.dat: {:?}",
+                            e
+                        ));
+                        self.status_msg = "Failed to restore mapper!".to_string();
+                    } else {
+                        self.remove_checksum_sidecar();
+                        success = true;
+                        println!(
+                            "Restored from {}",
+                            self.root.backup_composite_mapper_path.display()
+                        );
+                    }
+                }
+                Err(e) => {
+                    self.error_msg = Some(format!("Failed to load backup: {:?}", e));
+                    self.status_msg = "Failed to load backup!".to_string();
+                },
+            }
+        } else {
+            self.error_msg = Some(format!(
+                "Backup not found at {}",
+                self.root.backup_composite_mapper_path.display()
+            ));
+            self.status_msg = "Backup missing!".to_string();
+        }
+        self.emit_event(HookEvent::RestoreCompleted { success });
+    }
+
+    // Enabled mods are turned off first so the mapper doesn't keep pointing at
+    // a container that may be deleted. With delete_files_on_remove the .gpk
+    // goes too, unless another entry still lists it.
+    fn remove_selected_mods(&mut self) {
+        self.confirm_remove = false;
+        if self.selected_mods.is_empty() {
+            return;
+        }
+        let mut selected = self.selected_mods.clone();
+        selected.sort_unstable_by(|a, b| b.cmp(a));
+        selected.dedup();
+        // Locked mods stay in the list and stay selected
+        selected.retain(|&idx| idx < self.mod_list.len() && !self.mod_list[idx].locked);
+        // Enabled mods are turned off the usual way first; one that can't be
+        // reverted stays in the list so its patches aren't orphaned
+        let mut kept = 0;
+        for idx in selected.clone() {
+            let id = self.mod_list[idx].id;
+            if let Err(e) = self.set_mod_state(id, false, ChangeOrigin::Remove) {
+                self.error_msg = Some(format!("Turn off failed: {:?}", e));
+                selected.retain(|&i| i != idx);
+                kept += 1;
+            }
+        }
+        let removed_entries = self.preserving_selection(|app| {
+            selected.into_iter().map(|idx| app.mod_list.remove(idx)).collect::<Vec<_>>()
+        });
+        let removed = removed_entries.len();
+        self.install_stats.total_mods_removed += removed as u64;
+        self.save_app_config().ok();
+        self.update_mods_list(self.mod_list.clone());
+        if self.composite_map.dirty {
+            self.commit_changes();
+        }
+
+        let mut undeleted = Vec::new();
+        if self.delete_files_on_remove {
+            for entry in &removed_entries {
+                if entry.archived || self.mod_list.iter().any(|m| m.file.eq_ignore_ascii_case(&entry.file)) {
+                    continue;
+                }
+                match fs::remove_file(self.root.mods_dir.join(&entry.file)) {
+                    Ok(()) => self.log_msg(format!("Deleted {}", entry.file)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        self.log_msg(format!("{} was already gone from disk.", entry.file));
+                    }
+                    Err(e) => {
+                        self.log_msg(format!("Failed to delete {}: {}", entry.file, e));
+                        undeleted.push(entry.file.clone());
+                    }
+                }
+            }
+        }
+
+        let locked = self.selected_mods.len() - kept;
+        self.status_msg = if locked > 0 || kept > 0 {
+            format!("Removed {} mods; kept {} locked and {} that couldn't be turned off.", removed, locked, kept)
+        } else {
+            "Removed selected mods.".to_string()
+        };
+        if !undeleted.is_empty() {
+            self.error_msg = Some(format!(
+                "Removed from the list, but these files couldn't be deleted (TERA may still have them open): {}",
+                undeleted.join(", ")
+            ));
+        }
+    }
+
+    fn enable_selected_mods(&mut self) {
+        self.set_selected_mods_state(true);
+    }
+
+    fn disable_selected_mods(&mut self) {
+        self.set_selected_mods_state(false);
+    }
+
+    fn set_selected_mods_state(&mut self, enabled: bool) {
+        if self.solo_mod.is_some() {
+            self.status_msg = "Exit solo mode before changing mods.".to_string();
+            return;
+        }
+        let ids: Vec<u64> = self.selected_mods.iter().filter_map(|&i| self.mod_list.get(i)).map(|m| m.id).collect();
+        if ids.is_empty() {
+            self.status_msg = "No mods selected.".to_string();
+            return;
+        }
+        let (verb, action) = if enabled { ("enabled", "Turn on") } else { ("disabled", "Turn off") };
+        let mut changed = 0;
+        let mut blocked = 0;
+        for id in ids {
+            match self.set_mod_state(id, enabled, ChangeOrigin::Selection) {
+                Ok(outcome) if outcome.changed => changed += 1,
+                Ok(outcome) if !outcome.blocked_by.is_empty() => blocked += 1,
+                Ok(_) => {}
+                Err(e) => self.error_msg = Some(format!("{} failed: {:?}", action, e)),
+            }
+        }
+        if self.commit_mod_states() {
+            self.status_msg = format!("{} mods {} (pending TERA launch).", changed, verb);
+        } else {
+            self.status_msg = format!("{} mods {}.", changed, verb);
+        }
+        if blocked > 0 {
+            self.status_msg.push_str(&format!(" {} left off: they conflict with locked mods.", blocked));
+        }
+    }
+
+    // Moves the selected mods one row up or down. List order is apply order,
+    // so of two mods changing the same objects the later one wins. Mods
+    // already packed against the edge stay put. The new order is saved, and
+    // re-applied when it changes the order of enabled mods.
+    fn move_selected_mods(&mut self, up: bool) {
+        let mut selected: Vec<usize> = self.selected_mods.iter().copied().filter(|&i| i < self.mod_list.len()).collect();
+        selected.sort_unstable();
+        selected.dedup();
+        if selected.is_empty() {
+            self.status_msg = "No mods selected.".to_string();
+            return;
+        }
+        let enabled_order = |mods: &[ModEntry]| -> Vec<u64> { mods.iter().filter(|m| m.enabled).map(|m| m.id).collect() };
+        let before = enabled_order(&self.mod_list);
+
+        let moved = self.preserving_selection(|app| {
+            let mut moved = false;
+            if up {
+                // First row a selected mod can still move into
+                let mut free = 0;
+                for &i in &selected {
+                    let to = if i > free { i - 1 } else { i };
+                    app.mod_list.swap(i, to);
+                    moved |= to != i;
+                    free = to + 1;
+                }
+            } else {
+                // One past the last row a selected mod can still move into
+                let mut free = app.mod_list.len();
+                for &i in selected.iter().rev() {
+                    let to = if i + 1 < free { i + 1 } else { i };
+                    app.mod_list.swap(i, to);
+                    moved |= to != i;
+                    free = to;
+                }
+            }
+            moved
+        });
+        if !moved {
+            // Everything was already against the edge
+            return;
+        }
+        // Holds a list index
+        self.hovered_row = None;
+        self.update_mods_list(self.mod_list.clone());
+        self.flush_mod_list();
+
+        let direction = if up { "up" } else { "down" };
+        self.status_msg = format!("Moved {} mods {}.", self.selected_mods.len(), direction);
+        if enabled_order(&self.mod_list) == before {
+            return;
+        }
+        if self.defer_until_tera() {
+            self.status_msg.push_str(" New apply order pending TERA launch.");
+            return;
+        }
+        if let Err(e) = self.apply_enabled_mods() {
+            self.error_msg = Some(format!("Apply failed: {:?}", e));
+        }
+        self.commit_changes();
+    }
+
+    // Right-clicking a selected row acts on the whole selection, any other
+    // row is selected on its own first
+    fn select_for_context_menu(&mut self, index: usize) {
+        if !self.selected_mods.contains(&index) {
+            self.selected_mods = vec![index];
+            self.selection_anchor = Some(index);
+            self.selection_focus = Some(index);
+        }
+    }
+
+    fn open_mod_location(&mut self, index: usize) {
+        let m = &self.mod_list[index];
+        let path = self.root.mods_dir.join(&m.file);
+        if !path.is_file() {
+            self.error_msg = Some(if m.archived {
+                format!("{} was archived by storage cleanup; reinstall it to get the file back.", m.file)
+            } else {
+                format!("{} is missing from {}.", m.file, self.root.mods_dir.display())
+            });
+            return;
+        }
+        if let Err(e) = utils::reveal_in_file_manager(&path) {
+            self.error_msg = Some(format!("Failed to open the file manager: {}", e));
+        }
+    }
+
+    fn open_batch_edit(&mut self) {
+        let selected: Vec<&ModEntry> = self.selected_mods.iter().filter_map(|&i| self.mod_list.get(i)).collect();
+        let Some(first) = selected.first() else {
+            self.status_msg = "No mods selected.".to_string();
+            return;
+        };
+        let author_mixed = selected.iter().any(|m| m.author != first.author);
+        let group_mixed = selected.iter().any(|m| m.exclusive_group != first.exclusive_group);
+        let locked_mixed = selected.iter().any(|m| m.locked != first.locked);
+        self.batch_edit = Some(BatchEdit {
+            author: if author_mixed { String::new() } else { first.author.clone() },
+            author_mixed,
+            exclusive_group: if group_mixed { String::new() } else { first.exclusive_group.clone() },
+            group_mixed,
+            locked: (!locked_mixed).then_some(first.locked),
+            ..Default::default()
+        });
+    }
+
+    // Writes the dialog's values to every selected mod. Mixed fields that were
+    // left empty keep each mod's own value.
+    fn apply_batch_edit(&mut self) {
+        let Some(edit) = self.batch_edit.take() else {
+            return;
+        };
+        let split_tags = |text: &str| -> Vec<String> {
+            text.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+        };
+        let add_tags = split_tags(&edit.add_tags);
+        let remove_tags = split_tags(&edit.remove_tags);
+        let set_author = !edit.author_mixed || !edit.author.trim().is_empty();
+        let set_group = !edit.group_mixed || !edit.exclusive_group.trim().is_empty();
+
+        let mut updated = 0;
+        for &idx in &self.selected_mods {
+            let Some(m) = self.mod_list.get_mut(idx) else {
+                continue;
+            };
+            if set_author {
+                m.author = edit.author.trim().to_string();
+            }
+            if set_group {
+                m.exclusive_group = edit.exclusive_group.trim().to_string();
+            }
+            if let Some(locked) = edit.locked {
+                m.locked = locked;
+            }
+            m.tags.retain(|t| !remove_tags.iter().any(|r| r.eq_ignore_ascii_case(t)));
+            for tag in &add_tags {
+                if !m.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    m.tags.push(tag.clone());
+                }
+            }
+            updated += 1;
+        }
 
-        // 2. Collect enabled mods into a new Vector that owns the data (cloning).
-        // This breaks the link to 'self', allowing us to call mutable methods on 'self' afterwards.
-        let mods_to_apply: Vec<(ModFile, String)> = self
-            .mod_list
-            .iter()
-            .filter(|entry| entry.enabled)
-            .map(|entry| (entry.mod_file.clone(), entry.file.clone()))
-            .collect();
+        self.update_mods_list(self.mod_list.clone());
+        self.status_msg = format!("Updated {} mods.", updated);
+    }
 
-        // 3. Apply the mods using the cloned data
-        for (mod_file, filename) in mods_to_apply {
-            if let Err(e) = self.turn_on_mod(&mod_file) {
-                eprintln!("Failed to apply mod {}: {:?}", filename, e);
-                self.error_msg = Some(format!("Failed to apply mod {}: {:?}", filename, e));
-            }
+    // Starts or stops the presence thread to follow the settings. With the
+    // setting off or no valid client ID no connection to Discord is ever
+    // attempted; a changed ID starts a new connection.
+    fn update_discord_presence(&mut self) {
+        let client_id = self.discord_client_id.trim();
+        if !self.discord_presence || !discord::is_valid_client_id(client_id) {
+            self.discord = None;
+            return;
         }
-        
-        if !self.composite_map.composite_map.is_empty() {
-            self.composite_map.dirty = true;
+        if self.discord.as_ref().is_some_and(|d| d.client_id() != client_id) {
+            self.discord = None;
         }
-        
-        Ok(())
+        let presence = if self.tera_running {
+            Presence {
+                details: "In TERA".to_string(),
+                state: "Mods applied".to_string(),
+            }
+        } else {
+            Presence {
+                details: "Managing mods".to_string(),
+                state: format!("{} enabled", self.mod_list.iter().filter(|m| m.enabled).count()),
+            }
+        };
+        let client_id = client_id.to_string();
+        self.discord.get_or_insert_with(|| DiscordPresence::start(client_id)).update(presence);
     }
 
-    fn disable_all_mods(&mut self) {
-        let mut changes = Vec::new();
-
-        for (i, m) in self.mod_list.iter_mut().enumerate() {
-            if m.enabled {
-                m.enabled = false;
-                changes.push(i);
-            }
+    fn open_mod_details(&mut self) {
+        if let Some(&idx) = self.selected_mods.first() {
+            self.edit_mod = Some(idx);
+            self.edit_container_override = self.mod_list[idx].container_override.clone();
+            self.edit_file_name = self.mod_list[idx].file.clone();
+            self.reset_package_offset_edits(idx);
+        } else {
+            self.status_msg = "No mods selected.".to_string();
         }
+    }
 
-        // Nothing to do
-        if changes.is_empty() {
-            self.status_msg = "No mods were enabled.".to_string();
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        // Don't steal keys while a text field is being edited
+        if ctx.wants_keyboard_input() {
             return;
         }
 
-        // Apply changes
-        for &i in &changes {
-            let mod_file = self.mod_list[i].mod_file.clone();
+        let (select_all, remove, enable, disable, refresh, details, escape, help, perf, move_up, move_down) = ctx.input(|i| {
+            (
+                i.modifiers.command && i.key_pressed(egui::Key::A),
+                i.key_pressed(egui::Key::Delete),
+                i.modifiers.command && i.key_pressed(egui::Key::E),
+                i.modifiers.command && i.key_pressed(egui::Key::D),
+                i.modifiers.command && i.key_pressed(egui::Key::R),
+                i.key_pressed(egui::Key::F1),
+                i.key_pressed(egui::Key::Escape),
+                i.key_pressed(egui::Key::Questionmark),
+                i.key_pressed(egui::Key::F12),
+                i.modifiers.alt && i.key_pressed(egui::Key::ArrowUp),
+                i.modifiers.alt && i.key_pressed(egui::Key::ArrowDown),
+            )
+        });
 
-            if let Err(e) = self.turn_off_mod(&mod_file, false) {
-                self.error_msg = Some(format!(
-                    "Failed to disable {}: {:?}",
-                    mod_file.mod_name, e
-                ));
-                return;
-            }
+        if select_all {
+            self.selected_mods = (0..self.mod_list.len()).collect();
+        }
+        if remove && !self.selected_mods.is_empty() {
+            self.confirm_remove = true;
+        }
+        if enable {
+            self.enable_selected_mods();
+        }
+        if disable {
+            self.disable_selected_mods();
+        }
+        if refresh {
+            self.initialized = false;
+        }
+        if details {
+            self.open_mod_details();
+        }
+        if escape {
+            self.selected_mods.clear();
+            self.edit_mod = None;
+            self.show_settings = false;
+            self.show_stats = false;
+            self.show_shortcuts = false;
+            self.show_about = false;
+        }
+        if help {
+            self.show_shortcuts = !self.show_shortcuts;
+        }
+        if perf {
+            self.perf.enabled = !self.perf.enabled;
+        }
+        // Like the buttons, moving only makes sense when rows show list order
+        if (move_up || move_down) && self.mod_sort == ModSort::ListOrder {
+            self.move_selected_mods(move_up);
         }
-
-        // Mark composite dirty & commit
-        self.composite_map.dirty = true;
-        self.commit_changes();
-
-        // Save mod list
-        self.update_mods_list(self.mod_list.clone());
-        self.restore_composite_mapper();
-        // UI feedback
-        self.selected_mods.clear();
-        self.status_msg = "Backup Restored. All mods have been disabled.".to_string();
     }
-
 }
 
 impl App for TmmApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        let frame_started = self.perf.start();
         ctx.set_pixels_per_point(1.1);
         // 1. Handle Initialization if not done and root dir is set
-        if !self.initialized {
-            if !self.root_dir.as_os_str().is_empty() {
-                // We have a path, try to load.
-                self.initialize();
-                // If we got here without crashing, consider us initialized (even with errors, we displayed them)
-                self.initialized = true;
+        if !self.initialized && !self.root_dir.as_os_str().is_empty() {
+            // We have a path, try to load.
+            self.initialize();
+            // If we got here without crashing, consider us initialized (even with errors, we displayed them)
+            self.initialized = true;
+        }
+
+        // A pending conflict prompt is modal: until it is answered the rest of
+        // the window is disabled and shortcuts and dropped files are ignored
+        let modal = !self.pending_conflicts.is_empty();
+        if !modal {
+            self.handle_shortcuts(ctx);
+        }
+        self.handle_close_request(ctx);
+        if !modal {
+            self.handle_dropped_files(ctx);
+        }
+        self.poll_duplicate_checks();
+        self.poll_overwrite_checks();
+        self.poll_file_hashes();
+        self.requery_raw_picks();
+        if self.pending_raw_picks.iter().any(|p| p.edited_at.is_some()) {
+            ctx.request_repaint_after(RAW_PICK_QUERY_DEBOUNCE);
+        }
+        if !self.duplicate_checks.is_empty() || !self.overwrite_checks.is_empty() || !self.file_hash_jobs.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        // A failed save is left to the retry queue and its backoff
+        if self.mod_list_dirty && !self.save_retry.contains(SaveTarget::ModList) {
+            if self.mod_list_last_edit.elapsed() >= MOD_LIST_SAVE_DEBOUNCE {
+                self.flush_mod_list();
+            } else {
+                ctx.request_repaint_after(MOD_LIST_SAVE_DEBOUNCE);
             }
         }
 
+        self.retry_failed_saves();
+        if let Some(wait) = self.save_retry.next_retry_in(std::time::Instant::now()) {
+            // Also keeps the countdown in the header ticking
+            ctx.request_repaint_after(wait.min(std::time::Duration::from_secs(1)));
+        }
+
         let now = std::time::Instant::now();
-        let should_check = now.duration_since(self.last_tera_check) >= std::time::Duration::from_millis(10);
+        let should_check = self.is_watching_tera()
+            && now.duration_since(self.last_tera_check) >= std::time::Duration::from_millis(10);
 
         if should_check {
             self.last_tera_check = now;
             let running = self.check_tera();
+            let debounce = std::time::Duration::from_millis(self.tera_state_debounce_ms);
+            let mut closed = false;
+            if running {
+                if self.tera_close_pending {
+                    self.tera_close_pending = false;
+                    self.log_msg("TERA is running again within the close debounce; mods stay applied.".to_string());
+                }
+                self.tera_last_seen = Some(now);
+            } else if self.tera_running {
+                let gone_for = self.tera_last_seen.map_or(debounce, |t| now.duration_since(t));
+                if gone_for >= debounce {
+                    closed = true;
+                    if self.tera_close_pending {
+                        self.tera_close_pending = false;
+                        self.log_msg("TERA stayed gone for the close debounce; treating it as closed.".to_string());
+                    }
+                } else {
+                    if !self.tera_close_pending {
+                        self.tera_close_pending = true;
+                        self.log_msg(format!(
+                            "TERA not found; treating it as closed if it stays gone for {} ms.",
+                            self.tera_state_debounce_ms
+                        ));
+                    }
+                    ctx.request_repaint_after(debounce - gone_for);
+                }
+            }
 
             if running && !self.tera_running {
                 // TERA Launched
                 println!("TERA launched — applying all enabled mods");
+                self.install_stats.total_tera_launches_detected += 1;
                 self.status_msg = "TERA detected. Applying mods...".to_string();
                 self.error_msg = None; // Clear previous errors
                 
-                if let Err(e) = self.apply_enabled_mods() {
-                    self.error_msg = Some(format!("Apply failed: {:?}", e));
-                    self.status_msg = "Failed to apply mods!".to_string();
-                }
+                let skipped = match self.auto_apply_enabled_mods() {
+                    Ok(skipped) => skipped,
+                    Err(e) => {
+                        self.error_msg = Some(format!("Apply failed: {:?}", e));
+                        self.status_msg = "Failed to apply mods!".to_string();
+                        0
+                    }
+                };
                 
-                if let Err(e) = self.composite_map.save(&self.composite_mapper_path) {
+                if let Err(e) = self.save_mapper() {
                     self.error_msg = Some(format!(
                         "Failed to save CompositePackageMapper.dat: {:?}",
                         e
@@ -682,52 +4889,43 @@ impl App for TmmApp {
                 } else {
                     self.status_msg = format!(
                         "Applied {} mods successfully.",
-                        self.mod_list.iter().filter(|m| m.enabled).count()
+                        self.mod_list.iter().filter(|m| m.enabled).count() - skipped
                     );
+                    if skipped > 0 {
+                        self.status_msg.push_str(&format!(" Skipped {} marked manual apply only.", skipped));
+                    }
                     println!(
                         "Applied mods successfully — saved to {}",
-                        self.composite_mapper_path.display()
+                        self.root.composite_mapper_path.display()
                     );
                 }
                 self.tera_running = true;
-            } else if !running && self.tera_running {
+                self.tera_launch_time = Some(std::time::Instant::now());
+            } else if closed {
                 // TERA Closed
                 println!("TERA closed — restoring original composite map");
                 self.status_msg = "TERA closed.".to_string();
                 self.error_msg = None;
 
-                if self.wait_for_tera == true {
-                self.status_msg = "TERA closed. Restoring original files.".to_string();
-                if self.backup_composite_mapper_path.exists() {
-                    match CompositeMapperFile::new(self.backup_composite_mapper_path.clone()) {
-                        Ok(backup) => {
-                            self.composite_map = backup;
-                            if let Err(e) = self.composite_map.save(&self.composite_mapper_path) {
-                                self.error_msg = Some(format!(
-                                    "Failed to restore CompositePackageMapper.dat: {:?}",
-                                    e
-                                ));
-                                self.status_msg = "Failed to restore mapper!".to_string();
-                            } else {
-                                println!(
-                                    "Restored from {}",
-                                    self.backup_composite_mapper_path.display()
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            self.error_msg = Some(format!("Failed to load backup: {:?}", e));
-                            self.status_msg = "Failed to load backup!".to_string();
-                        },
+                let was_solo = self.solo_mod.take().is_some();
+                match self.apply_mode {
+                    ApplyMode::WaitForTera => self.on_tera_closed(),
+                    ApplyMode::Persistent => {
+                        self.status_msg = "TERA closed. Persistent mode: mods stay applied.".to_string();
                     }
-                } else {
-                    self.error_msg = Some(format!(
-                        "Backup not found at {}",
-                        self.backup_composite_mapper_path.display()
-                    ));
-                    self.status_msg = "Backup missing!".to_string();
-                }}
+                    ApplyMode::Immediate => {}
+                }
+                // Put the real enabled set back unless the mapper was just restored to vanilla
+                let restored = self.apply_mode == ApplyMode::WaitForTera
+                    && self.on_tera_close == OnTeraClose::RestoreVanilla;
+                if was_solo && !restored {
+                    if let Err(e) = self.auto_apply_enabled_mods() {
+                        self.error_msg = Some(format!("Apply failed: {:?}", e));
+                    }
+                }
                 self.tera_running = false;
+                self.tera_launch_time = None;
+                self.tera_last_seen = None;
                 self.commit_changes();
 
                 // FIX: Refresh system process list completely to ensure next launch is detected
@@ -736,12 +4934,91 @@ impl App for TmmApp {
             }
         }
 
-        CentralPanel::default().show(ctx, |ui| {
+        self.update_discord_presence();
+
+        TopBottomPanel::top("header_panel").show(ctx, |ui| {
+            if modal {
+                ui.disable();
+            }
             ui.horizontal(|ui| {
-                ui.heading("Tera Mod Manager");
+                // Triple-click the title for usage stats
+                let title = ui.add(
+                    egui::Label::new(egui::RichText::new("Tera Mod Manager").heading())
+                        .sense(egui::Sense::click()),
+                );
+                if title.triple_clicked() {
+                    self.show_stats = true;
+                }
+
+                if self.mod_list_dirty {
+                    ui.label(egui::RichText::new("●").color(egui::Color32::GRAY))
+                        .on_hover_text("Unsaved changes");
+                }
+
+                tera_status_ui(self, ui);
+
+                let patched: usize = self
+                    .mod_list
+                    .iter()
+                    .filter(|m| m.enabled)
+                    .map(|m| m.mod_file.packages.len())
+                    .sum();
+                ui.weak(format!("{} objects patched", patched))
+                    .on_hover_text("Game objects replaced by all enabled mods combined.");
 
                 // Use right-to-left layout to push content to the right side
                 ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.menu_button("Help", |ui| {
+                        if ui.button("Keyboard Shortcuts").clicked() {
+                            self.show_shortcuts = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("About").clicked() {
+                            self.show_about = true;
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.menu_button("Advanced", |ui| {
+                        if ui
+                            .button("Export changes report…")
+                            .on_hover_text("Lists every mapper entry that differs from vanilla, by mod.")
+                            .clicked()
+                        {
+                            ui.close_menu();
+                            self.export_changes_report_dialog();
+                        }
+                        if ui.button("Force Disable (no restore)").clicked() {
+                            self.confirm_force_disable = true;
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.menu_button("View", |ui| {
+                        let mut changed = ui.checkbox(&mut self.show_details_panel, "Details").changed();
+                        changed |= ui.checkbox(&mut self.show_log_panel, "Log").changed();
+                        changed |= ui.checkbox(&mut self.show_quick_panel, "Quick panel").changed();
+                        ui.separator();
+                        changed |= ui.checkbox(&mut self.show_patches_column, "Patches column").changed();
+                        changed |= ui.checkbox(&mut self.show_category_column, "Category column").changed();
+                        if changed {
+                            self.save_app_config().ok();
+                        }
+                    });
+
+                    if ui.button("Storage").clicked() {
+                        self.show_storage = !self.show_storage;
+                    }
+
+                    if ui.button("Conflicts").clicked() {
+                        self.show_conflicts = !self.show_conflicts;
+                        self.conflict_pairs = None;
+                    }
+
+                    if ui.button("Settings").clicked() {
+                        self.show_settings = !self.show_settings;
+                    }
+
                     if ui.button("GitHub").clicked() {
                         ui.ctx().output_mut(|o| {
                             o.open_url = Some(OpenUrl {
@@ -763,6 +5040,9 @@ impl App for TmmApp {
                 });
             });
 
+            solo_banner_ui(self, ui);
+            mapper_too_small_ui(self, ui);
+
             if let Some(err) = &self.error_msg {
                 ui.label(egui::RichText::new(err).color(egui::Color32::RED));
             }
@@ -775,37 +5055,773 @@ impl App for TmmApp {
                 ui.label(egui::RichText::new(&self.status_msg).color(egui::Color32::LIGHT_GREEN));
             }
 
+            save_retry_ui(self, ui);
+
             root_dir_ui(self, ui);
             buttons_ui(self, ui);
+            ui.add_space(4.0);
+        });
+
+        if self.show_log_panel {
+            let panel = TopBottomPanel::bottom("log_panel")
+                .resizable(true)
+                .default_height(self.log_panel_height)
+                .show(ctx, |ui| {
+                    if modal {
+                        ui.disable();
+                    }
+                    removed_entries_ui(self, ui);
+                    log_panel_ui(ui, &self.log);
+                });
+            self.log_panel_height = panel.response.rect.height();
+        }
+
+        if self.show_details_panel {
+            let selected = self.selected_mods.first().copied().filter(|&i| i < self.mod_list.len());
+            if let Some(i) = selected {
+                self.refresh_raw_preview(i);
+                self.refresh_package_resolution(i);
+            }
+            let raw_preview = self.root.raw_preview.as_ref().and_then(|(_, dump)| dump.as_deref());
+            let resolution = self.root.package_resolution.as_ref().map(|r| r.composite_names.as_slice()).unwrap_or_default();
+            let view = selected.map(|i| ModDetailsView {
+                entry: &self.mod_list[i],
+                hex_offsets: self.show_hex_offsets,
+                raw_preview,
+                resolution,
+                apply_position: self.apply_position(self.mod_list[i].id),
+                apply_after: self.mod_names(&self.mod_list[i].apply_after),
+            });
+            let panel = SidePanel::right("details_panel")
+                .resizable(true)
+                .default_width(self.details_panel_width)
+                .show(ctx, |ui| {
+                    if modal {
+                        ui.disable();
+                    }
+                    details_panel_ui(ui, view)
+                });
+            self.details_panel_width = panel.response.rect.width();
+        }
+
+        CentralPanel::default().show(ctx, |ui| {
+            if modal {
+                ui.disable();
+            }
             egui::ScrollArea::vertical().show(ui, |ui| {
                 mod_list_ui(self, ui);
             });
         });
+
+        // Other windows are hidden, not closed, while the prompt is up
+        if modal {
+            conflict_prompt_window_ui(self, ctx);
+        } else {
+            stats_window_ui(self, ctx);
+            mod_details_window_ui(self, ctx);
+            settings_window_ui(self, ctx);
+            shortcuts_window_ui(self, ctx);
+            about_window_ui(self, ctx);
+            missing_mods_window_ui(self, ctx);
+            duplicate_mods_window_ui(self, ctx);
+            conflicts_window_ui(self, ctx);
+            preview_apply_window_ui(self, ctx);
+            foreign_backups_window_ui(self, ctx);
+            batch_edit_window_ui(self, ctx);
+            storage_window_ui(self, ctx);
+            force_disable_window_ui(self, ctx);
+            quit_unsaved_window_ui(self, ctx);
+            persistent_mode_window_ui(self, ctx);
+            update_mods_window_ui(self, ctx);
+            overwrite_window_ui(self, ctx);
+            raw_pick_window_ui(self, ctx);
+            interrupted_save_window_ui(self, ctx);
+            remove_mods_window_ui(self, ctx);
+            consistency_check_window_ui(self, ctx);
+        }
+        quick_panel_ui(self, ctx);
+        perf_overlay_ui(self, ctx);
+        self.perf.record(Timing::Frame, frame_started);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.exit_solo();
+        self.flush_mod_list();
+        self.save_app_config().ok();
+        self.root.removal_audit.close();
     }
 }
 
-fn load_icon() -> IconData {
-    let png_bytes = include_bytes!("../assets/AppIcon.png");
-    from_png_bytes(png_bytes).expect("Failed to load icon.png")
+const APP_ICON_PNG: &[u8] = include_bytes!("../assets/AppIcon.png");
+
+// Why the bytes aren't a usable icon when they aren't; the caller logs it and
+// the window opens with the platform's default icon
+fn decode_icon(png_bytes: &[u8]) -> Result<IconData, String> {
+    match from_png_bytes(png_bytes) {
+        Ok(icon) if icon.width > 0 && icon.height > 0 => Ok(icon),
+        Ok(_) => Err("The app icon is empty; starting without it.".to_string()),
+        Err(e) => Err(format!("Failed to load the app icon ({}); starting without it.", e)),
+    }
+}
+
+// Theme and style for the main window. Only sets values, so it can't fail.
+fn setup_ui(cc: &eframe::CreationContext<'_>) {
+    cc.egui_ctx.set_theme(eframe::egui::Theme::Dark);
 }
 
 fn main() -> eframe::Result<()> {
-    let icon = load_icon();
-    let viewport = egui::ViewportBuilder::default()
-        .with_icon(Arc::new(icon));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    console::init(&args);
+    let cli = CliArgs::parse(args);
+    let mut viewport = egui::ViewportBuilder::default().with_min_inner_size(ui::MIN_WINDOW_SIZE);
+    match decode_icon(APP_ICON_PNG) {
+        Ok(icon) => viewport = viewport.with_icon(Arc::new(icon)),
+        Err(warning) => console::startup_warning(&warning),
+    }
 
     let options = eframe::NativeOptions {
         viewport,
         ..Default::default()
     };
         
-    eframe::run_native(
+    let result = eframe::run_native(
         "Tera Mod Manager",
         options,
         Box::new(|cc| {
-            cc.egui_ctx.set_theme(eframe::egui::Theme::Dark);
-            
-            Ok(Box::new(TmmApp::default()))
+            setup_ui(cc);
+            Ok(Box::new(TmmApp::new(cli)))
         }),
-    )
-}
\ No newline at end of file
+    );
+    if let Err(e) = &result {
+        console::startup_error(&format!("Failed to start Tera Mod Manager: {}", e));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn mapper_text(entries: usize) -> String {
+        let mut text = "S1Data_Test?".to_string();
+        for i in 0..entries {
+            text.push_str(&format!("Test.Object{},test_{:04},{},10,|", i, i, i * 10));
+        }
+        text.push('!');
+        text
+    }
+
+    fn save_mapper_file(path: &Path, entries: usize) {
+        let map = CompositeMapperFile {
+            composite_map: composite_mapper::parse_plaintext_strict(&mapper_text(entries)).unwrap(),
+            ..Default::default()
+        };
+        map.save(path).unwrap();
+    }
+
+    // An app rooted in `dir`, with a saved mapper loaded as the active map.
+    // Its settings and data folders are in `dir` too, never the user's.
+    fn app_with_mapper(dir: &TempDir) -> TmmApp {
+        let mut app = TmmApp {
+            config_dir: Some(dir.path().join("config")),
+            data_dir: Some(dir.path().join("data")),
+            ..Default::default()
+        };
+        app.root_dir = dir.path().join("S1Game");
+        app.root = RootContext::new(&app.root_dir, COOKED_PC_DIR, None);
+        fs::create_dir_all(&app.root.cooked_pc_dir).unwrap();
+        save_mapper_file(&app.root.composite_mapper_path, 3);
+        app.composite_map = CompositeMapperFile::new(app.root.composite_mapper_path.clone()).unwrap();
+        app
+    }
+
+    fn poison(app: &mut TmmApp) {
+        let mut entry = app.composite_map.values().next().unwrap().clone();
+        entry.composite_name = "poisoned".to_string();
+        entry.object_path = "Bad,Path".to_string();
+        assert!(app.composite_map.insert_entry(entry));
+    }
+
+    #[test]
+    fn poisoned_entry_is_rejected_on_every_save_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        let on_disk = fs::read(&app.root.composite_mapper_path).unwrap();
+        poison(&mut app);
+        let untouched = |app: &TmmApp| fs::read(&app.root.composite_mapper_path).unwrap() == on_disk;
+
+        assert!(app.save_mapper().is_err());
+        assert!(untouched(&app));
+        assert!(app.save_mapper_safely().is_err());
+        assert!(untouched(&app));
+
+        for safe_apply in [false, true] {
+            app.safe_apply = safe_apply;
+            app.write_checksum_sidecar = true;
+            app.error_msg = None;
+            app.commit_changes();
+            assert!(app.error_msg.is_some());
+            assert!(app.composite_map.dirty);
+            assert!(app.save_retry.contains(SaveTarget::Mapper));
+            assert!(untouched(&app));
+            assert!(!app.checksum_sidecar_path().exists());
+        }
+    }
+
+    #[test]
+    fn poisoned_import_leaves_the_mapper_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        app.min_backup_entries = 1;
+        let on_disk = fs::read(&app.root.composite_mapper_path).unwrap();
+        let source = dir.path().join("edited.txt");
+        fs::write(&source, mapper_text(3).replace("Test.Object1", "Test|Object1")).unwrap();
+
+        assert!(app.import_mapper_text(&source).is_err());
+        assert_eq!(fs::read(&app.root.composite_mapper_path).unwrap(), on_disk);
+        assert_eq!(app.composite_map.len(), 3);
+    }
+
+    #[test]
+    fn install_counts_report_each_kind_of_wait_separately() {
+        let counts = InstallCounts { installed: 1, checking: 2, waiting: 1, ..Default::default() };
+        assert_eq!(
+            counts.waiting_note(),
+            " Comparing 2 with files already there. 1 would overwrite an existing file."
+        );
+        assert_eq!(InstallCounts { installed: 3, failed: 1, ..Default::default() }.waiting_note(), "");
+    }
+
+    #[test]
+    fn bundled_icon_decodes() {
+        let icon = decode_icon(APP_ICON_PNG).unwrap();
+        assert!(icon.width > 0 && icon.height > 0);
+        assert_eq!(icon.rgba.len(), (icon.width * icon.height * 4) as usize);
+    }
+
+    #[test]
+    fn unusable_icon_bytes_fall_back_to_no_icon() {
+        assert!(decode_icon(&[]).is_err());
+        assert!(decode_icon(b"not a png").is_err());
+        // A PNG signature with nothing after it
+        assert!(decode_icon(&APP_ICON_PNG[..8]).is_err());
+        assert!(decode_icon(&APP_ICON_PNG[..APP_ICON_PNG.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn installing_a_mod_already_in_the_mods_folder_keeps_its_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        // A raw package named after the mapper's container
+        let mut contents = mod_model::PACKAGE_MAGIC.to_le_bytes().to_vec();
+        contents.extend((0..4096u32).map(|i| (i * 7) as u8));
+        fs::write(app.root.mods_dir.join("S1Data_Test.gpk"), &contents).unwrap();
+
+        // The same file, spelled through a '..' component
+        let mods_dir_name = app.root.mods_dir.file_name().unwrap().to_owned();
+        let spelled = app.root.mods_dir.join("..").join(mods_dir_name).join("S1Data_Test.gpk");
+        // Saving as a real install does, into the fixture's own config folder
+        assert!(app.install_mod(&spelled, true, false), "{:?}", app.error_msg);
+
+        assert_eq!(fs::read(app.root.mods_dir.join("S1Data_Test.gpk")).unwrap(), contents);
+        assert_eq!(app.mod_list.len(), 1);
+        assert_eq!(app.mod_list[0].file, "S1Data_Test.gpk");
+        assert_eq!(app.mod_list[0].mod_file.packages.len(), 3);
+        assert!(dir.path().join("config").join(CONFIG_FILE).exists());
+    }
+
+    // An app whose last save was cut short: the mapper and the temporary file
+    // are each intact (a full 3-entry mapper) or half-written. The clean
+    // backup has 2 entries so a restore can be told apart.
+    fn interrupted_save(dir: &TempDir, temp_intact: bool, mapper_intact: bool) -> (TmmApp, PathBuf) {
+        let mut app = app_with_mapper(dir);
+        app.min_backup_entries = 1;
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        save_mapper_file(&app.root.backup_composite_mapper_path, 2);
+        let mapper = app.root.composite_mapper_path.clone();
+        let temp = composite_mapper::temp_path(&mapper);
+        let full = fs::read(&mapper).unwrap();
+        let half = &full[..full.len() / 2];
+        fs::write(&temp, if temp_intact { &full[..] } else { half }).unwrap();
+        fs::write(&mapper, if mapper_intact { &full[..] } else { half }).unwrap();
+        (app, temp)
+    }
+
+    fn mapper_entries(app: &TmmApp) -> usize {
+        CompositeMapperFile::new(app.root.composite_mapper_path.clone()).unwrap().len()
+    }
+
+    #[test]
+    fn intact_mapper_drops_the_stale_temp_file() {
+        for temp_intact in [true, false] {
+            let dir = tempfile::tempdir().unwrap();
+            let (mut app, temp) = interrupted_save(&dir, temp_intact, true);
+            let mapper = fs::read(&app.root.composite_mapper_path).unwrap();
+
+            assert!(app.recover_interrupted_save());
+            assert!(!temp.exists());
+            assert_eq!(fs::read(&app.root.composite_mapper_path).unwrap(), mapper);
+            assert!(app.interrupted_save.is_none() && app.error_msg.is_none());
+        }
+    }
+
+    #[test]
+    fn intact_temp_over_a_damaged_mapper_asks_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, temp) = interrupted_save(&dir, true, false);
+        let damaged = fs::read(&app.root.composite_mapper_path).unwrap();
+
+        assert!(!app.recover_interrupted_save());
+        assert_eq!(app.interrupted_save.as_deref(), Some(temp.as_path()));
+        // Nothing is touched until the user answers
+        assert!(temp.exists());
+        assert_eq!(fs::read(&app.root.composite_mapper_path).unwrap(), damaged);
+
+        app.resolve_interrupted_save(true);
+        assert!(!temp.exists());
+        assert_eq!(mapper_entries(&app), 3);
+        assert!(app.interrupted_save.is_none());
+    }
+
+    #[test]
+    fn declining_the_temp_restores_the_clean_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, temp) = interrupted_save(&dir, true, false);
+
+        assert!(!app.recover_interrupted_save());
+        app.resolve_interrupted_save(false);
+        assert!(!temp.exists());
+        assert_eq!(mapper_entries(&app), 2);
+    }
+
+    #[test]
+    fn nothing_readable_restores_the_clean_backup_and_says_why() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, temp) = interrupted_save(&dir, false, false);
+
+        assert!(app.recover_interrupted_save());
+        assert!(!temp.exists());
+        assert_eq!(mapper_entries(&app), 2);
+        assert!(app.error_msg.as_deref().unwrap().contains("clean backup was restored"));
+    }
+
+    #[test]
+    fn no_temp_file_means_nothing_to_recover() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        assert!(app.recover_interrupted_save());
+        assert!(app.log.is_empty() && app.error_msg.is_none());
+    }
+
+    #[test]
+    fn unmatched_raw_mod_is_not_left_in_the_mods_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        let source = dir.path().join("Unrelated.gpk");
+        fs::write(&source, mod_model::PACKAGE_MAGIC.to_le_bytes().repeat(16)).unwrap();
+
+        assert!(!app.install_mod(&source, false, false));
+        assert!(app.error_msg.as_deref().unwrap().contains("Could not auto-detect"));
+        assert!(!app.root.mods_dir.join("Unrelated.gpk").exists());
+        assert!(source.exists());
+        assert!(app.mod_list.is_empty());
+    }
+
+    #[test]
+    fn failed_overwrite_keeps_the_file_that_was_there() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        // More matches for the name than raw matching keeps
+        save_mapper_file(&app.root.composite_mapper_path, raw_match::MAX_CANDIDATES + 1);
+        app.composite_map = CompositeMapperFile::new(app.root.composite_mapper_path.clone()).unwrap();
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        let incoming = dir.path().join("incoming");
+        fs::create_dir_all(&incoming).unwrap();
+        let package = |fill: u8| [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[fill; 64]].concat();
+
+        // Too many matches, and no match at all
+        for name in ["S1Data_Test.gpk", "Unrelated.gpk"] {
+            let existing = app.root.mods_dir.join(name);
+            fs::write(&existing, package(1)).unwrap();
+            let source = incoming.join(name);
+            fs::write(&source, package(2)).unwrap();
+
+            assert!(!app.install_mod_as(InstallSource::new(&source, None), name, false, false, true));
+            assert_eq!(fs::read(&existing).unwrap(), package(1), "{}", name);
+            assert!(!composite_mapper::temp_path(&existing).exists());
+            assert!(app.mod_list.is_empty());
+        }
+
+        // The overwrite waits for its targets like a new file, and still
+        // replaces the file once they are picked
+        assert_eq!(app.pending_raw_picks.len(), 1);
+        assert!(app.pending_raw_picks[0].overwrite);
+        app.pending_raw_picks[0].picked[0] = true;
+        app.resolve_raw_pick(0, true);
+        assert_eq!(fs::read(app.root.mods_dir.join("S1Data_Test.gpk")).unwrap(), package(2));
+        assert_eq!(app.mod_list.len(), 1);
+        assert_eq!(app.mod_list[0].mod_file.packages.len(), 1);
+    }
+
+    #[test]
+    fn container_length_is_checked_in_characters() {
+        let app = TmmApp { max_container_len: 8, ..Default::default() };
+
+        assert!(app.check_container_len("S1_Elin_").is_ok());
+        assert!(app.check_container_len("S1_Elin_A").is_err());
+        // Eight characters, sixteen bytes
+        assert!(app.check_container_len("ÜÜÜÜÜÜÜÜ").is_ok());
+        assert!(app.check_container_len("ÜÜÜÜÜÜÜÜÜ").is_err());
+    }
+
+    #[test]
+    fn over_long_container_renames_the_installed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        app.max_container_len = 8;
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        let source = dir.path().join("S1Data_Test.gpk");
+        fs::write(&source, [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[3; 64]].concat()).unwrap();
+
+        assert!(app.install_mod(&source, false, false), "{:?}", app.error_msg);
+        let m = app.mod_list[0].clone();
+        let container = utils::strip_gpk_ext(&m.file);
+        assert_eq!(container.chars().count(), 8);
+        assert_eq!(m.effective_container(), container);
+        assert!(m.container_override.is_empty());
+        assert!(app.root.mods_dir.join(&m.file).exists());
+        assert!(!app.root.mods_dir.join("S1Data_Test.gpk").exists());
+
+        // Every patched entry points at the file that is there
+        app.turn_on_mod(&m).unwrap();
+        assert!(app.composite_map.values().all(|e| e.filename == container));
+    }
+
+    // Installs a raw package named `file` that targets the given objects
+    fn install_targeting(app: &mut TmmApp, dir: &TempDir, file: &str, objects: &[usize], enable: bool) {
+        let source = dir.path().join(file);
+        fs::write(&source, [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[objects.len() as u8; 64]].concat())
+            .unwrap();
+        let source = InstallSource {
+            targets: objects.iter().map(|i| format!("Test.Object{}", i)).collect(),
+            ..InstallSource::new(&source, None)
+        };
+        assert!(app.install_mod_as(source, file, false, enable, false), "{:?}", app.error_msg);
+    }
+
+    #[test]
+    fn update_keeps_the_id_through_the_conflict_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        install_targeting(&mut app, &dir, "ModA.gpk", &[0], true);
+        install_targeting(&mut app, &dir, "ModB.gpk", &[1], true);
+        let (a, b) = (app.mod_list[0].id, app.mod_list[1].id);
+        app.mod_list[0].tags = vec!["kept".to_string()];
+        app.log.clear();
+
+        // The new version of A also patches B's object
+        let update = dir.path().join("update");
+        fs::create_dir_all(&update).unwrap();
+        let source = update.join("ModA.gpk");
+        fs::write(&source, [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[9; 80]].concat()).unwrap();
+        let source = InstallSource {
+            targets: vec!["Test.Object0".to_string(), "Test.Object1".to_string()],
+            ..InstallSource::new(&source, None)
+        };
+        app.update_mod_in_place(a, source).unwrap();
+
+        assert_eq!(app.mod_list.iter().map(|m| m.id).collect::<Vec<_>>(), [a, b]);
+        assert_eq!(app.mod_list[0].tags, ["kept"]);
+        assert_eq!(app.mod_list[0].mod_file.packages.len(), 2);
+        assert!(!app.mod_list[0].enabled);
+        assert!(app.log.iter().any(|l| l.contains("(update)")));
+        assert!(!app.log.iter().any(|l| l.starts_with("Installed")));
+        assert_eq!(app.pending_conflicts.len(), 1);
+        assert_eq!(app.pending_conflicts[0].id, a);
+
+        app.resolve_pending_conflict(0, ConflictChoice::Enable, false);
+        assert!(app.error_msg.is_none(), "{:?}", app.error_msg);
+        assert!(app.mod_list[0].enabled);
+        assert!(!app.mod_list[1].enabled);
+    }
+
+    #[test]
+    fn merging_duplicates_switches_through_set_mod_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        install_targeting(&mut app, &dir, "ModA.gpk", &[0], false);
+        install_targeting(&mut app, &dir, "ModA_copy.gpk", &[0], true);
+        let (a, copy) = (app.mod_list[0].id, app.mod_list[1].id);
+        app.log.clear();
+
+        app.duplicate_mods = vec![vec![a, copy]];
+        app.resolve_duplicate_mods(true);
+
+        assert_eq!(app.mod_list.len(), 1);
+        assert_eq!(app.mod_list[0].id, a);
+        assert!(app.mod_list[0].enabled);
+        assert!(app.log.iter().any(|l| l.starts_with("Disabled") && l.contains("(duplicates)")));
+        assert!(app.log.iter().any(|l| l.starts_with("Enabled") && l.contains("(duplicates)")));
+    }
+
+    #[test]
+    fn settings_are_kept_in_the_apps_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = app_with_mapper(&dir);
+        app.save_app_config().unwrap();
+        assert!(dir.path().join("config").join(CONFIG_FILE).exists());
+
+        let mut loaded = TmmApp { config_dir: app.config_dir.clone(), ..Default::default() };
+        loaded.load_app_config().unwrap();
+        assert_eq!(loaded.root_dir, app.root_dir);
+
+        // Nothing is read or written without a config folder
+        let unsaved = TmmApp::default();
+        unsaved.save_app_config().unwrap();
+        assert!(unsaved.root_dir.as_os_str().is_empty());
+    }
+
+    #[test]
+    fn settings_toml_keeps_machine_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = TmmApp {
+            mapper_dirs: vec![("C:\\TERA\\S1Game".to_string(), "D:\\Mapper".to_string())],
+            quick_panel_pos: Some((320.0, 48.5)),
+            ..Default::default()
+        };
+        let path = dir.path().join("settings.toml");
+        fs::write(&path, toml::to_string_pretty(&app.human_config()).unwrap()).unwrap();
+
+        let mut loaded = TmmApp::default();
+        loaded.load_app_config_toml(&path).unwrap();
+        assert_eq!(loaded.mapper_dirs, app.mapper_dirs);
+        assert_eq!(loaded.quick_panel_pos, app.quick_panel_pos);
+    }
+
+    #[test]
+    fn folder_import_skips_excluded_files_and_vanilla_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        app.backup_map = CleanMap::new(app.root.composite_mapper_path.clone()).unwrap();
+        app.exclusion_patterns = vec!["Skip*".to_string()];
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        let package = |fill: u8| [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[fill; 64]].concat();
+        fs::write(app.root.mods_dir.join("S1Data_Test.gpk"), package(1)).unwrap();
+        let folder = dir.path().join("downloads");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("Backup.gpk"), package(1)).unwrap();
+        fs::write(folder.join("Mod.gpk"), package(2)).unwrap();
+        fs::write(folder.join("Skip_Me.gpk"), package(3)).unwrap();
+
+        let (found, skipped) = app.scan_mod_folder(&folder);
+        assert_eq!(found, vec![folder.join("Mod.gpk")]);
+        let mut reasons: Vec<(String, String)> = skipped
+            .into_iter()
+            .map(|(path, reason)| (path.file_name().unwrap().to_string_lossy().into_owned(), reason))
+            .collect();
+        reasons.sort();
+        assert_eq!(
+            reasons,
+            [
+                ("Backup.gpk".to_string(), "copy of vanilla S1Data_Test.gpk".to_string()),
+                ("Skip_Me.gpk".to_string(), "matches Skip*".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_mod_renamed_on_disk_keeps_its_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        // Only files newer than the clean backup are scanned
+        save_mapper_file(&app.root.backup_composite_mapper_path, 3);
+        let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        File::options().write(true).open(&app.root.backup_composite_mapper_path).unwrap().set_modified(an_hour_ago).unwrap();
+
+        let contents = [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[7; 64]].concat();
+        let renamed = app.root.mods_dir.join("ArmorMod_v2.gpk");
+        fs::write(&renamed, &contents).unwrap();
+        app.mod_list = vec![ModEntry {
+            id: 42,
+            file: "ArmorMod.gpk".to_string(),
+            file_hash: utils::sha256_file(&renamed).unwrap(),
+            enabled: true,
+            locked: true,
+            conflict_wins: vec![7],
+            ..Default::default()
+        }];
+
+        app.detect_renamed_mod_files();
+        app.detect_new_mod_files();
+
+        assert_eq!(app.mod_list.len(), 1);
+        let m = &app.mod_list[0];
+        assert_eq!(m.file, "ArmorMod_v2.gpk");
+        assert_eq!(m.id, 42);
+        assert!(m.enabled && m.locked);
+        assert_eq!(m.conflict_wins, vec![7]);
+    }
+
+    // An app whose storage is S1Game/X, with mods A.gpk and B.gpk listed in
+    // S1Game/CookedPC by a version from before the layout stamp. X already
+    // holds a different B.gpk, and its mapper has B's object and a game
+    // object in a container also named B.
+    fn app_with_old_storage(dir: &TempDir) -> (TmmApp, PathBuf) {
+        let mut app = TmmApp {
+            config_dir: Some(dir.path().join("config")),
+            data_dir: Some(dir.path().join("data")),
+            ..Default::default()
+        };
+        app.root_dir = dir.path().join("S1Game");
+        app.root = RootContext::new(&app.root_dir, "X", None);
+        let old = app.root_dir.join(COOKED_PC_DIR);
+        fs::create_dir_all(&old).unwrap();
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        fs::write(old.join("A.gpk"), "a").unwrap();
+        fs::write(old.join("B.gpk"), "b").unwrap();
+        fs::write(app.root.mods_dir.join("B.gpk"), "another b").unwrap();
+        fs::write(old.join(BACKUP_COMPOSITE_MAPPER_FILE), "clean").unwrap();
+        let cfg = GameConfigFile {
+            mods: vec![
+                ModEntry {
+                    id: 1,
+                    file: "A.gpk".to_string(),
+                    source_path: old.join("A.gpk").to_string_lossy().into_owned(),
+                    ..Default::default()
+                },
+                ModEntry {
+                    id: 2,
+                    file: "B.gpk".to_string(),
+                    mod_file: ModFile { container: "B".to_string(), ..Default::default() },
+                    target_paths: vec!["S1Data.ArmorMod".to_string()],
+                    ..Default::default()
+                },
+            ],
+            written_by: None,
+        };
+        mod_model::write_game_config(&cfg, &mut File::create(old.join(GAME_CONFIG_FILE)).unwrap()).unwrap();
+        let map = CompositeMapperFile {
+            composite_map: composite_mapper::parse_plaintext_strict("B?S1Data.ArmorMod,c_mod,0,10,|S1Data.WeaponSkin,c_game,10,10,|!")
+                .unwrap(),
+            ..Default::default()
+        };
+        map.save(&app.root.composite_mapper_path).unwrap();
+        (app, old)
+    }
+
+    fn mapper_container(app: &TmmApp, composite_name: &str) -> String {
+        let map = CompositeMapperFile::new(app.root.composite_mapper_path.clone()).unwrap();
+        map.composite_map[composite_name].filename.clone()
+    }
+
+    #[test]
+    fn old_storage_is_moved_into_the_new_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, old) = app_with_old_storage(&dir);
+        let new = app.root.mods_dir.clone();
+
+        app.migrate_storage_layout().unwrap();
+        for name in ["A.gpk", "B.gpk", GAME_CONFIG_FILE, BACKUP_COMPOSITE_MAPPER_FILE] {
+            assert!(!old.join(name).exists(), "{} is still in the old folder", name);
+        }
+        assert_eq!(fs::read_to_string(new.join("A.gpk")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(new.join("B_2.gpk")).unwrap(), "b");
+        assert_eq!(fs::read_to_string(new.join("B.gpk")).unwrap(), "another b");
+        assert_eq!(fs::read_to_string(new.join(BACKUP_COMPOSITE_MAPPER_FILE)).unwrap(), "clean");
+
+        let mods = mod_model::read_game_config(&mut File::open(new.join(GAME_CONFIG_FILE)).unwrap()).unwrap().mods;
+        assert_eq!(mods[0].file, "A.gpk");
+        assert_eq!(Path::new(&mods[0].source_path), new.join("A.gpk"));
+        assert_eq!(mods[1].file, "B_2.gpk");
+        assert_eq!(mods[1].effective_container(), "B_2");
+        // Only the mod's entry follows; the game's container keeps its name
+        assert_eq!(mapper_container(&app, "c_mod"), "B_2");
+        assert_eq!(mapper_container(&app, "c_game"), "B");
+
+        assert!(storage_migration::Journal::read(&new).unwrap().unwrap().done);
+        assert_eq!(storage_migration::read_stamp(&new), storage_migration::LAYOUT_VERSION);
+
+        // A second start finds nothing to do
+        app.migrate_storage_layout().unwrap();
+        assert_eq!(fs::read_to_string(new.join("B_2.gpk")).unwrap(), "b");
+        assert_eq!(mapper_container(&app, "c_mod"), "B_2");
+    }
+
+    #[test]
+    fn an_interrupted_move_resumes_without_leaving_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, old) = app_with_old_storage(&dir);
+        let new = app.root.mods_dir.clone();
+
+        // Stopped after moving A and copying B, before B's original went
+        storage_migration::Journal::start(&old, &new).unwrap();
+        storage_migration::move_file(&old.join("A.gpk"), &new.join("A.gpk")).unwrap();
+        fs::copy(old.join("B.gpk"), new.join("B_2.gpk")).unwrap();
+
+        app.migrate_storage_layout().unwrap();
+        for (old_name, new_name) in [("A.gpk", "A.gpk"), ("B.gpk", "B_2.gpk")] {
+            assert!(!old.join(old_name).exists());
+            assert!(new.join(new_name).exists());
+        }
+        assert!(!old.join(GAME_CONFIG_FILE).exists());
+        assert!(new.join(GAME_CONFIG_FILE).exists());
+        assert_eq!(mapper_container(&app, "c_mod"), "B_2");
+        assert!(storage_migration::Journal::read(&new).unwrap().unwrap().done);
+    }
+
+    #[test]
+    fn stamped_or_newer_storage_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut app, old) = app_with_old_storage(&dir);
+        let new = app.root.mods_dir.clone();
+
+        // Written by a newer version
+        fs::write(new.join(storage_migration::LAYOUT_FILE), "2\n").unwrap();
+        app.migrate_storage_layout().unwrap();
+        assert!(old.join(GAME_CONFIG_FILE).exists());
+        assert_eq!(fs::read_to_string(new.join(storage_migration::LAYOUT_FILE)).unwrap(), "2\n");
+
+        // A folder in the current layout is another client folder's storage
+        fs::remove_file(new.join(storage_migration::LAYOUT_FILE)).unwrap();
+        storage_migration::write_stamp(&old).unwrap();
+        app.migrate_storage_layout().unwrap();
+        assert!(old.join(GAME_CONFIG_FILE).exists());
+        assert!(old.join("A.gpk").exists());
+        assert_eq!(storage_migration::read_stamp(&new), storage_migration::LAYOUT_VERSION);
+    }
+
+    #[test]
+    fn startup_hashes_unhashed_mods_off_the_ui_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app_with_mapper(&dir);
+        fs::create_dir_all(&app.root.mods_dir).unwrap();
+        save_mapper_file(&app.root.backup_composite_mapper_path, 3);
+        let contents = [mod_model::PACKAGE_MAGIC.to_le_bytes().as_slice(), &[5; 64]].concat();
+        fs::write(app.root.mods_dir.join("S1Data_Test.gpk"), &contents).unwrap();
+        let cfg = GameConfigFile {
+            mods: vec![ModEntry { id: 1, file: "S1Data_Test.gpk".to_string(), ..Default::default() }],
+            written_by: None,
+        };
+        mod_model::write_game_config(&cfg, &mut File::create(&app.root.game_config_path).unwrap()).unwrap();
+
+        app.initialize();
+        assert!(app.error_msg.is_none(), "{:?}", app.error_msg);
+        // Startup only queues the hash
+        assert!(app.mod_list[0].file_hash.is_empty());
+        assert_eq!(app.file_hash_jobs.len(), 1);
+
+        let started = std::time::Instant::now();
+        while !app.file_hash_jobs.is_empty() {
+            assert!(started.elapsed() < std::time::Duration::from_secs(10), "hashing never finished");
+            std::thread::sleep(std::time::Duration::from_millis(5));
+            app.poll_file_hashes();
+        }
+        assert_eq!(app.mod_list[0].file_hash, utils::sha256_hex(&contents));
+    }
+}
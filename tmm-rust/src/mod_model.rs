@@ -1,7 +1,16 @@
-use anyhow::Result;
+//! Mod files and the ModList.mods format.
+//!
+//! A packed mod stores its author in the metadata footer. Many community mods
+//! are packed without one, so two conventions are read as fallbacks, in order:
+//!
+//! - a composite package whose folder name is `MOD:Author:<name>` (such a
+//!   package names the author, not an object path)
+//! - a file name of the form `<name>_by_<author>.gpk`
+//!
+use anyhow::{Context, Result};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::default::Default;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 #[derive(Default, Clone, PartialEq, Eq)]
 pub struct CompositePackage {
@@ -33,18 +42,126 @@ pub struct ModFile {
 
 #[derive(Default, Clone, PartialEq)]
 pub struct ModEntry {
+    // Stable identity that survives file renames; 0 until one is assigned
+    pub id: u64,
     pub file: String,
     pub enabled: bool,
     pub mod_file: ModFile,
+    // Container name to register in the mapper instead of mod_file.container (empty = none)
+    pub container_override: String,
+    // Unix timestamps (seconds), 0 when unknown
+    pub installed_at: u64,
+    pub last_applied_at: u64,
+    // Where the mod was installed from, used to bring back archived files
+    pub source_path: String,
+    // The .gpk was deleted by storage cleanup; the entry is kept for reinstalling
+    pub archived: bool,
+    // SHA-256 of the .gpk, used to recognise a renamed file (empty = unknown)
+    pub file_hash: String,
+    // Author set in TMM, shown instead of mod_file.mod_author (empty = none)
+    pub author: String,
+    pub tags: Vec<String>,
+    // Enabling a mod disables the other enabled mods of its group (empty = none)
+    pub exclusive_group: String,
+    // Locked mods can't be toggled or removed until unlocked
+    pub locked: bool,
+    // Inferred from the object paths at scan time (empty = not inferred yet)
+    pub category: String,
+    // Category set in TMM, always shown instead of the inferred one (empty = none)
+    pub category_override: String,
+    // IDs of mods this one is applied after, whatever their list order. IDs of
+    // removed mods are kept so the constraint holds again on reinstall.
+    pub apply_after: Vec<u64>,
+    // Left out of the automatic apply when TERA starts; only applied when the
+    // user applies by hand
+    pub manual_apply_only: bool,
+    // From the metadata of a .tmod package (empty = none)
+    pub description: String,
+    pub version: String,
+    // Object paths the .tmod declared; used instead of matching a raw GPK by
+    // its file name (empty = none)
+    pub target_paths: Vec<String>,
+    // IDs of mods this one was chosen over in a conflict prompt with "remember"
+    // set. Enabling either side then settles the conflict without asking.
+    pub conflict_wins: Vec<u64>,
+}
+
+impl ModEntry {
+    pub fn display_author(&self) -> &str {
+        if self.author.is_empty() {
+            &self.mod_file.mod_author
+        } else {
+            &self.author
+        }
+    }
+
+    pub fn effective_category(&self) -> &str {
+        if self.category_override.is_empty() {
+            &self.category
+        } else {
+            &self.category_override
+        }
+    }
+
+    pub fn effective_container(&self) -> &str {
+        if self.container_override.is_empty() {
+            &self.mod_file.container
+        } else {
+            &self.container_override
+        }
+    }
+}
+
+// Next free ID, one past the highest assigned so IDs of removed mods are never reused
+pub fn next_mod_id(mods: &[ModEntry]) -> u64 {
+    mods.iter().map(|m| m.id).max().unwrap_or(0) + 1
+}
+
+// Gives an ID to entries loaded from an older ModList.mods and to any entry whose
+// ID clashes with an earlier one. Returns how many entries were changed.
+pub fn assign_missing_ids(mods: &mut [ModEntry]) -> usize {
+    let mut next = next_mod_id(mods);
+    let mut seen = std::collections::HashSet::new();
+    let mut assigned = 0;
+    for m in mods.iter_mut() {
+        if m.id == 0 || !seen.insert(m.id) {
+            m.id = next;
+            seen.insert(next);
+            next += 1;
+            assigned += 1;
+        }
+    }
+    assigned
+}
+
+// Whether cleanup may delete the mod's file: it is disabled, still installed
+// and neither installed nor applied since `cutoff` (Unix seconds). Entries
+// missing either timestamp or their source path are never stale; their age is
+// unknown or the file couldn't be reinstalled.
+pub fn is_stale(m: &ModEntry, cutoff: u64) -> bool {
+    !m.enabled
+        && !m.archived
+        && m.installed_at != 0
+        && m.last_applied_at != 0
+        && !m.source_path.is_empty()
+        && m.last_applied_at.max(m.installed_at) < cutoff
 }
 
 #[derive(Default, Clone, PartialEq)]
 pub struct GameConfigFile {
     pub mods: Vec<ModEntry>,
+    // TMM version that wrote the file, None before extension version 6
+    pub written_by: Option<String>,
 }
 
-const PACKAGE_MAGIC: u32 = 0x9E2A83C1;
+pub const PACKAGE_MAGIC: u32 = 0x9E2A83C1;
 const MAX_STRLEN: usize = 1024;
+const AUTHOR_FOLDER_PREFIX: &str = "MOD:Author:";
+const FILENAME_AUTHOR_SEPARATOR: &str = "_by_";
+// Version of the per-mod extension block written after the ModList.mods end marker.
+// Older TMM builds stop reading at the marker, so the block is invisible to them.
+// From version 6 the writing TMM version follows the extension version.
+const MOD_LIST_EXT_VERSION: i32 = 10;
 
 pub fn read_string<R: Read>(r: &mut R) -> Result<String> {
     let mut size: i32 = r.read_i32::<LittleEndian>()?;
@@ -90,87 +207,280 @@ pub fn write_string<W: Write>(w: &mut W, s: &str) -> Result<()> {
     Ok(())
 }
 
+// A .tmod is a GPK with a metadata block appended, so a mod can carry its
+// name, author and target objects instead of TMM guessing them from the file
+// name:
+//   [gpk][metadata][metadata size: u32][TMOD_MAGIC: u32]
+//   metadata: [format version: i32][name][author][description][version]
+//             [path count: i32][object paths]
+// Later format versions only append fields, so older readers skip them.
+const TMOD_MAGIC: u32 = 0x444F_4D54; // "TMOD"
+const TMOD_FORMAT_VERSION: i32 = 1;
+
+#[derive(Default, Clone, PartialEq)]
+pub struct TmodMetadata {
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    pub version: String,
+    pub object_paths: Vec<String>,
+}
+
+// Splits a .tmod into the GPK it wraps and its metadata
+pub fn read_tmod(data: &[u8]) -> Result<(&[u8], TmodMetadata)> {
+    let mut trailer = &data[offset_from_end(data.len(), 8)? as usize..];
+    let meta_size = trailer.read_u32::<LittleEndian>()? as usize;
+    if trailer.read_u32::<LittleEndian>()? != TMOD_MAGIC {
+        anyhow::bail!("Not a .tmod package: the metadata marker is missing");
+    }
+    let gpk_end = offset_from_end(data.len(), meta_size.saturating_add(8))? as usize;
+
+    let mut s = Cursor::new(&data[gpk_end..data.len() - 8]);
+    let format_version = s.read_i32::<LittleEndian>()?;
+    if format_version < 1 {
+        anyhow::bail!("Unsupported .tmod format version {}", format_version);
+    }
+    let mut meta = TmodMetadata {
+        name: read_string(&mut s)?,
+        author: read_string(&mut s)?,
+        description: read_string(&mut s)?,
+        version: read_string(&mut s)?,
+        ..Default::default()
+    };
+    let path_count = s.read_i32::<LittleEndian>()?;
+    meta.object_paths = (0..path_count).map(|_| read_string(&mut s)).collect::<Result<_>>()?;
+    Ok((&data[..gpk_end], meta))
+}
+
+pub fn write_tmod<W: Write>(gpk: &[u8], meta: &TmodMetadata, out: &mut W) -> Result<()> {
+    let mut block = Vec::new();
+    block.write_i32::<LittleEndian>(TMOD_FORMAT_VERSION)?;
+    write_string(&mut block, &meta.name)?;
+    write_string(&mut block, &meta.author)?;
+    write_string(&mut block, &meta.description)?;
+    write_string(&mut block, &meta.version)?;
+    block.write_i32::<LittleEndian>(meta.object_paths.len() as i32)?;
+    for path in &meta.object_paths {
+        write_string(&mut block, path)?;
+    }
+
+    out.write_all(gpk)?;
+    out.write_all(&block)?;
+    out.write_u32::<LittleEndian>(block.len() as u32)?;
+    out.write_u32::<LittleEndian>(TMOD_MAGIC)?;
+    Ok(())
+}
+
+// Position `back` bytes before the end of a file of `end` bytes
+fn offset_from_end(end: usize, back: usize) -> Result<u64> {
+    end.checked_sub(back)
+        .map(|pos| pos as u64)
+        .ok_or_else(|| anyhow::anyhow!("Malformed mod file: meta offset {} exceeds file size {}", back, end))
+}
+
+fn check_offset(offset: usize, end: usize) -> Result<u64> {
+    if offset > end {
+        return Err(anyhow::anyhow!("Malformed mod file: meta offset {} exceeds file size {}", offset, end));
+    }
+    Ok(offset as u64)
+}
+
+// Whether the file starts with the Unreal package signature (a raw GPK) or
+// ends with it (the footer of a TMM-packed mod). Anything else isn't a
+// package the client could load.
+pub fn has_package_signature<R: Read + Seek>(s: &mut R) -> Result<bool> {
+    let end = s.seek(SeekFrom::End(0))?;
+    if end < 4 {
+        return Ok(false);
+    }
+    s.seek(SeekFrom::Start(0))?;
+    if s.read_u32::<LittleEndian>()? == PACKAGE_MAGIC {
+        return Ok(true);
+    }
+    s.seek(SeekFrom::Start(end - 4))?;
+    Ok(s.read_u32::<LittleEndian>()? == PACKAGE_MAGIC)
+}
+
 pub fn read_mod_file<R: Read + Seek>(s: &mut R, m: &mut ModFile) -> Result<()> {
     s.seek(SeekFrom::End(0))?;
     let end = s.stream_position()? as usize;
-    s.seek(SeekFrom::Start((end - 4) as u64))?;
+    s.seek(SeekFrom::Start(offset_from_end(end, 4)?))?;
     let magic = s.read_u32::<LittleEndian>()?;
 
     if magic == PACKAGE_MAGIC {
-        s.seek(SeekFrom::Start((end - 8) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 8)?))?;
         let meta_size = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 12) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 12)?))?;
         let composite_count = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 16) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 16)?))?;
         let offsets_offset = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 20) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 20)?))?;
         let container_offset = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 24) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 24)?))?;
         let name_offset = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 28) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 28)?))?;
         let author_offset = s.read_i32::<LittleEndian>()? as usize;
 
-        s.seek(SeekFrom::Start((end - 32) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 32)?))?;
         m.mod_file_version = s.read_i32::<LittleEndian>()?;
 
-        s.seek(SeekFrom::Start((end - 36) as u64))?;
+        s.seek(SeekFrom::Start(offset_from_end(end, 36)?))?;
         m.region_lock = s.read_i32::<LittleEndian>()? != 0;
 
-        let composite_end = end - meta_size - 4;
+        let composite_end = offset_from_end(end, meta_size.saturating_add(4))? as usize;
 
         // Read author, name, container
-        s.seek(SeekFrom::Start(author_offset as u64))?;
+        s.seek(SeekFrom::Start(check_offset(author_offset, end)?))?;
         m.mod_author = read_string(s)?;
 
-        s.seek(SeekFrom::Start(name_offset as u64))?;
+        s.seek(SeekFrom::Start(check_offset(name_offset, end)?))?;
         m.mod_name = read_string(s)?;
 
-        s.seek(SeekFrom::Start(container_offset as u64))?;
+        s.seek(SeekFrom::Start(check_offset(container_offset, end)?))?;
         m.container = read_string(s)?;
 
+        // Each offset takes 4 bytes, so a count the file can't hold is corrupt
+        if composite_count > end / 4 {
+            return Err(anyhow::anyhow!("Malformed mod file: {} packages in {} bytes", composite_count, end));
+        }
+
         // Read offsets
-        s.seek(SeekFrom::Start(offsets_offset as u64))?;
+        s.seek(SeekFrom::Start(check_offset(offsets_offset, end)?))?;
         let mut offsets = vec![0usize; composite_count];
         for offset in &mut offsets {
             *offset = s.read_i32::<LittleEndian>()? as usize;
+            check_offset(*offset, end)?;
         }
 
         // Initialize packages
         m.packages = vec![CompositePackage::default(); composite_count];
 
         // Read each composite package
+        let mut folder_author = None;
+        let mut author_tags = vec![false; composite_count];
         for (idx, package) in m.packages.iter_mut().enumerate() {
             s.seek(SeekFrom::Start(offsets[idx] as u64))?;
-            read_composite_package(s, package)?;
+            let tag = read_composite_package(s, package)?;
+            author_tags[idx] = tag.is_some();
+            folder_author = folder_author.or(tag.flatten());
+        }
+        if m.mod_author.is_empty() {
+            m.mod_author = folder_author.unwrap_or_default();
         }
 
         // Set sizes for each package
-        for idx in 1..m.packages.len() {
-            m.packages[idx - 1].size = offsets[idx] - m.packages[idx - 1].offset;
+        for (idx, &next_offset) in offsets.iter().enumerate().skip(1) {
+            m.packages[idx - 1].size = next_offset
+                .checked_sub(m.packages[idx - 1].offset)
+                .ok_or_else(|| anyhow::anyhow!("Malformed mod file: package offsets out of order"))?;
         }
 
         if let Some(last) = m.packages.last_mut() {
-            last.size = composite_end.max(end - meta_size) - last.offset;
+            last.size = composite_end
+                .max(end - meta_size)
+                .checked_sub(last.offset)
+                .ok_or_else(|| anyhow::anyhow!("Malformed mod file: package offset {} past metadata", last.offset))?;
         }
+
+        // Author tags name no object, so they are dropped once every
+        // package's size has been taken from the offset that follows it
+        let mut tags = author_tags.into_iter();
+        m.packages.retain(|_| !tags.next().unwrap_or(false));
     } else {
         // Single package fallback
         let mut p = CompositePackage::default();
         s.seek(SeekFrom::Start(0))?;
-        read_composite_package(s, &mut p)?;
-        p.size = end;
-        m.packages.push(p);
+        match read_composite_package(s, &mut p)? {
+            Some(author) => m.mod_author = author.unwrap_or_default(),
+            None => {
+                p.size = end;
+                m.packages.push(p);
+            }
+        }
     }
 
     Ok(())
 }
 
 
-fn read_composite_package<R: Read + Seek>(s: &mut R, p: &mut CompositePackage) -> Result<()> {
+// Packs GPKs into one mod file in the layout read_mod_file reads:
+//   [packages][author][name][container][package offsets: i32 each]
+//   [region lock][mod file version][author offset][name offset]
+//   [container offset][offsets offset][package count][metadata size][PACKAGE_MAGIC]
+// Each package names its object in its folder name (`MOD:<object path>`);
+// the metadata size counts everything after the last package.
+pub fn write_mod_file<W: Write>(m: &ModFile, packages: &[Vec<u8>], out: &mut W) -> Result<()> {
+    let mut data = Vec::new();
+    let mut offsets = Vec::with_capacity(packages.len());
+    for package in packages {
+        offsets.push(data.len() as i32);
+        data.extend_from_slice(package);
+    }
+    let packages_end = data.len();
+
+    let author_offset = data.len() as i32;
+    write_string(&mut data, &m.mod_author)?;
+    let name_offset = data.len() as i32;
+    write_string(&mut data, &m.mod_name)?;
+    let container_offset = data.len() as i32;
+    write_string(&mut data, &m.container)?;
+    let offsets_offset = data.len() as i32;
+    for offset in &offsets {
+        data.write_i32::<LittleEndian>(*offset)?;
+    }
+    data.write_i32::<LittleEndian>(m.region_lock as i32)?;
+    data.write_i32::<LittleEndian>(m.mod_file_version)?;
+    data.write_i32::<LittleEndian>(author_offset)?;
+    data.write_i32::<LittleEndian>(name_offset)?;
+    data.write_i32::<LittleEndian>(container_offset)?;
+    data.write_i32::<LittleEndian>(offsets_offset)?;
+    data.write_i32::<LittleEndian>(packages.len() as i32)?;
+    let meta_size = data.len() + 8 - packages_end;
+    data.write_i32::<LittleEndian>(meta_size as i32)?;
+    data.write_u32::<LittleEndian>(PACKAGE_MAGIC)?;
+
+    out.write_all(&data)?;
+    Ok(())
+}
+
+// Checks that every package lies inside a file of `file_len` bytes and that
+// no two packages share bytes. Empty packages (raw mods) are not checked.
+pub fn check_package_ranges(packages: &[CompositePackage], file_len: usize) -> Result<()> {
+    let mut ranges = Vec::with_capacity(packages.len());
+    for pkg in packages.iter().filter(|p| p.size > 0) {
+        let end = pkg
+            .offset
+            .checked_add(pkg.size)
+            .filter(|&end| end <= file_len)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} runs past the end of the file ({} + {} > {} bytes)",
+                    pkg.object_path,
+                    pkg.offset,
+                    pkg.size,
+                    file_len
+                )
+            })?;
+        ranges.push((pkg.offset, end, &pkg.object_path));
+    }
+    ranges.sort();
+    for pair in ranges.windows(2) {
+        let ((_, a_end, a), (b_start, _, b)) = (pair[0], pair[1]);
+        if b_start < a_end {
+            anyhow::bail!("{} overlaps {}", a, b);
+        }
+    }
+    Ok(())
+}
+
+// Returns Some when the folder name is an author tag rather than an object
+// path, holding the author unless the tag is blank
+fn read_composite_package<R: Read + Seek>(s: &mut R, p: &mut CompositePackage) -> Result<Option<Option<String>>> {
     p.offset = s.stream_position()? as usize; // usize instead of i32
     s.seek(SeekFrom::Current(4))?;
     p.file_version = s.read_u16::<LittleEndian>()?;
@@ -178,33 +488,161 @@ fn read_composite_package<R: Read + Seek>(s: &mut R, p: &mut CompositePackage) -
     s.seek(SeekFrom::Start(p.offset as u64 + 12))?;
 
     let folder_name = read_string(s)?;
-    if folder_name.starts_with("MOD:") {
-        p.object_path = folder_name[4..].to_string();
+    if let Some(author) = folder_name.strip_prefix(AUTHOR_FOLDER_PREFIX) {
+        let author = author.trim();
+        return Ok(Some((!author.is_empty()).then(|| author.to_string())));
+    }
+    if let Some(object_path) = folder_name.strip_prefix("MOD:") {
+        p.object_path = object_path.to_string();
     }
 
-    Ok(())
+    Ok(None)
+}
+
+// `<name>_by_<author>.gpk`; the last `_by_` wins so mod names may contain one
+pub fn author_from_filename(filename: &str) -> Option<String> {
+    let stem = crate::utils::strip_gpk_ext(filename);
+    let at = stem.to_ascii_lowercase().rfind(FILENAME_AUTHOR_SEPARATOR)?;
+    let author = stem[at + FILENAME_AUTHOR_SEPARATOR.len()..].trim();
+    (at > 0 && !author.is_empty()).then(|| author.to_string())
+}
+
+// Falls back to the file name convention when the mod file names no author
+pub fn fill_missing_author(m: &mut ModFile, filename: &str) {
+    if m.mod_author.is_empty() {
+        if let Some(author) = author_from_filename(filename) {
+            m.mod_author = author;
+        }
+    }
 }
 
+// ModList.mods layout:
+//   [count][entries][PACKAGE_MAGIC][ext version][written by][extension records][PACKAGE_MAGIC][payload size: u32]
+// The payload size counts every byte before the final magic. Files written before
+// the footer existed end right after the first magic.
 pub fn read_game_config<R: Read>(s: &mut R) -> Result<GameConfigFile> {
+    let mut data = Vec::new();
+    s.read_to_end(&mut data)?;
+
+    let (payload, has_footer) = match split_game_config_footer(&data)? {
+        Some(payload) => (payload, true),
+        None => (data.as_slice(), false),
+    };
+
+    read_game_config_payload(payload, has_footer).context("Failed to read ModList.mods")
+}
+
+// The payload before a valid footer, or None when the file has no footer. A
+// footer whose size disagrees with the file means the file was cut short or
+// appended to.
+fn split_game_config_footer(data: &[u8]) -> Result<Option<&[u8]>> {
+    let Some(payload_len) = data.len().checked_sub(8) else {
+        return Ok(None);
+    };
+    let mut footer = &data[payload_len..];
+    let magic = footer.read_u32::<LittleEndian>()?;
+    let size = footer.read_u32::<LittleEndian>()? as usize;
+    if magic != PACKAGE_MAGIC {
+        return Ok(None);
+    }
+    if size != payload_len {
+        anyhow::bail!(
+            "ModList.mods appears truncated: its footer records {} bytes but {} precede it",
+            size,
+            payload_len
+        );
+    }
+    Ok(Some(&data[..payload_len]))
+}
+
+fn read_game_config_payload(payload: &[u8], has_footer: bool) -> Result<GameConfigFile> {
+    let mut s = Cursor::new(payload);
     let count = s.read_i32::<LittleEndian>()?;
-    let mut mods = Vec::with_capacity(count as usize);
+    let mut mods = Vec::with_capacity(count.max(0) as usize);
     for _ in 0..count {
         let enabled = s.read_i32::<LittleEndian>()? != 0;
-        let file = read_string(s)?;
-        let mod_name = read_string(s)?;
-        let container = read_string(s)?;
+        let file = read_string(&mut s)?;
+        let mod_name = read_string(&mut s)?;
+        let container = read_string(&mut s)?;
         
         // We create a default ModFile and populate the fields we persisted
-        let mut mod_file = ModFile::default();
-        mod_file.mod_name = mod_name;
-        mod_file.container = container;
+        let mod_file = ModFile {
+            mod_name,
+            container,
+            ..Default::default()
+        };
+
+        mods.push(ModEntry { file, enabled, mod_file, ..Default::default() });
+    }
+
+    if s.read_u32::<LittleEndian>()? != PACKAGE_MAGIC {
+        anyhow::bail!("missing end marker");
+    }
+
+    let mut written_by = None;
+    if has_footer {
+        let ext_version = s.read_i32::<LittleEndian>()?;
+        if ext_version >= 6 {
+            written_by = Some(read_string(&mut s)?);
+        }
+        for m in &mut mods {
+            read_mod_entry_extension(&mut s, ext_version, m)?;
+        }
+    } else if (s.position() as usize) < payload.len() {
+        // Extension data without a footer means the file was cut short
+        anyhow::bail!("unexpected trailing data");
+    }
+    Ok(GameConfigFile { mods, written_by })
+}
 
-        mods.push(ModEntry { file, enabled, mod_file });
+fn read_mod_entry_extension<R: Read>(s: &mut R, ext_version: i32, m: &mut ModEntry) -> Result<()> {
+    if ext_version >= 1 {
+        m.container_override = read_string(s)?;
+    }
+    if ext_version >= 2 {
+        m.installed_at = s.read_u64::<LittleEndian>()?;
+        m.last_applied_at = s.read_u64::<LittleEndian>()?;
+        m.source_path = read_string(s)?;
+        m.archived = s.read_i32::<LittleEndian>()? != 0;
+    }
+    if ext_version >= 3 {
+        m.id = s.read_u64::<LittleEndian>()?;
+        m.file_hash = read_string(s)?;
+    }
+    if ext_version >= 4 {
+        m.author = read_string(s)?;
+        let tag_count = s.read_i32::<LittleEndian>()?;
+        m.tags = (0..tag_count).map(|_| read_string(s)).collect::<Result<_>>()?;
+        m.exclusive_group = read_string(s)?;
+        m.locked = s.read_i32::<LittleEndian>()? != 0;
+    }
+    if ext_version >= 5 {
+        m.category = read_string(s)?;
+        m.category_override = read_string(s)?;
+    }
+    if ext_version >= 7 {
+        let after_count = s.read_i32::<LittleEndian>()?;
+        m.apply_after = (0..after_count).map(|_| s.read_u64::<LittleEndian>()).collect::<std::io::Result<_>>()?;
     }
-    Ok(GameConfigFile { mods })
+    if ext_version >= 8 {
+        m.manual_apply_only = s.read_i32::<LittleEndian>()? != 0;
+    }
+    if ext_version >= 9 {
+        m.description = read_string(s)?;
+        m.version = read_string(s)?;
+        let path_count = s.read_i32::<LittleEndian>()?;
+        m.target_paths = (0..path_count).map(|_| read_string(s)).collect::<Result<_>>()?;
+    }
+    if ext_version >= 10 {
+        let win_count = s.read_i32::<LittleEndian>()?;
+        m.conflict_wins = (0..win_count).map(|_| s.read_u64::<LittleEndian>()).collect::<std::io::Result<_>>()?;
+    }
+    Ok(())
 }
 
-pub fn write_game_config<W: Write>(cfg: &GameConfigFile, s: &mut W) -> Result<()> {
+pub fn write_game_config<W: Write>(cfg: &GameConfigFile, out: &mut W) -> Result<()> {
+    let mut buf = Vec::new();
+    let s = &mut buf;
     let count = cfg.mods.len() as i32;
     s.write_i32::<LittleEndian>(count)?;
     for m in &cfg.mods {
@@ -217,5 +655,340 @@ pub fn write_game_config<W: Write>(cfg: &GameConfigFile, s: &mut W) -> Result<()
         write_string(s, &m.mod_file.container)?;
     }
     s.write_u32::<LittleEndian>(PACKAGE_MAGIC)?;
+
+    s.write_i32::<LittleEndian>(MOD_LIST_EXT_VERSION)?;
+    write_string(s, crate::versioned_io::APP_VERSION)?;
+    for m in &cfg.mods {
+        write_string(s, &m.container_override)?;
+        s.write_u64::<LittleEndian>(m.installed_at)?;
+        s.write_u64::<LittleEndian>(m.last_applied_at)?;
+        write_string(s, &m.source_path)?;
+        s.write_i32::<LittleEndian>(if m.archived { 1 } else { 0 })?;
+        s.write_u64::<LittleEndian>(m.id)?;
+        write_string(s, &m.file_hash)?;
+        write_string(s, &m.author)?;
+        s.write_i32::<LittleEndian>(m.tags.len() as i32)?;
+        for tag in &m.tags {
+            write_string(s, tag)?;
+        }
+        write_string(s, &m.exclusive_group)?;
+        s.write_i32::<LittleEndian>(if m.locked { 1 } else { 0 })?;
+        write_string(s, &m.category)?;
+        write_string(s, &m.category_override)?;
+        s.write_i32::<LittleEndian>(m.apply_after.len() as i32)?;
+        for &id in &m.apply_after {
+            s.write_u64::<LittleEndian>(id)?;
+        }
+        s.write_i32::<LittleEndian>(if m.manual_apply_only { 1 } else { 0 })?;
+        write_string(s, &m.description)?;
+        write_string(s, &m.version)?;
+        s.write_i32::<LittleEndian>(m.target_paths.len() as i32)?;
+        for path in &m.target_paths {
+            write_string(s, path)?;
+        }
+        s.write_i32::<LittleEndian>(m.conflict_wins.len() as i32)?;
+        for &id in &m.conflict_wins {
+            s.write_u64::<LittleEndian>(id)?;
+        }
+    }
+
+    // Footer for truncation detection
+    let payload_size = buf.len() as u32;
+    buf.write_u32::<LittleEndian>(PACKAGE_MAGIC)?;
+    buf.write_u32::<LittleEndian>(payload_size)?;
+    out.write_all(&buf)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versioned_io::WrittenBy;
+
+    // A package header with the given folder name followed by `body` filler bytes
+    fn package(folder: &str, body: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(PACKAGE_MAGIC).unwrap();
+        data.write_u16::<LittleEndian>(610).unwrap();
+        data.write_u16::<LittleEndian>(14).unwrap();
+        data.write_u32::<LittleEndian>(0).unwrap();
+        write_string(&mut data, folder).unwrap();
+        data.extend(std::iter::repeat_n(0xAB, body));
+        data
+    }
+
+    fn pack(author: &str, packages: &[Vec<u8>]) -> ModFile {
+        let meta = ModFile {
+            mod_name: "Test".to_string(),
+            mod_author: author.to_string(),
+            container: "TestContainer".to_string(),
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        write_mod_file(&meta, packages, &mut data).unwrap();
+        let mut m = ModFile::default();
+        read_mod_file(&mut Cursor::new(data), &mut m).unwrap();
+        m
+    }
+
+    // A 50-byte file ending in a TMM footer with the given meta size, package
+    // count and offsets of the author, name, container and offset table
+    fn footer_only(meta_size: i32, count: i32, offsets: [i32; 4]) -> Vec<u8> {
+        let mut data = vec![0u8; 14];
+        let [author, name, container, table] = offsets;
+        for field in [0, 1, author, name, container, table, count, meta_size] {
+            data.write_i32::<LittleEndian>(field).unwrap();
+        }
+        data.write_u32::<LittleEndian>(PACKAGE_MAGIC).unwrap();
+        assert_eq!(data.len(), 50);
+        data
+    }
+
+    fn read(data: Vec<u8>) -> Result<ModFile> {
+        let mut m = ModFile::default();
+        read_mod_file(&mut Cursor::new(data), &mut m).map(|_| m)
+    }
+
+    #[test]
+    fn footer_pointing_past_the_file_is_rejected() {
+        // Meta size larger than the file
+        assert!(read(footer_only(1000, 0, [0, 0, 0, 0])).is_err());
+        // Negative meta size
+        assert!(read(footer_only(-1, 0, [0, 0, 0, 0])).is_err());
+        // Author string past the end
+        assert!(read(footer_only(36, 0, [4000, 0, 0, 0])).is_err());
+        // Offset table past the end
+        assert!(read(footer_only(36, 1, [0, 0, 0, 51])).is_err());
+        // More packages than the file can hold offsets for
+        assert!(read(footer_only(36, 1000, [0, 0, 0, 0])).is_err());
+    }
+
+    #[test]
+    fn files_shorter_than_the_magic_are_rejected() {
+        assert!(read(Vec::new()).is_err());
+        assert!(read(vec![0xC1, 0x83]).is_err());
+    }
+
+    fn mod_list() -> Vec<u8> {
+        let cfg = GameConfigFile {
+            mods: vec![ModEntry { file: "A.gpk".to_string(), id: 1, ..Default::default() }],
+            written_by: None,
+        };
+        let mut data = Vec::new();
+        write_game_config(&cfg, &mut data).unwrap();
+        data
+    }
+
+    // Replaces the version stamp and rewrites the footer to match
+    fn restamp(data: &[u8], stamp: &str) -> Vec<u8> {
+        let (mut old, mut new) = (Vec::new(), Vec::new());
+        write_string(&mut old, crate::versioned_io::APP_VERSION).unwrap();
+        write_string(&mut new, stamp).unwrap();
+        let payload = &data[..data.len() - 8];
+        let at = payload.windows(old.len()).position(|w| w == old.as_slice()).unwrap();
+        let mut out = [&payload[..at], new.as_slice(), &payload[at + old.len()..]].concat();
+        let size = out.len() as u32;
+        out.write_u32::<LittleEndian>(PACKAGE_MAGIC).unwrap();
+        out.write_u32::<LittleEndian>(size).unwrap();
+        out
+    }
+
+    fn written_by(data: &[u8]) -> WrittenBy {
+        WrittenBy::check(read_game_config(&mut Cursor::new(data)).unwrap().written_by.as_deref())
+    }
+
+    #[test]
+    fn mod_list_records_the_version_that_wrote_it() {
+        let cfg = read_game_config(&mut Cursor::new(mod_list())).unwrap();
+
+        assert_eq!(cfg.written_by.as_deref(), Some(crate::versioned_io::APP_VERSION));
+        assert_eq!(written_by(&mod_list()), WrittenBy::Compatible);
+    }
+
+    #[test]
+    fn mod_list_from_before_the_stamp_loads_normally() {
+        // Old files end right after the first magic
+        let data = mod_list();
+        let magic = PACKAGE_MAGIC.to_le_bytes();
+        let end = data.windows(4).position(|w| w == magic).unwrap() + 4;
+        let cfg = read_game_config(&mut Cursor::new(&data[..end])).unwrap();
+
+        assert_eq!(cfg.mods.len(), 1);
+        assert_eq!(cfg.written_by, None);
+        assert_eq!(written_by(&data[..end]), WrittenBy::Compatible);
+    }
+
+    #[test]
+    fn mod_list_from_a_newer_or_unknown_version_is_read_only() {
+        assert!(matches!(written_by(&restamp(&mod_list(), "999.0.0")), WrittenBy::Newer(v) if v == "999.0.0"));
+        assert!(matches!(written_by(&restamp(&mod_list(), "not a version")), WrittenBy::Unreadable(_)));
+        // The entries still load for read-only use
+        let cfg = read_game_config(&mut Cursor::new(restamp(&mod_list(), "999.0.0"))).unwrap();
+        assert_eq!(cfg.mods[0].file, "A.gpk");
+    }
+
+    #[test]
+    fn mod_list_footer_disagreeing_with_the_length_is_truncation() {
+        let mut data = mod_list();
+        let at = data.len() - 8;
+        data.remove(at - 1);
+        let error = read_game_config(&mut Cursor::new(data)).err().unwrap().to_string();
+
+        assert!(error.contains("truncated"), "{}", error);
+    }
+
+    #[test]
+    fn corrupt_mod_list_keeps_the_cause() {
+        // Five entries claimed, one present; the footer still matches
+        let mut data = mod_list();
+        data[..4].copy_from_slice(&5i32.to_le_bytes());
+        let error = read_game_config(&mut Cursor::new(data)).err().unwrap();
+
+        assert!(!error.to_string().contains("truncated"));
+        assert_eq!(error.to_string(), "Failed to read ModList.mods");
+        assert_eq!(error.root_cause().to_string(), "missing end marker");
+    }
+
+    #[test]
+    fn tmod_round_trips_its_gpk_and_metadata() {
+        let gpk = package("MOD:Armor.Mesh", 40);
+        let meta = TmodMetadata {
+            name: "Shiny Armor".to_string(),
+            author: "Sómeone".to_string(),
+            description: "Line one\nline two".to_string(),
+            version: "1.2".to_string(),
+            object_paths: vec!["Armor.Mesh".to_string(), "UI.Icon".to_string()],
+        };
+        let mut data = Vec::new();
+        write_tmod(&gpk, &meta, &mut data).unwrap();
+
+        let (read_gpk, read_meta) = read_tmod(&data).unwrap();
+        assert_eq!(read_gpk, gpk.as_slice());
+        assert!(read_meta == meta);
+    }
+
+    #[test]
+    fn tmod_without_marker_or_with_bad_size_is_rejected() {
+        let gpk = package("MOD:Armor.Mesh", 40);
+        assert!(read_tmod(&gpk).is_err());
+
+        let mut data = Vec::new();
+        write_tmod(&gpk, &TmodMetadata::default(), &mut data).unwrap();
+        // A metadata size larger than the file
+        let (size_at, too_big) = (data.len() - 8, data.len() as u32);
+        data[size_at..size_at + 4].copy_from_slice(&too_big.to_le_bytes());
+        assert!(read_tmod(&data).is_err());
+    }
+
+    fn range(object_path: &str, offset: usize, size: usize) -> CompositePackage {
+        CompositePackage { object_path: object_path.to_string(), offset, size, ..Default::default() }
+    }
+
+    #[test]
+    fn package_ranges_must_fit_the_file() {
+        assert!(check_package_ranges(&[range("A", 0, 60), range("B", 60, 40)], 100).is_ok());
+        assert!(check_package_ranges(&[range("A", 0, 60), range("B", 60, 41)], 100).is_err());
+        assert!(check_package_ranges(&[range("A", 101, 1)], 100).is_err());
+        assert!(check_package_ranges(&[range("A", usize::MAX, 2)], 100).is_err());
+    }
+
+    #[test]
+    fn package_ranges_must_not_overlap() {
+        assert!(check_package_ranges(&[range("B", 50, 50), range("A", 0, 51)], 100).is_err());
+        assert!(check_package_ranges(&[range("A", 10, 20), range("B", 0, 100)], 100).is_err());
+        // Raw mods point every object at the whole file with empty packages
+        assert!(check_package_ranges(&[range("A", 0, 0), range("B", 0, 0)], 100).is_ok());
+    }
+
+    fn stale_candidate() -> ModEntry {
+        ModEntry {
+            installed_at: 1_000,
+            last_applied_at: 2_000,
+            source_path: "C:/Downloads/Armor.gpk".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mods_unused_since_the_cutoff_are_stale() {
+        let m = stale_candidate();
+        assert!(is_stale(&m, 2_001));
+        assert!(!is_stale(&m, 2_000));
+        assert!(!is_stale(&ModEntry { enabled: true, ..m.clone() }, 2_001));
+        assert!(!is_stale(&ModEntry { archived: true, ..m }, 2_001));
+    }
+
+    #[test]
+    fn mods_of_unknown_age_or_without_a_source_are_never_stale() {
+        let m = stale_candidate();
+        assert!(!is_stale(&ModEntry { installed_at: 0, ..m.clone() }, u64::MAX));
+        assert!(!is_stale(&ModEntry { last_applied_at: 0, ..m.clone() }, u64::MAX));
+        assert!(!is_stale(&ModEntry { source_path: String::new(), ..m }, u64::MAX));
+    }
+
+    #[test]
+    fn footer_author_wins_over_folder_tag() {
+        let packages = [package("MOD:Armor.Mesh", 40), package("MOD:Author:Folder Author", 8)];
+        let m = pack("Footer Author", &packages);
+
+        assert_eq!(m.mod_author, "Footer Author");
+        assert_eq!(m.container, "TestContainer");
+    }
+
+    #[test]
+    fn folder_author_tag_is_dropped_from_packages() {
+        let packages = [
+            package("MOD:Armor.Mesh", 40),
+            package("MOD:Author:  Folder Author ", 8),
+            package("MOD:UI.Icon", 24),
+        ];
+        let m = pack("", &packages);
+
+        assert_eq!(m.mod_author, "Folder Author");
+        let paths: Vec<&str> = m.packages.iter().map(|p| p.object_path.as_str()).collect();
+        assert_eq!(paths, ["Armor.Mesh", "UI.Icon"]);
+        // Neighbours keep their own sizes; the tag's bytes go to neither
+        assert_eq!(m.packages[0].offset, 0);
+        assert_eq!(m.packages[0].size, packages[0].len());
+        assert_eq!(m.packages[1].offset, packages[0].len() + packages[1].len());
+        assert_eq!(m.packages[1].size, packages[2].len());
+    }
+
+    #[test]
+    fn blank_folder_tag_names_no_author() {
+        let m = pack("", &[package("MOD:Author:  ", 8), package("MOD:Armor.Mesh", 40)]);
+
+        assert_eq!(m.mod_author, "");
+        assert_eq!(m.packages.len(), 1);
+        assert_eq!(m.packages[0].object_path, "Armor.Mesh");
+    }
+
+    #[test]
+    fn single_author_tagged_package_leaves_no_packages() {
+        let mut m = ModFile::default();
+        read_mod_file(&mut Cursor::new(package("MOD:Author:Solo", 16)), &mut m).unwrap();
+
+        assert_eq!(m.mod_author, "Solo");
+        assert!(m.packages.is_empty());
+    }
+
+    #[test]
+    fn filename_author_fills_only_a_missing_author() {
+        let mut m = pack("", &[package("MOD:Armor.Mesh", 40)]);
+        fill_missing_author(&mut m, "Shiny_Armor_by_Someone.gpk");
+        assert_eq!(m.mod_author, "Someone");
+
+        let mut m = pack("Footer Author", &[package("MOD:Armor.Mesh", 40)]);
+        fill_missing_author(&mut m, "Shiny_Armor_by_Someone.gpk");
+        assert_eq!(m.mod_author, "Footer Author");
+    }
+
+    #[test]
+    fn filename_author_takes_the_last_separator() {
+        assert_eq!(author_from_filename("Stand_by_Me_by_Someone.gpk").as_deref(), Some("Someone"));
+        assert_eq!(author_from_filename("Armor_BY_Someone.GPK").as_deref(), Some("Someone"));
+        assert_eq!(author_from_filename("_by_Someone.gpk"), None);
+        assert_eq!(author_from_filename("Armor_by_.gpk"), None);
+        assert_eq!(author_from_filename("Armor.gpk"), None);
+    }
+}
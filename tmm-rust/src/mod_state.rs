@@ -0,0 +1,106 @@
+use serde::Serialize;
+
+// What turned a mod on or off. Recorded in the log and passed to the event
+// hook as `origin`; the serialized names are part of the hook interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOrigin {
+    // The row checkbox or a double-click on the row
+    Checkbox,
+    // The On/Off buttons and shortcuts acting on the selection
+    Selection,
+    // "Keep" in the Conflicts window
+    ConflictWindow,
+    // Disabled because a mod sharing objects with it was enabled
+    Conflict,
+    // Enabled as part of installing it
+    Install,
+    // Disable All
+    DisableAll,
+    // Turned off as part of removing it from the list
+    Remove,
+    // Force Disable (no restore), which rebuilds the mapper afterwards
+    ForceDisable,
+    // Switched off and on again around replacing its file with a new version
+    Update,
+    // Merge or Remove extras in the Duplicate Mod Entries window
+    Duplicates,
+}
+
+impl ChangeOrigin {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Checkbox => "checkbox",
+            Self::Selection => "selection",
+            Self::ConflictWindow => "conflicts window",
+            Self::Conflict => "conflict",
+            Self::Install => "install",
+            Self::DisableAll => "disable all",
+            Self::Remove => "remove",
+            Self::ForceDisable => "force disable",
+            Self::Update => "update",
+            Self::Duplicates => "duplicates",
+        }
+    }
+
+    // Locked mods refuse changes the user aims at them and being turned off
+    // by a conflicting mod; Disable All and Force Disable still turn them off.
+    pub fn respects_lock(self) -> bool {
+        matches!(self, Self::Checkbox | Self::Selection | Self::Conflict)
+    }
+
+    // The caller puts the whole mapper back afterwards, so the mod's own
+    // entries are left alone and only its state changes
+    pub fn leaves_mapper(self) -> bool {
+        self == Self::ForceDisable
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ChangeOutcome {
+    // False when the mod was already in that state, is locked, or was left
+    // off over a conflict
+    pub changed: bool,
+    // IDs of the mods turned off because they conflict with the one enabled
+    pub disabled_conflicts: Vec<u64>,
+    // The mapper was left alone until TERA launches; only the list changed
+    pub deferred: bool,
+    // Left off until the user settles the conflict in the prompt
+    pub awaiting_choice: bool,
+    // Left off because the mod with this ID won a remembered conflict choice
+    pub kept_off_by: Option<u64>,
+    // Left off because enabling it would turn off these locked mods
+    pub blocked_by: Vec<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_never_turn_off_locked_mods() {
+        assert!(ChangeOrigin::Conflict.respects_lock());
+        assert!(ChangeOrigin::Checkbox.respects_lock());
+        assert!(ChangeOrigin::Selection.respects_lock());
+        assert!(!ChangeOrigin::DisableAll.respects_lock());
+        assert!(!ChangeOrigin::ForceDisable.respects_lock());
+        assert!(!ChangeOrigin::Duplicates.respects_lock());
+    }
+
+    #[test]
+    fn only_force_disable_leaves_the_mapper_to_the_caller() {
+        let origins = [
+            ChangeOrigin::Checkbox,
+            ChangeOrigin::Selection,
+            ChangeOrigin::ConflictWindow,
+            ChangeOrigin::Conflict,
+            ChangeOrigin::Install,
+            ChangeOrigin::DisableAll,
+            ChangeOrigin::Remove,
+            ChangeOrigin::Update,
+            ChangeOrigin::Duplicates,
+        ];
+        assert!(origins.iter().all(|o| !o.leaves_mapper()));
+        assert!(ChangeOrigin::ForceDisable.leaves_mapper());
+    }
+}
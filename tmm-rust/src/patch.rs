@@ -0,0 +1,115 @@
+use anyhow::Result;
+
+use crate::apply_order;
+use crate::composite_mapper::{CleanMap, CompositeEntry, CompositeMapperFile};
+use crate::mod_model::{CompositePackage, ModEntry, ModFile};
+
+// How mods change the mapper. Kept apart from the app state so the benchmarks
+// and the golden-file tests run the same code the app does.
+
+// Points every object the mod overrides at its container. Objects the map
+// doesn't know are skipped with a warning rather than failing the mod.
+pub fn turn_on_mod(map: &mut CompositeMapperFile, mod_entry: &ModEntry) -> Result<()> {
+    let container = mod_entry.effective_container();
+
+    for pkg in &mod_entry.mod_file.packages {
+        let mut entry = CompositeEntry::default();
+
+        // Try to find the object
+        if !map.get_entry_by_incomplete_object_path(&pkg.object_path, &mut entry) {
+            // LOG the error but DON'T bail. Continue to the next package.
+            eprintln!("[TMM] Warning: Object '{}' not found in CompositeMap. Skipping.", pkg.object_path);
+            continue;
+        }
+
+        // Apply patch if found
+        if let Err(e) = map.apply_patch(&entry.composite_name, container, pkg.offset, pkg.size) {
+            eprintln!("[TMM] Warning: Failed to patch '{}': {:?}", pkg.object_path, e);
+        }
+    }
+
+    Ok(())
+}
+
+// Points every object the mod overrides back at its vanilla location. An
+// object the clean map doesn't have was added by the mod and is removed; the
+// removed entries are returned for the removal audit.
+pub fn turn_off_mod(
+    map: &mut CompositeMapperFile,
+    backup: &CleanMap,
+    mod_file: &ModFile,
+    silent: bool,
+) -> Result<Vec<CompositeEntry>> {
+    let mut removed = Vec::new();
+    for pkg in &mod_file.packages {
+        let mut original = CompositeEntry::default();
+
+        // Try to find the original entry in the backup (clean) map
+        if backup.get_entry_by_incomplete_object_path(&pkg.object_path, &mut original) {
+            map.apply_patch(&original.composite_name, &original.filename, original.offset, original.size)?;
+        } else {
+            let mut active_entry = CompositeEntry::default();
+            if map.get_entry_by_incomplete_object_path(&pkg.object_path, &mut active_entry) {
+                println!("[TMM] Removing new object entry: {}", pkg.object_path);
+                if map.remove_entry(&active_entry) {
+                    removed.push(active_entry);
+                }
+                map.dirty = true;
+            } else if !silent {
+                // If we can't find it in the active map either, it's likely a data mismatch.
+                eprintln!("[TMM] Warning: Object '{}' not found in active map or backup.", pkg.object_path);
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+pub struct Rebuild {
+    // Indices into the given mods, in the order they were applied
+    pub order: Vec<usize>,
+    // Apply-after cycles, as reported by apply_order::sort
+    pub cycles: Vec<Vec<u64>>,
+    // Outcome for each applied mod, in apply order
+    pub results: Vec<(usize, Result<()>)>,
+}
+
+// Resets the map to the clean backup and applies `mods` on top, in list order
+// adjusted by their apply-after constraints, so later mods win shared objects
+pub fn rebuild(map: &mut CompositeMapperFile, backup: &CleanMap, mods: &[ModEntry]) -> Rebuild {
+    map.composite_map = backup.entries().clone();
+    map.entries_changed();
+    map.cached_map.clear();
+
+    let sorted = apply_order::sort(mods);
+    let results = sorted
+        .order
+        .iter()
+        .map(|&i| (i, turn_on_mod(map, &mods[i])))
+        .collect();
+    if !map.is_empty() {
+        map.dirty = true;
+    }
+    Rebuild {
+        order: sorted.order,
+        cycles: sorted.cycles,
+        results,
+    }
+}
+
+// Indices of the enabled mods that override at least one object of `packages`
+pub fn find_conflicting_indices(mods: &[ModEntry], packages: &[CompositePackage]) -> Vec<usize> {
+    mods.iter()
+        .enumerate()
+        .filter(|(_, m)| m.enabled) // Only check against active mods
+        .filter(|(_, m)| {
+            packages.iter().any(|new_pkg| {
+                m.mod_file
+                    .packages
+                    .iter()
+                    .any(|existing_pkg| existing_pkg.object_path == new_pkg.object_path)
+            })
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
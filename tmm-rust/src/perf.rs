@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+// Which measurement a timing belongs to
+pub enum Timing {
+    Frame,
+    TeraCheck,
+    Apply,
+    Commit,
+    Scan,
+}
+
+// Timings and counters for the performance overlay. Nothing is measured while
+// the overlay is off: start() returns None and record() ignores it.
+#[derive(Default)]
+pub struct PerfStats {
+    pub enabled: bool,
+    pub frame: Option<Duration>,
+    pub tera_check: Option<Duration>,
+    pub apply: Option<Duration>,
+    pub commit: Option<Duration>,
+    pub scan: Option<Duration>,
+    // Mod entries cloned by apply_enabled_mods this session
+    pub mod_entry_clones: u64,
+}
+
+impl PerfStats {
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    pub fn record(&mut self, timing: Timing, started: Option<Instant>) {
+        let Some(started) = started else {
+            return;
+        };
+        let elapsed = Some(started.elapsed());
+        match timing {
+            Timing::Frame => self.frame = elapsed,
+            Timing::TeraCheck => self.tera_check = elapsed,
+            Timing::Apply => self.apply = elapsed,
+            Timing::Commit => self.commit = elapsed,
+            Timing::Scan => self.scan = elapsed,
+        }
+    }
+
+    pub fn count_clones(&mut self, count: usize) {
+        if self.enabled {
+            self.mod_entry_clones += count as u64;
+        }
+    }
+}
+
+pub fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(d) => format!("{:.2} ms", d.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::{Duration, Instant};
+
+use crate::composite_mapper::CompositeEntry;
+use crate::utils;
+
+// Raw (unpacked) GPKs carry no object paths, so their targets are guessed by
+// comparing the file name with the package names in the mapper. A very short
+// name ("a.gpk") overlaps most of a large map, so the search keeps only the
+// closest MAX_CANDIDATES matches and stops once SEARCH_BUDGET is spent,
+// reporting that the result was cut short.
+pub const MAX_CANDIDATES: usize = 2000;
+pub const SEARCH_BUDGET: Duration = Duration::from_millis(250);
+// The clock is only read every this many entries
+const CLOCK_CHECK_INTERVAL: usize = 256;
+
+pub struct RawMatches<'a> {
+    // Best matches in map order
+    pub entries: Vec<&'a CompositeEntry>,
+    // Matches were dropped by the candidate cap or the time budget
+    pub truncated: bool,
+}
+
+// How close an entry's package name is to the mod's file name, higher is
+// closer. None when neither stem contains the other. Both stems are expected
+// in lowercase without the .gpk extension.
+pub fn score(mod_stem: &str, entry_stem: &str) -> Option<u32> {
+    if !mod_stem.contains(entry_stem) && !entry_stem.contains(mod_stem) {
+        return None;
+    }
+    let diff = mod_stem.len().abs_diff(entry_stem.len());
+    Some(u32::MAX - diff.min(u32::MAX as usize) as u32)
+}
+
+pub fn find<'a>(entries: impl Iterator<Item = &'a CompositeEntry>, file_name: &str) -> RawMatches<'a> {
+    find_bounded(entries, file_name, MAX_CANDIDATES, SEARCH_BUDGET)
+}
+
+pub fn find_bounded<'a>(
+    entries: impl Iterator<Item = &'a CompositeEntry>,
+    file_name: &str,
+    max_candidates: usize,
+    budget: Duration,
+) -> RawMatches<'a> {
+    let mod_stem = utils::strip_gpk_ext(file_name).to_lowercase();
+    let started = Instant::now();
+    let mut truncated = false;
+    // (score, map position, entry); trimmed back to max_candidates whenever it
+    // doubles so a huge map never holds every match at once
+    let mut found: Vec<(u32, usize, &CompositeEntry)> = Vec::new();
+
+    for (position, entry) in entries.enumerate() {
+        if position % CLOCK_CHECK_INTERVAL == 0 && position > 0 && started.elapsed() >= budget {
+            truncated = true;
+            break;
+        }
+        let entry_stem = utils::strip_gpk_ext(&entry.filename).to_lowercase();
+        if let Some(score) = score(&mod_stem, &entry_stem) {
+            found.push((score, position, entry));
+            if found.len() >= max_candidates.saturating_mul(2).max(1) {
+                truncated |= keep_best(&mut found, max_candidates);
+            }
+        }
+    }
+    truncated |= keep_best(&mut found, max_candidates);
+
+    found.sort_by_key(|&(_, position, _)| position);
+    RawMatches {
+        entries: found.into_iter().map(|(_, _, entry)| entry).collect(),
+        truncated,
+    }
+}
+
+// Drops all but the max highest scores, earlier map positions winning ties.
+// Returns whether anything was dropped.
+fn keep_best(found: &mut Vec<(u32, usize, &CompositeEntry)>, max: usize) -> bool {
+    if found.len() <= max {
+        return false;
+    }
+    found.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    found.truncate(max);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(filenames: &[&str]) -> Vec<CompositeEntry> {
+        filenames
+            .iter()
+            .enumerate()
+            .map(|(i, filename)| CompositeEntry {
+                filename: filename.to_string(),
+                object_path: format!("Object.{}", i),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn paths(matches: &RawMatches) -> Vec<String> {
+        matches.entries.iter().map(|e| e.object_path.clone()).collect()
+    }
+
+    #[test]
+    fn score_requires_containment_either_way() {
+        assert!(score("s1_elin", "s1_elin_pc").is_some());
+        assert!(score("s1_elin_pc_mod", "s1_elin_pc").is_some());
+        assert_eq!(score("s1_elin", "s1_castanic"), None);
+    }
+
+    #[test]
+    fn score_prefers_the_closer_length() {
+        assert_eq!(score("s1_elin", "s1_elin"), Some(u32::MAX));
+        assert!(score("s1_elin", "s1_elin_pc").unwrap() > score("s1_elin", "s1_elin_pc_lod").unwrap());
+    }
+
+    #[test]
+    fn under_the_cap_nothing_is_truncated() {
+        let map = entries(&["S1_Elin_PC.gpk", "S1_Castanic.gpk", "S1_Elin.gpk"]);
+        let matches = find_bounded(map.iter(), "S1_Elin.gpk", 10, Duration::from_secs(60));
+
+        assert!(!matches.truncated);
+        assert_eq!(paths(&matches), ["Object.0", "Object.2"]);
+    }
+
+    #[test]
+    fn the_cap_keeps_the_best_scores_in_map_order() {
+        let map = entries(&["S1_Elin_PC_LOD.gpk", "S1_Elin.gpk", "S1_Elin_PC.gpk", "S1_Elin_X.gpk"]);
+        let matches = find_bounded(map.iter(), "S1_Elin.gpk", 2, Duration::from_secs(60));
+
+        assert!(matches.truncated);
+        // Exact match and the one-character-longer name, back in map order
+        assert_eq!(paths(&matches), ["Object.1", "Object.3"]);
+    }
+
+    #[test]
+    fn a_spent_budget_truncates_large_maps() {
+        let names: Vec<String> = (0..CLOCK_CHECK_INTERVAL * 2).map(|i| format!("S1_{}.gpk", i)).collect();
+        let map = entries(&names.iter().map(String::as_str).collect::<Vec<_>>());
+        // Every entry matches; the clock stops the scan at its first check
+        let matches = find_bounded(map.iter(), "S1.gpk", MAX_CANDIDATES, Duration::ZERO);
+
+        assert!(matches.truncated);
+        assert_eq!(matches.entries.len(), CLOCK_CHECK_INTERVAL);
+    }
+}
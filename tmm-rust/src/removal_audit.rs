@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::composite_mapper::CompositeEntry;
+use crate::versioned_io::{WrittenBy, APP_VERSION};
+
+// Oldest removals are forgotten past this
+const MAX_REMOVED_ENTRIES: usize = 200;
+pub const REMOVAL_JOURNAL_FILE: &str = "removed_entries.jsonl";
+
+// First line of the journal
+#[derive(Serialize, Deserialize)]
+struct JournalHeader {
+    tmm_version: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RemovedEntry {
+    pub entry: CompositeEntry,
+    // Unix seconds
+    pub removed_at: u64,
+    // Mod whose disabling removed the entry
+    pub mod_name: String,
+    // Read back from a journal the previous session didn't clean up
+    #[serde(skip)]
+    pub recovered: bool,
+}
+
+// Mapper entries removed this session (objects a mod had added), so a bad
+// removal can be undone by hand. Each removal is also appended to a journal
+// in the root's cache folder; a clean shutdown deletes it, so a journal found
+// on startup means the last session crashed and its removals are offered again.
+#[derive(Default)]
+pub struct RemovalAudit {
+    pub entries: Vec<RemovedEntry>,
+    // Empty when there is no cache folder; nothing is journaled then
+    journal_path: PathBuf,
+    // Set when the journal was left by a newer TMM; it is then only read
+    pub warning: Option<String>,
+}
+
+impl RemovalAudit {
+    pub fn open(cache_dir: &Path) -> Self {
+        if cache_dir.as_os_str().is_empty() {
+            return Self::default();
+        }
+        let mut journal_path = cache_dir.join(REMOVAL_JOURNAL_FILE);
+        let text = fs::read_to_string(&journal_path).unwrap_or_default();
+        let header = text
+            .lines()
+            .next()
+            .and_then(|line| serde_json::from_str::<JournalHeader>(line).ok());
+        let warning = WrittenBy::check(header.as_ref().map(|h| h.tmm_version.as_str())).warning(REMOVAL_JOURNAL_FILE);
+        if warning.is_some() {
+            journal_path = PathBuf::new();
+        }
+        let mut entries: Vec<RemovedEntry> = text
+            .lines()
+            // A line cut short by the crash is skipped
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        for removed in &mut entries {
+            removed.recovered = true;
+        }
+        let excess = entries.len().saturating_sub(MAX_REMOVED_ENTRIES);
+        entries.drain(..excess);
+        Self { entries, journal_path, warning }
+    }
+
+    pub fn record(&mut self, entry: CompositeEntry, mod_name: &str) {
+        let removed = RemovedEntry {
+            entry,
+            removed_at: crate::utils::unix_now(),
+            mod_name: mod_name.to_string(),
+            recovered: false,
+        };
+        if let Err(e) = self.append_to_journal(&removed) {
+            eprintln!("[TMM] Failed to journal removed entry: {}", e);
+        }
+        self.entries.push(removed);
+        if self.entries.len() > MAX_REMOVED_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn take(&mut self, index: usize) -> Option<RemovedEntry> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        let removed = self.entries.remove(index);
+        if let Err(e) = self.rewrite_journal() {
+            eprintln!("[TMM] Failed to update removal journal: {}", e);
+        }
+        Some(removed)
+    }
+
+    pub fn recovered_count(&self) -> usize {
+        self.entries.iter().filter(|r| r.recovered).count()
+    }
+
+    // Clean shutdown or a switch to another root: nothing left to recover
+    pub fn close(&mut self) {
+        self.entries.clear();
+        if !self.journal_path.as_os_str().is_empty() {
+            fs::remove_file(&self.journal_path).ok();
+        }
+    }
+
+    fn append_to_journal(&self, removed: &RemovedEntry) -> anyhow::Result<()> {
+        if self.journal_path.as_os_str().is_empty() {
+            return Ok(());
+        }
+        let mut journal = OpenOptions::new().create(true).append(true).open(&self.journal_path)?;
+        if journal.metadata()?.len() == 0 {
+            writeln!(journal, "{}", serde_json::to_string(&Self::header())?)?;
+        }
+        writeln!(journal, "{}", serde_json::to_string(removed)?)?;
+        Ok(())
+    }
+
+    fn header() -> JournalHeader {
+        JournalHeader { tmm_version: APP_VERSION.to_string() }
+    }
+
+    fn rewrite_journal(&self) -> anyhow::Result<()> {
+        if self.journal_path.as_os_str().is_empty() {
+            return Ok(());
+        }
+        let mut lines = serde_json::to_string(&Self::header())?;
+        lines.push('\n');
+        for removed in &self.entries {
+            lines.push_str(&serde_json::to_string(removed)?);
+            lines.push('\n');
+        }
+        fs::write(&self.journal_path, lines)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::{Duration, Instant};
+
+// First retry after a failed save, doubled on every further failure
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SaveTarget {
+    ModList,
+    Mapper,
+}
+
+impl SaveTarget {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ModList => "mod list",
+            Self::Mapper => "mapper",
+        }
+    }
+}
+
+struct PendingSave {
+    target: SaveTarget,
+    failures: u32,
+    next_attempt: Instant,
+    last_error: String,
+}
+
+// Saves that failed (a locked file, an antivirus scan holding it open) and are
+// tried again with exponential backoff until one succeeds. The queue never
+// reads the clock itself; callers pass `now`, so the schedule can be driven by
+// any clock.
+#[derive(Default)]
+pub struct SaveRetryQueue {
+    pending: Vec<PendingSave>,
+}
+
+impl SaveRetryQueue {
+    pub fn failed(&mut self, target: SaveTarget, error: String, now: Instant) {
+        let index = match self.pending.iter().position(|p| p.target == target) {
+            Some(i) => i,
+            None => {
+                self.pending.push(PendingSave { target, failures: 0, next_attempt: now, last_error: String::new() });
+                self.pending.len() - 1
+            }
+        };
+        let pending = &mut self.pending[index];
+        pending.failures += 1;
+        pending.next_attempt = now + retry_delay(pending.failures);
+        pending.last_error = error;
+    }
+
+    pub fn succeeded(&mut self, target: SaveTarget) {
+        self.pending.retain(|p| p.target != target);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn contains(&self, target: SaveTarget) -> bool {
+        self.pending.iter().any(|p| p.target == target)
+    }
+
+    // Targets whose retry is due, in the order they first failed
+    pub fn due(&self, now: Instant) -> Vec<SaveTarget> {
+        self.pending.iter().filter(|p| p.next_attempt <= now).map(|p| p.target).collect()
+    }
+
+    // Makes every pending save due immediately
+    pub fn retry_now(&mut self, now: Instant) {
+        for pending in &mut self.pending {
+            pending.next_attempt = now;
+        }
+    }
+
+    // Time until the earliest retry, None when nothing is pending
+    pub fn next_retry_in(&self, now: Instant) -> Option<Duration> {
+        self.pending.iter().map(|p| p.next_attempt.saturating_duration_since(now)).min()
+    }
+
+    // One line per pending save with its most recent error
+    pub fn summary(&self) -> String {
+        self.pending
+            .iter()
+            .map(|p| format!("{}: {}", p.target.label(), p.last_error))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn retry_delay(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    FIRST_RETRY_DELAY.saturating_mul(1 << doublings).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A clock that only moves when told to
+    struct FakeClock(Instant);
+
+    impl FakeClock {
+        fn now(&self) -> Instant {
+            self.0
+        }
+
+        fn advance(&mut self, by: Duration) {
+            self.0 += by;
+        }
+    }
+
+    // Fails its first `failures` writes, like a file held open by a scan
+    struct FailingWriter {
+        failures: u32,
+        attempts: u32,
+    }
+
+    impl FailingWriter {
+        fn write(&mut self) -> Result<(), String> {
+            self.attempts += 1;
+            if self.attempts <= self.failures {
+                Err(format!("locked (attempt {})", self.attempts))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    // Runs the due saves the way the app does, recording each outcome
+    fn run_due(queue: &mut SaveRetryQueue, writer: &mut FailingWriter, now: Instant) {
+        for target in queue.due(now) {
+            match writer.write() {
+                Ok(()) => queue.succeeded(target),
+                Err(e) => queue.failed(target, e, now),
+            }
+        }
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=8).map(|n| retry_delay(n).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 16, 32, 60, 60, 60]);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn a_failed_save_is_retried_only_once_due() {
+        let mut clock = FakeClock(Instant::now());
+        let mut queue = SaveRetryQueue::default();
+        queue.failed(SaveTarget::Mapper, "locked".to_string(), clock.now());
+
+        assert_eq!(queue.next_retry_in(clock.now()), Some(FIRST_RETRY_DELAY));
+        clock.advance(Duration::from_secs(1));
+        assert!(queue.due(clock.now()).is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(queue.due(clock.now()), [SaveTarget::Mapper]);
+    }
+
+    #[test]
+    fn retries_back_off_until_the_writer_succeeds() {
+        let mut clock = FakeClock(Instant::now());
+        let started = clock.now();
+        let mut queue = SaveRetryQueue::default();
+        let mut writer = FailingWriter { failures: 3, attempts: 0 };
+
+        // The first attempt is the save itself
+        if let Err(e) = writer.write() {
+            queue.failed(SaveTarget::ModList, e, clock.now());
+        }
+        while let Some(wait) = queue.next_retry_in(clock.now()) {
+            clock.advance(wait);
+            run_due(&mut queue, &mut writer, clock.now());
+        }
+
+        assert_eq!(writer.attempts, 4);
+        assert!(!queue.is_pending());
+        // 2 + 4 + 8 seconds of backoff
+        assert_eq!(clock.now() - started, Duration::from_secs(14));
+    }
+
+    #[test]
+    fn retry_now_makes_every_pending_save_due() {
+        let clock = FakeClock(Instant::now());
+        let mut queue = SaveRetryQueue::default();
+        queue.failed(SaveTarget::ModList, "locked".to_string(), clock.now());
+        queue.failed(SaveTarget::Mapper, "in use".to_string(), clock.now());
+        queue.failed(SaveTarget::Mapper, "still in use".to_string(), clock.now());
+
+        queue.retry_now(clock.now());
+        assert_eq!(queue.due(clock.now()), [SaveTarget::ModList, SaveTarget::Mapper]);
+        assert_eq!(queue.next_retry_in(clock.now()), Some(Duration::ZERO));
+        assert_eq!(queue.summary(), "mod list: locked\nmapper: still in use");
+    }
+
+    #[test]
+    fn targets_clear_independently() {
+        let now = Instant::now();
+        let mut queue = SaveRetryQueue::default();
+        queue.failed(SaveTarget::ModList, "locked".to_string(), now);
+        queue.failed(SaveTarget::Mapper, "locked".to_string(), now);
+
+        queue.succeeded(SaveTarget::ModList);
+        assert!(!queue.contains(SaveTarget::ModList));
+        assert!(queue.contains(SaveTarget::Mapper));
+        queue.succeeded(SaveTarget::Mapper);
+        assert_eq!(queue.next_retry_in(now), None);
+    }
+}
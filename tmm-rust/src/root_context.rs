@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::composite_mapper::FileCoverageStats;
+use crate::removal_audit::RemovalAudit;
+use crate::utils;
+
+// Everything tied to one game installation: its paths, the caches computed
+// from its files, and the folder TMM keeps for it under the data dir. A new
+// context is built whenever the root changes, so nothing read or computed for
+// one installation is ever used for, or written over, another.
+#[derive(Default)]
+pub struct RootContext {
+    pub root_dir: PathBuf,
+    pub client_dir: PathBuf,
+    // Folder under root_dir holding the mapper, COOKED_PC_DIR unless this
+    // client keeps it elsewhere; TMM's own files are stored there too
+    pub cooked_pc_name: String,
+    pub cooked_pc_dir: PathBuf,
+    pub mods_dir: PathBuf,
+    pub composite_mapper_path: PathBuf,
+    pub backup_composite_mapper_path: PathBuf,
+    pub game_config_path: PathBuf,
+    // <data dir>/roots/<root_key>; empty when there is no data dir
+    pub cache_dir: PathBuf,
+
+    // Computed on request in the Stats window, it stats every container
+    pub coverage_stats: Option<Vec<FileCoverageStats>>,
+    // Hex dump of the selected mod's first bytes, keyed by (mod id, file name).
+    // None when the file couldn't be read.
+    pub raw_preview: Option<((u64, String), Option<String>)>,
+    pub package_resolution: Option<PackageResolution>,
+    // Read when the Storage window needs it and dropped whenever the mod list
+    // changes, so the window doesn't stat every file each frame
+    pub storage_scan: Option<StorageScan>,
+    // Mapper entries removed while working on this root
+    pub removal_audit: RemovalAudit,
+}
+
+// Composite name each of the selected mod's packages resolves to in the
+// active mapper, None where it doesn't. Keyed by (mod id, mapper generation,
+// hash of the mod's object paths): only a change to the mapper's entries or to
+// the mod's packages changes which paths resolve.
+pub struct PackageResolution {
+    pub key: (u64, u64, u64),
+    pub composite_names: Vec<Option<String>>,
+}
+
+pub struct StorageScan {
+    // Size of each listed mod's file by mod ID, 0 when it is missing
+    pub sizes: HashMap<u64, u64>,
+    // Mods whose source file is still there to reinstall from
+    pub restorable: HashSet<u64>,
+}
+
+impl RootContext {
+    pub fn new(root_dir: &Path, cooked_pc_name: &str, data_dir: Option<&Path>) -> Self {
+        let cooked_pc_dir = root_dir.join(cooked_pc_name);
+        let mods_dir = if cooked_pc_name == crate::COOKED_PC_DIR {
+            root_dir.join(crate::MODS_STORAGE_DIR)
+        } else {
+            cooked_pc_dir.clone()
+        };
+        let cache_dir = data_dir
+            .map(|dir| dir.join("roots").join(root_key(root_dir)))
+            .unwrap_or_default();
+        Self {
+            root_dir: root_dir.to_path_buf(),
+            client_dir: root_dir.parent().unwrap_or(Path::new("")).to_path_buf(),
+            composite_mapper_path: cooked_pc_dir.join(crate::COMPOSITE_MAPPER_FILE),
+            cooked_pc_name: cooked_pc_name.to_string(),
+            cooked_pc_dir,
+            backup_composite_mapper_path: mods_dir.join(crate::BACKUP_COMPOSITE_MAPPER_FILE),
+            game_config_path: mods_dir.join(crate::GAME_CONFIG_FILE),
+            mods_dir,
+            removal_audit: RemovalAudit::open(&cache_dir),
+            cache_dir,
+            ..Default::default()
+        }
+    }
+
+    // Creates the cache folder with a note of which installation it belongs to
+    pub fn prepare_cache_dir(&self) -> std::io::Result<()> {
+        if self.cache_dir.as_os_str().is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.cache_dir)?;
+        fs::write(self.cache_dir.join("root.txt"), canonical_root(&self.root_dir))
+    }
+}
+
+// Stable name for a root: the same folder reached through a different spelling
+// or symlink maps to the same key
+pub fn root_key(root_dir: &Path) -> String {
+    utils::sha256_hex(canonical_root(root_dir).as_bytes())[..16].to_string()
+}
+
+fn canonical_root(root_dir: &Path) -> String {
+    let path = fs::canonicalize(root_dir).unwrap_or_else(|_| root_dir.to_path_buf());
+    let path = path.to_string_lossy();
+    // Windows paths are case-insensitive
+    if cfg!(target_os = "windows") {
+        path.to_lowercase()
+    } else {
+        path.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite_mapper::CompositeEntry;
+    use tempfile::TempDir;
+
+    // A data folder and two clients, each with an S1Game folder
+    fn client_dirs() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("data")).unwrap();
+        for root in ["ClientA/S1Game", "ClientB/S1Game"] {
+            fs::create_dir_all(dir.path().join(root)).unwrap();
+        }
+        dir
+    }
+
+    fn context(dir: &TempDir, client: &str) -> RootContext {
+        let root = dir.path().join(client).join("S1Game");
+        let context = RootContext::new(&root, crate::COOKED_PC_DIR, Some(&dir.path().join("data")));
+        context.prepare_cache_dir().unwrap();
+        context
+    }
+
+    fn removed(name: &str) -> CompositeEntry {
+        CompositeEntry {
+            filename: "S1Data".to_string(),
+            object_path: format!("Test.{}", name),
+            composite_name: name.to_string(),
+            offset: 0,
+            size: 10,
+        }
+    }
+
+    #[test]
+    fn each_root_gets_its_own_cache_folder() {
+        let dir = client_dirs();
+        let (a, b) = (context(&dir, "ClientA"), context(&dir, "ClientB"));
+
+        assert_ne!(a.cache_dir, b.cache_dir);
+        assert!(a.cache_dir.starts_with(dir.path().join("data").join("roots")));
+        assert_ne!(a.mods_dir, b.mods_dir);
+        assert_eq!(fs::read_to_string(a.cache_dir.join("root.txt")).unwrap(), canonical_root(&a.root_dir));
+        assert_eq!(fs::read_to_string(b.cache_dir.join("root.txt")).unwrap(), canonical_root(&b.root_dir));
+    }
+
+    #[test]
+    fn another_spelling_of_a_root_shares_its_key() {
+        let dir = client_dirs();
+        let root = dir.path().join("ClientA").join("S1Game");
+        let respelled = dir.path().join("ClientA").join(".").join("..").join("ClientA").join("S1Game");
+
+        assert_eq!(root_key(&root), root_key(&respelled));
+        assert_ne!(root_key(&root), root_key(&dir.path().join("ClientB").join("S1Game")));
+    }
+
+    #[test]
+    fn journals_never_cross_roots() {
+        let dir = client_dirs();
+        let mut a = context(&dir, "ClientA");
+        let mut b = context(&dir, "ClientB");
+        a.removal_audit.record(removed("a_0001"), "Mod A");
+        b.removal_audit.record(removed("b_0001"), "Mod B");
+
+        // Reopening each root, as after a crash, recovers only its own entries
+        let a_again = context(&dir, "ClientA");
+        let b_again = context(&dir, "ClientB");
+        let names = |c: &RootContext| -> Vec<String> {
+            c.removal_audit.entries.iter().map(|r| r.entry.composite_name.clone()).collect()
+        };
+        assert_eq!(names(&a_again), ["a_0001"]);
+        assert_eq!(names(&b_again), ["b_0001"]);
+
+        // A clean close of one root leaves the other's journal in place
+        b.removal_audit.close();
+        assert!(!b.cache_dir.join(crate::removal_audit::REMOVAL_JOURNAL_FILE).exists());
+        assert!(a.cache_dir.join(crate::removal_audit::REMOVAL_JOURNAL_FILE).exists());
+        assert_eq!(context(&dir, "ClientA").removal_audit.recovered_count(), 1);
+    }
+}
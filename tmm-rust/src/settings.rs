@@ -0,0 +1,182 @@
+use anyhow::Result;
+use bincode::config;
+use bincode::{decode_from_slice, encode_to_vec, Decode, Encode};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Clone, Encode, Decode, Serialize, Deserialize)]
+pub struct InstallStats {
+    pub total_mods_installed: u64,
+    pub total_mods_removed: u64,
+    pub total_applies: u64,
+    pub total_tera_launches_detected: u64,
+    pub first_run: SystemTime,
+}
+
+impl Default for InstallStats {
+    fn default() -> Self {
+        Self {
+            total_mods_installed: 0,
+            total_mods_removed: 0,
+            total_applies: 0,
+            total_tera_launches_detected: 0,
+            first_run: SystemTime::now(),
+        }
+    }
+}
+
+// What to do with the mapper when TERA closes in "Wait for TERA" mode
+#[derive(Clone, Copy, Default, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub enum OnTeraClose {
+    #[default]
+    RestoreVanilla,
+    KeepMods,
+    ApplyChanges,
+}
+
+impl OnTeraClose {
+    pub const ALL: [OnTeraClose; 3] = [Self::RestoreVanilla, Self::KeepMods, Self::ApplyChanges];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::RestoreVanilla => "Restore vanilla",
+            Self::KeepMods => "Keep mods",
+            Self::ApplyChanges => "Apply changes",
+        }
+    }
+}
+
+// When mods are written to the mapper
+#[derive(Clone, Copy, Default, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+pub enum ApplyMode {
+    // On startup and whenever the selection changes
+    #[default]
+    Immediate,
+    // When TERA starts; OnTeraClose decides what happens when it closes
+    WaitForTera,
+    // Like Immediate, but nothing ever restores the mapper on its own
+    Persistent,
+}
+
+impl ApplyMode {
+    pub const ALL: [ApplyMode; 3] = [Self::Immediate, Self::WaitForTera, Self::Persistent];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Immediate => "Apply immediately",
+            Self::WaitForTera => "Wait for TERA",
+            Self::Persistent => "Persistent",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Immediate => "Applies mods on startup and whenever you change the selection.",
+            Self::WaitForTera => "Applies mods when TERA starts and handles the mapper as set under \"When TERA closes\".",
+            Self::Persistent => "Keeps mods applied permanently. The mapper is never restored unless you press Restore.",
+        }
+    }
+}
+
+// Files in mods_dir matching these are never picked up as mods
+pub const DEFAULT_EXCLUSION_PATTERNS: [&str; 3] = ["*.bak", "*.part", "* - Copy.gpk"];
+
+pub fn default_exclusion_patterns() -> Vec<String> {
+    DEFAULT_EXCLUSION_PATTERNS.iter().map(|p| p.to_string()).collect()
+}
+
+// Settings as exported to JSON for moving between machines. Every field is
+// optional on import so hand-edited or older files only change what they list.
+// Usage stats stay behind since they describe this machine.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortableSettings {
+    pub root_dir: Option<PathBuf>,
+    // Superseded by apply_mode; still read from older exports
+    pub wait_for_tera: Option<bool>,
+    pub apply_mode: Option<ApplyMode>,
+    pub safe_apply: Option<bool>,
+    pub monitor_tera: Option<bool>,
+    pub min_backup_entries: Option<usize>,
+    pub on_tera_close: Option<OnTeraClose>,
+    pub show_details_panel: Option<bool>,
+    pub show_log_panel: Option<bool>,
+    pub details_panel_width: Option<f32>,
+    pub log_panel_height: Option<f32>,
+    pub tooltip_enabled: Option<bool>,
+    pub tooltip_delay_ms: Option<u64>,
+    pub exclusion_patterns: Option<Vec<String>>,
+    pub write_checksum_sidecar: Option<bool>,
+    pub double_click_toggle: Option<bool>,
+    pub show_hex_offsets: Option<bool>,
+    pub advanced_mode: Option<bool>,
+    pub discord_presence: Option<bool>,
+    pub human_readable_config: Option<bool>,
+    pub event_hook: Option<String>,
+    pub max_container_len: Option<usize>,
+    pub tera_state_debounce_ms: Option<u64>,
+    pub show_patches_column: Option<bool>,
+    pub show_category_column: Option<bool>,
+    pub show_quick_panel: Option<bool>,
+    pub delete_files_on_remove: Option<bool>,
+    pub allow_headerless_gpk: Option<bool>,
+    pub discord_client_id: Option<String>,
+}
+
+// settings.toml: the portable settings plus this machine's usage stats, for
+// reading or fixing the config by hand.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HumanConfig {
+    #[serde(flatten)]
+    pub settings: PortableSettings,
+    pub install_stats: Option<InstallStats>,
+    pub persistent_risk_accepted: Option<bool>,
+    // Mapper folder chosen for each root directory, as (root, folder)
+    pub mapper_dirs: Option<Vec<(String, String)>>,
+    // Where the quick panel was last dragged to, left unset until it is moved
+    pub quick_panel_pos: Option<(f32, f32)>,
+    // TMM version that wrote the file
+    pub tmm_version: Option<String>,
+}
+
+// settings.bin is a flat sequence of bincode values. New settings are only ever
+// appended, so a file written by an older version simply ends early and the
+// missing values fall back to their defaults.
+pub struct SettingsReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SettingsReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn read<T: Decode<()>>(&mut self) -> Result<T> {
+        let (value, read): (T, usize) = decode_from_slice(&self.buf[self.pos..], config::standard())?;
+        self.pos += read;
+        Ok(value)
+    }
+
+    pub fn read_or_default<T: Decode<()> + Default>(&mut self) -> T {
+        self.read().unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+pub struct SettingsWriter {
+    buf: Vec<u8>,
+}
+
+impl SettingsWriter {
+    pub fn write<T: Encode>(&mut self, value: &T) -> Result<()> {
+        self.buf.extend(encode_to_vec(value, config::standard())?);
+        Ok(())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
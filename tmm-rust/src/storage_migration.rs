@@ -0,0 +1,270 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::composite_mapper::{self, CompositeMapperFile};
+use crate::mod_model::{self, ModEntry, ModFile};
+use crate::utils;
+use crate::versioned_io::WrittenBy;
+
+// Stamp next to ModList.mods recording which storage layout the files are in.
+// A folder under the root with a mod list but no stamp is from before it.
+pub const LAYOUT_FILE: &str = "ModList.layout";
+pub const LAYOUT_VERSION: u32 = 1;
+// Written to the new folder before anything is moved and kept afterwards as
+// the record of the move
+pub const JOURNAL_FILE: &str = "ModList.migration";
+
+// A move of TMM's files from one storage folder to another. Each step can be
+// run again: files already moved are skipped, and the mod list, which names
+// everything else, goes last. Until `done` is set the journal is picked up
+// again on the next start.
+#[derive(Serialize, Deserialize)]
+pub struct Journal {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    // (file name in `from`, file name in `to`) of each listed mod. The names
+    // differ only where `to` already held a different file by that name.
+    pub files: Vec<(String, String)>,
+    // Unix seconds
+    pub started_at: u64,
+    pub done: bool,
+}
+
+// Storage layout of a folder, 0 when it has no stamp
+pub fn read_stamp(dir: &Path) -> u32 {
+    fs::read_to_string(dir.join(LAYOUT_FILE))
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn write_stamp(dir: &Path) -> Result<()> {
+    fs::write(dir.join(LAYOUT_FILE), format!("{}\n", LAYOUT_VERSION))?;
+    Ok(())
+}
+
+impl Journal {
+    pub fn read(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(JOURNAL_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&text).with_context(|| format!("{} is damaged", path.display()))?))
+    }
+
+    // Written to a temporary file first, so a crash leaves the old journal
+    fn write(&self) -> Result<()> {
+        let path = self.to.join(JOURNAL_FILE);
+        let temp = composite_mapper::temp_path(&path);
+        fs::write(&temp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    // Plans moving the files listed in `from`'s mod list to `to` and records
+    // the plan before anything is moved
+    pub fn start(from: &Path, to: &Path) -> Result<Self> {
+        let list = read_mod_list(&from.join(crate::GAME_CONFIG_FILE))?;
+        let mut taken: HashSet<String> = list.iter().map(|m| m.file.to_lowercase()).collect();
+        let mut files = Vec::new();
+        for m in list.iter().filter(|m| from.join(&m.file).is_file()) {
+            let dest = to.join(&m.file);
+            let new_name = if dest.exists() && !same_contents(&from.join(&m.file), &dest)? {
+                let stem = utils::strip_gpk_ext(&m.file);
+                (2..)
+                    .map(|n| format!("{}_{}.{}", stem, n, utils::GPK_EXTENSION))
+                    .find(|name| !to.join(name).exists() && !taken.contains(&name.to_lowercase()))
+                    .expect("some numbered name is free")
+            } else {
+                m.file.clone()
+            };
+            taken.insert(new_name.to_lowercase());
+            files.push((m.file.clone(), new_name));
+        }
+        let journal = Self {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            files,
+            started_at: utils::unix_now(),
+            done: false,
+        };
+        journal.write()?;
+        Ok(journal)
+    }
+
+    // Moves the mod files and the clean backup, rewrites the mod list into the
+    // new folder, points the mappers at renamed containers, then marks the
+    // journal done. Returns the file names that changed.
+    pub fn run(&mut self, mappers: &[&Path]) -> Result<Vec<(String, String)>> {
+        for (old, new) in &self.files {
+            move_file(&self.from.join(old), &self.to.join(new))?;
+        }
+        // The backup only moves into a folder without one of its own
+        let backup = (self.from.join(crate::BACKUP_COMPOSITE_MAPPER_FILE), self.to.join(crate::BACKUP_COMPOSITE_MAPPER_FILE));
+        if !backup.1.exists() || same_contents(&backup.0, &backup.1)? {
+            move_file(&backup.0, &backup.1)?;
+        }
+
+        let renamed: Vec<(String, String)> = self.files.iter().filter(|(old, new)| old != new).cloned().collect();
+        let old_list = self.from.join(crate::GAME_CONFIG_FILE);
+        // Still there means the rewritten list may not be complete yet
+        if old_list.exists() {
+            let mut cfg = mod_model::read_game_config(&mut File::open(&old_list)?)?;
+            for m in &mut cfg.mods {
+                self.rewrite_entry(m);
+            }
+            let new_list = self.to.join(crate::GAME_CONFIG_FILE);
+            let temp = composite_mapper::temp_path(&new_list);
+            mod_model::write_game_config(&cfg, &mut File::create(&temp)?)?;
+            fs::rename(&temp, &new_list)?;
+            fs::remove_file(&old_list)?;
+        }
+
+        // Containers that followed their file to a new name, with the objects
+        // the mod patches
+        let mods = read_mod_list(&self.to.join(crate::GAME_CONFIG_FILE))?;
+        let containers: Vec<RenamedContainer> = renamed
+            .iter()
+            .filter_map(|(old, new)| {
+                let m = mods.iter().find(|m| m.file == *new)?;
+                let new_stem = utils::strip_gpk_ext(new);
+                m.effective_container().eq_ignore_ascii_case(new_stem).then(|| RenamedContainer {
+                    old: utils::strip_gpk_ext(old).to_string(),
+                    new: new_stem.to_string(),
+                    object_paths: object_paths(&self.to.join(new), m),
+                })
+            })
+            .collect();
+        for mapper in mappers.iter().filter(|p| p.is_file()) {
+            rename_containers(mapper, &containers)?;
+        }
+
+        fs::remove_file(self.from.join(LAYOUT_FILE)).ok();
+        self.done = true;
+        self.write()?;
+        Ok(renamed)
+    }
+
+    // Follows a renamed file, its container when that was the file's name, and
+    // a source path that pointed into the old folder
+    fn rewrite_entry(&self, m: &mut ModEntry) {
+        if let Some((old, new)) = self.files.iter().find(|(old, new)| old != new && m.file == *old) {
+            let (old_stem, new_stem) = (utils::strip_gpk_ext(old), utils::strip_gpk_ext(new));
+            if m.container_override.eq_ignore_ascii_case(old_stem) {
+                m.container_override = new_stem.to_string();
+            } else if m.container_override.is_empty() && m.mod_file.container.eq_ignore_ascii_case(old_stem) {
+                m.mod_file.container = new_stem.to_string();
+            }
+            m.file = new.clone();
+        }
+        let source = Path::new(&m.source_path);
+        if let Ok(rest) = source.strip_prefix(&self.from) {
+            let name = rest.to_string_lossy();
+            let name = self.files.iter().find(|(old, _)| *old == name).map_or(name.as_ref(), |(_, new)| new.as_str());
+            m.source_path = self.to.join(name).to_string_lossy().into_owned();
+        }
+    }
+}
+
+// Mod list in `path`, refused when a newer TMM wrote it since rewriting it
+// would drop what this version doesn't know
+fn read_mod_list(path: &Path) -> Result<Vec<ModEntry>> {
+    let cfg = mod_model::read_game_config(&mut File::open(path)?)?;
+    if let Some(warning) = WrittenBy::check(cfg.written_by.as_deref()).warning(crate::GAME_CONFIG_FILE) {
+        anyhow::bail!(warning);
+    }
+    Ok(cfg.mods)
+}
+
+fn same_contents(a: &Path, b: &Path) -> Result<bool> {
+    Ok(fs::metadata(a)?.len() == fs::metadata(b)?.len() && fs::read(a)? == fs::read(b)?)
+}
+
+// Moves `from` to `to` unless an earlier run already did. Copied rather than
+// renamed across drives; the copy is complete before the original goes, and a
+// rerun after a crash in between finds both identical and drops the original.
+pub fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if !from.exists() {
+        return Ok(());
+    }
+    if to.exists() {
+        if !same_contents(from, to)? {
+            anyhow::bail!("{} and {} both exist and differ", from.display(), to.display());
+        }
+        fs::remove_file(from)?;
+        return Ok(());
+    }
+    if fs::rename(from, to).is_err() {
+        let temp = composite_mapper::temp_path(to);
+        fs::copy(from, &temp)?;
+        fs::rename(&temp, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+// Objects a mod patches: the packages in its .gpk, or for a raw file the
+// targets its .tmod declared
+fn object_paths(gpk: &Path, m: &ModEntry) -> Vec<String> {
+    let mut mod_file = ModFile::default();
+    let read = File::open(gpk).map_err(anyhow::Error::from).and_then(|mut f| mod_model::read_mod_file(&mut f, &mut mod_file));
+    if read.is_ok() && !mod_file.packages.is_empty() {
+        mod_file.packages.into_iter().map(|p| p.object_path).collect()
+    } else {
+        m.target_paths.clone()
+    }
+}
+
+struct RenamedContainer {
+    old: String,
+    new: String,
+    object_paths: Vec<String>,
+}
+
+// Points the mapper entries of a renamed mod at its new container. Entries of
+// a game package that shares the old name are left alone.
+fn rename_containers(mapper: &Path, containers: &[RenamedContainer]) -> Result<()> {
+    if containers.is_empty() {
+        return Ok(());
+    }
+    let mut map = CompositeMapperFile::new(mapper.to_path_buf())?;
+    let mut changed = false;
+    for entry in map.composite_map.values_mut() {
+        let renamed = containers.iter().find(|c| {
+            entry.filename.eq_ignore_ascii_case(&c.old)
+                && c.object_paths.iter().any(|p| utils::incomplete_paths_equal(&entry.object_path, p))
+        });
+        if let Some(c) = renamed {
+            entry.filename = c.new.clone();
+            changed = true;
+        }
+    }
+    if changed {
+        map.entries_changed();
+        map.save(mapper)?;
+    }
+    Ok(())
+}
+
+// Folders under `root_dir` other than `storage` that hold a mod list in an
+// older layout: TMM's files from before the storage folder changed. A folder
+// stamped with the current layout is another client folder's storage.
+pub fn find_old_storage(root_dir: &Path, storage: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = fs::read_dir(root_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|dir| {
+            dir.join(crate::GAME_CONFIG_FILE).is_file()
+                && read_stamp(dir) < LAYOUT_VERSION
+                && !utils::same_file(dir, storage)
+        })
+        .collect();
+    found.sort();
+    found
+}
@@ -2,7 +2,26 @@ use egui::{Ui};
 use egui_extras::{Column, TableBuilder}; // <--- Add this import
 
 
-use crate::TmmApp;
+use crate::discord;
+use crate::mod_model::ModEntry;
+use crate::mod_state::{ChangeOrigin, ChangeOutcome};
+use crate::perf;
+use crate::settings::{self, ApplyMode, OnTeraClose};
+use crate::utils;
+use crate::{ConflictChoice, OverwriteChoice, TmmApp};
+
+/// The window can't be resized below this, in points.
+pub const MIN_WINDOW_SIZE: [f32; 2] = [800.0, 600.0];
+/// Below this available width the action buttons wrap onto two rows.
+const TWO_ROW_BUTTONS_WIDTH: f32 = 900.0;
+/// Below this available width the Author column is hidden from the mod table.
+const HIDE_AUTHOR_COLUMN_WIDTH: f32 = 800.0;
+/// Further toggles of the same row within this window are ignored.
+const ROW_TOGGLE_COOLDOWN: std::time::Duration = std::time::Duration::from_millis(500);
+/// Object paths listed in a mod row tooltip before the rest are summarised.
+const TOOLTIP_MAX_PATHS: usize = 10;
+/// Hover text for actions blocked while the loaded mapper has too few entries.
+const MAPPER_TOO_SMALL_HINT: &str = "The loaded mapper has too few entries; see the warning above.";
 
 pub fn root_dir_ui(app: &mut TmmApp, ui: &mut Ui) {
     ui.horizontal(|ui| {
@@ -17,30 +36,288 @@ pub fn root_dir_ui(app: &mut TmmApp, ui: &mut Ui) {
 
         if ui.button(button_text).clicked() {
             if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                // Pending edits belong to the old root's mod list
+                app.flush_mod_list();
                 app.root_dir = path;
+                // An explicit choice replaces the --root session override
+                app.root_override_active = false;
+                app.root_dir_unvalidated = false;
                 // Reset initialization so the update loop reloads everything with the new path
                 app.initialized = false;
             }
         }
+
+        if app.root_override_active {
+            ui.label(egui::RichText::new("(session override)").weak());
+        }
+        if app.root_dir_unvalidated {
+            ui.colored_label(egui::Color32::YELLOW, "(imported, not found on this machine)");
+        }
+        if app.initialized && app.root.cooked_pc_name != crate::COOKED_PC_DIR {
+            ui.label(egui::RichText::new(format!("(mapper in {})", app.root.cooked_pc_name)).weak())
+                .on_hover_text(app.root.cooked_pc_dir.display().to_string());
+        }
     });
 }
 
+// Row order of the mod table; a sorted view never changes the list order itself
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ModSort {
+    #[default]
+    ListOrder,
+    ApplyOrder,
+    // Most patched objects first
+    Patches,
+    // Grouped by category in table order, list order within each
+    Category,
+}
+
+// Entries of a mod row's right-click menu
+enum RowMenuAction {
+    Enable,
+    Disable,
+    Remove,
+    OpenLocation,
+    CopyFileName,
+    Solo,
+    SelectConflicting,
+    SelectSameContainer,
+    SetManualApplyOnly(bool),
+    ExportTmod,
+}
+
+// The slice of app state the mod table renders from
+struct ModListView<'a> {
+    mods: &'a [ModEntry],
+    // Indices into `mods`, in the order shown
+    rows: &'a [usize],
+    apply_order: &'a [Option<usize>],
+    selected: &'a [usize],
+    sort: ModSort,
+    show_author: bool,
+    show_patches: bool,
+    show_category: bool,
+    mapper_too_small: bool,
+    // None when row tooltips are off
+    tooltip_delay: Option<std::time::Duration>,
+    double_click_toggle: bool,
+    // Row to scroll into view after the selection moved by keyboard
+    scroll_to: Option<usize>,
+    hovered_row: Option<(usize, std::time::Instant)>,
+}
+
+// What the user did in the mod table this frame, applied to the app afterwards
+struct ModListInput {
+    // (row, wanted enabled state)
+    changes: Vec<(usize, bool)>,
+    row_action: Option<(usize, RowMenuAction)>,
+    clicked_row: Option<(usize, egui::Modifiers)>,
+    sort: Option<ModSort>,
+    hovered_row: Option<(usize, std::time::Instant)>,
+}
+
 pub fn mod_list_ui(app: &mut TmmApp, ui: &mut Ui) {
-    let mut changes = Vec::new();
+    let show_author = ui.available_width() >= HIDE_AUTHOR_COLUMN_WIDTH;
+    let apply_order = app.apply_order();
+    let categories: Vec<String> = app.categories.categories().into_iter().map(String::from).collect();
+
+    category_filter_ui(app, ui, &categories);
+    let mut rows: Vec<usize> = (0..app.mod_list.len())
+        .filter(|&i| {
+            app.category_filter
+                .as_deref()
+                .is_none_or(|c| app.mod_list[i].effective_category() == c)
+        })
+        .collect();
+    match app.mod_sort {
+        ModSort::ListOrder => {}
+        // Enabled mods in application order, then disabled ones in list order
+        ModSort::ApplyOrder => rows.sort_by_key(|&i| (apply_order[i].is_none(), apply_order[i], i)),
+        ModSort::Patches => {
+            rows.sort_by_key(|&i| (std::cmp::Reverse(app.mod_list[i].mod_file.packages.len()), i))
+        }
+        ModSort::Category => rows.sort_by_key(|&i| {
+            let category = app.mod_list[i].effective_category();
+            (categories.iter().position(|c| c == category).unwrap_or(usize::MAX), i)
+        }),
+    }
+
+    // Up/Down walk the selection through the rows as shown; Alt+arrows move
+    // mods instead (handle_shortcuts)
+    let mut scroll_to_focus = false;
+    if !ui.ctx().wants_keyboard_input() {
+        let (up, down, shift) = ui.input(|i| {
+            let plain = !i.modifiers.alt && !i.modifiers.command;
+            (
+                plain && i.key_pressed(egui::Key::ArrowUp),
+                plain && i.key_pressed(egui::Key::ArrowDown),
+                i.modifiers.shift,
+            )
+        });
+        if up || down {
+            app.step_selection(down, shift, &rows);
+            scroll_to_focus = true;
+        }
+    }
+
+    let view = ModListView {
+        mods: &app.mod_list,
+        rows: &rows,
+        apply_order: &apply_order,
+        selected: &app.selected_mods,
+        sort: app.mod_sort,
+        show_author,
+        show_patches: app.show_patches_column,
+        show_category: app.show_category_column,
+        mapper_too_small: app.mapper_too_small,
+        tooltip_delay: app.tooltip_enabled.then(|| std::time::Duration::from_millis(app.tooltip_delay_ms)),
+        double_click_toggle: app.double_click_toggle,
+        scroll_to: app.selection_focus.filter(|_| scroll_to_focus),
+        hovered_row: app.hovered_row,
+    };
+    let ModListInput { mut changes, row_action, clicked_row, sort, hovered_row } = mod_table_ui(ui, view);
+    app.hovered_row = hovered_row;
+    if let Some(sort) = sort {
+        app.mod_sort = sort;
+    }
+
+    if let Some((i, modifiers)) = clicked_row {
+        app.click_row(i, modifiers, &rows);
+    }
+    // Enable, Disable, Remove and Copy act on the selection
+    if let Some((i, RowMenuAction::Enable | RowMenuAction::Disable | RowMenuAction::Remove | RowMenuAction::CopyFileName)) =
+        row_action
+    {
+        app.select_for_context_menu(i);
+    }
+    match row_action {
+        Some((_, RowMenuAction::Enable)) => app.enable_selected_mods(),
+        Some((_, RowMenuAction::Disable)) => app.disable_selected_mods(),
+        // Through the usual confirmation
+        Some((_, RowMenuAction::Remove)) => app.confirm_remove = true,
+        Some((i, RowMenuAction::OpenLocation)) => app.open_mod_location(i),
+        Some((_, RowMenuAction::CopyFileName)) => {
+            let names: Vec<&str> = app.selected_mods.iter().map(|&j| app.mod_list[j].file.as_str()).collect();
+            ui.ctx().copy_text(names.join("\n"));
+            app.status_msg = match names.as_slice() {
+                [name] => format!("Copied {}", name),
+                _ => format!("Copied {} file names.", names.len()),
+            };
+        }
+        Some((i, RowMenuAction::Solo)) => {
+            app.enter_solo(i);
+            return;
+        }
+        Some((i, RowMenuAction::SelectConflicting)) => {
+            let packages = app.mod_list[i].mod_file.packages.clone();
+            let conflicting = app.find_conflicting_indices(&packages);
+            app.add_to_selection(i, conflicting);
+        }
+        Some((i, RowMenuAction::ExportTmod)) => app.export_tmod_dialog(i),
+        Some((i, RowMenuAction::SetManualApplyOnly(manual_only))) => {
+            app.set_manual_apply_only(i, manual_only);
+        }
+        Some((i, RowMenuAction::SelectSameContainer)) => {
+            // Group by the container the mapper actually points at, so an
+            // override moves a mod into the group it patches
+            let container = app.mod_list[i].effective_container();
+            let same: Vec<usize> = (0..app.mod_list.len())
+                .filter(|&j| app.mod_list[j].effective_container().eq_ignore_ascii_case(container))
+                .collect();
+            app.add_to_selection(i, same);
+        }
+        None => {}
+    }
+    // Enabled flags stay frozen while solo mode owns the mapper
+    if app.solo_mod.is_some() && !changes.is_empty() {
+        app.status_msg = "Exit solo mode before changing mods.".to_string();
+        return;
+    }
+
+    // Toggles that don't change anything, or that hit a row again within
+    // ROW_TOGGLE_COOLDOWN (a triple-click), are dropped before any work is done
+    let now = std::time::Instant::now();
+    changes.retain(|&(i, enabled)| {
+        let m = &app.mod_list[i];
+        let cooling_down = app
+            .last_row_toggle
+            .get(&m.id)
+            .is_some_and(|&t| now.duration_since(t) < ROW_TOGGLE_COOLDOWN);
+        if m.enabled == enabled || m.locked || cooling_down {
+            return false;
+        }
+        app.last_row_toggle.insert(m.id, now);
+        true
+    });
+
+    if changes.is_empty() {
+        return;
+    }
+    for &(i, enabled) in &changes {
+        let id = app.mod_list[i].id;
+        match app.set_mod_state(id, enabled, ChangeOrigin::Checkbox) {
+            Ok(outcome) if outcome.awaiting_choice => {
+                app.status_msg = format!("{} conflicts with enabled mods; choose which to keep.", app.mod_list[i].mod_file.mod_name);
+            }
+            Ok(outcome) if !outcome.blocked_by.is_empty() => {
+                let names = app.mod_names(&outcome.blocked_by).join(", ");
+                app.status_msg = format!("{} left off: it conflicts with locked {}.", app.mod_list[i].mod_file.mod_name, names);
+            }
+            Ok(ChangeOutcome { kept_off_by: Some(winner), .. }) => {
+                let names = app.mod_names(&[id, winner]);
+                app.status_msg = format!("{} left off: {} was chosen over it before.", names[0], names[1]);
+            }
+            Ok(outcome) => {
+                let verb = if enabled { "Enabled" } else { "Disabled" };
+                let mut status = format!("{}: {}", verb, app.mod_list[i].mod_file.mod_name);
+                if !outcome.disabled_conflicts.is_empty() {
+                    let names = app.mod_names(&outcome.disabled_conflicts).join(", ");
+                    status.push_str(&format!(", turned off conflicting {}", names));
+                }
+                if outcome.deferred {
+                    status.push_str(" (pending TERA launch)");
+                }
+                app.status_msg = status;
+            }
+            Err(e) => {
+                let action = if enabled { "Turn on" } else { "Turn off" };
+                app.error_msg = Some(format!("{} failed: {:?}", action, e));
+            }
+        }
+    }
+    app.commit_mod_states();
+}
 
-    // Define table styling
+fn mod_table_ui(ui: &mut Ui, view: ModListView) -> ModListInput {
+    let mut input = ModListInput {
+        changes: Vec::new(),
+        row_action: None,
+        clicked_row: None,
+        sort: None,
+        hovered_row: view.hovered_row,
+    };
     let row_height = 30.0;
-    let _text_height = egui::FontId::default().size;
-    
+    let (show_author, show_patches, show_category) = (view.show_author, view.show_patches, view.show_category);
+
     egui::ScrollArea::vertical().show(ui, |ui| {
         // Create the table
-        TableBuilder::new(ui)
+        let mut table = TableBuilder::new(ui)
             .striped(true)
             .resizable(false)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
             .column(Column::auto())
-            .column(Column::initial(200.0).at_least(100.0))
-            .column(Column::initial(150.0).at_least(60.0))
+            .column(Column::auto())
+            .column(Column::initial(200.0).at_least(100.0));
+        if show_author {
+            table = table.column(Column::initial(150.0).at_least(60.0));
+        }
+        if show_patches {
+            table = table.column(Column::auto());
+        }
+        if show_category {
+            table = table.column(Column::auto());
+        }
+        table
             .column(Column::remainder())
             .header(20.0, |mut header| {
                 header.col(|ui| { ui.with_layout(
@@ -50,12 +327,31 @@ pub fn mod_list_ui(app: &mut TmmApp, ui: &mut Ui) {
                     },
                 );  
             });
+                header.col(|ui| {
+                    sort_header_ui(ui, view.sort, &mut input.sort, "Order", ModSort::ApplyOrder, "⬆")
+                        .on_hover_text("Position in the apply sequence. Click to sort.");
+                });
                 header.col(|ui| { ui.strong("Name"); });
-                header.col(|ui| { ui.strong("Author"); });
+                if show_author {
+                    header.col(|ui| { ui.strong("Author"); });
+                }
+                if show_patches {
+                    header.col(|ui| {
+                        sort_header_ui(ui, view.sort, &mut input.sort, "Patches", ModSort::Patches, "⬇")
+                            .on_hover_text("Game objects the mod replaces. Click to sort, most first.");
+                    });
+                }
+                if show_category {
+                    header.col(|ui| {
+                        sort_header_ui(ui, view.sort, &mut input.sort, "Category", ModSort::Category, "⬆")
+                            .on_hover_text("Inferred from the mod's object paths; change it in Mod Details. Click to group.");
+                    });
+                }
                 header.col(|ui| { ui.strong("File"); });
             })
             .body(|mut body| {
-            for (i, m) in app.mod_list.iter_mut().enumerate() {
+            for &i in view.rows {
+            let m = &view.mods[i];
 
             // --- Allocate row rect & response ---
             let ui = body.ui_mut();
@@ -78,7 +374,7 @@ pub fn mod_list_ui(app: &mut TmmApp, ui: &mut Ui) {
             let hover_color = visuals.widgets.hovered.bg_fill;
 
             // --- Paint background (BEFORE widgets) ---
-            if app.selected_mods.contains(&i) {
+            if view.selected.contains(&i) {
                 ui.painter().rect_filled(row_rect, 4.0, selection_color);
             } else if row_response.hovered() {
                 ui.painter().rect_filled(row_rect, 4.0, hover_color);
@@ -92,154 +388,2033 @@ pub fn mod_list_ui(app: &mut TmmApp, ui: &mut Ui) {
                         egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
                         |ui| {
                             let mut enabled = m.enabled;
-                            if ui.checkbox(&mut enabled, "").changed() {
-                                m.enabled = enabled;
-                                changes.push((i, enabled));
+                            // Mods can still be turned off on an unusable mapper
+                            let blocked = view.mapper_too_small && !m.enabled;
+                            let checkbox = ui
+                                .add_enabled(!m.locked && !blocked, egui::Checkbox::new(&mut enabled, ""))
+                                .on_disabled_hover_text(if m.locked { "Locked" } else { MAPPER_TOO_SMALL_HINT });
+                            if checkbox.changed() {
+                                input.changes.push((i, enabled));
                             }
                         },
                     );
                 });
 
-                row.col(|ui| { ui.label(&m.mod_file.mod_name); });
-                row.col(|ui| { ui.label(&m.mod_file.mod_author); });
+                row.col(|ui| {
+                    match view.apply_order[i] {
+                        Some(order) => ui.label(order.to_string()),
+                        None => ui.weak("—"),
+                    };
+                });
+                row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(&m.mod_file.mod_name);
+                        if m.manual_apply_only {
+                            ui.small("✋").on_hover_text(
+                                "Manual apply only: left out of the automatic apply when TERA starts. Apply by hand to include it.",
+                            );
+                        }
+                    });
+                });
+                if show_author {
+                    row.col(|ui| { ui.label(m.display_author()); });
+                }
+                if show_patches {
+                    row.col(|ui| {
+                        match m.mod_file.packages.len() {
+                            0 => ui.weak("?").on_hover_text(
+                                "No game objects resolved yet. Raw mods are matched by file name; rename the file to match the game package it replaces.",
+                            ),
+                            n => ui.label(n.to_string()),
+                        };
+                    });
+                }
+                if show_category {
+                    row.col(|ui| {
+                        if m.category_override.is_empty() {
+                            ui.label(m.effective_category());
+                        } else {
+                            ui.label(egui::RichText::new(m.effective_category()).italics())
+                                .on_hover_text(format!("Set by you; inferred: {}", m.category));
+                        }
+                    });
+                }
                 row.col(|ui| { ui.label(&m.file); });
             });
 
-            // --- Single click = selection ---
-            if row_response.clicked() {
-                if app.selected_mods.contains(&i) {
-                    app.selected_mods.retain(|&x| x != i);
+            // --- Hover tooltip with the mod's object paths ---
+            if let (Some(delay), true) = (view.tooltip_delay, row_response.hovered()) {
+                let since = match input.hovered_row {
+                    Some((row, since)) if row == i => since,
+                    _ => {
+                        let now = std::time::Instant::now();
+                        input.hovered_row = Some((i, now));
+                        now
+                    }
+                };
+                let elapsed = since.elapsed();
+                if elapsed >= delay {
+                    egui::show_tooltip_at_pointer(
+                        &row_response.ctx,
+                        row_response.layer_id,
+                        row_response.id.with("object_paths"),
+                        |ui| {
+                            let packages = &m.mod_file.packages;
+                            for p in packages.iter().take(TOOLTIP_MAX_PATHS) {
+                                ui.label(egui::RichText::new(&p.object_path).monospace());
+                            }
+                            if packages.len() > TOOLTIP_MAX_PATHS {
+                                ui.label(format!("...and {} more", packages.len() - TOOLTIP_MAX_PATHS));
+                            }
+                        },
+                    );
                 } else {
-                    app.selected_mods.push(i);
+                    row_response.ctx.request_repaint_after(delay.saturating_sub(elapsed));
                 }
+            } else if input.hovered_row.is_some_and(|(row, _)| row == i) {
+                input.hovered_row = None;
+            }
+
+            // --- Click = selection, with Ctrl toggling and Shift selecting a range ---
+            if row_response.clicked() {
+                input.clicked_row = Some((i, row_response.ctx.input(|i| i.modifiers)));
+            }
+            if view.scroll_to == Some(i) {
+                row_response.scroll_to_me(None);
             }
 
             // --- Double click = toggle enable ---
-            if row_response.double_clicked() {
-                let new_state = !m.enabled;
-                m.enabled = new_state;
-                changes.push((i, new_state));
+            if view.double_click_toggle && row_response.double_clicked() {
+                input.changes.push((i, !m.enabled));
             }
+
+            row_response.context_menu(|ui| {
+                for (label, action) in [
+                    ("Enable", RowMenuAction::Enable),
+                    ("Disable", RowMenuAction::Disable),
+                    ("Remove", RowMenuAction::Remove),
+                ] {
+                    if ui.button(label).clicked() {
+                        input.row_action = Some((i, action));
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                for (label, action) in [
+                    ("Open file location", RowMenuAction::OpenLocation),
+                    ("Copy file name", RowMenuAction::CopyFileName),
+                ] {
+                    if ui.button(label).clicked() {
+                        input.row_action = Some((i, action));
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                for (label, action) in [
+                    ("Apply only this mod (solo)", RowMenuAction::Solo),
+                    ("Select all conflicting mods", RowMenuAction::SelectConflicting),
+                    ("Select all in same container group", RowMenuAction::SelectSameContainer),
+                    ("Export as .tmod…", RowMenuAction::ExportTmod),
+                ] {
+                    if ui.button(label).clicked() {
+                        input.row_action = Some((i, action));
+                        ui.close_menu();
+                    }
+                }
+                let mut manual_only = m.manual_apply_only;
+                if ui
+                    .checkbox(&mut manual_only, "Manual apply only")
+                    .on_hover_text("Leave this mod out when TERA's launch triggers an apply.")
+                    .clicked()
+                {
+                    input.row_action = Some((i, RowMenuAction::SetManualApplyOnly(manual_only)));
+                    ui.close_menu();
+                }
+            });
         }
     })
     });
+    input
+}
 
-    // Apply Logic based on changes (identical to previous implementation)
-    if !changes.is_empty() {
-        for &(i, enabled) in &changes {
-            // Determine if we are enabling or disabling
-            if enabled {
-                // Use safe enable for conflict handling
-                if let Err(e) = app.enable_mod_safely(i) {
-                    app.error_msg = Some(format!("Turn on failed: {:?}", e));
-                } else {
-                    app.status_msg = format!("Enabled: {}", app.mod_list[i].mod_file.mod_name);
+pub fn tera_status_ui(app: &mut TmmApp, ui: &mut Ui) {
+    ui.add_space(8.0);
+    match app.tera_launch_time.filter(|_| app.tera_running) {
+        Some(launched) => {
+            let secs = launched.elapsed().as_secs();
+            ui.label(egui::RichText::new("●").color(egui::Color32::GREEN));
+            ui.label(format!("TERA Running ({:02}:{:02})", secs / 60, secs % 60));
+
+            // Wake up for the next tick of the timer even without input
+            let now = std::time::Instant::now();
+            if now.duration_since(app.last_status_update) >= std::time::Duration::from_secs(1) {
+                app.last_status_update = now;
+            }
+            let next_tick = std::time::Duration::from_secs(1)
+                .saturating_sub(now.duration_since(app.last_status_update));
+            ui.ctx().request_repaint_after(next_tick);
+        }
+        None => {
+            ui.label(egui::RichText::new("●").color(egui::Color32::GRAY));
+            let label = ui.label("TERA Not Running");
+            if !app.is_watching_tera() {
+                label.on_hover_text("TERA monitoring is off");
+            }
+        }
+    }
+}
+
+pub fn solo_banner_ui(app: &mut TmmApp, ui: &mut Ui) {
+    if app.solo_mod.is_none() {
+        return;
+    }
+    let name = app.solo_mod_name().unwrap_or("(removed mod)");
+    let text = format!("SOLO: {} — intent not saved", name);
+    let mut exit = false;
+    egui::Frame::none()
+        .fill(egui::Color32::from_rgb(120, 60, 0))
+        .inner_margin(6.0)
+        .rounding(4.0)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(text).strong().color(egui::Color32::WHITE));
+                exit = ui.button("Exit Solo").clicked();
+            });
+        });
+    if exit {
+        app.exit_solo();
+    }
+}
+
+// Shown while the loaded mapper has fewer entries than min_backup_entries.
+// Inspection (details, stats, preview) stays available.
+pub fn mapper_too_small_ui(app: &mut TmmApp, ui: &mut Ui) {
+    if !app.mapper_too_small {
+        return;
+    }
+    let mut open_settings = false;
+    egui::Frame::none()
+        .fill(egui::Color32::from_rgb(110, 20, 20))
+        .inner_margin(6.0)
+        .rounding(4.0)
+        .show(ui, |ui| {
+            ui.label(
+                egui::RichText::new(format!(
+                    "CompositePackageMapper.dat has only {} entries (expected at least {}). Applying and enabling mods is disabled.",
+                    app.composite_map.len(),
+                    app.min_backup_entries
+                ))
+                .strong()
+                .color(egui::Color32::WHITE),
+            );
+            ui.label(egui::RichText::new("Likely causes:").color(egui::Color32::WHITE));
+            for cause in [
+                "The wrong folder was selected; pick the TERA root that contains Client\\S1Game\\CookedPC.",
+                "The client is only partly downloaded or was interrupted while patching; repair or finish the download.",
+                "A private-server client that uses a different mapper format.",
+            ] {
+                ui.label(egui::RichText::new(format!("• {}", cause)).color(egui::Color32::WHITE));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Reload").clicked() {
+                    app.initialize();
                 }
-            } else {
-                // Disable logic (conflicts don't matter here, just turn off)
-                app.mod_list[i].enabled = false;
-                if !app.wait_for_tera {
-                    let mod_file = app.mod_list[i].mod_file.clone();
-                    if let Err(e) = app.turn_off_mod(&mod_file, false) {
-                        app.error_msg = Some(format!("Turn off failed: {:?}", e));
-                    } else {
-                        app.status_msg = format!("Disabled: {}", app.mod_list[i].mod_file.mod_name);
+                open_settings = ui
+                    .button("Settings…")
+                    .on_hover_text("The threshold is the minimum backup entries setting.")
+                    .clicked();
+            });
+        });
+    if open_settings {
+        app.show_settings = true;
+    }
+}
+
+fn category_filter_ui(app: &mut TmmApp, ui: &mut Ui, categories: &[String]) {
+    ui.horizontal(|ui| {
+        ui.label("Category:");
+        egui::ComboBox::from_id_salt("category_filter")
+            .selected_text(app.category_filter.as_deref().unwrap_or("All"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut app.category_filter, None, "All");
+                for category in categories {
+                    ui.selectable_value(&mut app.category_filter, Some(category.clone()), category);
+                }
+            });
+    });
+}
+
+// Clickable column header; clicking the active sort goes back to list order
+// A clicked header sets `clicked` to its sort, or back to list order when it
+// is the current one
+fn sort_header_ui(
+    ui: &mut Ui,
+    current: ModSort,
+    clicked: &mut Option<ModSort>,
+    title: &str,
+    sort: ModSort,
+    arrow: &str,
+) -> egui::Response {
+    let text = if current == sort {
+        format!("{} {}", title, arrow)
+    } else {
+        title.to_string()
+    };
+    let response = ui.add(egui::Label::new(egui::RichText::new(text).strong()).sense(egui::Sense::click()));
+    if response.clicked() {
+        *clicked = Some(if current == sort { ModSort::ListOrder } else { sort });
+    }
+    response
+}
+
+pub fn buttons_ui(app: &mut TmmApp, ui: &mut Ui) {
+    if ui.available_width() < TWO_ROW_BUTTONS_WIDTH {
+        ui.horizontal(|ui| primary_buttons_ui(app, ui));
+        ui.horizontal(|ui| secondary_buttons_ui(app, ui));
+    } else {
+        ui.horizontal(|ui| {
+            primary_buttons_ui(app, ui);
+            secondary_buttons_ui(app, ui);
+        });
+    }
+}
+
+// Add / Remove / On / Off
+fn primary_buttons_ui(app: &mut TmmApp, ui: &mut Ui) {
+    if ui.button("Add").clicked() {
+        if let Some(paths) = rfd::FileDialog::new()
+            .add_filter("TERA packages", &[utils::GPK_EXTENSION, utils::TMOD_EXTENSION])
+            .pick_files()
+        {
+            app.install_mods(&paths);
+        }
+    }
+    if ui
+        .button("Add Folder")
+        .on_hover_text("Installs every .gpk in a folder and its subfolders.")
+        .clicked()
+    {
+        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+            app.install_folder(&folder);
+        }
+    }
+    if ui.button("Remove").clicked() && !app.selected_mods.is_empty() {
+        app.confirm_remove = true;
+    }
+    if ui
+        .add_enabled(!app.mapper_too_small, egui::Button::new("On"))
+        .on_disabled_hover_text(MAPPER_TOO_SMALL_HINT)
+        .clicked()
+    {
+        app.enable_selected_mods();
+    }
+    if ui.button("Off").clicked() {
+        app.disable_selected_mods();
+    }
+    if ui.button("Edit").clicked() {
+        app.open_mod_details();
+    }
+    if ui
+        .add_enabled(app.selected_mods.len() > 1, egui::Button::new("Edit selected…"))
+        .clicked()
+    {
+        app.open_batch_edit();
+    }
+    // Rows only move visibly when the list is shown in its own order
+    let can_move = !app.selected_mods.is_empty() && app.mod_sort == ModSort::ListOrder;
+    for (arrow, up) in [("⬆", true), ("⬇", false)] {
+        if ui
+            .add_enabled(can_move, egui::Button::new(arrow))
+            .on_hover_text("Moves the selected mods in the list. Mods lower in the list are applied later and win where they change the same objects.")
+            .on_disabled_hover_text("Select mods with the list in list order to move them.")
+            .clicked()
+        {
+            app.move_selected_mods(up);
+        }
+    }
+}
+
+// Restore / Apply Now / apply mode
+fn secondary_buttons_ui(app: &mut TmmApp, ui: &mut Ui) {
+    if ui.button("Restore").clicked() {
+        app.restore_button();
+    }
+
+    if ui
+        .add_enabled(!app.mapper_too_small, egui::Button::new("Apply Now"))
+        .on_disabled_hover_text(MAPPER_TOO_SMALL_HINT)
+        .clicked()
+    {
+        app.save_button();
+    }
+
+    let can_launch = !app.defer_until_tera() && !app.tera_running && !app.mapper_too_small;
+    if ui
+        .add_enabled(can_launch, egui::Button::new("Apply and Launch"))
+        .on_hover_text("Applies your current mod selection and starts TERA.")
+        .on_disabled_hover_text(if app.mapper_too_small {
+            MAPPER_TOO_SMALL_HINT
+        } else {
+            "Unavailable while TERA is running or Wait for TERA is on."
+        })
+        .clicked()
+    {
+        app.apply_and_launch();
+    }
+
+    if ui.button("Preview Apply").clicked() {
+        app.preview_apply = Some(app.check_all_object_paths_resolvable());
+    }
+
+    let watching = app.is_watching_tera();
+    let mut chosen = app.apply_mode;
+    egui::ComboBox::from_id_salt("apply_mode")
+        .selected_text(app.apply_mode.label())
+        .show_ui(ui, |ui| {
+            for mode in ApplyMode::ALL {
+                // Waiting needs someone watching for the launch
+                let enabled = watching || mode != ApplyMode::WaitForTera;
+                ui.add_enabled_ui(enabled, |ui| {
+                    ui.selectable_value(&mut chosen, mode, mode.label())
+                        .on_hover_text(mode.description());
+                });
+            }
+        })
+        .response
+        .on_hover_text(app.apply_mode.description());
+    app.set_apply_mode(chosen);
+}
+
+pub fn stats_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.show_stats {
+        return;
+    }
+    let hot: Vec<(String, u32, String)> = app
+        .composite_map
+        .get_hot_entries(10)
+        .into_iter()
+        .map(|(name, count)| (name.to_string(), count, app.patched_by_label(name)))
+        .collect();
+    let stats = &app.install_stats;
+    let days = stats
+        .first_run
+        .elapsed()
+        .map(|d| d.as_secs() / 86_400)
+        .unwrap_or(0);
+
+    egui::Window::new("Stats")
+        .open(&mut app.show_stats)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("stats_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Mods installed:");
+                ui.label(stats.total_mods_installed.to_string());
+                ui.end_row();
+                ui.label("Mods removed:");
+                ui.label(stats.total_mods_removed.to_string());
+                ui.end_row();
+                ui.label("Applies:");
+                ui.label(stats.total_applies.to_string());
+                ui.end_row();
+                ui.label("TERA launches detected:");
+                ui.label(stats.total_tera_launches_detected.to_string());
+                ui.end_row();
+                ui.label("Days since first run:");
+                ui.label(days.to_string());
+                ui.end_row();
+                ui.label("Mapper file size:");
+                ui.label(format!("{} KB", app.composite_map.source_size / 1024));
+                ui.end_row();
+            });
+
+            if !hot.is_empty() {
+                ui.separator();
+                ui.strong("Most patched entries");
+                egui::Grid::new("hot_entries_grid").num_columns(3).show(ui, |ui| {
+                    for (name, count, patched_by) in &hot {
+                        ui.label(name);
+                        ui.label(count.to_string());
+                        ui.weak(patched_by);
+                        ui.end_row();
+                    }
+                });
+            }
+
+            ui.separator();
+            egui::CollapsingHeader::new("File coverage").id_salt("file_coverage").show(ui, |ui| {
+                ui.label(
+                    egui::RichText::new("Share of each package the mapper references. Low coverage can mean unused assets or missing entries.")
+                        .small()
+                        .weak(),
+                );
+                if ui.button("Compute").clicked() {
+                    app.root.coverage_stats = Some(app.composite_map.get_file_coverage_stats(&app.root.mods_dir));
+                }
+                if let Some(coverage) = &app.root.coverage_stats {
+                    egui::ScrollArea::vertical().id_salt("file_coverage_scroll").max_height(200.0).show(ui, |ui| {
+                        egui::Grid::new("file_coverage_grid").num_columns(3).striped(true).show(ui, |ui| {
+                            for c in coverage {
+                                ui.label(&c.filename);
+                                ui.label(utils::format_bytes(c.file_size_bytes));
+                                if c.file_size_bytes == 0 {
+                                    ui.weak("missing");
+                                } else {
+                                    ui.label(format!("{:.1}%", c.coverage_percent));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+            });
+        });
+}
+
+pub fn mod_details_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let Some(idx) = app.edit_mod.filter(|&i| i < app.mod_list.len()) else {
+        app.edit_mod = None;
+        return;
+    };
+
+    let mut open = true;
+    let mut save = false;
+    let mut rename = false;
+    let mut apply_offsets = false;
+    let mut set_category = None;
+    let mut add_after = None;
+    let mut remove_after = None;
+    let after_names = app.mod_names(&app.mod_list[idx].apply_after);
+    let wins_names = app.mod_names(&app.mod_list[idx].conflict_wins);
+    let mut forget_win = None;
+    egui::Window::new("Mod Details")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let m = &app.mod_list[idx];
+            egui::Grid::new("mod_details_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Name:");
+                ui.label(&m.mod_file.mod_name);
+                ui.end_row();
+                ui.label("File:");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut app.edit_file_name);
+                    rename = ui.button("Rename file…").clicked();
+                });
+                ui.end_row();
+                ui.label("Container:");
+                ui.label(&m.mod_file.container);
+                ui.end_row();
+                ui.label("Container override:");
+                ui.text_edit_singleline(&mut app.edit_container_override)
+                    .on_hover_text("Leave empty to use the container from the mod file.");
+                ui.end_row();
+                ui.label("Category:");
+                let inferred = format!("Inferred ({})", m.category);
+                let mut category = m.category_override.clone();
+                egui::ComboBox::from_id_salt("mod_details_category")
+                    .selected_text(if category.is_empty() { inferred.as_str() } else { category.as_str() })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut category, String::new(), inferred.as_str());
+                        for c in app.categories.categories() {
+                            ui.selectable_value(&mut category, c.to_string(), c);
+                        }
+                    });
+                if category != m.category_override {
+                    set_category = Some(category);
+                }
+                ui.end_row();
+                ui.label("Apply after:")
+                    .on_hover_text("Applied after these mods whatever the list order, so it wins where they change the same objects.");
+                ui.vertical(|ui| {
+                    for (&id, name) in m.apply_after.iter().zip(&after_names) {
+                        ui.horizontal(|ui| {
+                            ui.label(name);
+                            if ui.small_button("✖").on_hover_text("Remove constraint").clicked() {
+                                remove_after = Some(id);
+                            }
+                        });
                     }
-                    app.composite_map.dirty = true;
+                    egui::ComboBox::from_id_salt("mod_details_apply_after")
+                        .selected_text("Add…")
+                        .show_ui(ui, |ui| {
+                            for other in app.mod_list.iter().filter(|o| o.id != m.id && !m.apply_after.contains(&o.id)) {
+                                if ui.selectable_label(false, &other.mod_file.mod_name).clicked() {
+                                    add_after = Some(other.id);
+                                }
+                            }
+                        });
+                });
+                ui.end_row();
+                if !m.conflict_wins.is_empty() {
+                    ui.label("Chosen over:")
+                        .on_hover_text("Remembered conflict choices: this mod is kept on over these without asking.");
+                    ui.vertical(|ui| {
+                        for (&id, name) in m.conflict_wins.iter().zip(&wins_names) {
+                            ui.horizontal(|ui| {
+                                ui.label(name);
+                                if ui.small_button("✖").on_hover_text("Forget this choice").clicked() {
+                                    forget_win = Some(id);
+                                }
+                            });
+                        }
+                    });
+                    ui.end_row();
                 }
+            });
+            if ui.button("Save").clicked() {
+                save = true;
             }
+
+            if app.advanced_mode && !app.edit_package_offsets.is_empty() {
+                ui.separator();
+                ui.label("Package offsets (decimal or 0x hex):");
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    egui::Grid::new("edit_package_offsets_grid").num_columns(3).show(ui, |ui| {
+                        let packages = &app.mod_list[idx].mod_file.packages;
+                        for (p, (offset, size)) in packages.iter().zip(app.edit_package_offsets.iter_mut()) {
+                            ui.label(&p.object_path);
+                            ui.add(egui::TextEdit::singleline(offset).desired_width(90.0));
+                            ui.add(egui::TextEdit::singleline(size).desired_width(90.0));
+                            ui.end_row();
+                        }
+                    });
+                });
+                apply_offsets = ui
+                    .button("Apply offsets")
+                    .on_hover_text("Lasts until the mod list is reloaded from the .gpk.")
+                    .clicked();
+            }
+        });
+
+    if let Some(category) = set_category {
+        app.set_category_override(idx, &category);
+    }
+
+    if let Some(other) = add_after {
+        if let Err(e) = app.add_apply_after(idx, other) {
+            app.error_msg = Some(format!("Apply-after constraint failed: {}", e));
         }
+    }
+    if let Some(other) = remove_after {
+        app.remove_apply_after(idx, other);
+    }
+    if let Some(other) = forget_win {
+        app.forget_conflict_win(idx, other);
+    }
 
-        app.update_mods_list(app.mod_list.clone());
+    if apply_offsets {
+        let edits = app.edit_package_offsets.clone();
+        match app.set_package_offsets(idx, &edits) {
+            Ok(()) => app.status_msg = format!("Updated offsets for {}", app.mod_list[idx].mod_file.mod_name),
+            Err(e) => app.error_msg = Some(format!("Offset edit failed: {}", e)),
+        }
+    }
 
-        if !app.wait_for_tera {
-            app.commit_changes();
-        } else {
-            let status = if changes[0].1 { "Enabled" } else { "Disabled" };
-            app.status_msg = format!("{} (pending TERA launch).", status);
+    if rename {
+        let new_name = app.edit_file_name.clone();
+        match app.rename_mod_file(idx, &new_name) {
+            Ok(()) => {
+                app.edit_file_name = app.mod_list[idx].file.clone();
+                app.status_msg = format!("Renamed file to {}", app.mod_list[idx].file);
+            }
+            Err(e) => app.error_msg = Some(format!("Rename failed: {}", e)),
+        }
+    }
+
+    if save {
+        let container_override = app.edit_container_override.clone();
+        match app.set_container_override(idx, &container_override) {
+            Ok(()) => {
+                app.status_msg = format!("Updated container for {}", app.mod_list[idx].mod_file.mod_name);
+                open = false;
+            }
+            Err(e) => app.error_msg = Some(format!("Container override failed: {}", e)),
         }
     }
+
+    if !open {
+        app.edit_mod = None;
+    }
 }
 
-pub fn buttons_ui(app: &mut TmmApp, ui: &mut Ui) {
-    ui.horizontal(|ui| {
-        if ui.button("Add").clicked() {
-            if let Some(path) = rfd::FileDialog::new().pick_file() {
-                app.install_mod(&path, true);
+pub fn settings_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let mut changed = false;
+    let mut export_settings = false;
+    // Some(true) for the backup mapper
+    let mut export_mapper_text = None;
+    let mut import_mapper_text = false;
+    let mut import_fingerprints = false;
+    let mut import_settings = false;
+
+    egui::Window::new("Settings")
+        .open(&mut app.show_settings)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.add_enabled_ui(!app.cli.no_watch, |ui| {
+                changed |= ui.checkbox(&mut app.monitor_tera, "Monitor TERA process").changed();
+            })
+            .response
+            .on_disabled_hover_text("Disabled for this session by --no-watch.");
+            ui.label(
+                egui::RichText::new("Applies mods when TERA starts and restores them when it closes.")
+                    .small()
+                    .weak(),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Treat TERA as closed after:");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut app.tera_state_debounce_ms).range(0..=60_000).suffix(" ms"))
+                    .on_hover_text("How long tera.exe must be gone before mods are restored, so a quick restart after a crash doesn't trigger a restore and re-apply.")
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("When TERA closes:");
+                egui::ComboBox::from_id_salt("on_tera_close")
+                    .selected_text(app.on_tera_close.label())
+                    .show_ui(ui, |ui| {
+                        for option in OnTeraClose::ALL {
+                            changed |= ui
+                                .selectable_value(&mut app.on_tera_close, option, option.label())
+                                .changed();
+                        }
+                    });
+            });
+            changed |= ui.checkbox(&mut app.safe_apply, "Safe Apply").changed();
+            ui.label(
+                egui::RichText::new("Creates a backup before each apply. Slower but safer.")
+                    .small()
+                    .weak(),
+            );
+            changed |= ui.checkbox(&mut app.double_click_toggle, "Double-click toggles mods").changed();
+            changed |= ui.checkbox(&mut app.discord_presence, "Discord Rich Presence").changed();
+            if app.discord_presence {
+                ui.horizontal(|ui| {
+                    ui.label("Application ID:");
+                    changed |= ui.text_edit_singleline(&mut app.discord_client_id).changed();
+                });
+                let id = app.discord_client_id.trim();
+                let hint = if id.is_empty() {
+                    "TMM has no Discord application of its own. Create one on the Discord developer portal and paste its Application ID here; nothing is shown until then."
+                } else if !discord::is_valid_client_id(id) {
+                    "An Application ID is a number of up to 20 digits."
+                } else {
+                    "Your status shows under the name of that application."
+                };
+                ui.label(egui::RichText::new(hint).small().weak());
+            }
+            changed |= ui.checkbox(&mut app.show_hex_offsets, "Show offsets in hex").changed();
+            changed |= ui.checkbox(&mut app.advanced_mode, "Advanced mode").changed();
+            ui.label(
+                egui::RichText::new("Allows editing package offsets in Mod Details.")
+                    .small()
+                    .weak(),
+            );
+            changed |= ui.checkbox(&mut app.allow_headerless_gpk, "Accept .gpk files without a package signature").changed();
+            ui.label(
+                egui::RichText::new("Off by default: a file that isn't an Unreal package would be copied into the game folder.")
+                    .small()
+                    .weak(),
+            );
+            changed |= ui.checkbox(&mut app.human_readable_config, "Keep a readable settings.toml").changed();
+            ui.checkbox(&mut app.perf.enabled, "Performance overlay (F12)");
+            changed |= ui.checkbox(&mut app.tooltip_enabled, "Object path tooltips").changed();
+            ui.add_enabled_ui(app.tooltip_enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Tooltip delay (ms):");
+                    changed |= ui
+                        .add(egui::Slider::new(&mut app.tooltip_delay_ms, 0..=2000))
+                        .changed();
+                });
+            });
+            ui.label(
+                egui::RichText::new("Turning tooltips off helps with very large mod lists.")
+                    .small()
+                    .weak(),
+            );
+            changed |= ui.checkbox(&mut app.write_checksum_sidecar, "Write checksum file").changed();
+            ui.label(
+                egui::RichText::new("Saves CompositePackageMapper.dat.tmm.sha256 next to the mapper for external verification.")
+                    .small()
+                    .weak(),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Event hook:");
+                changed |= ui
+                    .add(egui::TextEdit::singleline(&mut app.event_hook).hint_text("command"))
+                    .lost_focus();
+            });
+            ui.label(
+                egui::RichText::new("Runs on mod install, enable, disable, apply and restore with a JSON payload on stdin. {event} is replaced with the event name.")
+                    .small()
+                    .weak(),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Max container name length:");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut app.max_container_len).range(crate::MIN_CONTAINER_LEN..=255))
+                    .changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Minimum backup entries:");
+                changed |= ui
+                    .add(egui::DragValue::new(&mut app.min_backup_entries).range(0..=1_000_000))
+                    .changed();
+            });
+            ui.separator();
+            ui.label("Ignored file patterns (one per line):");
+            let mut patterns = app.exclusion_patterns.join("\n");
+            if ui.add(egui::TextEdit::multiline(&mut patterns).desired_rows(4)).changed() {
+                app.exclusion_patterns = patterns.split('\n').map(str::to_string).collect();
+                changed = true;
+            }
+            ui.label(
+                egui::RichText::new("Matching files in CookedPC are never picked up as mods. * and ? are wildcards.")
+                    .small()
+                    .weak(),
+            );
+            if ui.button("Reset patterns").clicked() {
+                app.exclusion_patterns = settings::default_exclusion_patterns();
+                changed = true;
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                export_settings = ui.button("Export settings").clicked();
+                import_settings = ui.button("Import settings").clicked();
+            });
+            if ui
+                .button("Export Object Paths")
+                .on_hover_text("Writes every object path in the clean mapper to a text file, for modding tools.")
+                .clicked()
+            {
+                if let Some(dest) = rfd::FileDialog::new()
+                    .add_filter("Text", &["txt"])
+                    .set_file_name("object_paths.txt")
+                    .save_file()
+                {
+                    match app.backup_map.export_object_path_list_to_file(&dest) {
+                        Ok(()) => app.status_msg = format!("Exported object paths to {}", dest.display()),
+                        Err(e) => app.error_msg = Some(format!("Export failed: {}", e)),
+                    }
+                }
             }
+            ui.horizontal(|ui| {
+                ui.label("Export mapper (decrypted):");
+                export_mapper_text = ui
+                    .button("Active")
+                    .on_hover_text("The mapper as TMM holds it now, including changes not yet saved.")
+                    .clicked()
+                    .then_some(false);
+                if ui.button("Backup").on_hover_text("The clean mapper mods are applied over.").clicked() {
+                    export_mapper_text = Some(true);
+                }
+            });
+            import_mapper_text = ui
+                .button("Import mapper from text…")
+                .on_hover_text("Replaces the active mapper with an edited export. The next apply rebuilds it from the backup.")
+                .clicked();
+            import_fingerprints = ui
+                .button("Import mapper fingerprints…")
+                .on_hover_text(format!(
+                    "Adds a JSON list of known clean and modded mapper hashes. TMM ships without any; {} are known now.",
+                    app.fingerprints.len()
+                ))
+                .clicked();
+        });
+
+    if changed {
+        if let Err(e) = app.save_app_config() {
+            app.error_msg = Some(format!("Failed to save settings: {}", e));
         }
-        if ui.button("Remove").clicked() && !app.selected_mods.is_empty() {
-            app.selected_mods.sort_unstable_by(|a, b| b.cmp(a));
-            for &idx in &app.selected_mods {
-                app.mod_list.remove(idx);
+    }
+
+    if let Some(backup) = export_mapper_text {
+        app.export_mapper_text_dialog(backup);
+    }
+    if import_mapper_text {
+        app.import_mapper_text_dialog();
+    }
+    if import_fingerprints {
+        app.import_fingerprints_dialog();
+    }
+    if export_settings {
+        if let Some(dest) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("tmm-settings.json")
+            .save_file()
+        {
+            match app.export_settings_json().and_then(|json| Ok(std::fs::write(&dest, json)?)) {
+                Ok(()) => app.status_msg = format!("Exported settings to {}", dest.display()),
+                Err(e) => app.error_msg = Some(format!("Export failed: {}", e)),
             }
-            app.update_mods_list(app.mod_list.clone());
-            app.selected_mods.clear();
-            app.status_msg = "Removed selected mods.".to_string();
         }
-        if ui.button("On").clicked() {
-            let selected = app.selected_mods.clone();
-            if selected.is_empty() {
-                app.status_msg = "No mods selected.".to_string();
+    }
+    if import_settings {
+        if let Some(source) = rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+            let imported = std::fs::read_to_string(&source)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| app.import_settings_json(&json));
+            match imported {
+                Ok(()) => app.status_msg = format!("Imported settings from {}", source.display()),
+                Err(e) => app.error_msg = Some(format!("Import failed: {}", e)),
             }
-            for idx in selected {
-                // Use the new safe method that handles conflicts
-                if let Err(e) = app.enable_mod_safely(idx) {
-                    app.error_msg = Some(format!("Turn on failed: {:?}", e));
-                } else {
-                    app.status_msg = format!("Enabled: {}", app.mod_list[idx].mod_file.mod_name);
+        }
+    }
+}
+
+// (keys, action) pairs shown in the shortcut reference
+const SHORTCUTS: &[(&str, &str)] = &[
+    ("Ctrl+A", "Select all"),
+    ("Delete", "Remove selected"),
+    ("Ctrl+E", "Enable selected"),
+    ("Ctrl+D", "Disable selected"),
+    ("Ctrl+R", "Refresh"),
+    ("F1", "Details"),
+    ("Escape", "Clear selection / close panels"),
+    ("?", "Show this panel"),
+    ("F12", "Performance overlay"),
+    ("Up / Down", "Select previous / next row"),
+    ("Shift+Up / Shift+Down", "Extend selection"),
+    ("Alt+Up / Alt+Down", "Move selected up / down"),
+];
+
+pub fn shortcuts_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    egui::Window::new("Keyboard Shortcuts")
+        .open(&mut app.show_shortcuts)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("shortcuts_grid").num_columns(2).striped(true).show(ui, |ui| {
+                for (keys, action) in SHORTCUTS {
+                    ui.strong(*keys);
+                    ui.label(*action);
+                    ui.end_row();
                 }
+            });
+        });
+}
+
+// Licenses of the crates TMM is built with, kept alongside Cargo.toml
+const THIRD_PARTY_NOTICES: &str = include_str!("../THIRD_PARTY.txt");
+
+pub fn about_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    egui::Window::new("About")
+        .open(&mut app.show_about)
+        .collapsible(false)
+        .default_size([520.0, 420.0])
+        .show(ctx, |ui| {
+            ui.heading("Tera Mod Manager");
+            ui.label(format!("Version {}", env!("CARGO_PKG_VERSION")));
+            ui.separator();
+            ui.strong("Third-party licenses");
+            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                ui.label(egui::RichText::new(THIRD_PARTY_NOTICES).monospace());
+            });
+        });
+}
+
+// The slice of app state the details panel renders from
+pub struct ModDetailsView<'a> {
+    pub entry: &'a ModEntry,
+    pub hex_offsets: bool,
+    // None when the file couldn't be read
+    pub raw_preview: Option<&'a str>,
+    // Per package, the composite name it resolves to in the active mapper
+    pub resolution: &'a [Option<String>],
+    // (position, enabled mods), None when the mod isn't enabled
+    pub apply_position: Option<(usize, usize)>,
+    // Names of the mods it is applied after
+    pub apply_after: Vec<String>,
+}
+
+// "1st", "2nd", "37th", ...
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+pub fn details_panel_ui(ui: &mut Ui, view: Option<ModDetailsView>) {
+    ui.heading("Details");
+    ui.separator();
+    let Some(view) = view else {
+        ui.label("Select a mod to see its details.");
+        return;
+    };
+
+    let m = view.entry;
+    egui::Grid::new("details_panel_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Name:");
+        ui.label(&m.mod_file.mod_name);
+        ui.end_row();
+        ui.label("Author:");
+        ui.label(m.display_author());
+        ui.end_row();
+        if !m.version.is_empty() {
+            ui.label("Version:");
+            ui.label(&m.version);
+            ui.end_row();
+        }
+        if !m.description.is_empty() {
+            ui.label("Description:");
+            ui.label(&m.description);
+            ui.end_row();
+        }
+        ui.label("File:");
+        ui.label(&m.file);
+        ui.end_row();
+        ui.label("Container:");
+        ui.label(m.effective_container());
+        ui.end_row();
+        ui.label("Enabled:");
+        ui.label(if m.enabled { "Yes" } else { "No" });
+        ui.end_row();
+        ui.label("Objects:");
+        ui.label(m.mod_file.packages.len().to_string());
+        ui.end_row();
+        if !m.tags.is_empty() {
+            ui.label("Tags:");
+            ui.label(m.tags.join(", "));
+            ui.end_row();
+        }
+        if !m.exclusive_group.is_empty() {
+            ui.label("Exclusive group:");
+            ui.label(&m.exclusive_group);
+            ui.end_row();
+        }
+        if m.locked {
+            ui.label("Locked:");
+            ui.label("Yes");
+            ui.end_row();
+        }
+        if let Some((position, count)) = view.apply_position {
+            ui.label("Apply order:");
+            ui.label(format!("Applied {} of {}", ordinal(position), count));
+            ui.end_row();
+        }
+        if !view.apply_after.is_empty() {
+            ui.label("Applied after:");
+            ui.label(view.apply_after.join(", "));
+            ui.end_row();
+        }
+    });
+
+    egui::CollapsingHeader::new("Raw preview")
+        .id_salt("details_raw_preview")
+        .show(ui, |ui| match view.raw_preview {
+            Some(dump) => {
+                ui.label(egui::RichText::new(dump).monospace().small());
             }
-            // Commit changes if not waiting
-            if !app.wait_for_tera {
-                app.commit_changes();
-            } else {
-                app.status_msg = format!("{} mods enabled (pending TERA launch).", app.selected_mods.len());
+            None => {
+                ui.weak("File not accessible.");
+            }
+        });
+
+    if m.mod_file.packages.is_empty() {
+        return;
+    }
+    // Unresolved paths are why a mod can apply cleanly and still change nothing in game
+    let unresolved = view.resolution.iter().filter(|r| r.is_none()).count();
+    let header = if unresolved > 0 {
+        egui::RichText::new(format!("Packages ({} unresolved)", unresolved)).color(egui::Color32::ORANGE)
+    } else {
+        egui::RichText::new("Packages")
+    };
+    egui::CollapsingHeader::new(header)
+        .id_salt("details_packages")
+        .show(ui, |ui| {
+            egui::ScrollArea::vertical().id_salt("details_packages_scroll").show(ui, |ui| {
+                egui::Grid::new("details_packages_grid").striped(true).num_columns(4).show(ui, |ui| {
+                    ui.strong("Object");
+                    ui.strong("Offset");
+                    ui.strong("Size");
+                    ui.strong("Mapper");
+                    ui.end_row();
+                    for (i, p) in m.mod_file.packages.iter().enumerate() {
+                        match view.resolution.get(i) {
+                            Some(Some(composite_name)) => {
+                                ui.label(&p.object_path);
+                                ui.monospace(utils::format_offset(p.offset, view.hex_offsets));
+                                ui.monospace(utils::format_offset(p.size, view.hex_offsets));
+                                ui.label("✔").on_hover_text(format!("Resolves to {}", composite_name));
+                            }
+                            _ => {
+                                ui.label(egui::RichText::new(&p.object_path).color(egui::Color32::ORANGE));
+                                ui.monospace(utils::format_offset(p.offset, view.hex_offsets));
+                                ui.monospace(utils::format_offset(p.size, view.hex_offsets));
+                                ui.label(egui::RichText::new("⚠").color(egui::Color32::ORANGE)).on_hover_text(
+                                    "No single entry in the active mapper matches this path, so patching it does nothing.",
+                                );
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+// Mapper entries removed by disabling mods, each with a way back in
+pub fn removed_entries_ui(app: &mut TmmApp, ui: &mut Ui) {
+    let audit = &app.root.removal_audit;
+    if audit.entries.is_empty() {
+        return;
+    }
+    let mut reinsert = None;
+    egui::CollapsingHeader::new(format!("Removed mapper entries ({})", audit.entries.len()))
+        .id_salt("removed_entries")
+        .show(ui, |ui| {
+            egui::ScrollArea::vertical().id_salt("removed_entries_scroll").max_height(120.0).show(ui, |ui| {
+                egui::Grid::new("removed_entries_grid").num_columns(4).striped(true).show(ui, |ui| {
+                    for (i, removed) in audit.entries.iter().enumerate().rev() {
+                        ui.label(egui::RichText::new(&removed.entry.object_path).monospace().small())
+                            .on_hover_text(format!(
+                                "{} in {} at {} ({} bytes)",
+                                removed.entry.composite_name,
+                                removed.entry.filename,
+                                removed.entry.offset,
+                                removed.entry.size
+                            ));
+                        ui.label(&removed.mod_name);
+                        let when = utils::format_utc(removed.removed_at);
+                        if removed.recovered {
+                            ui.label(format!("{} (previous session)", when));
+                        } else {
+                            ui.label(when);
+                        }
+                        if ui.small_button("Re-insert").clicked() {
+                            reinsert = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+    if let Some(i) = reinsert {
+        app.reinsert_removed_entry(i);
+    }
+}
+
+pub fn log_panel_ui(ui: &mut Ui, log: &[String]) {
+    ui.strong("Log");
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            for line in log {
+                ui.label(egui::RichText::new(line).monospace().small());
             }
+        });
+}
+
+pub fn missing_mods_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if app.missing_mods.is_empty() {
+        return;
+    }
+
+    egui::Window::new("Missing Mod Files")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} mods listed in your config are missing from disk:",
+                app.missing_mods.len()
+            ));
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for m in app.mod_list.iter().filter(|m| app.missing_mods.contains(&m.id)) {
+                    ui.label(&m.file);
+                }
+            });
+            ui.label("Remove missing entries?");
+            ui.horizontal(|ui| {
+                if ui.button("Remove all missing").clicked() {
+                    app.remove_missing_mods();
+                }
+                if ui.button("Keep in list").clicked() {
+                    app.missing_mods.clear();
+                }
+                if ui.button("Locate manually").clicked() {
+                    app.locate_missing_mods();
+                }
+            });
+        });
+}
+
+pub fn duplicate_mods_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if app.duplicate_mods.is_empty() {
+        return;
+    }
+    let mut resolve: Option<bool> = None;
+
+    egui::Window::new("Duplicate Mod Entries")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("These entries point at the same file or at files with the same contents:");
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for group in &app.duplicate_mods {
+                    ui.group(|ui| {
+                        for m in app.mod_list.iter().filter(|m| group.contains(&m.id)) {
+                            let state = if m.enabled { "enabled" } else { "disabled" };
+                            ui.label(format!("{} ({}, {} packages)", m.file, state, m.mod_file.packages.len()));
+                        }
+                    });
+                }
+            });
+            ui.label("The first entry of each group is kept.");
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Merge")
+                    .on_hover_text("Keep one entry with the tags and details of all copies")
+                    .clicked()
+                {
+                    resolve = Some(true);
+                }
+                if ui.button("Remove extras").clicked() {
+                    resolve = Some(false);
+                }
+                if ui.button("Keep all").clicked() {
+                    app.duplicate_mods.clear();
+                }
+            });
+        });
+
+    if let Some(merge) = resolve {
+        app.resolve_duplicate_mods(merge);
+    }
+}
+
+pub fn force_disable_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.confirm_force_disable {
+        return;
+    }
+    let mut confirmed = false;
+
+    egui::Window::new("Force Disable All Mods")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("Disables every mod and rewrites CompositePackageMapper.dat from the clean mapper loaded at startup.");
+            ui.label("Use this only when the backup file is missing or corrupt:");
+            ui.label("• The backup file is not restored or checked.");
+            ui.label("• If the backup loaded at startup was already modded, those changes stay in the game.");
+            ui.label("• No safe-apply snapshot is taken.");
+            ui.horizontal(|ui| {
+                if ui.button("Force Disable").clicked() {
+                    confirmed = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    app.confirm_force_disable = false;
+                }
+            });
+        });
+
+    if confirmed {
+        app.confirm_force_disable = false;
+        match app.force_disable_all_without_restore() {
+            Ok(()) => app.status_msg = "All mods force disabled.".to_string(),
+            Err(e) => app.error_msg = Some(format!("Force disable failed: {}", e)),
         }
+    }
+}
+
+// Shown in the header while a failed save waits for its next retry
+pub fn save_retry_ui(app: &mut TmmApp, ui: &mut Ui) {
+    let Some(wait) = app.save_retry.next_retry_in(std::time::Instant::now()) else {
+        return;
+    };
+    let mut retry = false;
+    ui.horizontal(|ui| {
+        ui.label(
+            egui::RichText::new(format!("Save pending — retrying in {} s", wait.as_secs_f32().ceil() as u64))
+                .color(egui::Color32::ORANGE),
+        )
+        .on_hover_text(app.save_retry.summary());
+        retry = ui.button("Retry now").clicked();
+    });
+    if retry {
+        app.save_retry.retry_now(std::time::Instant::now());
+    }
+}
+
+pub fn quit_unsaved_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.confirm_quit_unsaved {
+        return;
+    }
+    // The save went through while the dialog was open
+    if !app.save_retry.is_pending() {
+        app.confirm_quit_unsaved = false;
+        return;
+    }
+    let mut quit = false;
+
+    egui::Window::new("Changes Not Saved")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("Some changes couldn't be saved yet and are lost if you quit now:");
+            ui.label(egui::RichText::new(app.save_retry.summary()).monospace());
+            ui.horizontal(|ui| {
+                quit = ui.button("Quit anyway").clicked();
+                if ui.button("Keep trying").clicked() {
+                    app.confirm_quit_unsaved = false;
+                }
+            });
+        });
+
+    if quit {
+        app.confirm_quit_unsaved = false;
+        app.quit_confirmed = true;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+}
+
+pub fn remove_mods_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.confirm_remove {
+        return;
+    }
+    let mut remove = false;
+    let mut option_changed = false;
+    let count = app.selected_mods.len();
+
+    egui::Window::new("Remove Mods")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!("Remove {} selected mods from the list? Enabled ones are turned off first.", count));
+            option_changed = ui
+                .checkbox(&mut app.delete_files_on_remove, "Also delete their .gpk files")
+                .on_hover_text(app.root.mods_dir.display().to_string())
+                .changed();
+            ui.horizontal(|ui| {
+                remove = ui.button("Remove").clicked();
+                if ui.button("Cancel").clicked() {
+                    app.confirm_remove = false;
+                }
+            });
+        });
+
+    if option_changed {
+        app.save_app_config().ok();
+    }
+    if remove {
+        app.remove_selected_mods();
+    }
+}
+
+pub fn update_mods_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if app.pending_updates.is_empty() {
+        return;
+    }
+    let mut resolved = None;
 
-        if ui.button("Off").clicked() {
-            let selected = app.selected_mods.clone();
-            if selected.is_empty() {
-                app.status_msg = "No mods selected.".to_string();
+    egui::Window::new("Update Installed Mods?")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("These files share a name with an installed mod but have different contents. Updating replaces the installed file and keeps the mod enabled or disabled as it is.");
+            for (i, pending) in app.pending_updates.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(&pending.file)
+                        .on_hover_text(pending.source.display().to_string());
+                    if ui.button("Update").clicked() {
+                        resolved = Some((i, true));
+                    }
+                    if ui.button("Keep installed").clicked() {
+                        resolved = Some((i, false));
+                    }
+                });
             }
-            for idx in selected {
-                app.mod_list[idx].enabled = false;
-                if !app.wait_for_tera {
-                    let mod_file = app.mod_list[idx].mod_file.clone();
-                    if let Err(e) = app.turn_off_mod(&mod_file, false) {
-                        app.error_msg = Some(format!("Turn off failed: {:?}", e));
-                    } else {
-                        app.status_msg = format!("Disabled: {}", app.mod_list[idx].mod_file.mod_name);
+        });
+
+    if let Some((i, update)) = resolved {
+        app.resolve_pending_update(i, update);
+    }
+}
+
+pub fn overwrite_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if app.pending_overwrites.is_empty() {
+        return;
+    }
+    let mut resolved = None;
+
+    egui::Window::new("File Already Exists")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "These files already exist in {} with different contents and aren't in the mod list.",
+                app.root.mods_dir.display()
+            ));
+            for (i, pending) in app.pending_overwrites.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(&pending.file)
+                        .on_hover_text(pending.source.display().to_string());
+                    let overwrite = ui
+                        .add_enabled(!pending.game_file, egui::Button::new("Overwrite"))
+                        .on_disabled_hover_text("This is a game package; overwriting it would break Restore.");
+                    if overwrite.clicked() {
+                        resolved = Some((i, OverwriteChoice::Overwrite));
+                    }
+                    if ui.button(format!("Install as {}", pending.renamed)).clicked() {
+                        resolved = Some((i, OverwriteChoice::Rename));
                     }
-                    app.composite_map.dirty = true;
+                    if ui.button("Cancel").clicked() {
+                        resolved = Some((i, OverwriteChoice::Cancel));
+                    }
+                });
+            }
+        });
+
+    if let Some((i, choice)) = resolved {
+        app.resolve_pending_overwrite(i, choice);
+    }
+}
+
+// A raw GPK whose name matched too much of the mapper. One file at a time;
+// the query is searched again once typing pauses.
+pub fn raw_pick_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let waiting = app.pending_raw_picks.len().saturating_sub(1);
+    let Some(pick) = app.pending_raw_picks.first_mut() else {
+        return;
+    };
+    let mut resolved = None;
+
+    egui::Window::new("Pick Raw Mod Targets")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "{} is not packed with TMM and its name matches too many game objects to guess which it replaces. Narrow the name and tick the objects it replaces.",
+                pick.file_name
+            ));
+            ui.horizontal(|ui| {
+                ui.label("Name:");
+                if ui.text_edit_singleline(&mut pick.query).changed() {
+                    pick.edited_at = Some(std::time::Instant::now());
                 }
+            });
+            if pick.matches.is_empty() {
+                ui.label("No game objects match this name.");
+            } else if pick.truncated {
+                ui.label(format!("Too many matches; showing the closest {}.", pick.matches.len()));
             }
-            app.update_mods_list(app.mod_list.clone());
+            egui::ScrollArea::vertical().max_height(280.0).show(ui, |ui| {
+                for ((object_path, package), picked) in pick.matches.iter().zip(pick.picked.iter_mut()) {
+                    ui.checkbox(picked, object_path).on_hover_text(package);
+                }
+            });
+            if waiting > 0 {
+                ui.label(format!("{} more files wait for their targets.", waiting));
+            }
+            ui.separator();
+            let count = pick.picked.iter().filter(|&&p| p).count();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(count > 0, egui::Button::new(format!("Install with {} selected", count)))
+                    .clicked()
+                {
+                    resolved = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    resolved = Some(false);
+                }
+            });
+        });
 
-            if !app.wait_for_tera {
-                app.commit_changes();
-            } else {
-                app.status_msg = format!("{} mods disabled (pending TERA launch).", app.selected_mods.len());
+    if let Some(install) = resolved {
+        app.resolve_raw_pick(0, install);
+    }
+}
+
+// One conflict at a time; the rest wait their turn
+pub fn conflict_prompt_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let Some(pending) = app.pending_conflicts.first() else {
+        return;
+    };
+    let name = app.mod_names(&[pending.id]).concat();
+    // (name, shared object paths, locked)
+    let others: Vec<(String, &[String], bool)> = pending
+        .conflicts
+        .iter()
+        .map(|(id, paths)| {
+            let locked = app.mod_list.iter().any(|m| m.id == *id && m.locked);
+            (app.mod_names(&[*id]).concat(), paths.as_slice(), locked)
+        })
+        .collect();
+    let blocked = others.iter().any(|(_, _, locked)| *locked);
+    let waiting = app.pending_conflicts.len() - 1;
+    let mut choice = None;
+    let mut remember = app.remember_conflict_choice;
+
+    egui::Window::new("Mod Conflict")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!("{} changes the same game objects as these enabled mods:", name));
+            egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                for (other, paths, locked) in &others {
+                    let other = if *locked { format!("{} (locked)", other) } else { other.clone() };
+                    if paths.is_empty() {
+                        ui.label(format!("{} (same exclusive group)", other));
+                        continue;
+                    }
+                    egui::CollapsingHeader::new(format!("{} ({} shared objects)", other, paths.len()))
+                        .id_salt(("conflict_prompt", other))
+                        .show(ui, |ui| {
+                            for path in paths.iter() {
+                                ui.label(egui::RichText::new(path).monospace());
+                            }
+                        });
+                }
+            });
+            ui.separator();
+            ui.checkbox(&mut remember, "Remember my choice for these mods");
+            ui.horizontal(|ui| {
+                let enable = ui
+                    .add_enabled(!blocked, egui::Button::new(format!("Enable {}", name)))
+                    .on_hover_text("Turns the mods above off.")
+                    .on_disabled_hover_text("Locked mods can't be turned off. Unlock them first.");
+                if enable.clicked() {
+                    choice = Some(ConflictChoice::Enable);
+                }
+                if ui.button("Keep the enabled mods").on_hover_text(format!("{} stays off.", name)).clicked() {
+                    choice = Some(ConflictChoice::KeepEnabled);
+                }
+                if ui.button("Cancel").on_hover_text("Changes nothing and remembers nothing.").clicked() {
+                    choice = Some(ConflictChoice::Cancel);
+                }
+            });
+            if waiting > 0 {
+                ui.weak(format!("{} more conflicts waiting.", waiting));
             }
+        });
+
+    app.remember_conflict_choice = remember;
+    if let Some(choice) = choice {
+        app.resolve_pending_conflict(0, choice, remember);
+    }
+}
+
+pub fn interrupted_save_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let Some(temp) = &app.interrupted_save else {
+        return;
+    };
+    let mut choice = None;
+
+    egui::Window::new("Recover Interrupted Save")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("TMM was interrupted while saving CompositePackageMapper.dat. The mapper can't be read, but the copy it was writing is complete:");
+            ui.monospace(temp.display().to_string());
+            ui.horizontal(|ui| {
+                if ui.button("Use the saved copy").clicked() {
+                    choice = Some(true);
+                }
+                if ui.button("Restore clean backup").on_hover_text("All mods are unapplied until you apply them again.").clicked() {
+                    choice = Some(false);
+                }
+            });
+        });
+
+    if let Some(use_temp) = choice {
+        app.resolve_interrupted_save(use_temp);
+    }
+}
+
+pub fn persistent_mode_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.confirm_persistent_mode {
+        return;
+    }
+    let mut accept = false;
+
+    egui::Window::new("Keep Mods Applied Permanently?")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("In Persistent mode TMM never restores CompositePackageMapper.dat on its own, not even when TERA closes.");
+            ui.label("If a game patch replaces the mapper while mods are applied, or a mod no longer matches the patched files, the client can fail to load or crash until you press Restore or repair the game.");
+            ui.label(egui::RichText::new("You are only asked this once.").small().weak());
+            ui.horizontal(|ui| {
+                accept = ui.button("I understand, use Persistent").clicked();
+                if ui.button("Cancel").clicked() {
+                    app.confirm_persistent_mode = false;
+                }
+            });
+        });
+
+    if accept {
+        app.accept_persistent_mode();
+    }
+}
+
+pub fn consistency_check_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.offer_consistency_check {
+        return;
+    }
+    let mut check = false;
+
+    egui::Window::new("Left Persistent Mode")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("The mapper may still hold mods applied in Persistent mode. Check the clean backup and the enabled mods against it now?");
+            ui.horizontal(|ui| {
+                check = ui.button("Check now").clicked();
+                if ui.button("Skip").clicked() {
+                    app.offer_consistency_check = false;
+                }
+            });
+        });
+
+    if check {
+        app.run_consistency_check();
+    }
+}
+
+// A second native window rendered from the same app state in the same frame.
+// Closing it only hides it. Backends without multi-viewport support get an
+// ordinary egui window instead.
+pub fn quick_panel_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.show_quick_panel {
+        return;
+    }
+    let mut builder = egui::ViewportBuilder::default()
+        .with_title("TMM Quick Panel")
+        .with_always_on_top()
+        .with_resizable(false)
+        .with_inner_size([240.0, 90.0]);
+    if let Some((x, y)) = app.quick_panel_pos {
+        builder = builder.with_position([x, y]);
+    }
+
+    ctx.show_viewport_immediate(egui::ViewportId::from_hash_of("quick_panel"), builder, |ctx, class| {
+        if class == egui::ViewportClass::Embedded {
+            let mut open = true;
+            egui::Window::new("Quick Panel")
+                .open(&mut open)
+                .resizable(false)
+                .collapsible(false)
+                .show(ctx, |ui| quick_panel_contents_ui(app, ui));
+            app.show_quick_panel = open;
+            return;
         }
-        // ... Restore, Apply Now, Wait for TERA buttons remain the same ...
-        if ui.button("Restore").clicked() {
-            app.restore_composite_mapper();
-            app.disable_all_mods();
+
+        egui::CentralPanel::default().show(ctx, |ui| quick_panel_contents_ui(app, ui));
+        if let Some(rect) = ctx.input(|i| i.viewport().outer_rect) {
+            app.quick_panel_pos = Some((rect.min.x, rect.min.y));
+        }
+        if ctx.input(|i| i.viewport().close_requested()) {
+            app.show_quick_panel = false;
         }
+    });
+}
 
-        if ui.button("Apply Now").clicked() {
+fn quick_panel_contents_ui(app: &mut TmmApp, ui: &mut Ui) {
+    // Waits like the main window while the conflict prompt is up
+    if !app.pending_conflicts.is_empty() {
+        ui.disable();
+    }
+    let enabled = app.mod_list.iter().filter(|m| m.enabled).count();
+    ui.label(format!("{} mods enabled", enabled));
+    ui.label(egui::RichText::new(app.apply_status()).strong());
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(!app.mapper_too_small, egui::Button::new("Apply Now"))
+            .on_disabled_hover_text(MAPPER_TOO_SMALL_HINT)
+            .clicked()
+        {
             app.save_button();
         }
-        
-        if ui.checkbox(&mut app.wait_for_tera, "Wait for TERA").changed() {
-            if let Err(e) = app.save_app_config() {
-                app.error_msg = Some(format!("Failed to save settings: {}", e));
+        if ui.button("Restore").clicked() {
+            app.restore_button();
+        }
+    });
+}
+
+pub fn preview_apply_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let Some(unresolvable) = &app.preview_apply else {
+        return;
+    };
+    let enabled = app.mod_list.iter().filter(|m| m.enabled).count();
+    let mut apply = false;
+    let mut close = false;
+
+    egui::Window::new("Preview Apply")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            if unresolvable.is_empty() {
+                ui.label(format!("All object paths of the {} enabled mods resolve.", enabled));
             } else {
-                let state = if app.wait_for_tera { "enabled" } else { "disabled" };
-                app.status_msg = format!("Wait for TERA {}.", state);
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!(
+                        "{} object paths are missing from the game's mapper and will be skipped:",
+                        unresolvable.len()
+                    ),
+                );
+                egui::ScrollArea::vertical().max_height(250.0).show(ui, |ui| {
+                    egui::Grid::new("unresolvable_grid").striped(true).num_columns(2).show(ui, |ui| {
+                        for u in unresolvable {
+                            ui.label(&u.mod_name);
+                            ui.label(egui::RichText::new(&u.object_path).monospace());
+                            ui.end_row();
+                        }
+                    });
+                });
             }
+            ui.horizontal(|ui| {
+                apply = ui.button("Apply Now").clicked();
+                close = ui.button("Close").clicked();
+            });
+        });
+
+    if apply {
+        app.save_button();
+    }
+    if apply || close {
+        app.preview_apply = None;
+    }
+}
+
+pub fn foreign_backups_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if app.foreign_backups.is_empty() {
+        return;
+    }
+    let mut adopt = None;
+    let mut ignore = false;
+
+    egui::Window::new("Backups From Other Tools")
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label("TMM has no usable clean backup, but these look like unmodified mappers:");
+            egui::Grid::new("foreign_backups_grid").striped(true).num_columns(3).show(ui, |ui| {
+                for (i, backup) in app.foreign_backups.iter().enumerate() {
+                    ui.label(backup.path.file_name().unwrap_or_default().to_string_lossy());
+                    ui.label(format!("{} entries", backup.entries));
+                    if ui.button("Use as clean backup").clicked() {
+                        adopt = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.label(egui::RichText::new("The file is copied; the original stays in place.").small().weak());
+            ignore = ui.button("Ignore").clicked();
+        });
+
+    if let Some(i) = adopt {
+        match app.adopt_foreign_backup(i) {
+            Ok(()) => app.status_msg = "Clean backup restored from another tool's copy.".to_string(),
+            Err(e) => app.error_msg = Some(format!("Backup rejected: {}", e)),
         }
-    });
+    }
+    if ignore {
+        app.log_msg("Ignored mapper backups from other tools.".to_string());
+        app.foreign_backups.clear();
+    }
+}
+
+pub fn batch_edit_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    let count = app.selected_mods.len();
+    let Some(edit) = &mut app.batch_edit else {
+        return;
+    };
+    let mut open = true;
+    let mut apply = false;
+
+    egui::Window::new("Edit Selected Mods")
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.label(format!("Changes apply to {} mods. Fields left at (mixed) stay as they are.", count));
+            egui::Grid::new("batch_edit_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Author:");
+                ui.add(egui::TextEdit::singleline(&mut edit.author).hint_text(if edit.author_mixed { "(mixed)" } else { "" }));
+                ui.end_row();
+                ui.label("Exclusive group:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut edit.exclusive_group)
+                        .hint_text(if edit.group_mixed { "(mixed)" } else { "" }),
+                );
+                ui.end_row();
+                ui.label("Add tags:");
+                ui.add(egui::TextEdit::singleline(&mut edit.add_tags).hint_text("tag1, tag2"));
+                ui.end_row();
+                ui.label("Remove tags:");
+                ui.add(egui::TextEdit::singleline(&mut edit.remove_tags).hint_text("tag1, tag2"));
+                ui.end_row();
+                ui.label("Locked:");
+                let label = match edit.locked {
+                    None => "(mixed)",
+                    Some(true) => "Locked",
+                    Some(false) => "Unlocked",
+                };
+                egui::ComboBox::from_id_salt("batch_edit_locked")
+                    .selected_text(label)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut edit.locked, Some(true), "Locked");
+                        ui.selectable_value(&mut edit.locked, Some(false), "Unlocked");
+                    });
+                ui.end_row();
+            });
+            apply = ui.button("Apply").clicked();
+        });
+
+    if apply {
+        app.apply_batch_edit();
+    } else if !open {
+        app.batch_edit = None;
+    }
+}
+
+pub fn conflicts_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.show_conflicts {
+        return;
+    }
+    if app.conflict_pairs.is_none() {
+        app.conflict_pairs = Some(app.build_conflict_pairs(app.conflicts_enabled_only));
+        app.conflict_detail = None;
+    }
+
+    let mut open = true;
+    let mut refresh = false;
+    let mut clicked_pair = None;
+    let mut keep = None;
+
+    egui::Window::new("Mod Conflicts")
+        .open(&mut open)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                refresh |= ui.checkbox(&mut app.conflicts_enabled_only, "Enabled only").changed();
+                refresh |= ui.button("Refresh").clicked();
+                if ui.button("Export CSV").clicked() {
+                    if let Some(dest) = rfd::FileDialog::new()
+                        .add_filter("CSV", &["csv"])
+                        .set_file_name("conflicts.csv")
+                        .save_file()
+                    {
+                        match app.export_conflicts_csv(&dest) {
+                            Ok(()) => app.status_msg = format!("Exported conflicts to {}", dest.display()),
+                            Err(e) => app.error_msg = Some(format!("Export failed: {}", e)),
+                        }
+                    }
+                }
+            });
+            ui.separator();
+
+            let pairs = app.conflict_pairs.as_deref().unwrap_or_default();
+            if pairs.is_empty() {
+                ui.label("No conflicts.");
+            }
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                egui::Grid::new("conflicts_grid").num_columns(3).striped(true).show(ui, |ui| {
+                    for pair in pairs {
+                        let (Some(a), Some(b)) = (app.mod_index(pair.a), app.mod_index(pair.b)) else {
+                            continue;
+                        };
+                        ui.label(&app.mod_list[a].mod_file.mod_name);
+                        ui.label(&app.mod_list[b].mod_file.mod_name);
+                        if ui.link(format!("{} shared", pair.shared)).clicked() {
+                            clicked_pair = Some((a, b));
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+            let detail = app.conflict_detail.as_ref().and_then(|(a, b, paths)| {
+                Some((app.mod_index(*a)?, app.mod_index(*b)?, paths))
+            });
+            if let Some((a, b, paths)) = detail {
+                ui.separator();
+                ui.strong(format!(
+                    "{} ↔ {}",
+                    app.mod_list[a].mod_file.mod_name, app.mod_list[b].mod_file.mod_name
+                ));
+                egui::ScrollArea::vertical()
+                    .id_salt("conflict_detail")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        for path in paths {
+                            ui.label(egui::RichText::new(path).monospace());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button(format!("Keep {}", app.mod_list[a].mod_file.mod_name)).clicked() {
+                        keep = Some(a);
+                    }
+                    if ui.button(format!("Keep {}", app.mod_list[b].mod_file.mod_name)).clicked() {
+                        keep = Some(b);
+                    }
+                });
+            }
+        });
+
+    if let Some((a, b)) = clicked_pair {
+        let paths = app.shared_object_paths(a, b);
+        app.conflict_detail = Some((app.mod_list[a].id, app.mod_list[b].id, paths));
+    }
+    if let Some(idx) = keep {
+        // Enabling through the normal path disables everything it conflicts with
+        let (id, name) = (app.mod_list[idx].id, app.mod_list[idx].mod_file.mod_name.clone());
+        match app.set_mod_state(id, true, ChangeOrigin::ConflictWindow) {
+            Ok(outcome) if !outcome.blocked_by.is_empty() => {
+                let names = app.mod_names(&outcome.blocked_by).join(", ");
+                app.status_msg = format!("Can't keep {}: unlock {} first.", name, names);
+            }
+            Ok(_) => {
+                app.commit_mod_states();
+                app.status_msg = format!("Enabled: {}", name);
+            }
+            Err(e) => app.error_msg = Some(format!("Turn on failed: {:?}", e)),
+        }
+        refresh = true;
+    }
+    if refresh {
+        app.conflict_pairs = None;
+    }
+    app.show_conflicts = open;
+}
+
+pub fn storage_window_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.show_storage {
+        return;
+    }
+
+    let (total, disabled) = app.storage_overview();
+    let stale = app.stale_disabled_mods(app.storage_cleanup_days);
+    let mut open = true;
+    let mut cleanup = false;
+    let mut reinstall = None;
+
+    egui::Window::new("Storage")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("storage_grid").num_columns(2).show(ui, |ui| {
+                ui.label("Installed mod files:");
+                ui.label(utils::format_bytes(total));
+                ui.end_row();
+                ui.label("Disabled mods:");
+                ui.label(utils::format_bytes(disabled));
+                ui.end_row();
+            });
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Remove files of mods disabled for more than");
+                ui.add(egui::DragValue::new(&mut app.storage_cleanup_days).range(1..=3650));
+                ui.label("days");
+            });
+            if app.confirm_storage_cleanup {
+                ui.label(
+                    egui::RichText::new(format!(
+                        "Delete the files of {} mods? They stay in the list and can be reinstalled from their source.",
+                        stale.len()
+                    ))
+                    .color(egui::Color32::ORANGE),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button("Delete").clicked() {
+                        cleanup = true;
+                        app.confirm_storage_cleanup = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        app.confirm_storage_cleanup = false;
+                    }
+                });
+            } else if ui
+                .add_enabled(!stale.is_empty(), egui::Button::new(format!("Clean up ({} mods)", stale.len())))
+                .clicked()
+            {
+                app.confirm_storage_cleanup = true;
+            }
+
+            let archived: Vec<usize> = (0..app.mod_list.len()).filter(|&i| app.mod_list[i].archived).collect();
+            if !archived.is_empty() {
+                ui.separator();
+                ui.strong("File missing — reinstall from source available");
+                for idx in archived {
+                    ui.horizontal(|ui| {
+                        ui.label(&app.mod_list[idx].file);
+                        if ui.button("Reinstall").clicked() {
+                            reinstall = Some(idx);
+                        }
+                    });
+                }
+            }
+        });
+
+    if cleanup {
+        app.cleanup_stale_mods(app.storage_cleanup_days);
+    }
+    if let Some(idx) = reinstall {
+        match app.reinstall_archived_mod(idx) {
+            Ok(()) => {
+                app.status_msg = format!("Reinstalled {}", app.mod_list[idx].file);
+                // Rescan so the restored file's packages are read
+                app.initialized = false;
+            }
+            Err(e) => app.error_msg = Some(format!("Reinstall failed: {}", e)),
+        }
+    }
+    app.show_storage = open;
+}
+
+pub fn perf_overlay_ui(app: &mut TmmApp, ctx: &egui::Context) {
+    if !app.perf.enabled {
+        return;
+    }
+    // The overlay itself would otherwise only refresh on input
+    ctx.request_repaint_after(std::time::Duration::from_millis(250));
+
+    egui::Window::new("Performance")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+        .resizable(false)
+        .collapsible(false)
+        .title_bar(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("perf_grid").num_columns(2).show(ui, |ui| {
+                let rows = [
+                    ("Frame", perf::format_duration(app.perf.frame)),
+                    ("TERA check", perf::format_duration(app.perf.tera_check)),
+                    ("Last apply", perf::format_duration(app.perf.apply)),
+                    ("Last commit", perf::format_duration(app.perf.commit)),
+                    ("Last scan", perf::format_duration(app.perf.scan)),
+                    ("Mapper entries", app.composite_map.len().to_string()),
+                    ("Mod entry clones", app.perf.mod_entry_clones.to_string()),
+                ];
+                for (label, value) in rows {
+                    ui.label(label);
+                    ui.monospace(value);
+                    ui.end_row();
+                }
+            });
+        });
 }
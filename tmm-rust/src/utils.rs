@@ -1,3 +1,12 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const GPK_EXTENSION: &str = "gpk";
+pub const TMOD_EXTENSION: &str = "tmod";
+
 pub fn normalize_object_name(path: &str) -> String {
     // 1. Get the part after the last slash (if any)
     let name = path.rsplit('/').next().unwrap_or(path);
@@ -34,3 +43,342 @@ pub fn ascii_eq_ignore_case(a: &str, b: &str) -> bool {
     a.len() == b.len()
         && a.bytes().zip(b.bytes()).all(|(x, y)| x.eq_ignore_ascii_case(&y))
 }
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// SHA-256 of a file, read in 1 MiB chunks so large packages aren't loaded
+/// into memory whole. Same format as [`sha256_hex`].
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Whether two paths name the same file once symlinks, `..` and (on Windows)
+/// letter case are resolved. Paths that can't be resolved are compared as
+/// given.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Removes a trailing `.gpk` in any letter case.
+pub fn strip_gpk_ext(name: &str) -> &str {
+    let ext_len = GPK_EXTENSION.len() + 1;
+    if name.len() > ext_len
+        && name.is_char_boundary(name.len() - ext_len)
+        && ascii_eq_ignore_case(&name[name.len() - ext_len..], ".gpk")
+    {
+        &name[..name.len() - ext_len]
+    } else {
+        name
+    }
+}
+
+/// Cuts `name` to at most `max` characters ending in `_` plus four hex digits
+/// of its hash, so long names sharing a prefix stay distinct. While `taken`
+/// rejects a candidate, a counter is added to the suffix.
+pub fn shorten_container(name: &str, max: usize, taken: impl Fn(&str) -> bool) -> String {
+    let hash = sha256_hex(name.as_bytes());
+    let mut n = 0;
+    loop {
+        let suffix = if n == 0 {
+            format!("_{}", &hash[..4])
+        } else {
+            format!("_{}{}", &hash[..4], n)
+        };
+        let head: String = name.chars().take(max.saturating_sub(suffix.len())).collect();
+        let candidate = head + &suffix;
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+pub fn is_gpk(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(GPK_EXTENSION))
+}
+
+/// A `.tmod` package: a GPK with TMM metadata appended.
+pub fn is_tmod(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(TMOD_EXTENSION))
+}
+
+/// Files Add, drag and drop and Add Folder accept.
+pub fn is_installable(path: &Path) -> bool {
+    is_gpk(path) || is_tmod(path)
+}
+
+/// Case-insensitive wildcard match where `*` matches any run of characters and
+/// `?` exactly one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` and the name index it was tried against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether TMM may write to `path`. Directories are probed by creating and
+/// removing a `.tmm_test` file; existing files are opened for writing without
+/// truncating. Only `PermissionDenied` counts as unwritable, other errors are
+/// left for the real write to report.
+pub fn is_path_writable(path: &Path) -> bool {
+    let result = if path.is_dir() {
+        let probe = path.join(".tmm_test");
+        let result = OpenOptions::new().write(true).create(true).truncate(true).open(&probe);
+        if result.is_ok() {
+            fs::remove_file(&probe).ok();
+        }
+        result
+    } else {
+        OpenOptions::new().write(true).open(path)
+    };
+    !matches!(result, Err(e) if e.kind() == ErrorKind::PermissionDenied)
+}
+
+/// Opens the system file manager on the folder holding `path`, with the file
+/// selected where the platform supports it. Only starting the file manager
+/// can fail here; Explorer reports nothing useful through its exit code.
+pub fn reveal_in_file_manager(path: &Path) -> std::io::Result<()> {
+    #[cfg(target_os = "windows")]
+    {
+        // explorer parses its own command line and doesn't understand the
+        // quoting std applies to a single argument containing spaces
+        use std::os::windows::process::CommandExt;
+        std::process::Command::new("explorer")
+            .raw_arg(format!("/select,\"{}\"", path.display()))
+            .spawn()?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open").arg("-R").arg(path).spawn()?;
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let folder = path.parent().unwrap_or(path);
+        std::process::Command::new("xdg-open").arg(folder).spawn()?;
+    }
+    Ok(())
+}
+
+/// Formats bytes as hex lines of 16, each prefixed with its offset:
+/// `0000: 9E 2A 83 C1 ...`
+pub fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, line)| {
+            let hex: Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("{:04X}: {}", i * 16, hex.join(" "))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Formats an offset or size the way UE Explorer shows it when `hex` is set.
+pub fn format_offset(value: usize, hex: bool) -> String {
+    if hex {
+        format!("0x{:X}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal offset.
+pub fn parse_offset(text: &str) -> Option<usize> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats Unix seconds as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days conversion for the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpk_extension_is_matched_in_any_case() {
+        for name in ["S1_Elin.gpk", "S1_Elin.GPK", "S1_Elin.Gpk"] {
+            assert_eq!(strip_gpk_ext(name), "S1_Elin");
+            assert!(is_gpk(Path::new(name)));
+            assert!(is_installable(Path::new(name)));
+        }
+        assert!(is_tmod(Path::new("S1_Elin.TMOD")));
+    }
+
+    #[test]
+    fn names_without_the_gpk_extension_are_kept() {
+        for name in ["S1_Elin", "S1_Elin.gpk.bak", "S1_Elingpk", ".gpk", "Ünïcode.gp"] {
+            assert_eq!(strip_gpk_ext(name), name);
+        }
+        assert_eq!(strip_gpk_ext("Ünïcode.gpk"), "Ünïcode");
+        assert!(!is_gpk(Path::new("S1_Elin")));
+        assert!(!is_gpk(Path::new("S1_Elin.gpk.bak")));
+        assert!(!is_installable(Path::new("readme.txt")));
+    }
+
+    #[test]
+    fn parse_offset_reads_decimal_and_hex() {
+        assert_eq!(parse_offset("1234"), Some(1234));
+        assert_eq!(parse_offset("  0x4D2 "), Some(1234));
+        assert_eq!(parse_offset("0X4d2"), Some(1234));
+        assert_eq!(parse_offset("0"), Some(0));
+    }
+
+    #[test]
+    fn parse_offset_rejects_garbage() {
+        assert_eq!(parse_offset(""), None);
+        assert_eq!(parse_offset("0x"), None);
+        assert_eq!(parse_offset("-5"), None);
+        assert_eq!(parse_offset("12ab"), None);
+        assert_eq!(parse_offset("0xZZ"), None);
+        assert_eq!(parse_offset("99999999999999999999999"), None);
+    }
+
+    #[test]
+    fn parse_offset_reads_what_format_offset_writes() {
+        for value in [0, 1, 0xFFFF, usize::MAX] {
+            assert_eq!(parse_offset(&format_offset(value, true)), Some(value));
+            assert_eq!(parse_offset(&format_offset(value, false)), Some(value));
+        }
+    }
+
+    #[test]
+    fn shortened_containers_fit_the_limit_exactly() {
+        let name = "S1_Elin_Costume_".repeat(5);
+        for max in [10, 32, 64] {
+            let long = &name[..max + 1];
+            let short = shorten_container(long, max, |_| false);
+            assert_eq!(short.chars().count(), max);
+            assert!(short.starts_with(&name[..max - 5]));
+            assert_eq!(&short[max - 5..], format!("_{}", &sha256_hex(long.as_bytes())[..4]));
+        }
+    }
+
+    #[test]
+    fn shortening_counts_up_until_a_name_is_free() {
+        let name = "S1_Elin_Costume_Remodel_Variant";
+        let hash = &sha256_hex(name.as_bytes())[..4];
+        let first = shorten_container(name, 16, |_| false);
+        let second = shorten_container(name, 16, |c| c == first);
+        let third = shorten_container(name, 16, |c| c == first || c == second);
+
+        assert_eq!(first, format!("S1_Elin_Cos_{}", hash));
+        assert_eq!(second, format!("S1_Elin_Co_{}1", hash));
+        assert_eq!(third, format!("S1_Elin_Co_{}2", hash));
+        // Names sharing the kept prefix still get different suffixes
+        assert_ne!(
+            shorten_container("S1_Elin_Costume_A", 16, |_| false),
+            shorten_container("S1_Elin_Costume_B", 16, |_| false)
+        );
+    }
+
+    #[test]
+    fn multibyte_names_are_cut_at_characters() {
+        let name = "Ünïcödé_Cöstümé_Ëlïn_Mödél";
+        let short = shorten_container(name, 12, |_| false);
+
+        assert_eq!(short.chars().count(), 12);
+        assert!(short.len() > 12);
+        assert!(short.starts_with("Ünïcödé"));
+    }
+
+    #[test]
+    fn same_file_sees_through_differently_spelled_paths() {
+        let dir = std::env::temp_dir().join(format!("tmm-utils-same-file-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let file = dir.join("mod.gpk");
+        fs::write(&file, b"mod").unwrap();
+        fs::write(dir.join("other.gpk"), b"mod").unwrap();
+
+        assert!(same_file(&file, &dir.join("sub").join("..").join("mod.gpk")));
+        assert!(!same_file(&file, &dir.join("other.gpk")));
+        // Paths that don't resolve are compared as given
+        assert!(same_file(&dir.join("missing.gpk"), &dir.join("missing.gpk")));
+        assert!(!same_file(&dir.join("missing.gpk"), &dir.join("sub").join("..").join("missing.gpk")));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,100 @@
+// Which TMM version last wrote a persisted file. Every artifact records the
+// writing version; one written by a newer major version may hold data this
+// build doesn't know about and would drop on save, so it is loaded read-only.
+// Files from before the stamp existed count as older.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum WrittenBy {
+    // This major version or an older one, or no stamp at all
+    #[default]
+    Compatible,
+    // A newer major version
+    Newer(String),
+    // A stamp that isn't a version; the file was damaged or hand-edited
+    Unreadable(String),
+}
+
+impl WrittenBy {
+    pub fn check(stamp: Option<&str>) -> Self {
+        let Some(stamp) = stamp else {
+            return Self::Compatible;
+        };
+        match (major(stamp), major(APP_VERSION)) {
+            (Some(written), Some(running)) if written > running => Self::Newer(stamp.to_string()),
+            (Some(_), _) => Self::Compatible,
+            (None, _) => Self::Unreadable(stamp.to_string()),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        *self != Self::Compatible
+    }
+
+    // Explanation for the user, None when the artifact loads normally
+    pub fn warning(&self, artifact: &str) -> Option<String> {
+        match self {
+            Self::Compatible => None,
+            Self::Newer(version) => Some(format!(
+                "{} was written by TMM {}, newer than this version ({}). It is read-only until you update TMM; changes to it won't be saved.",
+                artifact, version, APP_VERSION
+            )),
+            Self::Unreadable(stamp) => Some(format!(
+                "{} has an unreadable version stamp ({:?}). It is read-only so it isn't overwritten; changes to it won't be saved.",
+                artifact, stamp
+            )),
+        }
+    }
+}
+
+fn major(version: &str) -> Option<u64> {
+    let version = version.trim();
+    let major = version.split('.').next()?;
+    // The remaining components must at least look like a version
+    if !version.split('.').all(|part| !part.is_empty()) {
+        return None;
+    }
+    major.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_major() -> u64 {
+        major(APP_VERSION).unwrap()
+    }
+
+    #[test]
+    fn files_from_this_or_older_versions_load_normally() {
+        assert_eq!(WrittenBy::check(Some(APP_VERSION)), WrittenBy::Compatible);
+        assert_eq!(WrittenBy::check(None), WrittenBy::Compatible);
+        assert_eq!(WrittenBy::check(Some("0.0.1")), WrittenBy::Compatible);
+        // Newer minor and patch versions of the same major are compatible
+        let same_major = format!("{}.999.999", running_major());
+        assert_eq!(WrittenBy::check(Some(&same_major)), WrittenBy::Compatible);
+        assert!(!WrittenBy::check(Some(APP_VERSION)).is_read_only());
+        assert_eq!(WrittenBy::Compatible.warning("ModList.mods"), None);
+    }
+
+    #[test]
+    fn files_from_a_newer_major_version_are_read_only() {
+        let newer = format!("{}.0.0", running_major() + 1);
+        let written_by = WrittenBy::check(Some(&newer));
+
+        assert_eq!(written_by, WrittenBy::Newer(newer.clone()));
+        assert!(written_by.is_read_only());
+        let warning = written_by.warning("settings.bin").unwrap();
+        assert!(warning.contains("settings.bin") && warning.contains(&newer) && warning.contains(APP_VERSION));
+    }
+
+    #[test]
+    fn corrupted_stamps_are_read_only() {
+        for stamp in ["", "garbage", "1..2", "v1.0.0", ".1", "\u{FFFD}\u{FFFD}"] {
+            let written_by = WrittenBy::check(Some(stamp));
+            assert_eq!(written_by, WrittenBy::Unreadable(stamp.to_string()), "{:?}", stamp);
+            assert!(written_by.is_read_only());
+            assert!(written_by.warning("ModList.mods").unwrap().contains("unreadable version stamp"));
+        }
+    }
+}
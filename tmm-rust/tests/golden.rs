@@ -0,0 +1,184 @@
+// Golden-file tests for the full enable/disable cycle. A small encrypted
+// mapper (tests/golden/mapper.dat, the encryption of mapper.txt) is loaded,
+// two mods are packed from generated packages, and the saved mapper is
+// compared byte for byte after each step:
+//
+//     01_vanilla -> 02_a -> 03_a_b -> 04_b -> 05_vanilla
+//
+// Every state is reached both by switching single mods, as toggling a row
+// does, and by rebuilding from the clean map, as an apply does; both must
+// write the same bytes. Each golden is kept encrypted (.dat, what the game
+// reads) and as plaintext (.txt, for reviewing diffs).
+//
+// A change that alters serialization, encryption or patching fails here. When
+// the change is intended, regenerate the fixtures and review the .txt diffs:
+//
+//     UPDATE_GOLDENS=1 cargo test --test golden
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use tmm_rust::composite_mapper::{CleanMap, CompositeMapperFile};
+use tmm_rust::mod_model::{self, ModEntry, ModFile};
+use tmm_rust::patch;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn updating() -> bool {
+    std::env::var_os("UPDATE_GOLDENS").is_some_and(|v| v == "1")
+}
+
+// Compares `actual` with the golden file, or rewrites it under UPDATE_GOLDENS=1
+fn check_golden(name: &str, actual: &[u8]) {
+    let path = golden_dir().join(name);
+    if updating() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read(&path).unwrap_or_else(|e| panic!("missing golden {}: {}", path.display(), e));
+    assert!(
+        expected == actual,
+        "{} differs from the golden file ({} bytes expected, {} written); \
+         rerun with UPDATE_GOLDENS=1 if the change is intended",
+        name,
+        expected.len(),
+        actual.len()
+    );
+}
+
+// Packs a mod with the mod file packer, writes it to `dir` and reads it back
+// the way installing does
+fn packed_mod(dir: &Path, id: u64, container: &str, object_paths: &[&str]) -> ModEntry {
+    let meta = ModFile {
+        mod_name: format!("Golden {}", container),
+        mod_author: "TMM tests".to_string(),
+        container: container.to_string(),
+        mod_file_version: 1,
+        ..Default::default()
+    };
+    let packages: Vec<Vec<u8>> = object_paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| support::fake_package(path, 64 + 32 * i, support::SEED + id))
+        .collect();
+    let file = format!("{}.gpk", container);
+    let mut data = Vec::new();
+    mod_model::write_mod_file(&meta, &packages, &mut data).unwrap();
+    fs::write(dir.join(&file), &data).unwrap();
+
+    let mut mod_file = ModFile::default();
+    mod_model::read_mod_file(&mut fs::File::open(dir.join(&file)).unwrap(), &mut mod_file).unwrap();
+    ModEntry {
+        id,
+        file,
+        mod_file,
+        ..Default::default()
+    }
+}
+
+// Saves the map and checks the written file and its decryption
+fn check_state(dir: &Path, name: &str, map: &CompositeMapperFile) {
+    let path = dir.join(format!("{}.dat", name));
+    map.save(&path).unwrap();
+    let saved = fs::read(&path).unwrap();
+    let plaintext = CompositeMapperFile::new(path).unwrap().plaintext;
+    check_golden(&format!("expected/{}.dat", name), &saved);
+    check_golden(&format!("expected/{}.txt", name), plaintext.as_bytes());
+}
+
+fn load_fixture(dir: &Path) -> (CompositeMapperFile, CleanMap) {
+    static REGENERATE: Once = Once::new();
+    let fixture = golden_dir().join("mapper.dat");
+    REGENERATE.call_once(|| {
+        if !updating() {
+            return;
+        }
+        let text = fs::read_to_string(golden_dir().join("mapper.txt")).unwrap();
+        let map = CompositeMapperFile {
+            composite_map: tmm_rust::composite_mapper::parse_plaintext_strict(&text).unwrap(),
+            ..Default::default()
+        };
+        map.save(&fixture).unwrap();
+    });
+    let path = dir.join("CompositePackageMapper.dat");
+    fs::copy(&fixture, &path).unwrap();
+    let map = CompositeMapperFile::new(path.clone()).unwrap();
+    let backup = CleanMap::new(path).unwrap();
+    (map, backup)
+}
+
+#[test]
+fn fixture_decrypts_to_its_plaintext() {
+    let dir = tempfile::tempdir().unwrap();
+    let (map, _) = load_fixture(dir.path());
+    let expected = fs::read_to_string(golden_dir().join("mapper.txt")).unwrap();
+
+    assert_eq!(map.plaintext, expected);
+    assert!(map.parse_errors.is_empty() && map.empty_blocks.is_empty());
+    assert_eq!(map.len(), 7);
+}
+
+#[test]
+fn saving_an_untouched_fixture_reproduces_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let (map, _) = load_fixture(dir.path());
+    let path = dir.path().join("resaved.dat");
+    map.save(&path).unwrap();
+
+    assert_eq!(fs::read(path).unwrap(), fs::read(golden_dir().join("mapper.dat")).unwrap());
+}
+
+#[test]
+fn enable_disable_cycle_matches_goldens() {
+    let dir = tempfile::tempdir().unwrap();
+    let (mut map, backup) = load_fixture(dir.path());
+    let mod_a = packed_mod(dir.path(), 1, "GoldenModA", &["Armor.Mesh.PlateArmor", "UI.Icon.MainIcon"]);
+    let mod_b = packed_mod(dir.path(), 2, "GoldenModB", &["Weapon.Mesh.LongSword", "UI.Icon.AltIcon"]);
+    assert!(patch::find_conflicting_indices(std::slice::from_ref(&mod_a), &mod_b.mod_file.packages).is_empty());
+
+    let states: [(&str, &[&ModEntry]); 5] = [
+        ("01_vanilla", &[]),
+        ("02_a", &[&mod_a]),
+        ("03_a_b", &[&mod_a, &mod_b]),
+        ("04_b", &[&mod_b]),
+        ("05_vanilla", &[]),
+    ];
+    let mut enabled: Vec<&ModEntry> = Vec::new();
+    for (name, wanted) in states {
+        // Switch single mods, as a row toggle does
+        for m in enabled.iter().filter(|m| !wanted.iter().any(|w| w.id == m.id)) {
+            patch::turn_off_mod(&mut map, &backup, &m.mod_file, false).unwrap();
+        }
+        for m in wanted.iter().filter(|w| !enabled.iter().any(|m| m.id == w.id)) {
+            patch::turn_on_mod(&mut map, m).unwrap();
+        }
+        enabled = wanted.to_vec();
+        check_state(dir.path(), name, &map);
+
+        // Rebuild from the clean map, as an apply does
+        let mods: Vec<ModEntry> = wanted.iter().map(|&m| m.clone()).collect();
+        let mut rebuilt = map.clone();
+        let rebuild = patch::rebuild(&mut rebuilt, &backup, &mods);
+        assert!(rebuild.results.iter().all(|(_, r)| r.is_ok()));
+        let (incremental, full) = (dir.path().join("incremental.dat"), dir.path().join("full.dat"));
+        map.save(&incremental).unwrap();
+        rebuilt.save(&full).unwrap();
+        assert!(
+            fs::read(incremental).unwrap() == fs::read(full).unwrap(),
+            "{}: switching mods and rebuilding wrote different mappers",
+            name
+        );
+    }
+
+    // Back to vanilla is byte for byte the mapper we started from
+    if !updating() {
+        assert_eq!(
+            fs::read(golden_dir().join("expected/05_vanilla.dat")).unwrap(),
+            fs::read(golden_dir().join("mapper.dat")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,68 @@
+// apply_enabled_mods and the conflict scan, run through the library on the
+// seeded fixtures the benchmarks use
+mod support;
+
+use tmm_rust::composite_mapper::CleanMap;
+use tmm_rust::patch;
+
+#[test]
+fn rebuild_points_every_package_at_its_mod() {
+    let mapper = support::synthetic_mapper(500, support::SEED);
+    let mods = support::synthetic_mod_set(&mapper, 3, 20, support::SEED);
+    let backup = CleanMap::from(mapper.clone());
+    let mut map = mapper.clone();
+
+    let rebuild = patch::rebuild(&mut map, &backup, &mods);
+
+    assert_eq!(rebuild.order, vec![0, 1, 2]);
+    assert!(rebuild.cycles.is_empty());
+    assert!(rebuild.results.iter().all(|(_, r)| r.is_ok()));
+    // The last mod listing an object wins it
+    for entry in map.values() {
+        let winner = mods
+            .iter()
+            .rev()
+            .find(|m| m.mod_file.packages.iter().any(|p| p.object_path == entry.object_path));
+        match winner {
+            Some(m) => assert_eq!(entry.filename, m.mod_file.container),
+            None => assert_eq!(entry.filename, backup.get(&entry.composite_name).unwrap().filename),
+        }
+    }
+}
+
+#[test]
+fn rebuild_with_no_mods_restores_the_clean_map() {
+    let mapper = support::synthetic_mapper(200, support::SEED);
+    let mods = support::synthetic_mod_set(&mapper, 2, 10, support::SEED);
+    let backup = CleanMap::from(mapper.clone());
+    let mut map = mapper.clone();
+    patch::rebuild(&mut map, &backup, &mods);
+
+    patch::rebuild(&mut map, &backup, &[]);
+
+    let restored: Vec<_> = map.values().map(|e| (&e.filename, e.offset, e.size)).collect();
+    let clean: Vec<_> = backup.values().map(|e| (&e.filename, e.offset, e.size)).collect();
+    assert_eq!(restored, clean);
+}
+
+#[test]
+fn conflicts_are_enabled_mods_sharing_an_object() {
+    let mapper = support::synthetic_mapper(200, support::SEED);
+    let mut mods = support::synthetic_mod_set(&mapper, 3, 5, support::SEED);
+    let shared = mods[0].mod_file.packages[0].clone();
+    mods[2].mod_file.packages = vec![shared.clone()];
+    mods[1].mod_file.packages.retain(|p| p.object_path != shared.object_path);
+
+    assert_eq!(patch::find_conflicting_indices(&mods, std::slice::from_ref(&shared)), vec![0, 2]);
+
+    mods[0].enabled = false;
+    assert_eq!(patch::find_conflicting_indices(&mods, &[shared]), vec![2]);
+}
+
+#[test]
+fn a_mod_sharing_several_objects_is_listed_once() {
+    let mapper = support::synthetic_mapper(200, support::SEED);
+    let mods = support::synthetic_mod_set(&mapper, 1, 5, support::SEED);
+
+    assert_eq!(patch::find_conflicting_indices(&mods, &mods[0].mod_file.packages), vec![0]);
+}
@@ -0,0 +1,104 @@
+// Deterministic fixtures shared by the integration tests and the benchmarks.
+// The same seed always produces the same mapper and mod set, so benchmark
+// numbers from different runs are comparable and test failures reproduce.
+#![allow(dead_code)]
+
+use indexmap::IndexMap;
+
+use tmm_rust::composite_mapper::{CompositeEntry, CompositeMapperFile};
+use tmm_rust::mod_model::{CompositePackage, ModEntry, ModFile};
+
+pub const SEED: u64 = 0x5445_5241_4d4f_4453;
+
+// xorshift64*; good enough for fixtures and needs no extra dependency
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// A mapper shaped like the client's: entries spread over containers of about
+// 50 objects each, offsets ascending within a container
+pub fn synthetic_mapper(entries: usize, seed: u64) -> CompositeMapperFile {
+    let mut rng = Rng::new(seed);
+    let mut composite_map = IndexMap::with_capacity(entries);
+    let mut offset = 0;
+    for i in 0..entries {
+        let container = i / 50;
+        if i % 50 == 0 {
+            offset = 0;
+        }
+        let size = 256 + rng.below(64 * 1024);
+        let entry = CompositeEntry {
+            filename: format!("S1Data_{:05}", container),
+            object_path: format!("Pkg_{:05}.Object_{:07}", container, i),
+            composite_name: format!("c{:08x}_{:07}", rng.next_u64() as u32, i),
+            offset,
+            size,
+        };
+        offset += size;
+        composite_map.insert(entry.composite_name.clone(), entry);
+    }
+    CompositeMapperFile {
+        composite_map,
+        ..Default::default()
+    }
+}
+
+// `mods` enabled mods in containers Mod_000, Mod_001, ..., each overriding
+// `packages` objects of the mapper. Objects are drawn with replacement, so a
+// mod can list an object twice and mods overlap about as often as chance has
+// it, which is what makes conflicts show up in larger sets.
+pub fn synthetic_mod_set(mapper: &CompositeMapperFile, mods: usize, packages: usize, seed: u64) -> Vec<ModEntry> {
+    let entries: Vec<&CompositeEntry> = mapper.values().collect();
+    let mut rng = Rng::new(seed);
+    (0..mods)
+        .map(|i| ModEntry {
+            id: i as u64 + 1,
+            file: format!("Mod_{:03}.gpk", i),
+            enabled: true,
+            mod_file: ModFile {
+                mod_name: format!("Mod {}", i),
+                container: format!("Mod_{:03}", i),
+                packages: (0..packages)
+                    .map(|p| CompositePackage {
+                        object_path: entries[rng.below(entries.len())].object_path.clone(),
+                        offset: p * 1024,
+                        size: 1024,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect()
+}
+
+// A GPK as far as read_mod_file looks at one: the package signature, file
+// and licensee versions, header size, and a `MOD:<object path>` folder name,
+// followed by `body` seeded filler bytes
+pub fn fake_package(object_path: &str, body: usize, seed: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&tmm_rust::mod_model::PACKAGE_MAGIC.to_le_bytes());
+    data.extend_from_slice(&610u16.to_le_bytes());
+    data.extend_from_slice(&14u16.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    tmm_rust::mod_model::write_string(&mut data, &format!("MOD:{}", object_path)).unwrap();
+    let mut rng = Rng::new(seed);
+    data.extend((0..body).map(|_| rng.next_u64() as u8));
+    data
+}